@@ -0,0 +1,53 @@
+#![no_main]
+
+use std::cell::RefCell;
+
+use chess_engine::*;
+use libfuzzer_sys::fuzz_target;
+
+thread_local! {
+  static TABLE: RefCell<HashTable> = RefCell::new(HashTable::new(1));
+}
+
+/*
+  Any text given to parse_fen: it has to return an error or a board whose FEN is read back
+  to the same board. Every position that is read, legal or not, has its legal moves made and
+  taken back, checking the board after each step and the SAN of the move, and it's searched
+  to a small depth (the evaluation and the quiescence search).
+*/
+fuzz_target!(|data: &[u8]| {
+  let mut board: Board = Board::new();
+  let Ok(fen) = std::str::from_utf8(data) else {
+    return;
+  };
+
+  if board.parse_fen(fen).is_err() {
+    return;
+  }
+  let fen: String = board.to_fen();
+  board
+    .parse_fen(&fen)
+    .expect("to_fen wrote a FEN that parse_fen can not read");
+  assert_eq!(board.to_fen(), fen);
+  assert_eq!(board.validate(), Ok(()));
+
+  for mov_e in generate_legal_moves(&mut board) {
+    let san: String = move_to_san(&mut board, mov_e);
+    assert_eq!(parse_san(&mut board, &san), mov_e, "{} in {}", san, fen);
+    assert_eq!(
+      Move::from_compact(mov_e.to_compact(), board.pieces()),
+      Some(mov_e)
+    );
+
+    assert!(board.make_move(mov_e));
+    assert_eq!(board.validate(), Ok(()));
+    board.take_move();
+    assert_eq!(board.validate(), Ok(()));
+    assert_eq!(board.to_fen(), fen);
+  }
+
+  let mut info: SearchInfo = SearchInfo::new();
+  info.set_depth(2);
+  TABLE.with(|table| search_best_move(&mut board, &mut info, &mut table.borrow_mut()));
+  assert_eq!(board.to_fen(), fen);
+});
@@ -0,0 +1,64 @@
+#![no_main]
+
+use chess_engine::*;
+use libfuzzer_sys::fuzz_target;
+
+const POSITIONS: [(&str, bool); 6] = [
+  (START_FEN, false),
+  (
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    false,
+  ),
+  ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", false),
+  ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", false),
+  (
+    "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    false,
+  ),
+  (
+    "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+    true,
+  ),
+];
+
+/*
+  The first byte chooses the position, each of the others a legal move to make or, if the
+  high bit is set, to take back the last one. The board is checked after every step and
+  at the end all the moves are taken back to the start position.
+*/
+fuzz_target!(|data: &[u8]| {
+  let mut board: Board = Board::new();
+  let Some((&index, steps)) = data.split_first() else {
+    return;
+  };
+  let (fen, chess960): (&str, bool) = POSITIONS[index as usize % POSITIONS.len()];
+  let mut made_moves: usize = 0;
+
+  board.set_chess960(chess960);
+  board.parse_fen(fen).unwrap();
+  let start_fen: String = board.to_fen();
+
+  for &step in steps {
+    if step & 0x80 != 0 && made_moves > 0 {
+      board.take_move();
+      made_moves -= 1;
+    } else {
+      let moves: Vec<Move> = generate_legal_moves(&mut board);
+      if moves.is_empty() {
+        continue;
+      }
+      assert!(board.make_move(moves[(step & 0x7F) as usize % moves.len()]));
+      made_moves += 1;
+    }
+    assert_eq!(board.validate(), Ok(()));
+    assert_eq!(
+      Board::from_fen(&board.to_fen()).map(|read_board| read_board.to_fen()),
+      Ok(board.to_fen())
+    );
+  }
+
+  for _ in 0..made_moves {
+    board.take_move();
+  }
+  assert_eq!(board.to_fen(), start_fen);
+});
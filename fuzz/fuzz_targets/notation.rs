@@ -0,0 +1,32 @@
+#![no_main]
+
+use chess_engine::*;
+use libfuzzer_sys::fuzz_target;
+
+/*
+  A FEN on the first line (the start position if it's not a legal one) and then moves
+  in SAN or in the UCI notation: the moves that are read are made, checking the board.
+*/
+fuzz_target!(|data: &[u8]| {
+  let mut board: Board = Board::new();
+  let Ok(text) = std::str::from_utf8(data) else {
+    return;
+  };
+  let (fen, moves): (&str, &str) = text.split_once('\n').unwrap_or((text, ""));
+
+  if board.parse_fen(fen).is_err() || board.is_legal_position().is_err() {
+    board.parse_fen(START_FEN).unwrap();
+  }
+  for token in moves.split_whitespace() {
+    let mut mov_e: Move = parse_san(&mut board, token);
+    if mov_e == Move::NO_MOVE {
+      mov_e = parse_move(&board, token);
+    }
+    if mov_e != Move::NO_MOVE && board.make_move(mov_e) {
+      assert_eq!(board.validate(), Ok(()));
+      assert!(generate_legal_moves(&mut board)
+        .iter()
+        .all(|&legal_move| parse_move(&board, &legal_move.to_string()) == legal_move));
+    }
+  }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use chess_engine::*;
+use libfuzzer_sys::fuzz_target;
+
+/*
+  An EPD or a PGN text (from the first byte): every opening read has to be a legal position.
+*/
+fuzz_target!(|data: &[u8]| {
+  let mut board: Board = Board::new();
+  let Some((&kind, text)) = data.split_first() else {
+    return;
+  };
+  let Ok(text) = std::str::from_utf8(text) else {
+    return;
+  };
+  let openings: Result<Vec<String>, String> = if kind % 2 == 0 {
+    parse_epd_openings(&mut board, text)
+  } else {
+    parse_pgn_openings(&mut board, text)
+  };
+
+  for opening in openings.unwrap_or_default() {
+    board
+      .parse_fen(&opening)
+      .expect("an opening that parse_fen can not read");
+    assert_eq!(board.is_legal_position(), Ok(()));
+    assert_eq!(board.validate(), Ok(()));
+  }
+});
@@ -1,10 +1,11 @@
+use crate::bitboards::*;
 use crate::board::*;
 use crate::definitions::*;
-use crate::file_rank_to_square_120;
 
 /**
  * Given an attacking piece position in this notation:
  *
+ * ```text
  *          A    B    C    D    E    F    G    H
  *    000  001  002  003  004  005  006  007  008  009
  *    010  011  012  013  014  015  016  017  018  019
@@ -18,111 +19,318 @@ use crate::file_rank_to_square_120;
  * 8  090  091  092  093  094  095  096  097  098  099
  *    100  101  102  103  104  105  106  107  108  109
  *    110  111  112  113  114  115  116  117  118  119
+ * ```
  *
  * For example a Knight on E4 (55) attacks 8 board squares:
  * G3 (55 - 8 = 47), F2 (55 - 19 = 36), D2 (55 - 21 = 34), C3 (55 - 12 = 43),
  * C5 (55 + 8 = 63), D6 (55 + 19 = 74), F6 (55 + 21 = 76), G5 (55 + 12 = 67).
  */
-const KNIGHT_DIRECTIONS: [i32; 8] = [-8, -19, -21, -12, 8, 19, 21, 12];
-const ROOK_DIRECTIONS: [i32; 4] = [-1, -10, 1, 10];
-const BISHOP_DIRECTIONS: [i32; 4] = [-9, -11, 9, 11];
-const KING_DIRECTION: [i32; 8] = [-1, -10, -1, -10, -9, -11, 9, 11];
-
-pub fn show_squares_attacked_by_side(side: Colors, board: &Board) {
-  let mut square_120: i32;
-
-  for rank in (ChessboardRanks::R1 as i32..=ChessboardRanks::R8 as i32).rev() {
-    for file in ChessboardFiles::A as i32..=ChessboardFiles::H as i32 {
-      square_120 = file_rank_to_square_120!(file, rank);
-      if square_attacked(square_120, side, board) {
-        print!("X");
-      } else {
-        print!("-");
-      }
-    }
-    println!();
+pub const KNIGHT_DIRECTIONS: [i32; 8] = [-8, -19, -21, -12, 8, 19, 21, 12];
+pub const ROOK_DIRECTIONS: [i32; 4] = [-1, -10, 1, 10];
+pub const BISHOP_DIRECTIONS: [i32; 4] = [-9, -11, 9, 11];
+pub const KING_DIRECTION: [i32; 8] = [-1, -10, 1, 10, -9, -11, 9, 11];
+
+/**
+ * The squares attacked by the side as a grid of X and - (see bitboard_to_string).
+ */
+pub fn attacked_squares_to_string(side: Colors, board: &Board) -> String {
+  bitboard_to_string(board.attack_map(side))
+}
+
+// The bit of a square in a bitboard (64 squares notation)
+fn square_bit(square: Squares) -> u64 {
+  1u64 << Definitions::global().board_120_squares_in_64_squares_notation()[square as usize]
+}
+
+// NoSquare and OffBoard are not squares of the chessboard, nothing attacks them
+fn is_on_board(square: Squares) -> bool {
+  !matches!(square, Squares::NoSquare | Squares::OffBoard)
+}
+
+/**
+ * The square of the first piece met going from the square in the direction, None if the
+ * edge of the board is reached before.
+ */
+fn next_piece(
+  square: Squares,
+  direction: i32,
+  pieces: &[Pieces; BOARD_SQUARE_NUMBER],
+) -> Option<Squares> {
+  let mut temp_square: i32 = square as i32 + direction;
+
+  while pieces[temp_square as usize] == Pieces::Empty {
+    temp_square += direction;
+  }
+  if pieces[temp_square as usize] == Pieces::OffBoard {
+    None
+  } else {
+    Some(Squares::from_120(temp_square))
   }
-  println!("\n");
 }
 
-// The side required is the attacking one
-pub fn square_attacked(square_120: i32, side: Colors, board: &Board) -> bool {
-  let mut attacking_piece: i32;
-  let mut current_direction: i32;
-  let mut temp_square: i32;
+/**
+ * It calls found(square) for each piece of the side that attacks the square, looking at
+ * the given pieces array (the board one, or a copy changed during the static exchange
+ * evaluation). The pieces are visited in the order pawns, knights, rooks and queens on the
+ * files and ranks, bishops and queens on the diagonals, king: it stops as soon as found
+ * returns true, and returns whether it has stopped.
+ */
+pub fn find_attackers(
+  square: Squares,
+  side: Colors,
+  pieces: &[Pieces; BOARD_SQUARE_NUMBER],
+  mut found: impl FnMut(Squares) -> bool,
+) -> bool {
+  let square_120: i32 = square as i32;
+  let mut attacking_piece: Pieces;
+  let (pawn, pawn_directions): (Pieces, [i32; 2]) = if side == Colors::White {
+    (Pieces::Wp, [-11, -9])
+  } else {
+    (Pieces::Bp, [11, 9])
+  };
 
   // pawns
-  if side == Colors::White {
-    if board.pieces()[(square_120 - 11) as usize] == Pieces::Wp as i32
-      || board.pieces()[(square_120 - 9) as usize] == Pieces::Wp as i32
+  for direction in pawn_directions {
+    if pieces[(square_120 + direction) as usize] == pawn
+      && found(Squares::from_120(square_120 + direction))
     {
       return true;
     }
-  } else if board.pieces()[(square_120 + 11) as usize] == Pieces::Bp as i32
-    || board.pieces()[(square_120 + 9) as usize] == Pieces::Bp as i32
-  {
-    return true;
   }
 
   // knights
-  for index in 0..8 {
-    attacking_piece = board.pieces()[(square_120 + KNIGHT_DIRECTIONS[index as usize]) as usize];
-    if attacking_piece != Squares::OffBoard as i32
+  for direction in KNIGHT_DIRECTIONS {
+    attacking_piece = pieces[(square_120 + direction) as usize];
+    if attacking_piece != Pieces::OffBoard
       && PIECE_KNIGHT[attacking_piece as usize]
       && PIECE_COLOR[attacking_piece as usize] == side
+      && found(Squares::from_120(square_120 + direction))
     {
       return true;
     }
   }
 
   // rooks, queens
-  for index in 0..4 {
-    current_direction = ROOK_DIRECTIONS[index];
-    temp_square = square_120 + current_direction;
-    attacking_piece = board.pieces()[temp_square as usize];
-    while attacking_piece != Squares::OffBoard as i32 {
-      if attacking_piece != Pieces::Empty as i32 {
-        if PIECE_ROOK_QUEEN[attacking_piece as usize]
-          && PIECE_COLOR[attacking_piece as usize] == side
-        {
-          return true;
-        }
-        break;
+  for direction in ROOK_DIRECTIONS {
+    if let Some(temp_square) = next_piece(square, direction, pieces) {
+      attacking_piece = pieces[temp_square as usize];
+      if PIECE_ROOK_QUEEN[attacking_piece as usize]
+        && PIECE_COLOR[attacking_piece as usize] == side
+        && found(temp_square)
+      {
+        return true;
       }
-      temp_square += current_direction;
-      attacking_piece = board.pieces()[temp_square as usize];
     }
   }
 
   // bishops, queens
-  for index in 0..4 {
-    current_direction = BISHOP_DIRECTIONS[index];
-    temp_square = square_120 + current_direction;
-    attacking_piece = board.pieces()[temp_square as usize];
-    while attacking_piece != Squares::OffBoard as i32 {
-      if attacking_piece != Pieces::Empty as i32 {
-        if PIECE_BISHOP_QUEEN[attacking_piece as usize]
-          && PIECE_COLOR[attacking_piece as usize] == side
-        {
-          return true;
-        }
-        break;
+  for direction in BISHOP_DIRECTIONS {
+    if let Some(temp_square) = next_piece(square, direction, pieces) {
+      attacking_piece = pieces[temp_square as usize];
+      if PIECE_BISHOP_QUEEN[attacking_piece as usize]
+        && PIECE_COLOR[attacking_piece as usize] == side
+        && found(temp_square)
+      {
+        return true;
       }
-      temp_square += current_direction;
-      attacking_piece = board.pieces()[temp_square as usize];
     }
   }
 
   // kings
-  for index in 0..8 {
-    attacking_piece = board.pieces()[(square_120 + KING_DIRECTION[index as usize]) as usize];
-    if attacking_piece != Squares::OffBoard as i32
+  for direction in KING_DIRECTION {
+    attacking_piece = pieces[(square_120 + direction) as usize];
+    if attacking_piece != Pieces::OffBoard
       && PIECE_KING[attacking_piece as usize]
       && PIECE_COLOR[attacking_piece as usize] == side
+      && found(Squares::from_120(square_120 + direction))
     {
       return true;
     }
   }
 
-  return false;
+  false
+}
+
+/**
+ * It calls visit(square) for each square attacked by the piece on the square, looking at
+ * the given pieces array: a slider stops on the first piece met (attacked too, of any side).
+ * Nothing is visited for an empty square.
+ */
+pub fn visit_piece_attacks(
+  square: Squares,
+  pieces: &[Pieces; BOARD_SQUARE_NUMBER],
+  mut visit: impl FnMut(Squares),
+) {
+  let piece: Pieces = pieces[square as usize];
+  let directions: &[i32] = match piece {
+    Pieces::Empty | Pieces::OffBoard => return,
+    Pieces::Wp => &[9, 11],
+    Pieces::Bp => &[-9, -11],
+    piece if PIECE_KNIGHT[piece as usize] => &KNIGHT_DIRECTIONS,
+    piece
+      if PIECE_KING[piece as usize]
+        || (PIECE_ROOK_QUEEN[piece as usize] && PIECE_BISHOP_QUEEN[piece as usize]) =>
+    {
+      &KING_DIRECTION
+    }
+    piece if PIECE_ROOK_QUEEN[piece as usize] => &ROOK_DIRECTIONS,
+    _ => &BISHOP_DIRECTIONS,
+  };
+  let mut temp_square: i32;
+
+  for &direction in directions {
+    temp_square = square as i32 + direction;
+    while pieces[temp_square as usize] != Pieces::OffBoard {
+      visit(Squares::from_120(temp_square));
+      if pieces[temp_square as usize] != Pieces::Empty || !PIECE_SLIDES[piece as usize] {
+        break;
+      }
+      temp_square += direction;
+    }
+  }
+}
+
+// The side required is the attacking one
+pub fn square_attacked(square: Squares, side: Colors, board: &Board) -> bool {
+  find_attackers(square, side, board.pieces(), |_| true)
+}
+
+/**
+ * The slider of the side on the line going from the square in the direction that is behind
+ * the first piece met (of any side), so it would attack the square without that piece.
+ */
+fn xray_attacker(
+  square: Squares,
+  direction: i32,
+  side: Colors,
+  is_piece: &[bool; 13],
+  pieces: &[Pieces; BOARD_SQUARE_NUMBER],
+) -> Option<Squares> {
+  let blocker: Squares = next_piece(square, direction, pieces)?;
+  let attacker: Squares = next_piece(blocker, direction, pieces)?;
+  let piece: Pieces = pieces[attacker as usize];
+
+  if is_piece[piece as usize] && PIECE_COLOR[piece as usize] == side {
+    Some(attacker)
+  } else {
+    None
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Attack_and_Defend_Maps>
+ * <https://www.chessprogramming.org/Pin>
+ * <https://www.chessprogramming.org/X-ray_Attacks_(Bitboards)>
+ *
+ * The attack queries return sets of squares as bitboards (64 squares notation, see
+ * bitboard_squares), they are found with find_attackers, visit_piece_attacks and xray_attacker.
+ * A square that is not on the chessboard (NoSquare, OffBoard) has no attackers.
+ */
+impl Board {
+  /**
+   * The squares of the pieces of the side that attack the square (the checkers, for the
+   * square of a king).
+   */
+  pub fn attackers_to(&self, square: Squares, side: Colors) -> u64 {
+    let mut attackers: u64 = 0;
+
+    if !is_on_board(square) {
+      return attackers;
+    }
+
+    find_attackers(square, side, self.pieces(), |attacker| {
+      attackers |= square_bit(attacker);
+      false
+    });
+    attackers
+  }
+
+  /**
+   * The squares attacked by at least one piece of the side.
+   */
+  pub fn attack_map(&self, side: Colors) -> u64 {
+    let mut attacked: u64 = 0;
+
+    for square in Squares::iter() {
+      let piece: Pieces = self.piece_at(square);
+      if piece != Pieces::Empty && PIECE_COLOR[piece as usize] == side {
+        visit_piece_attacks(square, self.pieces(), |target| {
+          attacked |= square_bit(target);
+        });
+      }
+    }
+    attacked
+  }
+
+  /**
+   * The number of pieces of the side that attack each square (64 squares notation).
+   */
+  pub fn attacker_counts(&self, side: Colors) -> [u32; 64] {
+    let mut counts: [u32; 64] = [0; 64];
+
+    for square in Squares::iter() {
+      counts[square.rank() as usize * 8 + square.file() as usize] =
+        self.attackers_to(square, side).count_ones();
+    }
+    counts
+  }
+
+  /**
+   * The pieces of the side that can not leave the line between their king and an opponent
+   * slider without leaving the king in check (none if the side has no king).
+   */
+  pub fn pinned_pieces(&self, side: Colors) -> u64 {
+    let king_square: Squares = self.king_square()[side as usize];
+    let opponent: Colors = if side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    let mut pinned: u64 = 0;
+
+    if king_square == Squares::NoSquare {
+      return pinned;
+    }
+    for (directions, is_piece) in [
+      (ROOK_DIRECTIONS, PIECE_ROOK_QUEEN),
+      (BISHOP_DIRECTIONS, PIECE_BISHOP_QUEEN),
+    ] {
+      for direction in directions {
+        if let (Some(blocker), Some(_)) = (
+          next_piece(king_square, direction, self.pieces()),
+          xray_attacker(king_square, direction, opponent, &is_piece, self.pieces()),
+        ) {
+          if PIECE_COLOR[self.pieces()[blocker as usize] as usize] == side {
+            pinned |= square_bit(blocker);
+          }
+        }
+      }
+    }
+    pinned
+  }
+
+  /**
+   * The sliders of the side that attack the square through one piece of any side: the
+   * second rook or queen of a battery, or a slider behind a pinned piece.
+   */
+  pub fn xray_attackers(&self, square: Squares, side: Colors) -> u64 {
+    let mut attackers: u64 = 0;
+
+    if !is_on_board(square) {
+      return attackers;
+    }
+
+    for (directions, is_piece) in [
+      (ROOK_DIRECTIONS, PIECE_ROOK_QUEEN),
+      (BISHOP_DIRECTIONS, PIECE_BISHOP_QUEEN),
+    ] {
+      for direction in directions {
+        if let Some(attacker) = xray_attacker(square, direction, side, &is_piece, self.pieces()) {
+          attackers |= square_bit(attacker);
+        }
+      }
+    }
+    attackers
+  }
 }
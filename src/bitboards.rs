@@ -1,5 +1,5 @@
 use crate::definitions::*;
-use crate::file_rank_to_square_120;
+use crate::render::render_grid;
 
 /**
  * References:
@@ -16,44 +16,48 @@ const BIT_TABLE: [i32; 64] = [
 ];
 
 /**
- * The bitboard printed disposition follow this structure (as we play with white):
+ * The bitboard as a grid of X (bit set) and - (as we play with white), the squares are the
+ * bits in the 64 squares notation (A1 is the least significant one):
  *
- *    A B C D E F G H
+ * ```text
  * 8  - - - - - - - -
  * 7  - - - - - - - -
  * 6  - - - - - - - -
  * 5  - - - - - - - -
- * 4  - - - - - - - -
+ * 4  - - - - X - - -
  * 3  - - - - - - - -
  * 2  - - - - - - - -
  * 1  - - - - - - - -
  *
+ *    a b c d e f g h
+ * ```
  */
-pub fn print_bitboard(definitions: &Definitions, bitboard: u64) {
-  let shift_me: u64 = 1u64;
+pub fn bitboard_to_string(bitboard: u64) -> String {
+  let definitions: &Definitions = Definitions::global();
 
-  let mut square_120: i32;
-  let mut square_64: i32;
-
-  println!();
-  for rank in (ChessboardRanks::R1 as i32..=ChessboardRanks::R8 as i32).rev() {
-    for file in ChessboardFiles::A as i32..=ChessboardFiles::H as i32 {
-      square_120 = file_rank_to_square_120!(file, rank);
-      square_64 = definitions.board_120_squares_in_64_squares_notation()[square_120 as usize];
-      // bitwise operations explanation:
-      // shift_me = (H8 last bit) 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000001 (A1 first bit)
-      // square_64 = (for example) 28 (E4)
-      // shift_me << square_64 = (H8 last bit) 00000000 00000000 00000000 00000000 00001000 00000000 00000000 00000000 (A1 first bit)
-      // if bitboard has a bit in that position (& operator), an X is printed, instead a dash (-) is printed
-      if ((shift_me << square_64) & bitboard) != 0 {
-        print!(" X ");
-      } else {
-        print!(" - ");
-      }
+  render_grid(false, true, false, |square| {
+    let square_64: i32 = definitions.board_120_squares_in_64_squares_notation()[square as usize];
+    if ((1u64 << square_64) & bitboard) != 0 {
+      "X".to_string()
+    } else {
+      "-".to_string()
     }
-    println!();
+  })
+}
+
+/**
+ * The squares of the bits set in the bitboard, from A1 to H8.
+ */
+pub fn bitboard_squares(mut bitboard: u64) -> Vec<Squares> {
+  let definitions: &Definitions = Definitions::global();
+  let mut squares: Vec<Squares> = Vec::new();
+
+  while bitboard != 0 {
+    squares.push(Squares::from_120(
+      definitions.board_64_squares_in_120_squares_notation()[pop_first_bit(&mut bitboard) as usize],
+    ));
   }
-  println!();
+  squares
 }
 
 /**
@@ -62,7 +66,7 @@ pub fn print_bitboard(definitions: &Definitions, bitboard: u64) {
  * This is necessary because Rust do not allow to overflow a type size as C does.
  */
 fn get_overflow_as_32_bit(number: u64) -> u32 {
-  (number % (u32::max_value() as u64 + 1)) as u32
+  (number % (u32::MAX as u64 + 1)) as u32
 }
 
 /**
@@ -75,7 +79,7 @@ fn get_overflow_as_32_bit(number: u64) -> u32 {
  */
 pub fn pop_first_bit(bitboard: &mut u64) -> i32 {
   let b: u64 = *bitboard ^ (*bitboard - 1);
-  let fold: u64 = ((b & 0xffffffff) ^ (b >> 32)) as u64;
+  let fold: u64 = (b & 0xffffffff) ^ (b >> 32);
   *bitboard &= *bitboard - 1;
   BIT_TABLE[(get_overflow_as_32_bit(fold * 0x783a9b23) >> 26) as usize]
 }
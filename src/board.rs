@@ -1,20 +1,250 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::attack::*;
 use crate::bitboards::*;
 use crate::definitions::*;
 use crate::file_rank_to_square_120;
 use crate::hashkeys::*;
+use crate::nnue::*;
+
+/**
+ * The king destination, the rook destination and the castle of each castle move,
+ * white and black: in Chess960 too the king ends on the C or G file and the rook next to it.
+ */
+pub const STANDARD_CASTLE_ROOKS: [Squares; 4] =
+  [Squares::H1, Squares::A1, Squares::H8, Squares::A8];
+
+pub const CASTLES: [[(Squares, Squares, CastlingRights); 2]; 2] = [
+  [
+    (Squares::G1, Squares::F1, CastlingRights::WHITE_KING_SIDE),
+    (Squares::C1, Squares::D1, CastlingRights::WHITE_QUEEN_SIDE),
+  ],
+  [
+    (Squares::G8, Squares::F8, CastlingRights::BLACK_KING_SIDE),
+    (Squares::C8, Squares::D8, CastlingRights::BLACK_QUEEN_SIDE),
+  ],
+];
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Chess960_Numbering_Scheme>
+ *
+ * The placements of the two knights on the five squares left free by the bishops and the queen.
+ */
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+  (0, 1),
+  (0, 2),
+  (0, 3),
+  (0, 4),
+  (1, 2),
+  (1, 3),
+  (1, 4),
+  (2, 3),
+  (2, 4),
+  (3, 4),
+];
+
+/**
+ * The index of the castle in the castle_rooks array (the position of its bit).
+ */
+pub fn castle_index(castle: CastlingRights) -> usize {
+  castle.bits().trailing_zeros() as usize
+}
+
+/**
+ * The start position of Chess960 with the given index (0 to 959, 518 is the standard one).
+ */
+pub fn chess960_fen(index: usize) -> String {
+  let mut back_rank: [char; 8] = [' '; 8];
+  let mut rest: usize = index % 960;
+  let mut free_squares: Vec<usize>;
 
-#[derive(Copy, Clone)]
-pub struct Board<'a> {
-  definitions: &'a Definitions,
+  back_rank[2 * (rest % 4) + 1] = 'B';
+  rest /= 4;
+  back_rank[2 * (rest % 4)] = 'B';
+  rest /= 4;
+  free_squares = (0..8).filter(|&file| back_rank[file] == ' ').collect();
+  back_rank[free_squares[rest % 6]] = 'Q';
+  rest /= 6;
+  free_squares = (0..8).filter(|&file| back_rank[file] == ' ').collect();
+  back_rank[free_squares[CHESS960_KNIGHTS[rest].0]] = 'N';
+  back_rank[free_squares[CHESS960_KNIGHTS[rest].1]] = 'N';
+  // The king between the rooks
+  for (&file, piece) in (0..8)
+    .filter(|&file| back_rank[file] == ' ')
+    .collect::<Vec<usize>>()
+    .iter()
+    .zip(['R', 'K', 'R'])
+  {
+    back_rank[file] = piece;
+  }
+
+  let white: String = back_rank.iter().collect();
+  format!(
+    "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+    white.to_lowercase(),
+    white
+  )
+}
+
+/**
+ * A value kept updated incrementally by the board that does not agree with the one computed
+ * again from the pieces on the squares (see Board::validate).
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum InvariantViolation {
+  /**
+   * A square of the chessboard that holds neither a piece nor Empty (the board is not set).
+   */
+  Square {
+    square: Squares,
+    piece: Pieces,
+  },
   /**
-   * It contains the whole chess board squares, and for each square contains the chess piece on it (Empty if none).
+   * An entry of the piece list on a square without that piece, or on the square
+   * of another entry.
    */
-  pieces: [i32; BOARD_SQUARE_NUMBER],
+  PieceList {
+    piece: Pieces,
+    square: Squares,
+  },
+  PieceCount {
+    piece: Pieces,
+    expected: i32,
+    found: i32,
+  },
+  PawnBitboard {
+    color: Colors,
+    expected: u64,
+    found: u64,
+  },
+  /**
+   * The material or the number of big, major or minor pieces of a color.
+   */
+  Counter {
+    name: &'static str,
+    color: Colors,
+    expected: i32,
+    found: i32,
+  },
+  KingSquare {
+    color: Colors,
+    square: Squares,
+  },
+  Side,
+  PositionKey {
+    expected: u64,
+    found: u64,
+  },
+  PawnKey {
+    expected: u64,
+    found: u64,
+  },
+  /**
+   * The en passant square is not on the rank passed by a pawn of the opponent of the side.
+   */
+  EnPassantSquare {
+    square: Squares,
+    side: Colors,
+  },
+  /**
+   * The castle is allowed but its king or its rook are not on their start squares.
+   */
+  CastlingRights {
+    castle: CastlingRights,
+  },
+  Accumulator,
+}
+
+impl fmt::Display for InvariantViolation {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      InvariantViolation::Square { square, piece } => {
+        write!(formatter, "square: {} holds {}", square, piece)
+      }
+      InvariantViolation::PieceList { piece, square } => {
+        write!(formatter, "piece list: {} listed on {}", piece, square)
+      }
+      InvariantViolation::PieceCount {
+        piece,
+        expected,
+        found,
+      } => write!(
+        formatter,
+        "{} count: {} instead of {}",
+        piece, found, expected
+      ),
+      InvariantViolation::PawnBitboard {
+        color,
+        expected,
+        found,
+      } => write!(
+        formatter,
+        "{:?} pawns bitboard: {:#x} instead of {:#x}",
+        color, found, expected
+      ),
+      InvariantViolation::Counter {
+        name,
+        color,
+        expected,
+        found,
+      } => write!(
+        formatter,
+        "{:?} {}: {} instead of {}",
+        color, name, found, expected
+      ),
+      InvariantViolation::KingSquare { color, square } => {
+        write!(formatter, "{:?} king square: no king on {}", color, square)
+      }
+      InvariantViolation::Side => write!(formatter, "side: no side to move"),
+      InvariantViolation::PositionKey { expected, found } => {
+        write!(
+          formatter,
+          "position key: {:#x} instead of {:#x}",
+          found, expected
+        )
+      }
+      InvariantViolation::PawnKey { expected, found } => {
+        write!(
+          formatter,
+          "pawn key: {:#x} instead of {:#x}",
+          found, expected
+        )
+      }
+      InvariantViolation::EnPassantSquare { square, side } => {
+        write!(
+          formatter,
+          "en passant square: {} with {:?} to move",
+          square, side
+        )
+      }
+      InvariantViolation::CastlingRights { castle } => {
+        write!(
+          formatter,
+          "castling rights: {} without its king and rook",
+          castle
+        )
+      }
+      InvariantViolation::Accumulator => write!(formatter, "accumulator: not the refreshed one"),
+    }
+  }
+}
+
+#[derive(Clone)]
+pub struct Board {
+  definitions: &'static Definitions,
+  /**
+   * It contains the whole chess board squares, and for each square contains the chess piece on it
+   * (Empty if none, OffBoard for the squares outside the chessboard).
+   */
+  pieces: [Pieces; BOARD_SQUARE_NUMBER],
   /**
    * The pawns are stored in a bitboard where each square is a bit - hence 64 bits
    * The reason for using bitboards for the pawns was twofold.
    * 1. To show bitboards, so setting moving and clearing bits.
    * 2. It makes evaluation of pawn structures easier as you can use bit masks.
+   *
    * So we'll have three bitboards, once with the white pawns, another with the black pawns
    * and a third with both color pawns (intersection).
    * We could rapresent every chess piece on a bitboard (maybe in the future will be done), but
@@ -41,11 +271,37 @@ pub struct Board<'a> {
    * looking back and determining repetitions when we'll come to storing our history.
    */
   total_half_moves: i32,
-  castel_permission: u8,
+  /**
+   * The half moves played before the position of the FEN, from its move number and side,
+   * so that to_fen writes the move number of the game.
+   */
+  fen_half_moves: i32,
+  castling_rights: CastlingRights,
+  /**
+   * The start square of the rook of each castle, in the order of the CastlingRights bits
+   * (see castle_index).
+   * They are read from the FEN, because in Chess960 the rooks can start on any file.
+   */
+  castle_rooks: [Squares; 4],
+  /**
+   * When a piece moves from or to a square, the castling rights are bitwise ANDed with
+   * the value of the square: the king square removes both the castles of its color,
+   * the square of a castle rook removes its castle, every other square keeps them all.
+   */
+  castle_permission_mask: [CastlingRights; BOARD_SQUARE_NUMBER],
+  /**
+   * The castles are written as the king that takes its rook (UCI_Chess960 option).
+   */
+  chess960: bool,
   /**
    * It's a unique key which is generated for each game position.
    */
   position_key: u64,
+  /**
+   * The key of the pawns only, it indexes the pawn hash table: positions with the same pawns
+   * have the same pawn structure evaluation.
+   */
+  pawn_key: u64,
   /**
    * The number of pieces that are on the board. Indexed by piece type (Pieces enum).
    */
@@ -68,7 +324,7 @@ pub struct Board<'a> {
   material: [i32; 2],
   history: [Undo; MAX_GAME_HALF_MOVES],
   /**
-   * It's an array of 13 piece types, eachone contains the list of pieces (10).
+   * It's an array of 13 piece types, eachone contains the list of squares of the pieces (10).
    * Why ten elements? Because for example at the start of the game you have 2 rooks, assumed
    * that you promote all the pawns at rook, you can have at maximum 10 equal pieces.
    * Use case: to set the first white knight to E3 -> pieces_list\[Pieces::Wn\]\[0\] = ChessboardFiles::E + ChessboardRanks::R3
@@ -76,12 +332,24 @@ pub struct Board<'a> {
    * less empty squares and to get all the pieces on the board is sufficient to loop every piece type untill
    * we get NoSquare as square value. So it's increment the performance of the search move engine.
    */
-  pieces_list: [[i32; 10]; 13],
+  pieces_list: [[Squares; 10]; 13],
+  /**
+   * The NNUE network, if the evaluation uses it. The accumulators are a stack: make_move pushes
+   * the accumulator of the new position and take_move pops it, so the last one is always
+   * the accumulator of the current position.
+   */
+  network: Option<Arc<Network>>,
+  accumulators: Vec<Accumulator>,
 }
 
-impl<'a> Board<'a> {
-  pub fn new(definitions: &'a Definitions) -> Board<'a> {
-    let pieces: [i32; BOARD_SQUARE_NUMBER] = [0; BOARD_SQUARE_NUMBER];
+impl Board {
+  /**
+   * An empty board with White to move (see reset_board), a position has to be set with
+   * parse_fen (or use from_fen).
+   */
+  pub fn new() -> Board {
+    let definitions: &'static Definitions = Definitions::global();
+    let pieces: [Pieces; BOARD_SQUARE_NUMBER] = [Pieces::OffBoard; BOARD_SQUARE_NUMBER];
     let pawns: [u64; 3] = [0; 3];
     let king_square: [Squares; 2] = [Squares::NoSquare; 2];
     let side: Colors = Colors::White;
@@ -89,20 +357,25 @@ impl<'a> Board<'a> {
     let fifty_full_moves: i32 = 0;
     let actual_half_moves: i32 = 0;
     let total_half_moves: i32 = 0;
-    let castel_permission: u8 = 0;
+    let fen_half_moves: i32 = 0;
+    let castling_rights: CastlingRights = CastlingRights::NONE;
+    let castle_rooks: [Squares; 4] = STANDARD_CASTLE_ROOKS;
+    let castle_permission_mask: [CastlingRights; BOARD_SQUARE_NUMBER] =
+      [CastlingRights::ALL; BOARD_SQUARE_NUMBER];
+    let chess960: bool = false;
     let position_key: u64 = 0;
+    let pawn_key: u64 = 0;
     let actual_pieces_number: [i32; 13] = [0; 13];
     let big_pieces_number: [i32; 2] = [0; 2];
     let major_pieces_number: [i32; 2] = [0; 2];
     let minor_pieces_number: [i32; 2] = [0; 2];
     let material: [i32; 2] = [0; 2];
     let history: [Undo; MAX_GAME_HALF_MOVES] = [Undo::new(); MAX_GAME_HALF_MOVES];
-    let pieces_list: [[i32; 10]; 13] = [
-      [0; 10], [0; 10], [0; 10], [0; 10], [0; 10], [0; 10], [0; 10], [0; 10], [0; 10], [0; 10],
-      [0; 10], [0; 10], [0; 10],
-    ];
+    let pieces_list: [[Squares; 10]; 13] = [[Squares::NoSquare; 10]; 13];
+    let network: Option<Arc<Network>> = None;
+    let accumulators: Vec<Accumulator> = Vec::new();
 
-    Board {
+    let mut board: Board = Board {
       definitions,
       pieces,
       pawns,
@@ -112,8 +385,13 @@ impl<'a> Board<'a> {
       fifty_full_moves,
       actual_half_moves,
       total_half_moves,
-      castel_permission,
+      fen_half_moves,
+      castling_rights,
+      castle_rooks,
+      castle_permission_mask,
+      chess960,
       position_key,
+      pawn_key,
       actual_pieces_number,
       big_pieces_number,
       major_pieces_number,
@@ -121,17 +399,129 @@ impl<'a> Board<'a> {
       material,
       history,
       pieces_list,
-    }
+      network,
+      accumulators,
+    };
+    board.reset_board();
+    board
+  }
+
+  pub fn from_fen(fen: &str) -> Result<Board, String> {
+    let mut board: Board = Board::new();
+
+    board.parse_fen(fen)?;
+    Ok(board)
+  }
+
+  pub(crate) fn definitions(&self) -> &'static Definitions {
+    self.definitions
   }
 
-  pub fn pieces(&self) -> [i32; BOARD_SQUARE_NUMBER] {
-    self.pieces
+  pub fn pieces(&self) -> &[Pieces; BOARD_SQUARE_NUMBER] {
+    &self.pieces
+  }
+
+  pub fn piece_at(&self, square: Squares) -> Pieces {
+    self.pieces[square as usize]
   }
 
   pub fn pawns(&self) -> [u64; 3] {
     self.pawns
   }
 
+  pub fn king_square(&self) -> [Squares; 2] {
+    self.king_square
+  }
+
+  pub fn fifty_full_moves(&self) -> i32 {
+    self.fifty_full_moves
+  }
+
+  pub fn actual_half_moves(&self) -> i32 {
+    self.actual_half_moves
+  }
+
+  /**
+   * The search sets it to zero before starting, so that it counts the half moves from the root.
+   */
+  pub fn set_actual_half_moves(&mut self, actual_half_moves: i32) {
+    self.actual_half_moves = actual_half_moves;
+  }
+
+  pub fn total_half_moves(&self) -> i32 {
+    self.total_half_moves
+  }
+
+  pub fn position_key(&self) -> u64 {
+    self.position_key
+  }
+
+  pub fn pawn_key(&self) -> u64 {
+    self.pawn_key
+  }
+
+  pub fn actual_pieces_number(&self) -> &[i32; 13] {
+    &self.actual_pieces_number
+  }
+
+  pub fn big_pieces_number(&self) -> [i32; 2] {
+    self.big_pieces_number
+  }
+
+  pub fn major_pieces_number(&self) -> [i32; 2] {
+    self.major_pieces_number
+  }
+
+  pub fn minor_pieces_number(&self) -> [i32; 2] {
+    self.minor_pieces_number
+  }
+
+  pub fn material(&self) -> [i32; 2] {
+    self.material
+  }
+
+  pub fn pieces_list(&self) -> &[[Squares; 10]; 13] {
+    &self.pieces_list
+  }
+
+  /**
+   * The played half moves, from the first one to the last one.
+   */
+  pub fn history(&self) -> &[Undo] {
+    &self.history[0..self.total_half_moves as usize]
+  }
+
+  pub fn network(&self) -> Option<&Network> {
+    self.network.as_deref()
+  }
+
+  /**
+   * It sets the network used by the evaluation (None for the classical evaluation),
+   * computing the accumulator of the current position.
+   */
+  pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+    self.network = network;
+    self.refresh_accumulators();
+  }
+
+  /**
+   * The accumulator of the current position, there is one only if a network is set.
+   */
+  pub fn accumulator(&self) -> &Accumulator {
+    self
+      .accumulators
+      .last()
+      .expect("Error: no accumulator without a network")
+  }
+
+  fn refresh_accumulators(&mut self) {
+    self.accumulators.clear();
+    if let Some(network) = self.network.as_deref() {
+      let accumulator: Accumulator = network.refresh_accumulator(self);
+      self.accumulators.push(accumulator);
+    }
+  }
+
   pub fn side(&self) -> Colors {
     self.side
   }
@@ -140,435 +530,1035 @@ impl<'a> Board<'a> {
     self.en_passant_square
   }
 
-  pub fn castel_permission(&self) -> u8 {
-    self.castel_permission
+  pub fn castling_rights(&self) -> CastlingRights {
+    self.castling_rights
   }
 
+  pub fn castle_rooks(&self) -> [Squares; 4] {
+    self.castle_rooks
+  }
+
+  pub fn chess960(&self) -> bool {
+    self.chess960
+  }
+
+  pub fn set_chess960(&mut self, chess960: bool) {
+    self.chess960 = chess960;
+  }
+
+  /**
+   * The start and the destination squares of the rook moved by the castle whose king arrives on the square.
+   */
+  pub fn castle_rook_squares(&self, king_to: Squares) -> (Squares, Squares) {
+    let castles: &[(Squares, Squares, CastlingRights); 2] = if (king_to as i32) < Squares::A2 as i32
+    {
+      &CASTLES[Colors::White as usize]
+    } else {
+      &CASTLES[Colors::Black as usize]
+    };
+    let (_, rook_to, castle): (Squares, Squares, CastlingRights) = if king_to == castles[0].0 {
+      castles[0]
+    } else {
+      castles[1]
+    };
+
+    (self.castle_rooks[castle_index(castle)], rook_to)
+  }
+
+  /**
+   * The move in long algebraic notation: with the UCI_Chess960 option a castle is written
+   * as the king that takes its rook, ex. "e1h1" instead of "e1g1".
+   */
+  pub fn move_to_algebraic(&self, mov_e: Move) -> String {
+    if self.chess960 && mov_e.is_castle() {
+      let (rook_from, _): (Squares, Squares) = self.castle_rook_squares(mov_e.to_square());
+      return Move::quiet(mov_e.from_square(), rook_from).to_string();
+    }
+    mov_e.to_string()
+  }
+
+  /**
+   * It empties the board: no pieces, White to move, no castles and no en passant square,
+   * with the keys and the accumulator of that position (so that it's valid, see validate).
+   */
   pub fn reset_board(&mut self) {
-    self.pieces = [Squares::OffBoard as i32; BOARD_SQUARE_NUMBER];
-    for index in 0..64 {
-      self.pieces[self.definitions.board_64_squares_in_120_squares_notation()[index] as usize] =
-        Pieces::Empty as i32;
+    self.pieces = [Pieces::OffBoard; BOARD_SQUARE_NUMBER];
+    for square in Squares::iter() {
+      self.pieces[square as usize] = Pieces::Empty;
     }
     self.big_pieces_number = [0; 2];
     self.major_pieces_number = [0; 2];
     self.minor_pieces_number = [0; 2];
+    self.material = [0; 2];
     self.pawns = [0u64; 3];
     self.actual_pieces_number = [0; 13];
+    self.pieces_list = [[Squares::NoSquare; 10]; 13];
     self.king_square = [Squares::NoSquare; 2];
-    self.side = Colors::Both;
+    self.side = Colors::White;
     self.en_passant_square = Squares::NoSquare;
     self.fifty_full_moves = 0;
     self.actual_half_moves = 0;
     self.total_half_moves = 0;
-    self.castel_permission = 0;
-    self.position_key = 0u64;
-  }
-
-  // fen = Forsyth–Edwards Notation
-  pub fn parse_fen(&mut self, fen: &str) {
-    let mut rank: ChessboardRanks = ChessboardRanks::R8;
-    let mut file: ChessboardFiles = ChessboardFiles::A;
-    let mut piece: Pieces = Pieces::Empty;
-    let mut count: i32;
-    let mut char_index: usize = 0;
-    let mut square_120: i32;
-    let mut square_64: i32;
-    let mut fen_char: char = fen.as_bytes()[0] as char;
-    let mut stop: bool = false;
-    let mut index: i32 = 0;
+    self.fen_half_moves = 0;
+    self.castling_rights = CastlingRights::NONE;
+    self.castle_rooks = STANDARD_CASTLE_ROOKS;
+    self.castle_permission_mask = [CastlingRights::ALL; BOARD_SQUARE_NUMBER];
+    self.position_key = generate_position_key(self.definitions, self);
+    self.pawn_key = 0u64;
+    self.refresh_accumulators();
+  }
+
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Forsyth-Edwards_Notation>
+   *
+   * It sets up the position of the FEN (fen = Forsyth–Edwards Notation): the pieces,
+   * the side, the castles, the en passant square and the move counters, that can be missing
+   * (as in the EPD positions) and are then 0 and 1. A move number 0 is read as 1.
+   * If the FEN is not well formed the board is left empty and the error is returned.
+   */
+  pub fn parse_fen(&mut self, fen: &str) -> Result<(), String> {
+    let result: Result<(), String> = self.parse_fen_fields(fen);
+
+    if result.is_err() {
+      self.reset_board();
+    }
+    result
+  }
+
+  fn parse_fen_fields(&mut self, fen: &str) -> Result<(), String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let mut pieces_number: [usize; 13] = [0; 13];
+    let mut file: i32;
+    let mut piece: Pieces;
 
     self.reset_board();
 
-    while (rank as i32 >= ChessboardRanks::R1 as i32) && fen_char != ' ' {
-      count = 1;
-      match fen_char {
-        'p' => piece = Pieces::Bp,
-        'r' => piece = Pieces::Br,
-        'n' => piece = Pieces::Bn,
-        'b' => piece = Pieces::Bb,
-        'k' => piece = Pieces::Bk,
-        'q' => piece = Pieces::Bq,
-        'P' => piece = Pieces::Wp,
-        'R' => piece = Pieces::Wr,
-        'N' => piece = Pieces::Wn,
-        'B' => piece = Pieces::Wb,
-        'K' => piece = Pieces::Wk,
-        'Q' => piece = Pieces::Wq,
-        '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' => {
-          piece = Pieces::Empty;
-          count = fen_char as i32 - '0' as i32;
+    if fields.len() < 4 {
+      return Err(format!("FEN error: \"{}\" has less than 4 fields", fen));
+    }
+
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    if ranks.len() != 8 {
+      return Err(format!("FEN error: \"{}\" has not 8 ranks", fields[0]));
+    }
+    // The first rank of the FEN is the 8th one
+    for (rank, rank_text) in (0..8).rev().zip(ranks) {
+      file = 0;
+      for fen_char in rank_text.chars() {
+        /*
+          A digit is the number of empty squares to skip, a letter a piece on the square:
+          both move on the file, that has to end on the H file at the end of the rank.
+        */
+        if let Some(empty_squares) = fen_char
+          .to_digit(10)
+          .filter(|digit| (1..=8).contains(digit))
+        {
+          file += empty_squares as i32;
+          continue;
         }
-        '/' | ' ' => {
-          rank = ChessboardRanks::from_u32(rank as u32 - 1);
-          file = ChessboardFiles::A;
+        piece = match fen_char.to_string().parse::<Pieces>() {
+          Ok(piece) if piece != Pieces::Empty => piece,
+          _ => return Err(format!("FEN error: unknown piece '{}'", fen_char)),
+        };
+        // The piece lists have a place for 10 pieces of each type (2 and 8 promoted pawns)
+        pieces_number[piece as usize] += 1;
+        if file >= 8 || pieces_number[piece as usize] > 10 {
+          return Err(format!("FEN error: \"{}\" has too many pieces", fields[0]));
         }
-        _ => println!("FEN error"),
+        self.pieces[file_rank_to_square_120!(file, rank) as usize] = piece;
+        file += 1;
       }
-      if fen_char != '/' && fen_char != ' ' {
-        /*
-          Image to have the File A and the Rank 4, and the actual fen_char is 4.
-          We loop from 0 to 3 and the File goes from A to E (0 = A->B, 1 = B->C, 2 = C->D, 3 = D->E).
-          If in that File on that Rank there is a piece then it's added, if not we go on the next File.
-          "count" equal to 1 will add a piece, if it's different will skip the Files as much as count.
-        */
-        for _ in 0..count {
-          square_64 = rank as i32 * 8 + file as i32;
-          square_120 =
-            self.definitions.board_64_squares_in_120_squares_notation()[square_64 as usize];
-          if piece != Pieces::Empty {
-            self.pieces[square_120 as usize] = piece as i32;
-          }
-          file = ChessboardFiles::from_u32(file as u32 + 1);
+      if file != 8 {
+        return Err(format!("FEN error: \"{}\" has not 8 files", rank_text));
+      }
+    }
+    // The move generation and the search need the king of each side
+    for (king, color) in [(Pieces::Wk, "white"), (Pieces::Bk, "black")] {
+      if pieces_number[king as usize] != 1 {
+        return Err(format!(
+          "FEN error: \"{}\" has {} {} kings",
+          fields[0], pieces_number[king as usize], color
+        ));
+      }
+    }
+
+    self.side = match fields[1] {
+      "w" => Colors::White,
+      "b" => Colors::Black,
+      side => return Err(format!("FEN error: unknown side \"{}\"", side)),
+    };
+
+    if fields[2] != "-" {
+      for fen_char in fields[2].chars() {
+        if !matches!(fen_char, 'K' | 'Q' | 'k' | 'q' | 'A'..='H' | 'a'..='h') {
+          return Err(format!("FEN error: unknown castle '{}'", fen_char));
+        }
+        if let Some((castle, rook_square)) = self.parse_castle_character(fen_char) {
+          self.castling_rights.insert(castle);
+          self.castle_rooks[castle_index(castle)] = rook_square;
         }
       }
-      // read the next character.
-      char_index += 1;
-      fen_char = fen.as_bytes()[char_index] as char;
     }
-    // "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR " string has been read (even space).
-    char_index += 1;
-    fen_char = fen.as_bytes()[char_index] as char;
 
-    self.side = if fen_char == 'w' {
+    // The en passant square is empty and behind a pawn of the side that has just moved
+    if fields[3] != "-" {
+      let (rank, pawn_offset, pawn): (ChessboardRanks, i32, Pieces) = if self.side == Colors::White
+      {
+        (ChessboardRanks::R6, -10, Pieces::Bp)
+      } else {
+        (ChessboardRanks::R3, 10, Pieces::Wp)
+      };
+      self.en_passant_square = fields[3]
+        .parse::<Squares>()
+        .ok()
+        .filter(|&square| {
+          square.rank() as i32 == rank as i32
+            && self.pieces[square as usize] == Pieces::Empty
+            && self.pieces[(square as i32 + pawn_offset) as usize] == pawn
+        })
+        .ok_or(format!(
+          "FEN error: wrong en passant square \"{}\"",
+          fields[3]
+        ))?;
+    }
+
+    // The halfmove clock (of the fifty moves rule) and the move number of the game
+    if let Some(clock) = fields.get(4) {
+      self.fifty_full_moves = clock
+        .parse::<u16>()
+        .map_err(|_| format!("FEN error: wrong halfmove clock \"{}\"", clock))?
+        as i32;
+    }
+    if let Some(move_number) = fields.get(5) {
+      let move_number: i32 = move_number
+        .parse::<u16>()
+        .map_err(|_| format!("FEN error: wrong move number \"{}\"", move_number))?
+        .max(1) as i32;
+      self.fen_half_moves = (move_number - 1) * 2 + (self.side == Colors::Black) as i32;
+    }
+
+    self.position_key = generate_position_key(self.definitions, self);
+    self.pawn_key = generate_pawn_key(self.definitions, self);
+    self.update_lists_material();
+    self.update_castle_permission_mask();
+    self.refresh_accumulators();
+
+    Ok(())
+  }
+
+  /**
+   * The rank and the file of the king of the color on its first rank, if it's there.
+   */
+  fn first_rank_king(&self, color: Colors) -> Option<(i32, i32)> {
+    let (rank, king): (i32, Pieces) = if color == Colors::White {
+      (ChessboardRanks::R1 as i32, Pieces::Wk)
+    } else {
+      (ChessboardRanks::R8 as i32, Pieces::Bk)
+    };
+
+    (ChessboardFiles::A as i32..=ChessboardFiles::H as i32)
+      .find(|&file| self.pieces[file_rank_to_square_120!(file, rank) as usize] == king)
+      .map(|file| (rank, file))
+  }
+
+  /**
+   * References:
+   * <https://en.wikipedia.org/wiki/X-FEN>
+   *
+   * A character of the castle field: KQkq are the castles with the outermost rook on that side
+   * of the king (X-FEN), the file letters (Shredder-FEN, and X-FEN for an inner rook) give
+   * the file of the rook. It returns the castle with the start square of its rook.
+   */
+  fn parse_castle_character(&self, character: char) -> Option<(CastlingRights, Squares)> {
+    let color: Colors = if character.is_ascii_uppercase() {
       Colors::White
     } else {
       Colors::Black
     };
+    let rook: Pieces = if color == Colors::White {
+      Pieces::Wr
+    } else {
+      Pieces::Br
+    };
+    let (rank, king_file): (i32, i32) = self.first_rank_king(color)?;
+    let is_rook = |file: &i32| self.pieces[file_rank_to_square_120!(*file, rank) as usize] == rook;
+    let rook_file: i32 = match character.to_ascii_lowercase() {
+      'k' => (king_file + 1..=ChessboardFiles::H as i32)
+        .rev()
+        .find(is_rook)?,
+      'q' => (ChessboardFiles::A as i32..king_file).find(is_rook)?,
+      file @ 'a'..='h' => Some(file as i32 - 'a' as i32).filter(is_rook)?,
+      _ => return None,
+    };
+    let castle: CastlingRights = CASTLES[color as usize][(rook_file < king_file) as usize].2;
+
+    Some((
+      castle,
+      Squares::from_120(file_rank_to_square_120!(rook_file, rank)),
+    ))
+  }
 
-    // "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w" string has been read,
-    // we need to skip a space and position on the new character, so +2.
-    char_index += 2;
-    fen_char = fen.as_bytes()[char_index] as char;
-    while index < 4 && !stop {
-      if fen_char == ' ' {
-        stop = true;
+  /**
+   * The castle character of the FEN: K or Q (k or q) if the rook is the outermost one
+   * on that side of the king, the file of the rook otherwise.
+   */
+  fn castle_character(&self, castle: CastlingRights) -> char {
+    let rook_square: Squares = self.castle_rooks[castle_index(castle)];
+    let white: bool = castle_index(castle) < 2;
+    let king_side: bool = castle_index(castle).is_multiple_of(2);
+    let rook: Pieces = self.pieces[rook_square as usize];
+    let rook_file: i32 = self.definitions.files_board()[rook_square as usize];
+    let rank: i32 = self.definitions.ranks_board()[rook_square as usize];
+    let outer_files = if king_side {
+      rook_file + 1..=ChessboardFiles::H as i32
+    } else {
+      ChessboardFiles::A as i32..=rook_file - 1
+    };
+    let character: char = if outer_files
+      .into_iter()
+      .any(|file| self.pieces[file_rank_to_square_120!(file, rank) as usize] == rook)
+    {
+      FILE_CHARACTERS[rook_file as usize]
+    } else if king_side {
+      'k'
+    } else {
+      'q'
+    };
+
+    if white {
+      character.to_ascii_uppercase()
+    } else {
+      character
+    }
+  }
+
+  fn update_castle_permission_mask(&mut self) {
+    for (color, castles) in CASTLES.iter().enumerate() {
+      if let Some((rank, file)) = self.first_rank_king(if color == 0 {
+        Colors::White
       } else {
-        match fen_char {
-          'K' => self.castel_permission |= Castle::WhiteKingSideCastel as u8,
-          'Q' => self.castel_permission |= Castle::WhiteQueenSideCastel as u8,
-          'k' => self.castel_permission |= Castle::BlackKingSideCastel as u8,
-          'q' => self.castel_permission |= Castle::BlackQueenSideCastel as u8,
-          _ => {}
-        }
-        char_index += 1;
-        fen_char = fen.as_bytes()[char_index] as char;
+        Colors::Black
+      }) {
+        self.castle_permission_mask[file_rank_to_square_120!(file, rank) as usize] &=
+          !(castles[0].2 | castles[1].2);
+      }
+      for &(_, _, castle) in castles {
+        self.castle_permission_mask[self.castle_rooks[castle_index(castle)] as usize] &= !castle;
       }
-      index += 1;
-    }
-    // "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq " string has been read (even space).
-    char_index += 1;
-    fen_char = fen.as_bytes()[char_index] as char;
-    if fen_char != '-' {
-      file = ChessboardFiles::from_u32(fen.as_bytes()[char_index] as u32 - 'a' as u32);
-      rank = ChessboardRanks::from_u32(fen.as_bytes()[char_index + 1] as u32 - '1' as u32);
-      self.en_passant_square =
-        Squares::from_u32(file_rank_to_square_120!(file as i32, rank as i32) as u32);
     }
-    self.position_key = generate_position_key(self.definitions, self);
-    self.update_lists_material();
   }
 
-  pub fn print_board(&self) {
-    let mut piece: i32;
-    let mut square_120: i32;
+  /**
+   * The FEN of the position, with the halfmove clock and the move number of the game
+   * (the ones read by parse_fen, updated by the moves made since).
+   */
+  pub fn to_fen(&self) -> String {
+    let mut fen: String = String::new();
+    let mut empty_squares: u8;
+    let mut piece: Pieces;
 
-    println!("\nGame Board:\n");
-    for rank in (ChessboardRanks::R1 as i32..=ChessboardRanks::R8 as i32).rev() {
-      print!("{}   ", rank + 1);
-      for file in ChessboardFiles::A as i32..=ChessboardFiles::H as i32 {
-        square_120 = file_rank_to_square_120!(file, rank);
-        piece = self.pieces[square_120 as usize];
-        print!("{}   ", PIECE_CHARACTERS[piece as usize]);
+    for rank in ChessboardRanks::iter().rev() {
+      empty_squares = 0;
+      for file in ChessboardFiles::iter() {
+        piece = self.piece_at(Squares::from_file_rank(file, rank));
+        if piece == Pieces::Empty {
+          empty_squares += 1;
+        } else {
+          if empty_squares > 0 {
+            fen.push((b'0' + empty_squares) as char);
+            empty_squares = 0;
+          }
+          fen.push_str(&piece.to_string());
+        }
+      }
+      if empty_squares > 0 {
+        fen.push((b'0' + empty_squares) as char);
+      }
+      if rank != ChessboardRanks::R1 {
+        fen.push('/');
       }
-      println!();
     }
 
-    print!("\n    ");
-    for file in ChessboardFiles::A as i32..=ChessboardFiles::H as i32 {
-      print!("{}   ", ('a' as u8 + file as u8) as char);
+    fen.push(' ');
+    fen.push(SIDE_CHARACTERS[self.side as usize]);
+    fen.push(' ');
+    if self.castling_rights.is_empty() {
+      fen.push('-');
+    }
+    for castle in self.castling_rights.iter() {
+      fen.push(self.castle_character(castle));
     }
-    println!();
+    fen.push(' ');
+    fen.push_str(&self.en_passant_square.to_string());
 
-    println!("side:{}", SIDE_CHARACTERS[self.side() as usize]);
-    println!("enPas:{}", self.en_passant_square as i32);
-    println!(
-      "castle:{}{}{}{}",
-      if (self.castel_permission & Castle::WhiteKingSideCastel as u8) != 0 {
-        'K'
-      } else {
-        '-'
-      },
-      if (self.castel_permission & Castle::WhiteQueenSideCastel as u8) != 0 {
-        'Q'
-      } else {
-        '-'
-      },
-      if (self.castel_permission & Castle::BlackKingSideCastel as u8) != 0 {
-        'k'
-      } else {
-        '-'
-      },
-      if (self.castel_permission & Castle::BlackQueenSideCastel as u8) != 0 {
-        'q'
-      } else {
-        '-'
-      },
-    );
-    println!("{:X?}", self.position_key);
+    format!(
+      "{} {} {}",
+      fen,
+      self.fifty_full_moves,
+      1 + (self.fen_half_moves + self.total_half_moves) / 2
+    )
+  }
+
+  pub fn print_board(&self) {
+    println!("\n{}", self);
   }
 
   pub fn update_lists_material(&mut self) {
     let mut piece: Pieces;
     let mut color: Colors;
 
-    for square_120 in 0..BOARD_SQUARE_NUMBER {
-      if self.pieces[square_120 as usize] != Squares::OffBoard as i32 {
-        piece = Pieces::from_u32(self.pieces[square_120 as usize] as u32);
-        if piece != Pieces::Empty {
-          color = PIECE_COLOR[piece as usize];
+    for square in Squares::iter() {
+      let square_120: usize = square as usize;
+      piece = self.pieces[square_120];
+      if piece != Pieces::Empty {
+        color = PIECE_COLOR[piece as usize];
 
-          if PIECE_BIG[piece as usize] == true {
-            self.big_pieces_number[color as usize] += 1;
-          }
-          if PIECE_MAJOR[piece as usize] == true {
-            self.major_pieces_number[color as usize] += 1;
-          }
-          if PIECE_MINOR[piece as usize] == true {
-            self.minor_pieces_number[color as usize] += 1;
-          }
+        if PIECE_BIG[piece as usize] {
+          self.big_pieces_number[color as usize] += 1;
+        }
+        if PIECE_MAJOR[piece as usize] {
+          self.major_pieces_number[color as usize] += 1;
+        }
+        if PIECE_MINOR[piece as usize] {
+          self.minor_pieces_number[color as usize] += 1;
+        }
 
-          self.material[color as usize] += PIECE_VALUE[piece as usize];
-          self.pieces_list[piece as usize][self.actual_pieces_number[piece as usize] as usize] =
-            piece as i32;
-          self.actual_pieces_number[piece as usize] += 1;
+        self.material[color as usize] += PIECE_VALUE[piece as usize];
+        self.pieces_list[piece as usize][self.actual_pieces_number[piece as usize] as usize] =
+          Squares::from_120(square_120 as i32);
+        self.actual_pieces_number[piece as usize] += 1;
 
-          if piece == Pieces::Wk {
-            self.king_square[Colors::White as usize] = Squares::from_u32(square_120 as u32);
-          }
-          if piece == Pieces::Bk {
-            self.king_square[Colors::Black as usize] = Squares::from_u32(square_120 as u32);
-          }
+        if piece == Pieces::Wk {
+          self.king_square[Colors::White as usize] = square;
+        }
+        if piece == Pieces::Bk {
+          self.king_square[Colors::Black as usize] = square;
+        }
 
-          if piece == Pieces::Wp {
-            set_bit_to_bitboard(
-              self.definitions,
-              &mut self.pawns[Colors::White as usize],
-              self.definitions.board_120_squares_in_64_squares_notation()[square_120 as usize],
-            );
-            set_bit_to_bitboard(
-              self.definitions,
-              &mut self.pawns[Colors::Both as usize],
-              self.definitions.board_120_squares_in_64_squares_notation()[square_120 as usize],
-            );
-          } else if piece == Pieces::Bp {
-            set_bit_to_bitboard(
-              self.definitions,
-              &mut self.pawns[Colors::Black as usize],
-              self.definitions.board_120_squares_in_64_squares_notation()[square_120 as usize],
-            );
-            set_bit_to_bitboard(
-              self.definitions,
-              &mut self.pawns[Colors::Both as usize],
-              self.definitions.board_120_squares_in_64_squares_notation()[square_120 as usize],
-            );
-          }
+        if piece == Pieces::Wp {
+          set_bit_to_bitboard(
+            self.definitions,
+            &mut self.pawns[Colors::White as usize],
+            self.definitions.board_120_squares_in_64_squares_notation()[square_120],
+          );
+          set_bit_to_bitboard(
+            self.definitions,
+            &mut self.pawns[Colors::Both as usize],
+            self.definitions.board_120_squares_in_64_squares_notation()[square_120],
+          );
+        } else if piece == Pieces::Bp {
+          set_bit_to_bitboard(
+            self.definitions,
+            &mut self.pawns[Colors::Black as usize],
+            self.definitions.board_120_squares_in_64_squares_notation()[square_120],
+          );
+          set_bit_to_bitboard(
+            self.definitions,
+            &mut self.pawns[Colors::Both as usize],
+            self.definitions.board_120_squares_in_64_squares_notation()[square_120],
+          );
         }
       }
     }
   }
 
-  pub fn check_board(&self) {
-    let mut temp_big_piece: [i32; 2] = [0; 2];
-    let mut temp_major_piece: [i32; 2] = [0; 2];
-    let mut temp_minor_piece: [i32; 2] = [0; 2];
-    let mut temp_material: [i32; 3] = [0; 3];
-    let mut temp_pawns: [u64; 3] = [0; 3];
-    let mut temp_actual_piece_number: [i32; 13] = [0; 13];
-    let mut temp_piece: i32;
-    let mut piece_count: i32;
-    let mut square_120: i32;
-    let mut square_64: i32;
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Debugging#Verification>
+   *
+   * It computes again from the pieces on the squares all the state that the board keeps
+   * updated incrementally (piece lists, counters, pawn bitboards, material, king squares,
+   * keys and accumulator) and checks en passant square and castling rights, returning every
+   * value that does not agree.
+   */
+  pub fn validate(&self) -> Result<(), Vec<InvariantViolation>> {
+    let mut violations: Vec<InvariantViolation> = Vec::new();
+    let mut piece_number: [i32; 13] = [0; 13];
+    let mut big_pieces: [i32; 2] = [0; 2];
+    let mut major_pieces: [i32; 2] = [0; 2];
+    let mut minor_pieces: [i32; 2] = [0; 2];
+    let mut material: [i32; 2] = [0; 2];
+    let mut pawns: [u64; 3] = [0; 3];
+    let mut listed_squares: Vec<i32> = Vec::new();
+    let mut piece: Pieces;
     let mut color: Colors;
 
-    temp_pawns[Colors::White as usize] = self.pawns[Colors::White as usize];
-    temp_pawns[Colors::Black as usize] = self.pawns[Colors::Black as usize];
-    temp_pawns[Colors::Both as usize] = self.pawns[Colors::Both as usize];
-
-    // Check piece list
-    for piece in Pieces::Wp as i32..=Pieces::Bk as i32 {
-      for actual_piece_number in 0..self.actual_pieces_number[piece as usize] {
-        square_120 = self.pieces_list[piece as usize][actual_piece_number as usize];
-        // If the "piece" is a white pawn, so the "square_120" has to be a white pawn
-        if square_120 != piece {
-          panic!(
-            "Error: {} != {}, self.pieces_list is not aligned",
-            square_120, piece
-          );
+    for square in Squares::iter() {
+      piece = self.pieces[square as usize];
+      if piece == Pieces::OffBoard {
+        violations.push(InvariantViolation::Square { square, piece });
+        continue;
+      }
+      piece_number[piece as usize] += 1;
+      if piece == Pieces::Empty {
+        continue;
+      }
+      color = PIECE_COLOR[piece as usize];
+      big_pieces[color as usize] += PIECE_BIG[piece as usize] as i32;
+      major_pieces[color as usize] += PIECE_MAJOR[piece as usize] as i32;
+      minor_pieces[color as usize] += PIECE_MINOR[piece as usize] as i32;
+      material[color as usize] += PIECE_VALUE[piece as usize];
+      if PIECE_PAWN[piece as usize] {
+        let square_64: i32 =
+          self.definitions.board_120_squares_in_64_squares_notation()[square as usize];
+        set_bit_to_bitboard(self.definitions, &mut pawns[color as usize], square_64);
+        set_bit_to_bitboard(
+          self.definitions,
+          &mut pawns[Colors::Both as usize],
+          square_64,
+        );
+      }
+    }
+    // The keys and the accumulator are computed only from the pieces
+    let squares_set: bool = violations.is_empty();
+
+    // Every entry of the piece list is on its own square with that piece on it, and with the
+    // counts equal to the board ones every piece of the board is in the list
+    for piece in Pieces::iter() {
+      if piece_number[piece as usize] != self.actual_pieces_number[piece as usize] {
+        violations.push(InvariantViolation::PieceCount {
+          piece,
+          expected: piece_number[piece as usize],
+          found: self.actual_pieces_number[piece as usize],
+        });
+      }
+      for index in 0..self.actual_pieces_number[piece as usize].clamp(0, 10) {
+        let square: Squares = self.pieces_list[piece as usize][index as usize];
+        let square_120: i32 = square as i32;
+        if square == Squares::NoSquare
+          || self.pieces[square_120 as usize] != piece
+          || listed_squares.contains(&square_120)
+        {
+          violations.push(InvariantViolation::PieceList { piece, square });
         }
+        listed_squares.push(square_120);
       }
     }
 
-    // Check piece count and other counters
-    for square_64 in 0..64 {
-      square_120 = self.definitions.board_64_squares_in_120_squares_notation()[square_64];
-      temp_piece = self.pieces[square_120 as usize];
-      temp_actual_piece_number[temp_piece as usize] += 1;
-      color = PIECE_COLOR[temp_piece as usize];
-      if PIECE_BIG[temp_piece as usize] == true {
-        temp_big_piece[color as usize] += 1;
+    for color in [Colors::White, Colors::Black, Colors::Both] {
+      if pawns[color as usize] != self.pawns[color as usize] {
+        violations.push(InvariantViolation::PawnBitboard {
+          color,
+          expected: pawns[color as usize],
+          found: self.pawns[color as usize],
+        });
       }
-      if PIECE_MAJOR[temp_piece as usize] == true {
-        temp_major_piece[color as usize] += 1;
+    }
+
+    for color in [Colors::White, Colors::Black] {
+      for (name, expected, found) in [
+        ("material", material, self.material),
+        ("big pieces", big_pieces, self.big_pieces_number),
+        ("major pieces", major_pieces, self.major_pieces_number),
+        ("minor pieces", minor_pieces, self.minor_pieces_number),
+      ] {
+        if expected[color as usize] != found[color as usize] {
+          violations.push(InvariantViolation::Counter {
+            name,
+            color,
+            expected: expected[color as usize],
+            found: found[color as usize],
+          });
+        }
+      }
+
+      let king: Pieces = if color == Colors::White {
+        Pieces::Wk
+      } else {
+        Pieces::Bk
+      };
+      // A board without that king (an empty board) has no king square
+      if (piece_number[king as usize] == 0 && self.king_square[color as usize] != Squares::NoSquare)
+        || (piece_number[king as usize] != 0
+          && self.pieces[self.king_square[color as usize] as usize] != king)
+      {
+        violations.push(InvariantViolation::KingSquare {
+          color,
+          square: self.king_square[color as usize],
+        });
+      }
+    }
+
+    if self.side == Colors::Both {
+      violations.push(InvariantViolation::Side);
+    }
+    if squares_set && generate_position_key(self.definitions, self) != self.position_key {
+      violations.push(InvariantViolation::PositionKey {
+        expected: generate_position_key(self.definitions, self),
+        found: self.position_key,
+      });
+    }
+    if squares_set && generate_pawn_key(self.definitions, self) != self.pawn_key {
+      violations.push(InvariantViolation::PawnKey {
+        expected: generate_pawn_key(self.definitions, self),
+        found: self.pawn_key,
+      });
+    }
+
+    // The en passant square is behind the pawn that has just moved, so on the third rank
+    // of the opponent of the side to move
+    if self.en_passant_square != Squares::NoSquare
+      && self.en_passant_square.rank() as i32
+        != if self.side == Colors::White {
+          ChessboardRanks::R6 as i32
+        } else {
+          ChessboardRanks::R3 as i32
+        }
+    {
+      violations.push(InvariantViolation::EnPassantSquare {
+        square: self.en_passant_square,
+        side: self.side,
+      });
+    }
+
+    for castle in self.castling_rights.iter() {
+      if !self.castle_has_king_and_rook(castle) {
+        violations.push(InvariantViolation::CastlingRights { castle });
       }
-      if PIECE_MINOR[temp_piece as usize] == true {
-        temp_minor_piece[color as usize] += 1;
+    }
+
+    if let Some(network) = self.network.as_deref().filter(|_| squares_set) {
+      if network.refresh_accumulator(self) != *self.accumulator() {
+        violations.push(InvariantViolation::Accumulator);
       }
-      temp_material[color as usize] += PIECE_VALUE[temp_piece as usize];
     }
 
-    for piece in Pieces::Wp as i32..=Pieces::Bk as i32 {
-      // The number of pieces that have been found on the board, have to be equal to the actual pieces number
-      if temp_actual_piece_number[piece as usize] != self.actual_pieces_number[piece as usize] {
+    if violations.is_empty() {
+      Ok(())
+    } else {
+      Err(violations)
+    }
+  }
+
+  /**
+   * The king is on its first rank and the rook of the castle on its start square, on the same
+   * side of the king where the king arrives.
+   */
+  pub(crate) fn castle_has_king_and_rook(&self, castle: CastlingRights) -> bool {
+    let color: Colors = if castle_index(castle) < 2 {
+      Colors::White
+    } else {
+      Colors::Black
+    };
+    let (rank, rook): (ChessboardRanks, Pieces) = if color == Colors::White {
+      (ChessboardRanks::R1, Pieces::Wr)
+    } else {
+      (ChessboardRanks::R8, Pieces::Br)
+    };
+    let king_side: bool = castle_index(castle).is_multiple_of(2);
+    let king_square: Squares = self.king_square[color as usize];
+    let rook_square: Squares = self.castle_rooks[castle_index(castle)];
+
+    king_square.rank() as i32 == rank as i32
+      && rook_square.rank() as i32 == rank as i32
+      && self.pieces[rook_square as usize] == rook
+      && (rook_square.file() as i32 > king_square.file() as i32) == king_side
+  }
+
+  /**
+   * In the debug builds the board is validated after every move made or taken back,
+   * so that an incremental update that goes wrong is found where it happens.
+   */
+  fn debug_validate(&self, mov_e: Move) {
+    if cfg!(debug_assertions) {
+      if let Err(violations) = self.validate() {
         panic!(
-          "Error: {} != {}, self.actual_pieces_number is not aligned",
-          temp_actual_piece_number[piece as usize], self.actual_pieces_number[piece as usize]
+          "Error: the board is not valid after {}: {}",
+          mov_e,
+          violations
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
         );
       }
     }
+  }
 
-    // Check bitboards count
-    piece_count = count_bits(temp_pawns[Colors::White as usize]) as i32;
-    if piece_count != self.actual_pieces_number[Pieces::Wp as usize] {
-      panic!(
-        "Error: {} != {}, self.actual_pieces_number is not aligned",
-        piece_count,
-        self.actual_pieces_number[Pieces::Wp as usize]
+  fn hash_piece(&mut self, piece: Pieces, square_120: i32) {
+    self.position_key ^= self.definitions.piece_keys()[piece as usize][square_120 as usize];
+    if PIECE_PAWN[piece as usize] {
+      self.pawn_key ^= self.definitions.piece_keys()[piece as usize][square_120 as usize];
+    }
+  }
+
+  fn hash_castle(&mut self) {
+    self.position_key ^= self.definitions.castle_keys()[self.castling_rights.bits() as usize];
+  }
+
+  fn hash_side(&mut self) {
+    self.position_key ^= self.definitions.side_key();
+  }
+
+  fn hash_en_passant(&mut self) {
+    self.position_key ^=
+      self.definitions.piece_keys()[Pieces::Empty as usize][self.en_passant_square as usize];
+  }
+
+  /**
+   * It removes the piece on the square from every board structure (and from the position key).
+   */
+  fn clear_piece(&mut self, square_120: i32) {
+    let piece: Pieces = self.pieces[square_120 as usize];
+    let color: Colors = PIECE_COLOR[piece as usize];
+    let mut temp_piece_index: i32 = -1;
+
+    self.hash_piece(piece, square_120);
+
+    self.pieces[square_120 as usize] = Pieces::Empty;
+    self.material[color as usize] -= PIECE_VALUE[piece as usize];
+
+    if PIECE_BIG[piece as usize] {
+      self.big_pieces_number[color as usize] -= 1;
+      if PIECE_MAJOR[piece as usize] {
+        self.major_pieces_number[color as usize] -= 1;
+      } else {
+        self.minor_pieces_number[color as usize] -= 1;
+      }
+    } else {
+      let square_64: i32 =
+        self.definitions.board_120_squares_in_64_squares_notation()[square_120 as usize];
+      clear_bit_to_bitboard(self.definitions, &mut self.pawns[color as usize], square_64);
+      clear_bit_to_bitboard(
+        self.definitions,
+        &mut self.pawns[Colors::Both as usize],
+        square_64,
       );
     }
-    piece_count = count_bits(temp_pawns[Colors::Black as usize]) as i32;
-    if piece_count != self.actual_pieces_number[Pieces::Bp as usize] {
-      panic!(
-        "Error: {} != {}, self.actual_pieces_number is not aligned",
-        piece_count,
-        self.actual_pieces_number[Pieces::Bp as usize]
+
+    for index in 0..self.actual_pieces_number[piece as usize] {
+      if self.pieces_list[piece as usize][index as usize] as i32 == square_120 {
+        temp_piece_index = index;
+        break;
+      }
+    }
+
+    // The last piece of the list takes the place of the removed one
+    self.actual_pieces_number[piece as usize] -= 1;
+    self.pieces_list[piece as usize][temp_piece_index as usize] =
+      self.pieces_list[piece as usize][self.actual_pieces_number[piece as usize] as usize];
+    self.pieces_list[piece as usize][self.actual_pieces_number[piece as usize] as usize] =
+      Squares::NoSquare;
+  }
+
+  /**
+   * It adds the piece on the square to every board structure (and to the position key).
+   */
+  fn add_piece(&mut self, square_120: i32, piece: Pieces) {
+    let color: Colors = PIECE_COLOR[piece as usize];
+
+    self.hash_piece(piece, square_120);
+
+    self.pieces[square_120 as usize] = piece;
+
+    if PIECE_BIG[piece as usize] {
+      self.big_pieces_number[color as usize] += 1;
+      if PIECE_MAJOR[piece as usize] {
+        self.major_pieces_number[color as usize] += 1;
+      } else {
+        self.minor_pieces_number[color as usize] += 1;
+      }
+    } else {
+      let square_64: i32 =
+        self.definitions.board_120_squares_in_64_squares_notation()[square_120 as usize];
+      set_bit_to_bitboard(self.definitions, &mut self.pawns[color as usize], square_64);
+      set_bit_to_bitboard(
+        self.definitions,
+        &mut self.pawns[Colors::Both as usize],
+        square_64,
       );
     }
-    piece_count = count_bits(temp_pawns[Colors::Both as usize]) as i32;
-    if piece_count
-      != (self.actual_pieces_number[Pieces::Bp as usize]
-        + self.actual_pieces_number[Pieces::Wp as usize])
-    {
-      panic!(
-        "Error: {} != {}, self.actual_pieces_number is not aligned",
-        piece_count,
-        (self.actual_pieces_number[Pieces::Bp as usize]
-          + self.actual_pieces_number[Pieces::Wp as usize])
+
+    self.material[color as usize] += PIECE_VALUE[piece as usize];
+    self.pieces_list[piece as usize][self.actual_pieces_number[piece as usize] as usize] =
+      Squares::from_120(square_120);
+    self.actual_pieces_number[piece as usize] += 1;
+  }
+
+  /**
+   * It moves the piece from a square to another one, the "to" square has to be empty.
+   */
+  fn move_piece(&mut self, from: i32, to: i32) {
+    let piece: Pieces = self.pieces[from as usize];
+    let color: Colors = PIECE_COLOR[piece as usize];
+
+    self.hash_piece(piece, from);
+    self.pieces[from as usize] = Pieces::Empty;
+
+    self.hash_piece(piece, to);
+    self.pieces[to as usize] = piece;
+
+    if !PIECE_BIG[piece as usize] {
+      let from_64: i32 = self.definitions.board_120_squares_in_64_squares_notation()[from as usize];
+      let to_64: i32 = self.definitions.board_120_squares_in_64_squares_notation()[to as usize];
+      clear_bit_to_bitboard(self.definitions, &mut self.pawns[color as usize], from_64);
+      clear_bit_to_bitboard(
+        self.definitions,
+        &mut self.pawns[Colors::Both as usize],
+        from_64,
+      );
+      set_bit_to_bitboard(self.definitions, &mut self.pawns[color as usize], to_64);
+      set_bit_to_bitboard(
+        self.definitions,
+        &mut self.pawns[Colors::Both as usize],
+        to_64,
       );
     }
 
-    // Check bitboards square
-    while temp_pawns[Colors::White as usize] != 0 {
-      square_64 = pop_first_bit(&mut temp_pawns[Colors::White as usize]);
-      if self.pieces
-        [self.definitions.board_64_squares_in_120_squares_notation()[square_64 as usize] as usize]
-        != Pieces::Wp as i32
-      {
-        panic!(
-          "Error: {} != {}, self.pieces is not aligned",
-          self.pieces[self.definitions.board_64_squares_in_120_squares_notation()
-            [square_64 as usize] as usize],
-          Pieces::Wp as i32
-        );
+    for index in 0..self.actual_pieces_number[piece as usize] {
+      if self.pieces_list[piece as usize][index as usize] as i32 == from {
+        self.pieces_list[piece as usize][index as usize] = Squares::from_120(to);
+        break;
       }
     }
+  }
 
-    while temp_pawns[Colors::Black as usize] != 0 {
-      square_64 = pop_first_bit(&mut temp_pawns[Colors::Black as usize]);
-      if self.pieces
-        [self.definitions.board_64_squares_in_120_squares_notation()[square_64 as usize] as usize]
-        != Pieces::Bp as i32
-      {
-        panic!(
-          "Error: {} != {}, self.pieces is not aligned",
-          self.pieces[self.definitions.board_64_squares_in_120_squares_notation()
-            [square_64 as usize] as usize],
-          Pieces::Bp as i32
-        );
+  /**
+   * It makes the move on the board, saving the previous state in the history.
+   * If the move leaves the king of the side that moved in check, the move is taken back
+   * and false is returned (the move is not legal).
+   */
+  pub fn make_move(&mut self, mov_e: Move) -> bool {
+    let from: i32 = mov_e.from_square() as i32;
+    let to: i32 = mov_e.to_square() as i32;
+    let side: Colors = self.side;
+
+    self.history[self.total_half_moves as usize].save(
+      mov_e.mov_e(),
+      self.castling_rights,
+      self.en_passant_square,
+      self.fifty_full_moves,
+      self.position_key,
+    );
+
+    if mov_e.is_en_passant() {
+      if side == Colors::White {
+        self.clear_piece(to - 10);
+      } else {
+        self.clear_piece(to + 10);
       }
+    } else if mov_e.is_castle() {
+      // In Chess960 the king and the rook can arrive on the square of each other,
+      // so both are removed before placing them
+      let (rook_from, rook_to): (Squares, Squares) = self.castle_rook_squares(mov_e.to_square());
+      let king: Pieces = self.pieces[from as usize];
+      let rook: Pieces = self.pieces[rook_from as usize];
+      self.clear_piece(from);
+      self.clear_piece(rook_from as i32);
+      self.add_piece(rook_to as i32, rook);
+      self.add_piece(to, king);
     }
 
-    while temp_pawns[Colors::Both as usize] != 0 {
-      square_64 = pop_first_bit(&mut temp_pawns[Colors::Both as usize]);
-      if self.pieces
-        [self.definitions.board_64_squares_in_120_squares_notation()[square_64 as usize] as usize]
-        != Pieces::Bp as i32
-        && self.pieces
-          [self.definitions.board_64_squares_in_120_squares_notation()[square_64 as usize] as usize]
-          != Pieces::Wp as i32
-      {
-        panic!(
-          "Error: {} != {} && {} != {}, self.pieces is not aligned",
-          self.pieces[self.definitions.board_64_squares_in_120_squares_notation()
-            [square_64 as usize] as usize],
-          Pieces::Bp as i32,
-          self.pieces[self.definitions.board_64_squares_in_120_squares_notation()
-            [square_64 as usize] as usize],
-          Pieces::Wp as i32,
-        );
+    if self.en_passant_square != Squares::NoSquare {
+      self.hash_en_passant();
+    }
+    self.hash_castle();
+
+    self.castling_rights &= self.castle_permission_mask[from as usize];
+    self.castling_rights &= self.castle_permission_mask[to as usize];
+    self.en_passant_square = Squares::NoSquare;
+
+    self.hash_castle();
+
+    self.fifty_full_moves += 1;
+
+    if mov_e.captured_piece().is_some() {
+      self.clear_piece(to);
+      self.fifty_full_moves = 0;
+    }
+
+    self.total_half_moves += 1;
+    self.actual_half_moves += 1;
+
+    if PIECE_PAWN[self.pieces[from as usize] as usize] {
+      self.fifty_full_moves = 0;
+      if mov_e.is_double_push() {
+        let en_passant_square: i32 = if side == Colors::White {
+          from + 10
+        } else {
+          from - 10
+        };
+        self.en_passant_square = Squares::try_from(en_passant_square as u32)
+          .expect("Error: the pawn start skips a square of the chessboard");
+        self.hash_en_passant();
       }
     }
 
-    // Check the material
-    if temp_material[Colors::White as usize] != self.material[Colors::White as usize]
-      || temp_material[Colors::Black as usize] != self.material[Colors::Black as usize]
-    {
-      panic!("Error: self.material is not aligned")
+    if !mov_e.is_castle() {
+      self.move_piece(from, to);
     }
-    if temp_big_piece[Colors::White as usize] != self.big_pieces_number[Colors::White as usize]
-      || temp_big_piece[Colors::Black as usize] != self.big_pieces_number[Colors::Black as usize]
-    {
-      panic!("Error: self.big_pieces_number is not aligned")
+
+    if let Some(promoted) = mov_e.promoted() {
+      self.clear_piece(to);
+      self.add_piece(to, promoted);
     }
-    if temp_major_piece[Colors::White as usize] != self.major_pieces_number[Colors::White as usize]
-      || temp_major_piece[Colors::Black as usize]
-        != self.major_pieces_number[Colors::Black as usize]
-    {
-      panic!("Error: self.major_pieces_number is not aligned")
+
+    if PIECE_KING[self.pieces[to as usize] as usize] {
+      self.king_square[side as usize] =
+        Squares::try_from(to as u32).expect("Error: the king moves on the chessboard");
     }
-    if temp_minor_piece[Colors::White as usize] != self.minor_pieces_number[Colors::White as usize]
-      || temp_minor_piece[Colors::Black as usize]
-        != self.minor_pieces_number[Colors::Black as usize]
-    {
-      panic!("Error: self.minor_pieces_number is not aligned")
+
+    self.side = if side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    self.hash_side();
+
+    if let Some(network) = self.network.as_deref() {
+      let accumulator: Accumulator =
+        network.update_accumulator(self, self.accumulator(), mov_e, side);
+      self.accumulators.push(accumulator);
+    }
+    self.debug_validate(mov_e);
+
+    if square_attacked(self.king_square[side as usize], self.side, self) {
+      self.take_move();
+      return false;
+    }
+
+    true
+  }
+
+  /**
+   * It takes back the last move made, restoring the state saved in the history.
+   */
+  pub fn take_move(&mut self) {
+    self.total_half_moves -= 1;
+    self.actual_half_moves -= 1;
+
+    let undo: Undo = self.history[self.total_half_moves as usize];
+    let mov_e: Move = Move::new(undo.half_move(), 0);
+    let from: i32 = mov_e.from_square() as i32;
+    let to: i32 = mov_e.to_square() as i32;
+
+    if self.network.is_some() {
+      self.accumulators.pop();
     }
 
-    // Check the side and position key
-    if self.side != Colors::White && self.side != Colors::Black {
-      panic!("Error: self.side is not aligned")
+    if self.en_passant_square != Squares::NoSquare {
+      self.hash_en_passant();
     }
-    if generate_position_key(self.definitions, &self) != self.position_key {
-      panic!("Error: self.position_key is not aligned")
+    self.hash_castle();
+
+    self.castling_rights = undo.castle_permission();
+    self.fifty_full_moves = undo.fifty_full_moves();
+    self.en_passant_square = undo.en_passant_square();
+
+    if self.en_passant_square != Squares::NoSquare {
+      self.hash_en_passant();
     }
+    self.hash_castle();
 
-    // Check en passant square
-    if !(self.en_passant_square == Squares::NoSquare
-      || (self.definitions.ranks_board()[self.en_passant_square as usize]
-        == ChessboardRanks::R6 as i32
-        && self.side == Colors::White)
-      || (self.definitions.ranks_board()[self.en_passant_square as usize]
-        == ChessboardRanks::R3 as i32
-        && self.side == Colors::Black))
-    {
-      panic!("Error: self.en_passant_square is not aligned")
+    self.side = if self.side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    self.hash_side();
+
+    if mov_e.is_en_passant() {
+      if self.side == Colors::White {
+        self.add_piece(to - 10, Pieces::Bp);
+      } else {
+        self.add_piece(to + 10, Pieces::Wp);
+      }
     }
 
-    // Check king square
-    if self.pieces[self.king_square[Colors::White as usize] as usize] != Pieces::Wk as i32 {
-      panic!("Error: self.king_square white is not aligned")
+    if mov_e.is_castle() {
+      let (rook_from, rook_to): (Squares, Squares) = self.castle_rook_squares(mov_e.to_square());
+      let king: Pieces = self.pieces[to as usize];
+      let rook: Pieces = self.pieces[rook_to as usize];
+      self.clear_piece(to);
+      self.clear_piece(rook_to as i32);
+      self.add_piece(rook_from as i32, rook);
+      self.add_piece(from, king);
+    } else {
+      self.move_piece(to, from);
     }
-    if self.pieces[self.king_square[Colors::Black as usize] as usize] != Pieces::Bk as i32 {
-      panic!("Error: self.king_square black is not aligned")
+
+    if PIECE_KING[self.pieces[from as usize] as usize] {
+      self.king_square[self.side as usize] =
+        Squares::try_from(from as u32).expect("Error: the king moves on the chessboard");
+    }
+
+    if let Some(captured) = mov_e.captured_piece() {
+      self.add_piece(to, captured);
     }
+
+    if let Some(promoted) = mov_e.promoted() {
+      self.clear_piece(from);
+      self.add_piece(
+        from,
+        if PIECE_COLOR[promoted as usize] == Colors::White {
+          Pieces::Wp
+        } else {
+          Pieces::Bp
+        },
+      );
+    }
+    self.debug_validate(mov_e);
+  }
+
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Null_Move>
+   *
+   * The side to move passes, used by the null move pruning. The fifty moves counter is
+   * set to zero so that no repetition is found across the null move.
+   */
+  pub fn make_null_move(&mut self) {
+    self.history[self.total_half_moves as usize].save(
+      Move::NO_MOVE.mov_e(),
+      self.castling_rights,
+      self.en_passant_square,
+      self.fifty_full_moves,
+      self.position_key,
+    );
+
+    if self.en_passant_square != Squares::NoSquare {
+      self.hash_en_passant();
+    }
+    self.en_passant_square = Squares::NoSquare;
+    self.fifty_full_moves = 0;
+
+    self.side = if self.side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    self.hash_side();
+
+    self.total_half_moves += 1;
+    self.actual_half_moves += 1;
+    self.debug_validate(Move::NO_MOVE);
+  }
+
+  pub fn take_null_move(&mut self) {
+    self.total_half_moves -= 1;
+    self.actual_half_moves -= 1;
+
+    let undo: Undo = self.history[self.total_half_moves as usize];
+
+    self.fifty_full_moves = undo.fifty_full_moves();
+    self.en_passant_square = undo.en_passant_square();
+    if self.en_passant_square != Squares::NoSquare {
+      self.hash_en_passant();
+    }
+
+    self.side = if self.side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    self.hash_side();
+    self.debug_validate(Move::NO_MOVE);
+  }
+}
+
+impl Default for Board {
+  fn default() -> Board {
+    Board::new()
   }
 }
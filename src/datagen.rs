@@ -0,0 +1,549 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::bitboards::*;
+use crate::board::*;
+use crate::definitions::*;
+use crate::movegen::*;
+use crate::pvtable::*;
+use crate::search::*;
+
+/**
+ * An opening is played again if the search finds one side better by more than this score.
+ */
+const MAX_OPENING_SCORE: i32 = 1000;
+/**
+ * The game is won when both sides agree for WIN_ADJUDICATION_PLIES plies that the score is
+ * at least WIN_ADJUDICATION_SCORE, and it's drawn when after DRAW_ADJUDICATION_START plies
+ * the score stays within DRAW_ADJUDICATION_SCORE for DRAW_ADJUDICATION_PLIES plies.
+ */
+const WIN_ADJUDICATION_SCORE: i32 = 2000;
+const WIN_ADJUDICATION_PLIES: i32 = 4;
+const DRAW_ADJUDICATION_SCORE: i32 = 10;
+const DRAW_ADJUDICATION_PLIES: i32 = 8;
+const DRAW_ADJUDICATION_START: i32 = 80;
+const MAX_GAME_PLIES: i32 = 400;
+const HASH_MEGABYTES: usize = 16;
+/**
+ * Every position is written in RECORD_SIZE bytes (see pack_position).
+ */
+const RECORD_SIZE: usize = 32;
+const NO_EN_PASSANT: u8 = 64;
+
+/**
+ * The datagen settings, given on the command line after the output file:
+ * datagen <output file> [--games <n>] [--threads <n>] [--nodes <n>] [--random-plies <n>]
+ *         [--seed <n>] [--text] [--chess960]
+ */
+struct DatagenSettings {
+  output_path: String,
+  games: usize,
+  threads: usize,
+  nodes: u64,
+  random_plies: usize,
+  seed: u64,
+  text: bool,
+  /**
+   * The games start from a random Chess960 position instead of the standard one.
+   */
+  chess960: bool,
+}
+
+impl DatagenSettings {
+  fn from_arguments(arguments: &[String]) -> Result<DatagenSettings, String> {
+    let mut settings: DatagenSettings = DatagenSettings {
+      output_path: String::new(),
+      games: 1000,
+      threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+      nodes: 5000,
+      random_plies: 8,
+      seed: rand::thread_rng().gen(),
+      text: false,
+      chess960: false,
+    };
+    let mut tokens = arguments.iter();
+
+    while let Some(token) = tokens.next() {
+      match token.as_str() {
+        "--games" | "--threads" | "--nodes" | "--random-plies" | "--seed" => {
+          let value: u64 = tokens
+            .next()
+            .ok_or_else(|| format!("{} needs a value", token))?
+            .parse::<u64>()
+            .map_err(|error| format!("{}: {}", token, error))?;
+          match token.as_str() {
+            "--games" => settings.games = value as usize,
+            "--threads" => settings.threads = (value as usize).max(1),
+            "--nodes" => settings.nodes = value.max(1),
+            "--random-plies" => settings.random_plies = value as usize,
+            _ => settings.seed = value,
+          }
+        }
+        "--text" => settings.text = true,
+        "--chess960" => settings.chess960 = true,
+        _ => settings.output_path = token.clone(),
+      }
+    }
+
+    if settings.output_path.is_empty() {
+      return Err(String::from(
+        "usage: datagen <output file> [--games <n>] [--threads <n>] [--nodes <n>] \
+         [--random-plies <n>] [--seed <n>] [--text] [--chess960]",
+      ));
+    }
+    Ok(settings)
+  }
+}
+
+/**
+ * A recorded position of a game, written when the result of the game is known.
+ */
+struct GamePosition {
+  packed: [u8; RECORD_SIZE],
+  fen: String,
+  score: i32,
+}
+
+/**
+ * The compact format, little endian:
+ * bytes 0-7 the occupied squares bitboard (a1 = bit 0), bytes 8-23 the pieces (Pieces enum values)
+ * on the occupied squares from a1 to h8, four bits each with the low nibble first,
+ * bytes 24-25 the score (i16, white point of view), byte 26 the result (0 black won, 1 draw,
+ * 2 white won), byte 27 the side to move (0 white, 1 black), byte 28 the castle permission,
+ * byte 29 the en passant square (0-63, 64 if none), byte 30 the fifty moves counter,
+ * byte 31 is not used.
+ */
+fn pack_position(board: &Board, white_score: i32) -> [u8; RECORD_SIZE] {
+  let definitions: &Definitions = board.definitions();
+  let mut packed: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+  let mut occupancy: u64 = 0;
+  let mut pieces_number: usize = 0;
+  let mut square_64: i32;
+  let mut piece: Pieces;
+
+  for square in Squares::iter() {
+    if board.piece_at(square) != Pieces::Empty {
+      occupancy |= 1u64 << definitions.board_120_squares_in_64_squares_notation()[square as usize];
+    }
+  }
+  packed[0..8].copy_from_slice(&occupancy.to_le_bytes());
+
+  while occupancy != 0 {
+    square_64 = pop_first_bit(&mut occupancy);
+    piece = board.pieces()
+      [definitions.board_64_squares_in_120_squares_notation()[square_64 as usize] as usize];
+    packed[8 + pieces_number / 2] |= (piece as u8) << (4 * (pieces_number % 2));
+    pieces_number += 1;
+  }
+
+  packed[24..26].copy_from_slice(&(white_score as i16).to_le_bytes());
+  packed[27] = board.side() as u8;
+  packed[28] = board.castling_rights().bits();
+  packed[29] = if board.en_passant_square() == Squares::NoSquare {
+    NO_EN_PASSANT
+  } else {
+    definitions.board_120_squares_in_64_squares_notation()[board.en_passant_square() as usize] as u8
+  };
+  packed[30] = board.fifty_full_moves().min(u8::MAX as i32) as u8;
+
+  packed
+}
+
+/**
+ * What each thread of the generator needs to play its games.
+ */
+struct DatagenThread {
+  board: Board,
+  info: SearchInfo,
+  table: HashTable,
+  rng: StdRng,
+}
+
+impl DatagenThread {
+  fn new(seed: u64) -> DatagenThread {
+    DatagenThread {
+      board: Board::new(),
+      info: SearchInfo::new(),
+      table: HashTable::new(HASH_MEGABYTES),
+      rng: StdRng::seed_from_u64(seed),
+    }
+  }
+
+  fn search(&mut self, nodes: u64) -> (Move, i32) {
+    self.info.clear_limits();
+    self.info.set_node_limit(nodes);
+    search_best_move(&mut self.board, &mut self.info, &mut self.table)
+  }
+
+  /**
+   * Random legal moves from the start position (a random one in Chess960), the opening
+   * is played again if the game is over or too unbalanced at its end.
+   */
+  fn play_opening(&mut self, settings: &DatagenSettings) {
+    let mut legal_moves: Vec<Move>;
+    let mut plies: usize;
+
+    'opening: loop {
+      if settings.chess960 {
+        self
+          .board
+          .parse_fen(&chess960_fen(self.rng.gen_range(0..960)))
+          .expect("Error: the Chess960 start positions are valid FENs");
+      } else {
+        self
+          .board
+          .parse_fen(START_FEN)
+          .expect("Error: the start position is a valid FEN");
+      }
+      // One more ply half of the times, so that both sides start the game
+      plies = settings.random_plies + self.rng.gen_range(0..2);
+      for _ in 0..plies {
+        legal_moves = generate_legal_moves(&mut self.board);
+        if legal_moves.is_empty() {
+          continue 'opening;
+        }
+        self
+          .board
+          .make_move(legal_moves[self.rng.gen_range(0..legal_moves.len())]);
+      }
+      if generate_legal_moves(&mut self.board).is_empty() {
+        continue;
+      }
+      self.table.clear();
+      if self.search(settings.nodes).1.abs() <= MAX_OPENING_SCORE {
+        return;
+      }
+    }
+  }
+
+  /**
+   * It plays a game searching every move at fixed nodes, and returns the positions to record
+   * with the result (white point of view). The positions in check, those where the best move is
+   * a capture or a promotion, the mate scores, the positions already found and those where the
+   * search did not complete a depth are not recorded.
+   */
+  fn play_game(
+    &mut self,
+    settings: &DatagenSettings,
+    seen_positions: &Mutex<HashSet<u64>>,
+  ) -> (Vec<GamePosition>, f32) {
+    let mut positions: Vec<GamePosition> = Vec::new();
+    let mut win_plies: i32 = 0;
+    let mut draw_plies: i32 = 0;
+    let mut best_move: Move;
+    let mut score: i32;
+    let mut white_score: i32;
+    let mut in_check: bool;
+
+    self.play_opening(settings);
+    self.table.clear();
+
+    for ply in 0.. {
+      in_check = is_in_check(&self.board);
+      if generate_legal_moves(&mut self.board).is_empty() {
+        if !in_check {
+          return (positions, 0.5);
+        }
+        return (
+          positions,
+          if self.board.side() == Colors::White {
+            0.0
+          } else {
+            1.0
+          },
+        );
+      }
+      if self.board.fifty_full_moves() >= 100
+        || is_repetition(&self.board)
+        || insufficient_material(&self.board)
+        || ply >= MAX_GAME_PLIES
+      {
+        return (positions, 0.5);
+      }
+
+      (best_move, score) = self.search(settings.nodes);
+      white_score = if self.board.side() == Colors::White {
+        score
+      } else {
+        -score
+      };
+
+      // The score of a search stopped before the first depth is not known
+      if self.info.completed_depth() == 0 {
+        self.board.make_move(best_move);
+        continue;
+      }
+      if !in_check
+        && !best_move.is_capture()
+        && best_move.promoted().is_none()
+        && score.abs() < IS_MATE
+        && seen_positions
+          .lock()
+          .expect("datagen lock poisoned")
+          .insert(self.board.position_key())
+      {
+        positions.push(GamePosition {
+          packed: pack_position(&self.board, white_score),
+          fen: self.board.to_fen(),
+          score: white_score,
+        });
+      }
+
+      win_plies = if score.abs() >= WIN_ADJUDICATION_SCORE {
+        win_plies + 1
+      } else {
+        0
+      };
+      if win_plies >= WIN_ADJUDICATION_PLIES {
+        return (positions, if white_score > 0 { 1.0 } else { 0.0 });
+      }
+      draw_plies = if ply >= DRAW_ADJUDICATION_START && score.abs() <= DRAW_ADJUDICATION_SCORE {
+        draw_plies + 1
+      } else {
+        0
+      };
+      if draw_plies >= DRAW_ADJUDICATION_PLIES {
+        return (positions, 0.5);
+      }
+
+      self.board.make_move(best_move);
+    }
+
+    unreachable!()
+  }
+}
+
+fn write_game(
+  writer: &mut impl Write,
+  positions: &[GamePosition],
+  result: f32,
+  text: bool,
+) -> std::io::Result<()> {
+  let mut packed: [u8; RECORD_SIZE];
+
+  for position in positions {
+    if text {
+      writeln!(
+        writer,
+        "{} | {} | {:.1}",
+        position.fen, position.score, result
+      )?;
+    } else {
+      packed = position.packed;
+      packed[26] = (result * 2.0) as u8;
+      writer.write_all(&packed)?;
+    }
+  }
+
+  Ok(())
+}
+
+/**
+ * The datagen subcommand: self-play games from random openings at fixed nodes per move,
+ * played by many threads, whose quiet positions are written with the search score and
+ * the result of the game, in the compact format or as "fen | score | result" lines (--text).
+ */
+pub fn datagen(arguments: &[String]) {
+  let settings: DatagenSettings = match DatagenSettings::from_arguments(arguments) {
+    Ok(settings) => settings,
+    Err(error) => {
+      println!("{}", error);
+      return;
+    }
+  };
+  let file: File = match File::create(&settings.output_path) {
+    Ok(file) => file,
+    Err(error) => {
+      println!("{}: {}", settings.output_path, error);
+      return;
+    }
+  };
+  let writer: Mutex<BufWriter<File>> = Mutex::new(BufWriter::new(file));
+  let seen_positions: Mutex<HashSet<u64>> = Mutex::new(HashSet::new());
+  let started_games: AtomicUsize = AtomicUsize::new(0);
+  let finished_games: AtomicUsize = AtomicUsize::new(0);
+  let written_positions: AtomicUsize = AtomicUsize::new(0);
+  let start_time: Instant = Instant::now();
+
+  println!(
+    "games {} threads {} nodes {} seed {}",
+    settings.games, settings.threads, settings.nodes, settings.seed
+  );
+
+  thread::scope(|scope| {
+    for thread_index in 0..settings.threads {
+      let settings: &DatagenSettings = &settings;
+      let writer: &Mutex<BufWriter<File>> = &writer;
+      let seen_positions: &Mutex<HashSet<u64>> = &seen_positions;
+      let started_games: &AtomicUsize = &started_games;
+      let finished_games: &AtomicUsize = &finished_games;
+      let written_positions: &AtomicUsize = &written_positions;
+
+      scope.spawn(move || {
+        let mut datagen_thread: DatagenThread =
+          DatagenThread::new(settings.seed.wrapping_add(thread_index as u64));
+
+        while started_games.fetch_add(1, Ordering::Relaxed) < settings.games {
+          let (positions, result): (Vec<GamePosition>, f32) =
+            datagen_thread.play_game(settings, seen_positions);
+
+          if let Err(error) = write_game(
+            &mut *writer.lock().expect("datagen lock poisoned"),
+            &positions,
+            result,
+            settings.text,
+          ) {
+            println!("{}: {}", settings.output_path, error);
+            return;
+          }
+
+          let games: usize = finished_games.fetch_add(1, Ordering::Relaxed) + 1;
+          let total_positions: usize =
+            written_positions.fetch_add(positions.len(), Ordering::Relaxed) + positions.len();
+          if games.is_multiple_of(10) || games == settings.games {
+            println!(
+              "games {} positions {} positions/s {:.0}",
+              games,
+              total_positions,
+              total_positions as f64 / start_time.elapsed().as_secs_f64()
+            );
+          }
+        }
+      });
+    }
+  });
+
+  let mut writer: BufWriter<File> = writer.into_inner().expect("datagen lock poisoned");
+  if let Err(error) = writer.flush() {
+    println!("{}: {}", settings.output_path, error);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn settings(seed: u64) -> DatagenSettings {
+    DatagenSettings {
+      output_path: String::new(),
+      games: 1,
+      threads: 1,
+      nodes: 300,
+      random_plies: 8,
+      seed,
+      text: false,
+      chess960: false,
+    }
+  }
+
+  fn play_game(seed: u64) -> (Vec<GamePosition>, f32) {
+    DatagenThread::new(seed).play_game(&settings(seed), &Mutex::new(HashSet::new()))
+  }
+
+  #[test]
+  fn packed_position() {
+    let board: Board = Board::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 5 20").unwrap();
+    let packed: [u8; RECORD_SIZE] = pack_position(&board, -123);
+
+    // a1, e1, h1 and e8
+    assert_eq!(packed[0..8], 0x1000_0000_0000_0091u64.to_le_bytes());
+    assert_eq!(
+      packed[8..10],
+      [
+        Pieces::Wr as u8 | (Pieces::Wk as u8) << 4,
+        Pieces::Wr as u8 | (Pieces::Bk as u8) << 4,
+      ]
+    );
+    assert_eq!(packed[10..24], [0; 14]);
+    assert_eq!(packed[24..26], (-123i16).to_le_bytes());
+    assert_eq!(packed[26..32], [0, 0, 3, NO_EN_PASSANT, 5, 0]);
+
+    let board: Board =
+      Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 3").unwrap();
+    let packed: [u8; RECORD_SIZE] = pack_position(&board, 40);
+
+    assert_eq!(packed[24..26], 40i16.to_le_bytes());
+    // Black to move, white king side and black queen side, en passant on e3
+    assert_eq!(packed[27..31], [1, 9, 20, 0]);
+  }
+
+  #[test]
+  fn games_depend_only_on_the_seed() {
+    let (positions, result): (Vec<GamePosition>, f32) = play_game(7);
+    let (same_positions, same_result): (Vec<GamePosition>, f32) = play_game(7);
+    let (other_positions, _): (Vec<GamePosition>, f32) = play_game(8);
+
+    assert!(!positions.is_empty());
+    assert_eq!(result, same_result);
+    assert_eq!(positions.len(), same_positions.len());
+    for (position, same_position) in positions.iter().zip(&same_positions) {
+      assert_eq!(position.fen, same_position.fen);
+      assert_eq!(position.score, same_position.score);
+      assert_eq!(position.packed, same_position.packed);
+    }
+    assert_ne!(positions[0].fen, other_positions[0].fen);
+  }
+
+  #[test]
+  fn written_records_match_the_text_lines() {
+    let (positions, result): (Vec<GamePosition>, f32) = play_game(11);
+    let mut binary: Vec<u8> = Vec::new();
+    let mut text: Vec<u8> = Vec::new();
+
+    write_game(&mut binary, &positions, result, false).unwrap();
+    write_game(&mut text, &positions, result, true).unwrap();
+    let text: String = String::from_utf8(text).unwrap();
+
+    assert_eq!(binary.len(), positions.len() * RECORD_SIZE);
+    assert_eq!(text.lines().count(), positions.len());
+    for (record, line) in binary.chunks(RECORD_SIZE).zip(text.lines()) {
+      let fields: Vec<&str> = line.split(" | ").collect();
+      let board: Board = Board::from_fen(fields[0]).unwrap();
+      let definitions: &Definitions = board.definitions();
+      let mut occupancy: u64 = u64::from_le_bytes(record[0..8].try_into().unwrap());
+      let mut pieces_number: usize = 0;
+      let mut square_64: i32;
+
+      assert_eq!(fields.len(), 3, "{}", line);
+      assert_eq!(fields[2], format!("{:.1}", result));
+      assert_eq!(
+        i16::from_le_bytes([record[24], record[25]]).to_string(),
+        fields[1]
+      );
+      assert_eq!(record[26] as f32, result * 2.0);
+      assert_eq!(record[27], board.side() as u8);
+      assert_eq!(record[28], board.castling_rights().bits());
+      assert_eq!(record[30] as i32, board.fifty_full_moves());
+      assert_eq!(record[31], 0);
+      for square in Squares::iter() {
+        square_64 = definitions.board_120_squares_in_64_squares_notation()[square as usize];
+        assert_eq!(
+          occupancy & (1u64 << square_64) != 0,
+          board.piece_at(square) != Pieces::Empty,
+          "{}",
+          line
+        );
+      }
+      while occupancy != 0 {
+        square_64 = pop_first_bit(&mut occupancy);
+        assert_eq!(
+          (record[8 + pieces_number / 2] >> (4 * (pieces_number % 2))) & 15,
+          board.pieces()
+            [definitions.board_64_squares_in_120_squares_notation()[square_64 as usize] as usize]
+            as u8,
+          "{}",
+          line
+        );
+        pieces_number += 1;
+      }
+    }
+  }
+}
@@ -1,3 +1,9 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{BitAnd, BitAndAssign, BitOr, Not};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
 use rand::Rng;
 
 /* CONSTANTS */
@@ -5,6 +11,14 @@ use rand::Rng;
 pub const PROGRAM_NAME: &str = "Rust Chess Engine";
 pub const BOARD_SQUARE_NUMBER: usize = 120;
 pub const MAX_GAME_HALF_MOVES: usize = 2048;
+pub const MAX_POSITION_MOVES: usize = 256;
+pub const MAX_DEPTH: usize = 64;
+pub const INFINITE: i32 = 30000;
+/**
+ * Every score above this value (in absolute value) is a mate score, the distance
+ * from INFINITE is the number of half moves to the mate.
+ */
+pub const IS_MATE: i32 = INFINITE - MAX_DEPTH as i32;
 // <https://en.wikipedia.org/wiki/Forsyth%E2%80%93Edwards_Notation>
 pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 pub const PIECE_CHARACTERS: [char; 13] = [
@@ -41,6 +55,9 @@ pub const PIECE_COLOR: [Colors; 13] = [
   Colors::Black,
   Colors::Black,
 ];
+pub const PIECE_PAWN: [bool; 13] = [
+  false, true, false, false, false, false, false, true, false, false, false, false, false,
+];
 pub const PIECE_KNIGHT: [bool; 13] = [
   false, false, true, false, false, false, false, false, true, false, false, false, false,
 ];
@@ -53,6 +70,10 @@ pub const PIECE_ROOK_QUEEN: [bool; 13] = [
 pub const PIECE_BISHOP_QUEEN: [bool; 13] = [
   false, false, false, true, false, true, false, false, false, true, false, true, false,
 ];
+#[rustfmt::skip]
+pub const PIECE_SLIDES: [bool; 13] = [
+  false, false, false, true, true, true, false, false, false, true, true, true, false,
+];
 
 /* MACROS */
 
@@ -67,6 +88,17 @@ macro_rules! file_rank_to_square_120 {
   };
 }
 
+/**
+ * It builds the "move" integer described on the Move struct.
+ * f -> from square, t -> to square, ca -> captured piece, pro -> promoted piece, fl -> flag
+ */
+#[macro_export]
+macro_rules! build_move {
+  ( $f:expr, $t:expr, $ca:expr, $pro:expr, $fl:expr ) => {
+    ($f) | (($t) << 7) | (($ca) << 14) | (($pro) << 20) | ($fl)
+  };
+}
+
 /* FUNCTIONS */
 
 fn generate_random_chess_piece_hash() -> u64 {
@@ -76,29 +108,65 @@ fn generate_random_chess_piece_hash() -> u64 {
 
 /* STRUCTS (and their implementations) */
 
-// A square can be empty or contain a Wn (White kNight) chess piece for example
-#[derive(Copy, Clone)]
+// A square can be empty or contain a Wn (White kNight) chess piece for example.
+// OffBoard is the content of the squares of the 120 squares board outside the chessboard.
+#[rustfmt::skip]
+#[derive(Copy, Clone, Debug)]
 pub enum Pieces {
-  Empty, Wp, Wn, Wb, Wr, Wq, Wk, Bp, Bn, Bb, Br, Bq, Bk,
+  Empty, Wp, Wn, Wb, Wr, Wq, Wk, Bp, Bn, Bb, Br, Bq, Bk, OffBoard,
 }
 
+#[rustfmt::skip]
+const PIECES: [Pieces; 14] = [
+  Pieces::Empty, Pieces::Wp, Pieces::Wn, Pieces::Wb, Pieces::Wr, Pieces::Wq, Pieces::Wk,
+  Pieces::Bp, Pieces::Bn, Pieces::Bb, Pieces::Br, Pieces::Bq, Pieces::Bk, Pieces::OffBoard,
+];
+
 impl Pieces {
-  pub fn from_u32(value: u32) -> Pieces {
-    match value {
-      0 => Pieces::Empty,
-      1 => Pieces::Wp,
-      2 => Pieces::Wn,
-      3 => Pieces::Wb,
-      4 => Pieces::Wr,
-      5 => Pieces::Wq,
-      6 => Pieces::Wk,
-      7 => Pieces::Bp,
-      8 => Pieces::Bn,
-      9 => Pieces::Bb,
-      10 => Pieces::Br,
-      11 => Pieces::Bq,
-      12 => Pieces::Bk,
-      _ => panic!("Unknown value: {}", value),
+  /**
+   * The twelve chess pieces, from the white pawn to the black king (no Empty and OffBoard).
+   */
+  pub fn iter() -> impl DoubleEndedIterator<Item = Pieces> {
+    PIECES[1..13].iter().copied()
+  }
+}
+
+impl TryFrom<u32> for Pieces {
+  type Error = String;
+
+  fn try_from(value: u32) -> Result<Pieces, String> {
+    PIECES
+      .get(value as usize)
+      .copied()
+      .ok_or(format!("Unknown piece: {}", value))
+  }
+}
+
+/**
+ * The FEN letter of the piece, uppercase for white and lowercase for black ("." if empty).
+ */
+impl fmt::Display for Pieces {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Pieces::OffBoard => write!(formatter, "#"),
+      piece => write!(formatter, "{}", PIECE_CHARACTERS[*piece as usize]),
+    }
+  }
+}
+
+impl FromStr for Pieces {
+  type Err = String;
+
+  fn from_str(letter: &str) -> Result<Pieces, String> {
+    let mut characters = letter.chars();
+
+    match (characters.next(), characters.next()) {
+      (Some(character), None) => PIECE_CHARACTERS
+        .iter()
+        .position(|&piece_character| piece_character == character)
+        .map(|index| PIECES[index])
+        .ok_or(format!("Unknown piece: {}", letter)),
+      _ => Err(format!("Unknown piece: {}", letter)),
     }
   }
 }
@@ -109,25 +177,36 @@ impl PartialEq for Pieces {
   }
 }
 
-#[derive(Copy, Clone)]
+#[rustfmt::skip]
+#[derive(Copy, Clone, Debug)]
 pub enum ChessboardFiles {
   A, B, C, D, E, F, G, H, None,
 }
 
+#[rustfmt::skip]
+const FILES: [ChessboardFiles; 9] = [
+  ChessboardFiles::A, ChessboardFiles::B, ChessboardFiles::C, ChessboardFiles::D,
+  ChessboardFiles::E, ChessboardFiles::F, ChessboardFiles::G, ChessboardFiles::H,
+  ChessboardFiles::None,
+];
+
 impl ChessboardFiles {
-  pub fn from_u32(value: u32) -> ChessboardFiles {
-    match value {
-      0 => ChessboardFiles::A,
-      1 => ChessboardFiles::B,
-      2 => ChessboardFiles::C,
-      3 => ChessboardFiles::D,
-      4 => ChessboardFiles::E,
-      5 => ChessboardFiles::F,
-      6 => ChessboardFiles::G,
-      7 => ChessboardFiles::H,
-      8 => ChessboardFiles::None,
-      _ => panic!("Unknown value: {}", value),
-    }
+  /**
+   * The files of the chessboard, from A to H.
+   */
+  pub fn iter() -> impl DoubleEndedIterator<Item = ChessboardFiles> {
+    FILES[0..8].iter().copied()
+  }
+}
+
+impl TryFrom<u32> for ChessboardFiles {
+  type Error = String;
+
+  fn try_from(value: u32) -> Result<ChessboardFiles, String> {
+    FILES
+      .get(value as usize)
+      .copied()
+      .ok_or(format!("Unknown file: {}", value))
   }
 }
 
@@ -138,25 +217,36 @@ impl PartialEq for ChessboardFiles {
 }
 
 // R stands for "Rank"
-#[derive(Copy, Clone)]
+#[rustfmt::skip]
+#[derive(Copy, Clone, Debug)]
 pub enum ChessboardRanks {
   R1, R2, R3, R4, R5, R6, R7, R8, None
 }
 
+#[rustfmt::skip]
+const RANKS: [ChessboardRanks; 9] = [
+  ChessboardRanks::R1, ChessboardRanks::R2, ChessboardRanks::R3, ChessboardRanks::R4,
+  ChessboardRanks::R5, ChessboardRanks::R6, ChessboardRanks::R7, ChessboardRanks::R8,
+  ChessboardRanks::None,
+];
+
 impl ChessboardRanks {
-  pub fn from_u32(value: u32) -> ChessboardRanks {
-    match value {
-      0 => ChessboardRanks::R1,
-      1 => ChessboardRanks::R2,
-      2 => ChessboardRanks::R3,
-      3 => ChessboardRanks::R4,
-      4 => ChessboardRanks::R5,
-      5 => ChessboardRanks::R6,
-      6 => ChessboardRanks::R7,
-      7 => ChessboardRanks::R8,
-      8 => ChessboardRanks::None,
-      _ => panic!("Unknown value: {}", value),
-    }
+  /**
+   * The ranks of the chessboard, from 1 to 8 (use rev() to go from 8 to 1).
+   */
+  pub fn iter() -> impl DoubleEndedIterator<Item = ChessboardRanks> {
+    RANKS[0..8].iter().copied()
+  }
+}
+
+impl TryFrom<u32> for ChessboardRanks {
+  type Error = String;
+
+  fn try_from(value: u32) -> Result<ChessboardRanks, String> {
+    RANKS
+      .get(value as usize)
+      .copied()
+      .ok_or(format!("Unknown rank: {}", value))
   }
 }
 
@@ -166,18 +256,33 @@ impl PartialEq for ChessboardRanks {
   }
 }
 
+#[rustfmt::skip]
 #[derive(Copy, Clone, Debug)]
 pub enum Colors {
   White, Black, Both
 }
 
+impl TryFrom<u32> for Colors {
+  type Error = String;
+
+  fn try_from(value: u32) -> Result<Colors, String> {
+    match value {
+      0 => Ok(Colors::White),
+      1 => Ok(Colors::Black),
+      2 => Ok(Colors::Both),
+      _ => Err(format!("Unknown color: {}", value)),
+    }
+  }
+}
+
 impl PartialEq for Colors {
   fn eq(&self, other: &Self) -> bool {
     *self as u32 == *other as u32
   }
 }
 
-#[derive(Copy, Clone)]
+#[rustfmt::skip]
+#[derive(Copy, Clone, Debug)]
 pub enum Squares {
   A1 = 21, B1, C1, D1, E1, F1, G1, H1,
   A2 = 31, B2, C2, D2, E2, F2, G2, H2,
@@ -189,76 +294,132 @@ pub enum Squares {
   A8 = 91, B8, C8, D8, E8, F8, G8, H8, NoSquare, OffBoard
 }
 
+// The 64 squares of the chessboard, in the order of the 64 squares notation
+#[rustfmt::skip]
+const SQUARES: [Squares; 64] = [
+  Squares::A1, Squares::B1, Squares::C1, Squares::D1,
+  Squares::E1, Squares::F1, Squares::G1, Squares::H1,
+  Squares::A2, Squares::B2, Squares::C2, Squares::D2,
+  Squares::E2, Squares::F2, Squares::G2, Squares::H2,
+  Squares::A3, Squares::B3, Squares::C3, Squares::D3,
+  Squares::E3, Squares::F3, Squares::G3, Squares::H3,
+  Squares::A4, Squares::B4, Squares::C4, Squares::D4,
+  Squares::E4, Squares::F4, Squares::G4, Squares::H4,
+  Squares::A5, Squares::B5, Squares::C5, Squares::D5,
+  Squares::E5, Squares::F5, Squares::G5, Squares::H5,
+  Squares::A6, Squares::B6, Squares::C6, Squares::D6,
+  Squares::E6, Squares::F6, Squares::G6, Squares::H6,
+  Squares::A7, Squares::B7, Squares::C7, Squares::D7,
+  Squares::E7, Squares::F7, Squares::G7, Squares::H7,
+  Squares::A8, Squares::B8, Squares::C8, Squares::D8,
+  Squares::E8, Squares::F8, Squares::G8, Squares::H8,
+];
+
+// The square of each index of the 120 squares board, NoSquare outside the chessboard.
+// It has 128 elements so that every 7 bits square of a move can be read without checks.
+const SQUARES_120: [Squares; 128] = {
+  let mut squares: [Squares; 128] = [Squares::NoSquare; 128];
+  let mut square_64: usize = 0;
+
+  while square_64 < 64 {
+    squares[21 + square_64 / 8 * 10 + square_64 % 8] = SQUARES[square_64];
+    square_64 += 1;
+  }
+  squares
+};
+
 impl Squares {
-  pub fn from_u32(value: u32) -> Squares {
+  /**
+   * The 64 squares of the chessboard, from A1 to H8 rank by rank.
+   */
+  pub fn iter() -> impl DoubleEndedIterator<Item = Squares> {
+    SQUARES.iter().copied()
+  }
+
+  /**
+   * The square of a 120 squares board index, NoSquare if it's not on the chessboard
+   * (the move generator works with the indexes).
+   */
+  pub(crate) fn from_120(square_120: i32) -> Squares {
+    SQUARES_120[(square_120 & 0x7F) as usize]
+  }
+
+  pub fn from_file_rank(file: ChessboardFiles, rank: ChessboardRanks) -> Squares {
+    if file == ChessboardFiles::None || rank == ChessboardRanks::None {
+      return Squares::NoSquare;
+    }
+    SQUARES[rank as usize * 8 + file as usize]
+  }
+
+  /**
+   * ChessboardFiles::None for NoSquare and OffBoard.
+   */
+  pub fn file(&self) -> ChessboardFiles {
+    match *self {
+      Squares::NoSquare | Squares::OffBoard => ChessboardFiles::None,
+      square => FILES[(square as usize - 21) % 10],
+    }
+  }
+
+  /**
+   * ChessboardRanks::None for NoSquare and OffBoard.
+   */
+  pub fn rank(&self) -> ChessboardRanks {
+    match *self {
+      Squares::NoSquare | Squares::OffBoard => ChessboardRanks::None,
+      square => RANKS[(square as usize - 21) / 10],
+    }
+  }
+}
+
+/**
+ * From the 120 squares notation, only the squares of the chessboard (not NoSquare and OffBoard).
+ */
+impl TryFrom<u32> for Squares {
+  type Error = String;
+
+  fn try_from(value: u32) -> Result<Squares, String> {
     match value {
-      21 => Squares::A1,
-      22 => Squares::B1,
-      23 => Squares::C1,
-      24 => Squares::D1,
-      25 => Squares::E1,
-      26 => Squares::F1,
-      27 => Squares::G1,
-      28 => Squares::H1,
-      31 => Squares::A2,
-      32 => Squares::B2,
-      33 => Squares::C2,
-      34 => Squares::D2,
-      35 => Squares::E2,
-      36 => Squares::F2,
-      37 => Squares::G2,
-      38 => Squares::H2,
-      41 => Squares::A3,
-      42 => Squares::B3,
-      43 => Squares::C3,
-      44 => Squares::D3,
-      45 => Squares::E3,
-      46 => Squares::F3,
-      47 => Squares::G3,
-      48 => Squares::H3,
-      51 => Squares::A4,
-      52 => Squares::B4,
-      53 => Squares::C4,
-      54 => Squares::D4,
-      55 => Squares::E4,
-      56 => Squares::F4,
-      57 => Squares::G4,
-      58 => Squares::H4,
-      61 => Squares::A5,
-      62 => Squares::B5,
-      63 => Squares::C5,
-      64 => Squares::D5,
-      65 => Squares::E5,
-      66 => Squares::F5,
-      67 => Squares::G5,
-      68 => Squares::H5,
-      71 => Squares::A6,
-      72 => Squares::B6,
-      73 => Squares::C6,
-      74 => Squares::D6,
-      75 => Squares::E6,
-      76 => Squares::F6,
-      77 => Squares::G6,
-      78 => Squares::H6,
-      81 => Squares::A7,
-      82 => Squares::B7,
-      83 => Squares::C7,
-      84 => Squares::D7,
-      85 => Squares::E7,
-      86 => Squares::F7,
-      87 => Squares::G7,
-      88 => Squares::H7,
-      91 => Squares::A8,
-      92 => Squares::B8,
-      93 => Squares::C8,
-      94 => Squares::D8,
-      95 => Squares::E8,
-      96 => Squares::F8,
-      97 => Squares::G8,
-      98 => Squares::H8,
-      99 => Squares::NoSquare,
-      100 => Squares::OffBoard,
-      _ => panic!("Unknown value: {}", value),
+      21..=98 if (1..=8).contains(&(value % 10)) => {
+        Ok(SQUARES[((value / 10 - 2) * 8 + value % 10 - 1) as usize])
+      }
+      _ => Err(format!("Unknown square: {}", value)),
+    }
+  }
+}
+
+/**
+ * The algebraic notation of the square, ex. "e4" ("-" for NoSquare, as in the FEN).
+ */
+impl fmt::Display for Squares {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    match (self.file(), self.rank()) {
+      (ChessboardFiles::None, _) | (_, ChessboardRanks::None) => write!(formatter, "-"),
+      (file, rank) => write!(
+        formatter,
+        "{}{}",
+        FILE_CHARACTERS[file as usize], RANK_CHARACTERS[rank as usize]
+      ),
+    }
+  }
+}
+
+/**
+ * A square of the chessboard in algebraic notation, ex. "e4". The "-" of the FEN (no en passant
+ * square) is not a square, it's an error as NoSquare is never parsed.
+ */
+impl FromStr for Squares {
+  type Err = String;
+
+  fn from_str(algebraic: &str) -> Result<Squares, String> {
+    let characters: Vec<char> = algebraic.chars().collect();
+
+    match characters[..] {
+      [file @ 'a'..='h', rank @ '1'..='8'] => Ok(Squares::from_file_rank(
+        FILES[file as usize - 'a' as usize],
+        RANKS[rank as usize - '1' as usize],
+      )),
+      _ => Err(format!("Unknown square: {}", algebraic)),
     }
   }
 }
@@ -270,22 +431,124 @@ impl PartialEq for Squares {
 }
 
 /**
- * They are rapresented by 4 bits. Ex. bit 1 -> [1 0 0 0]; bit 4 -> [0 0 1 0].
+ * The castles that are still allowed, a set of 4 bits. Ex. bit 1 -> [1 0 0 0]; bit 4 -> [0 0 1 0].
  * The case [1 0 0 1] tell us that white castel on king side and black castel on queen side.
  */
-pub enum Castle {
-  WhiteKingSideCastel = 1,
-  WhiteQueenSideCastel = 2,
-  BlackKingSideCastel = 4,
-  BlackQueenSideCastel = 8,
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CastlingRights(u8);
+
+impl CastlingRights {
+  pub const NONE: CastlingRights = CastlingRights(0);
+  pub const WHITE_KING_SIDE: CastlingRights = CastlingRights(1);
+  pub const WHITE_QUEEN_SIDE: CastlingRights = CastlingRights(2);
+  pub const BLACK_KING_SIDE: CastlingRights = CastlingRights(4);
+  pub const BLACK_QUEEN_SIDE: CastlingRights = CastlingRights(8);
+  pub const ALL: CastlingRights = CastlingRights(15);
+
+  pub fn bits(&self) -> u8 {
+    self.0
+  }
+
+  /**
+   * None if some bit is not a castle.
+   */
+  pub fn from_bits(bits: u8) -> Option<CastlingRights> {
+    if bits & !CastlingRights::ALL.0 == 0 {
+      Some(CastlingRights(bits))
+    } else {
+      None
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn contains(&self, other: CastlingRights) -> bool {
+    self.0 & other.0 == other.0
+  }
+
+  pub fn insert(&mut self, other: CastlingRights) {
+    self.0 |= other.0;
+  }
+
+  pub fn remove(&mut self, other: CastlingRights) {
+    self.0 &= !other.0;
+  }
+
+  /**
+   * The single castles of the set, in the order of their bits.
+   */
+  pub fn iter(&self) -> impl Iterator<Item = CastlingRights> {
+    let bits: u8 = self.0;
+
+    (0..4)
+      .map(|bit| CastlingRights(1 << bit))
+      .filter(move |castle| bits & castle.0 != 0)
+  }
+}
+
+impl BitOr for CastlingRights {
+  type Output = CastlingRights;
+
+  fn bitor(self, other: CastlingRights) -> CastlingRights {
+    CastlingRights(self.0 | other.0)
+  }
+}
+
+impl BitAnd for CastlingRights {
+  type Output = CastlingRights;
+
+  fn bitand(self, other: CastlingRights) -> CastlingRights {
+    CastlingRights(self.0 & other.0)
+  }
+}
+
+impl BitAndAssign for CastlingRights {
+  fn bitand_assign(&mut self, other: CastlingRights) {
+    self.0 &= other.0;
+  }
+}
+
+impl Not for CastlingRights {
+  type Output = CastlingRights;
+
+  fn not(self) -> CastlingRights {
+    CastlingRights(!self.0 & CastlingRights::ALL.0)
+  }
+}
+
+/**
+ * The FEN castle field with the standard letters ("KQkq", "-" if none).
+ */
+impl fmt::Display for CastlingRights {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    if self.is_empty() {
+      return write!(formatter, "-");
+    }
+    for castle in self.iter() {
+      write!(
+        formatter,
+        "{}",
+        ['K', 'Q', 'k', 'q'][castle.0.trailing_zeros() as usize]
+      )?;
+    }
+    Ok(())
+  }
 }
+
+#[derive(Copy, Clone)]
 pub struct Move {
   /* 'move' is a reserved keyword in Rust */
   mov_e: i32,
+  /**
+   * Used by the search to order the moves, the moves with higher score are tried first.
+   */
   score: i32,
 }
 
 /**
+ * ```text
  *     A  B  C  D  E  F  G  H
  * 1  21 22 23 24 25 26 27 28
  * 2  31 32 33 34 35 36 37 38
@@ -295,6 +558,7 @@ pub struct Move {
  * 6  71 72 73 74 75 76 77 78
  * 7  81 82 83 84 85 86 87 88
  * 8  91 92 93 94 95 96 97 98
+ * ```
  *
  * 7 bits are sufficient to index a square ranging from 21 to 98:
  * 21 = 001 0101 / 98 = 110 0010
@@ -322,11 +586,99 @@ pub struct Move {
  * the 25 bit to indicate if it is a "Castle" move
  * 0001 0000 0000 0000 0000 0000 0000 -> Castle 0x1000000
  */
+pub const MOVE_FLAG_EN_PASSANT: i32 = 0x40000;
+pub const MOVE_FLAG_PAWN_START: i32 = 0x80000;
+pub const MOVE_FLAG_CASTLE: i32 = 0x1000000;
+pub const MOVE_FLAG_CAPTURE: i32 = 0x7C000;
+pub const MOVE_FLAG_PROMOTION: i32 = 0xF00000;
+
+/**
+ * What a move does, from the flags of the "move" integer. A promotion can be a capture too.
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MoveKind {
+  Quiet,
+  DoublePush,
+  Capture,
+  EnPassant,
+  Castle,
+  Promotion,
+}
+
+/**
+ * The 4 bits flag of the 16 bits move (see Move::to_compact).
+ */
+const COMPACT_QUIET: u16 = 0;
+const COMPACT_DOUBLE_PUSH: u16 = 1;
+const COMPACT_CASTLE: u16 = 2;
+const COMPACT_EN_PASSANT: u16 = 3;
+// 4 knight, 5 bishop, 6 rook, 7 queen
+const COMPACT_PROMOTION: u16 = 4;
+const COMPACT_QUEEN_PROMOTION: u16 = 7;
+
 impl Move {
+  /**
+   * The "null" move, no square can be 0 so no real move is equal to it.
+   */
+  pub const NO_MOVE: Move = Move { mov_e: 0, score: 0 };
+
   pub fn new(mov_e: i32, score: i32) -> Move {
     Move { mov_e, score }
   }
 
+  pub fn quiet(from: Squares, to: Squares) -> Move {
+    Move::new(build_move!(from as i32, to as i32, 0, 0, 0), 0)
+  }
+
+  pub fn capture(from: Squares, to: Squares, captured: Pieces) -> Move {
+    Move::new(
+      build_move!(from as i32, to as i32, captured as i32, 0, 0),
+      0,
+    )
+  }
+
+  /**
+   * A pawn arriving on the last rank, with or without a capture.
+   */
+  pub fn promotion(from: Squares, to: Squares, captured: Option<Pieces>, promoted: Pieces) -> Move {
+    let captured: Pieces = captured.unwrap_or(Pieces::Empty);
+
+    Move::new(
+      build_move!(from as i32, to as i32, captured as i32, promoted as i32, 0),
+      0,
+    )
+  }
+
+  /**
+   * The captured pawn is not stored, it's always the pawn behind the arrival square.
+   */
+  pub fn en_passant(from: Squares, to: Squares) -> Move {
+    Move::new(
+      build_move!(from as i32, to as i32, 0, 0, MOVE_FLAG_EN_PASSANT),
+      0,
+    )
+  }
+
+  /**
+   * The king move of the castle, the king arrives on the C or G file (in Chess960 too).
+   */
+  pub fn castle(from: Squares, to: Squares) -> Move {
+    Move::new(
+      build_move!(from as i32, to as i32, 0, 0, MOVE_FLAG_CASTLE),
+      0,
+    )
+  }
+
+  /**
+   * A pawn moving two squares from its start rank.
+   */
+  pub fn double_push(from: Squares, to: Squares) -> Move {
+    Move::new(
+      build_move!(from as i32, to as i32, 0, 0, MOVE_FLAG_PAWN_START),
+      0,
+    )
+  }
+
   pub fn mov_e(&self) -> i32 {
     self.mov_e
   }
@@ -335,40 +687,268 @@ impl Move {
     self.mov_e = mov_e;
   }
 
-  pub fn from_square(&self) -> i32 {
-    self.mov_e & 0x7F
+  pub fn score(&self) -> i32 {
+    self.score
+  }
+
+  pub fn set_score(&mut self, score: i32) {
+    self.score = score;
+  }
+
+  /**
+   * NoSquare for NO_MOVE.
+   */
+  pub fn from_square(&self) -> Squares {
+    SQUARES_120[(self.mov_e & 0x7F) as usize]
+  }
+
+  pub fn to_square(&self) -> Squares {
+    SQUARES_120[((self.mov_e >> 7) & 0x7F) as usize]
+  }
+
+  /**
+   * None for an en passant move too, the captured pawn is not stored.
+   */
+  pub fn captured_piece(&self) -> Option<Pieces> {
+    match PIECES[((self.mov_e >> 14) & 0xF) as usize] {
+      Pieces::Empty => None,
+      piece => Some(piece),
+    }
+  }
+
+  pub fn promoted(&self) -> Option<Pieces> {
+    match PIECES[((self.mov_e >> 20) & 0xF) as usize] {
+      Pieces::Empty => None,
+      piece => Some(piece),
+    }
+  }
+
+  /**
+   * En passant included.
+   */
+  pub fn is_capture(&self) -> bool {
+    self.mov_e & MOVE_FLAG_CAPTURE != 0
+  }
+
+  pub fn is_promotion(&self) -> bool {
+    self.mov_e & MOVE_FLAG_PROMOTION != 0
+  }
+
+  pub fn is_en_passant(&self) -> bool {
+    self.mov_e & MOVE_FLAG_EN_PASSANT != 0
+  }
+
+  pub fn is_double_push(&self) -> bool {
+    self.mov_e & MOVE_FLAG_PAWN_START != 0
+  }
+
+  pub fn is_castle(&self) -> bool {
+    self.mov_e & MOVE_FLAG_CASTLE != 0
+  }
+
+  pub fn kind(&self) -> MoveKind {
+    if self.is_promotion() {
+      MoveKind::Promotion
+    } else if self.is_en_passant() {
+      MoveKind::EnPassant
+    } else if self.is_castle() {
+      MoveKind::Castle
+    } else if self.is_double_push() {
+      MoveKind::DoublePush
+    } else if self.is_capture() {
+      MoveKind::Capture
+    } else {
+      MoveKind::Quiet
+    }
+  }
+
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Encoding_Moves#From-To_Based>
+   *
+   * The move in 16 bits, as it's stored in the hash table: the start square (bits 0 to 5)
+   * and the arrival square (bits 6 to 11) in 64 squares notation, and a 4 bits flag (see the
+   * COMPACT_ constants). The captured piece is not stored, it's read from the board when the
+   * move is decoded (see from_compact). NO_MOVE is 0.
+   */
+  pub fn to_compact(&self) -> u16 {
+    let square_64 = |square: Squares| (square.rank() as u16) * 8 + square.file() as u16;
+
+    if *self == Move::NO_MOVE {
+      return 0;
+    }
+    let flag: u16 = match (self.kind(), self.promoted()) {
+      (MoveKind::Promotion, Some(promoted)) => {
+        COMPACT_PROMOTION + (promoted as u16 - Pieces::Wn as u16) % 6
+      }
+      (MoveKind::DoublePush, _) => COMPACT_DOUBLE_PUSH,
+      (MoveKind::Castle, _) => COMPACT_CASTLE,
+      (MoveKind::EnPassant, _) => COMPACT_EN_PASSANT,
+      _ => COMPACT_QUIET,
+    };
+
+    square_64(self.from_square()) | (square_64(self.to_square()) << 6) | (flag << 12)
+  }
+
+  /**
+   * The move of a 16 bits move (see to_compact), given the pieces on the board
+   * before the move is made. None for 0 (NO_MOVE) and for a value that is not a move of
+   * the piece on the start square: a flag above the queen promotion, no piece to move,
+   * a capture of an own piece, a castle that is not made by a king, or a double push,
+   * an en passant or a promotion (not to the last rank) that is not made by a pawn.
+   */
+  pub fn from_compact(compact: u16, pieces: &[Pieces; BOARD_SQUARE_NUMBER]) -> Option<Move> {
+    let from: Squares = SQUARES[(compact & 0x3F) as usize];
+    let to: Squares = SQUARES[((compact >> 6) & 0x3F) as usize];
+    let flag: u16 = compact >> 12;
+    let piece: Pieces = pieces[from as usize];
+    let captured: Option<Pieces> = match pieces[to as usize] {
+      Pieces::Empty | Pieces::OffBoard => None,
+      piece => Some(piece),
+    };
+    let last_rank: ChessboardRanks = if PIECE_COLOR[piece as usize] == Colors::White {
+      ChessboardRanks::R8
+    } else {
+      ChessboardRanks::R1
+    };
+
+    if compact == 0 || matches!(piece, Pieces::Empty | Pieces::OffBoard) {
+      return None;
+    }
+    // A Chess960 king can castle to its own square or to the square of its rook
+    if flag == COMPACT_CASTLE {
+      return PIECE_KING[piece as usize].then(|| Move::castle(from, to));
+    }
+    if from == to
+      || captured
+        .is_some_and(|captured| PIECE_COLOR[captured as usize] == PIECE_COLOR[piece as usize])
+    {
+      return None;
+    }
+    match flag {
+      COMPACT_QUIET => Some(match captured {
+        Some(captured) => Move::capture(from, to, captured),
+        None => Move::quiet(from, to),
+      }),
+      COMPACT_DOUBLE_PUSH if PIECE_PAWN[piece as usize] => Some(Move::double_push(from, to)),
+      COMPACT_EN_PASSANT if PIECE_PAWN[piece as usize] => Some(Move::en_passant(from, to)),
+      COMPACT_PROMOTION..=COMPACT_QUEEN_PROMOTION
+        if PIECE_PAWN[piece as usize] && to.rank() as i32 == last_rank as i32 =>
+      {
+        // The promoted piece has the color of the pawn
+        let first_piece: Pieces = if piece == Pieces::Bp {
+          Pieces::Bn
+        } else {
+          Pieces::Wn
+        };
+        let promoted: Pieces = PIECES[(first_piece as u16 + flag - COMPACT_PROMOTION) as usize];
+
+        Some(Move::promotion(from, to, captured, promoted))
+      }
+      _ => None,
+    }
+  }
+}
+
+/**
+ * It shows the decoded move,
+ * ex. Move { e2e4, DoublePush, captured: None, promoted: None, score: 0 }.
+ */
+impl fmt::Debug for Move {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      formatter,
+      "Move {{ {}, {:?}, captured: {:?}, promoted: {:?}, score: {} }}",
+      self,
+      self.kind(),
+      self.captured_piece(),
+      self.promoted(),
+      self.score
+    )
+  }
+}
+
+/**
+ * Long algebraic notation (the one used by the UCI protocol), ex. "e2e4" or "a7a8q".
+ */
+impl fmt::Display for Move {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    if *self == Move::NO_MOVE {
+      return write!(formatter, "0000");
+    }
+
+    write!(formatter, "{}{}", self.from_square(), self.to_square())?;
+    match self.promoted() {
+      Some(promoted) => write!(formatter, "{}", promoted.to_string().to_lowercase()),
+      None => Ok(()),
+    }
+  }
+}
+
+/**
+ * Two moves are the same move if they have the same "move" integer, the score is not considered.
+ */
+impl PartialEq for Move {
+  fn eq(&self, other: &Self) -> bool {
+    self.mov_e == other.mov_e
+  }
+}
+
+impl Eq for Move {}
+
+impl Hash for Move {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.mov_e.hash(state);
   }
+}
+
+/**
+ * The list of the moves generated for a position, 256 moves are more than enough
+ * for every legal chess position (the maximum known is 218).
+ */
+#[derive(Copy, Clone)]
+pub struct MoveList {
+  moves: [Move; MAX_POSITION_MOVES],
+  count: usize,
+}
 
-  pub fn to_square(&self) -> i32 {
-    (self.mov_e >> 7) & 0x7F
+impl MoveList {
+  pub fn new() -> MoveList {
+    let moves: [Move; MAX_POSITION_MOVES] = [Move::NO_MOVE; MAX_POSITION_MOVES];
+    let count: usize = 0;
+
+    MoveList { moves, count }
   }
 
-  pub fn captured_piece(&self) -> i32 {
-    (self.mov_e >> 14) & 0xF
+  pub fn moves(&self) -> &[Move] {
+    &self.moves[0..self.count]
   }
 
-  pub fn promoted(&self) -> i32 {
-    (self.mov_e >> 20) & 0xF
+  pub fn moves_mut(&mut self) -> &mut [Move] {
+    &mut self.moves[0..self.count]
   }
 
-  pub fn captured_piece_with_en_passant(&self) -> i32 {
-    self.mov_e & 0x7C000
+  pub fn count(&self) -> usize {
+    self.count
   }
 
-  pub fn promotion(&self) -> i32 {
-    self.mov_e & 0xF00000
+  pub fn add_move(&mut self, mov_e: Move) {
+    self.moves[self.count] = mov_e;
+    self.count += 1;
   }
 
-  pub fn en_passant(&self) -> i32 {
-    self.mov_e & 0x40000
+  pub fn contains(&self, mov_e: Move) -> bool {
+    self.moves().contains(&mov_e)
   }
 
-  pub fn pawn_start(&self) -> i32 {
-    self.mov_e & 0x80000
+  pub fn clear(&mut self) {
+    self.count = 0;
   }
+}
 
-  pub fn castle(&self) -> i32 {
-    self.mov_e & 0x1000000
+impl Default for MoveList {
+  fn default() -> MoveList {
+    MoveList::new()
   }
 }
 
@@ -381,11 +961,11 @@ pub struct Undo {
   /**
    * Before the move has made.
    */
-  castle_permission: i32,
+  castle_permission: CastlingRights,
   /**
    * Before the move has made.
    */
-  en_passant_square: i32,
+  en_passant_square: Squares,
   /**
    * Fifty moves counter for draw, in our case will be hundred moves because we'll
    * using half moves and not full moves. Before the move has made.
@@ -400,8 +980,8 @@ pub struct Undo {
 impl Undo {
   pub fn new() -> Undo {
     let half_move: i32 = 0;
-    let castle_permission: i32 = 0;
-    let en_passant_square: i32 = 0;
+    let castle_permission: CastlingRights = CastlingRights::NONE;
+    let en_passant_square: Squares = Squares::NoSquare;
     let fifty_full_moves: i32 = 0;
     let position_key: u64 = 0;
 
@@ -413,10 +993,54 @@ impl Undo {
       position_key,
     }
   }
+
+  /**
+   * It stores the board state before the "half_move" is made, so that it can be restored.
+   */
+  pub fn save(
+    &mut self,
+    half_move: i32,
+    castle_permission: CastlingRights,
+    en_passant_square: Squares,
+    fifty_full_moves: i32,
+    position_key: u64,
+  ) {
+    self.half_move = half_move;
+    self.castle_permission = castle_permission;
+    self.en_passant_square = en_passant_square;
+    self.fifty_full_moves = fifty_full_moves;
+    self.position_key = position_key;
+  }
+
+  pub fn half_move(&self) -> i32 {
+    self.half_move
+  }
+
+  pub fn castle_permission(&self) -> CastlingRights {
+    self.castle_permission
+  }
+
+  pub fn en_passant_square(&self) -> Squares {
+    self.en_passant_square
+  }
+
+  pub fn fifty_full_moves(&self) -> i32 {
+    self.fifty_full_moves
+  }
+
+  pub fn position_key(&self) -> u64 {
+    self.position_key
+  }
 }
 
+/**
+ * The definitions are the same for every board, so they are computed once
+ * (the first time they are needed) and shared by all the boards.
+ */
+static DEFINITIONS: OnceLock<Definitions> = OnceLock::new();
+
 #[derive(Copy, Clone)]
-pub struct Definitions {
+pub(crate) struct Definitions {
   /**
    * The board with 120 squares is the board reference for the search engine.
    * Check out why we need to change the boards squares notation on the init_squares() function
@@ -424,6 +1048,7 @@ pub struct Definitions {
    *
    * Original structure of definitions.board_120_squares_in_64_squares_notation,
    * more like "board_120_squares_in_120_squares_notation" (as we play with black):
+   * ```text
    *          A    B    C    D    E    F    G    H
    *    000  001  002  003  004  005  006  007  008  009
    *    010  011  012  013  014  015  016  017  018  019
@@ -437,12 +1062,14 @@ pub struct Definitions {
    * 8  090  091  092  093  094  095  096  097  098  099
    *    100  101  102  103  104  105  106  107  108  109
    *    110  111  112  113  114  115  116  117  118  119
+   * ```
    */
   board_120_squares_in_64_squares_notation: [i32; BOARD_SQUARE_NUMBER],
   /**
    * Original structure of definitions.board_64_squares_in_120_squares_notation,
    * more like "board_64_squares_in_64_squares_notation" (as we play with black):
    *
+   * ```text
    *     A  B  C  D  E  F  G  H
    * 1  00 01 02 03 04 05 06 07
    * 2  08 09 10 11 12 13 14 15
@@ -452,6 +1079,7 @@ pub struct Definitions {
    * 6  40 41 42 43 44 45 46 47
    * 7  48 49 50 51 52 53 54 55
    * 8  56 57 58 59 60 61 62 63
+   * ```
    */
   board_64_squares_in_120_squares_notation: [i32; 64],
   bit_mask_to_set_bit_inside_bitboard: [u64; 64],
@@ -469,6 +1097,31 @@ pub struct Definitions {
    * we use this structure.
    */
   ranks_board: [i32; BOARD_SQUARE_NUMBER],
+  /**
+   * The bitboard masks used by the pawn structure evaluation (in 64 squares notation):
+   * - file_masks: every square of the file.
+   * - adjacent_files_masks: every square of the files on the left and on the right of the file,
+   *   a pawn without own pawns in this mask is isolated.
+   * - passed_pawn_masks: for each color and square, every square in front of the pawn on its file
+   *   and on the adjacent files, a pawn without opponent pawns in this mask is passed.
+   *
+   * Example, passed_pawn_masks\[Colors::White\]\[E4\]:
+   *
+   * ```text
+   *    A B C D E F G H
+   * 8  - - - X X X - -
+   * 7  - - - X X X - -
+   * 6  - - - X X X - -
+   * 5  - - - X X X - -
+   * 4  - - - - - - - -
+   * 3  - - - - - - - -
+   * 2  - - - - - - - -
+   * 1  - - - - - - - -
+   * ```
+   */
+  file_masks: [u64; 8],
+  adjacent_files_masks: [u64; 8],
+  passed_pawn_masks: [[u64; 64]; 2],
 }
 
 impl Definitions {
@@ -487,6 +1140,9 @@ impl Definitions {
 
     let files_board: [i32; BOARD_SQUARE_NUMBER] = [0; BOARD_SQUARE_NUMBER];
     let ranks_board: [i32; BOARD_SQUARE_NUMBER] = [0; BOARD_SQUARE_NUMBER];
+    let file_masks: [u64; 8] = [0; 8];
+    let adjacent_files_masks: [u64; 8] = [0; 8];
+    let passed_pawn_masks: [[u64; 64]; 2] = [[0; 64]; 2];
 
     Definitions {
       board_120_squares_in_64_squares_notation,
@@ -498,43 +1154,69 @@ impl Definitions {
       castle_keys,
       files_board,
       ranks_board,
+      file_masks,
+      adjacent_files_masks,
+      passed_pawn_masks,
     }
   }
 
-  pub fn board_120_squares_in_64_squares_notation(&self) -> [i32; BOARD_SQUARE_NUMBER] {
-    self.board_120_squares_in_64_squares_notation
+  pub fn board_120_squares_in_64_squares_notation(&self) -> &[i32; BOARD_SQUARE_NUMBER] {
+    &self.board_120_squares_in_64_squares_notation
   }
 
-  pub fn board_64_squares_in_120_squares_notation(&self) -> [i32; 64] {
-    self.board_64_squares_in_120_squares_notation
+  pub fn board_64_squares_in_120_squares_notation(&self) -> &[i32; 64] {
+    &self.board_64_squares_in_120_squares_notation
   }
 
-  pub fn bit_mask_to_set_bit_inside_bitboard(&self) -> [u64; 64] {
-    self.bit_mask_to_set_bit_inside_bitboard
+  pub fn bit_mask_to_set_bit_inside_bitboard(&self) -> &[u64; 64] {
+    &self.bit_mask_to_set_bit_inside_bitboard
   }
 
-  pub fn bit_mask_to_clear_bit_inside_bitboard(&self) -> [u64; 64] {
-    self.bit_mask_to_clear_bit_inside_bitboard
+  pub fn bit_mask_to_clear_bit_inside_bitboard(&self) -> &[u64; 64] {
+    &self.bit_mask_to_clear_bit_inside_bitboard
   }
 
-  pub fn piece_keys(&self) -> [[u64; 120]; 13] {
-    self.piece_keys
+  pub fn piece_keys(&self) -> &[[u64; 120]; 13] {
+    &self.piece_keys
   }
 
   pub fn side_key(&self) -> u64 {
     self.side_key
   }
 
-  pub fn castle_keys(&self) -> [u64; 16] {
-    self.castle_keys
+  pub fn castle_keys(&self) -> &[u64; 16] {
+    &self.castle_keys
+  }
+
+  pub fn files_board(&self) -> &[i32; BOARD_SQUARE_NUMBER] {
+    &self.files_board
+  }
+
+  pub fn ranks_board(&self) -> &[i32; BOARD_SQUARE_NUMBER] {
+    &self.ranks_board
   }
 
-  pub fn files_board(&self) -> [i32; BOARD_SQUARE_NUMBER] {
-    self.files_board
+  pub fn file_masks(&self) -> &[u64; 8] {
+    &self.file_masks
   }
 
-  pub fn ranks_board(&self) -> [i32; BOARD_SQUARE_NUMBER] {
-    self.ranks_board
+  pub fn adjacent_files_masks(&self) -> &[u64; 8] {
+    &self.adjacent_files_masks
+  }
+
+  pub fn passed_pawn_masks(&self) -> &[[u64; 64]; 2] {
+    &self.passed_pawn_masks
+  }
+
+  /**
+   * The initialized definitions shared by all the boards.
+   */
+  pub fn global() -> &'static Definitions {
+    DEFINITIONS.get_or_init(|| {
+      let mut definitions: Definitions = Definitions::new();
+      definitions.init();
+      definitions
+    })
   }
 
   pub fn init(&mut self) {
@@ -542,6 +1224,7 @@ impl Definitions {
     self.init_masks();
     self.init_hash_keys();
     self.init_files_ranks_board();
+    self.init_pawn_masks();
   }
 
   /**
@@ -568,6 +1251,7 @@ impl Definitions {
    *
    * Final structure of definitions.board_64_squares_in_120_squares_notation (as we play with black):
    *
+   * ```text
    *     A  B  C  D  E  F  G  H
    * 1  21 22 23 24 25 26 27 28
    * 2  31 32 33 34 35 36 37 38
@@ -577,8 +1261,10 @@ impl Definitions {
    * 6  71 72 73 74 75 76 77 78
    * 7  81 82 83 84 85 86 87 88
    * 8  91 92 93 94 95 96 97 98
+   * ```
    *
    * Final structure of definitions.board_120_squares_in_64_squares_notation (as we play with black):
+   * ```text
    *        A  B  C  D  E  F  G  H
    *    65 65 65 65 65 65 65 65 65 65
    *    65 65 65 65 65 65 65 65 65 65
@@ -592,6 +1278,7 @@ impl Definitions {
    * 8  65 56 57 58 59 60 61 62 63 65
    *    65 65 65 65 65 65 65 65 65 65
    *    65 65 65 65 65 65 65 65 65 65
+   * ```
    */
   fn init_squares(&mut self) {
     let mut square_120: i32;
@@ -619,10 +1306,20 @@ impl Definitions {
     }
   }
 
+  /**
+   * Every piece on every square, the side and every castle permission combination need their own random key,
+   * so each array element is generated separately.
+   */
   fn init_hash_keys(&mut self) {
-    self.piece_keys = [[generate_random_chess_piece_hash(); 120]; 13];
+    for piece_keys in self.piece_keys.iter_mut() {
+      for piece_key in piece_keys.iter_mut() {
+        *piece_key = generate_random_chess_piece_hash();
+      }
+    }
     self.side_key = generate_random_chess_piece_hash();
-    self.castle_keys = [generate_random_chess_piece_hash(); 16];
+    for castle_key in self.castle_keys.iter_mut() {
+      *castle_key = generate_random_chess_piece_hash();
+    }
   }
 
   /**
@@ -670,4 +1367,47 @@ impl Definitions {
       }
     }
   }
+
+  fn init_pawn_masks(&mut self) {
+    let mut file: i32;
+    let mut rank: i32;
+
+    for square_64 in 0..64 {
+      file = square_64 % 8;
+      self.file_masks[file as usize] |= 1u64 << square_64;
+    }
+
+    for file in ChessboardFiles::A as usize..=ChessboardFiles::H as usize {
+      if file > ChessboardFiles::A as usize {
+        self.adjacent_files_masks[file] |= self.file_masks[file - 1];
+      }
+      if file < ChessboardFiles::H as usize {
+        self.adjacent_files_masks[file] |= self.file_masks[file + 1];
+      }
+    }
+
+    for square_64 in 0..64 {
+      file = square_64 % 8;
+      rank = square_64 / 8;
+      for temp_square_64 in 0..64 {
+        if (temp_square_64 % 8 - file).abs() > 1 {
+          continue;
+        }
+        if temp_square_64 / 8 > rank {
+          self.passed_pawn_masks[Colors::White as usize][square_64 as usize] |=
+            1u64 << temp_square_64;
+        }
+        if temp_square_64 / 8 < rank {
+          self.passed_pawn_masks[Colors::Black as usize][square_64 as usize] |=
+            1u64 << temp_square_64;
+        }
+      }
+    }
+  }
+}
+
+impl Default for Definitions {
+  fn default() -> Definitions {
+    Definitions::new()
+  }
 }
@@ -0,0 +1,371 @@
+use crate::board::*;
+use crate::definitions::*;
+use crate::pawns::*;
+
+/**
+ * The scale factor of an evaluation that is not changed: the drawish endgames
+ * scale the evaluation down to a fraction of SCALE_NORMAL.
+ */
+pub const SCALE_NORMAL: i32 = 64;
+
+/**
+ * The score added to an endgame that is known to be won, below the mate
+ * and the tablebase scores.
+ */
+const KNOWN_WIN: i32 = 10000;
+
+/**
+ * The mop-up weights: the weak king is pushed to the edge (or to the corner where
+ * the mate is possible) and the strong king comes near to it.
+ */
+const PUSH_TO_EDGE: i32 = 20;
+const PUSH_TO_CORNER: i32 = 40;
+const PUSH_CLOSE: i32 = 10;
+
+const ROOKS_SCALE: i32 = 8;
+/**
+ * A rook against a bishop or a knight (without pawns) is a draw but in a few positions.
+ */
+const ROOK_MINOR_SCALE: i32 = 8;
+const OPPOSITE_BISHOPS_SCALE: i32 = 16;
+const OPPOSITE_BISHOPS_PAWNS_SCALE: i32 = 32;
+
+const FILE_A_MASK: u64 = 0x0101010101010101;
+const FILE_H_MASK: u64 = FILE_A_MASK << 7;
+
+/**
+ * The material signatures of the known endgames: the number of pawns, knights, bishops, rooks
+ * and queens of the strong side, then of the weak side, 4 bits each. The signatures with the
+ * pawns masked out match the endgames with any number of pawns.
+ */
+const WEAK_PAWNS_MASK: u64 = 0xF << 20;
+const PAWNS_MASK: u64 = 0xF | WEAK_PAWNS_MASK;
+const KQK: u64 = signature([0, 0, 0, 0, 1], [0, 0, 0, 0, 0]);
+const KRK: u64 = signature([0, 0, 0, 1, 0], [0, 0, 0, 0, 0]);
+const KBNK: u64 = signature([0, 1, 1, 0, 0], [0, 0, 0, 0, 0]);
+const KRKR: u64 = signature([0, 0, 0, 1, 0], [0, 0, 0, 1, 0]);
+const KRKB: u64 = signature([0, 0, 0, 1, 0], [0, 0, 1, 0, 0]);
+const KRKN: u64 = signature([0, 0, 0, 1, 0], [0, 1, 0, 0, 0]);
+const KBK: u64 = signature([0, 0, 1, 0, 0], [0, 0, 0, 0, 0]);
+const KBKB: u64 = signature([0, 0, 1, 0, 0], [0, 0, 1, 0, 0]);
+
+/**
+ * What is known about an endgame: its exact score (from the white point of view),
+ * or the scale factor of the normal evaluation.
+ */
+pub enum Endgame {
+  Score(i32),
+  Scale(i32),
+}
+
+const fn signature(strong: [u64; 5], weak: [u64; 5]) -> u64 {
+  let mut signature: u64 = 0;
+  let mut piece: usize = 0;
+
+  while piece < 5 {
+    signature |= strong[piece] << (4 * piece) | weak[piece] << (4 * (piece + 5));
+    piece += 1;
+  }
+
+  signature
+}
+
+/**
+ * The material signature of the board, with the given color as the strong side.
+ */
+fn material_signature(board: &Board, strong: Colors) -> u64 {
+  let (strong_pawn, weak_pawn): (usize, usize) = if strong == Colors::White {
+    (Pieces::Wp as usize, Pieces::Bp as usize)
+  } else {
+    (Pieces::Bp as usize, Pieces::Wp as usize)
+  };
+  let counts: &[i32; 13] = board.actual_pieces_number();
+
+  (0..5).fold(0, |signature, piece| {
+    signature
+      | (counts[strong_pawn + piece] as u64) << (4 * piece)
+      | (counts[weak_pawn + piece] as u64) << (4 * (piece + 5))
+  })
+}
+
+fn square_64(board: &Board, square: Squares) -> i32 {
+  board
+    .definitions()
+    .board_120_squares_in_64_squares_notation()[square as usize]
+}
+
+fn king_squares_64(board: &Board, strong: Colors) -> (i32, i32) {
+  let king_square: [Squares; 2] = board.king_square();
+  let weak: Colors = if strong == Colors::White {
+    Colors::Black
+  } else {
+    Colors::White
+  };
+
+  (
+    square_64(board, king_square[strong as usize]),
+    square_64(board, king_square[weak as usize]),
+  )
+}
+
+/**
+ * The distance of the square from the four central squares, from 0 to 6.
+ */
+fn center_distance(square_64: i32) -> i32 {
+  let (file, rank): (i32, i32) = (square_64 % 8, square_64 / 8);
+
+  (3 - file).max(file - 4) + (3 - rank).max(rank - 4)
+}
+
+fn is_dark_square(square_64: i32) -> bool {
+  (square_64 % 8 + square_64 / 8) % 2 == 0
+}
+
+/**
+ * The material of the strong side less the material of the weak side.
+ */
+fn material_advantage(board: &Board, strong: Colors) -> i32 {
+  let material: [i32; 2] = board.material();
+
+  if strong == Colors::White {
+    material[Colors::White as usize] - material[Colors::Black as usize]
+  } else {
+    material[Colors::Black as usize] - material[Colors::White as usize]
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Mop-up_Evaluation>
+ *
+ * KQvK and KRvK: the mate is given on the edge, so the weak king is pushed there.
+ */
+fn evaluate_lone_king(board: &Board, strong: Colors) -> i32 {
+  let (strong_king, weak_king): (i32, i32) = king_squares_64(board, strong);
+
+  KNOWN_WIN
+    + material_advantage(board, strong)
+    + PUSH_TO_EDGE * center_distance(weak_king)
+    + PUSH_CLOSE * (7 - distance(strong_king, weak_king))
+}
+
+/**
+ * KBNvK: the mate is given only in a corner of the color of the bishop. The weak king is pushed
+ * to the a1-h8 corners (the dark ones), or to the a8-h1 corners if the bishop is on the light squares.
+ */
+fn evaluate_bishop_knight(board: &Board, strong: Colors) -> i32 {
+  let (strong_king, mut weak_king): (i32, i32) = king_squares_64(board, strong);
+  let bishop: Pieces = if strong == Colors::White {
+    Pieces::Wb
+  } else {
+    Pieces::Bb
+  };
+  let bishop_square: i32 = square_64(board, board.pieces_list()[bishop as usize][0]);
+
+  if !is_dark_square(bishop_square) {
+    weak_king ^= 7;
+  }
+
+  KNOWN_WIN
+    + material_advantage(board, strong)
+    + PUSH_TO_CORNER * (7 - weak_king / 8 - weak_king % 8).abs()
+    + PUSH_CLOSE * (7 - distance(strong_king, weak_king))
+}
+
+/**
+ * KB with pawns vs K: if all the pawns are on the same rook file and the bishop doesn't control
+ * the promotion square, the weak king in front of them can't be driven away (also without pawns
+ * the bishop can't mate).
+ */
+fn is_wrong_rook_pawn(board: &Board, strong: Colors) -> bool {
+  let pawns: u64 = board.pawns()[strong as usize];
+  let (bishop, promotion_rank): (Pieces, i32) = if strong == Colors::White {
+    (Pieces::Wb, 7)
+  } else {
+    (Pieces::Bb, 0)
+  };
+  let promotion_square: i32 = if pawns & !FILE_A_MASK == 0 {
+    promotion_rank * 8
+  } else if pawns & !FILE_H_MASK == 0 {
+    promotion_rank * 8 + 7
+  } else {
+    return false;
+  };
+  let bishop_square: i32 = square_64(board, board.pieces_list()[bishop as usize][0]);
+  let (_, weak_king): (i32, i32) = king_squares_64(board, strong);
+
+  pawns == 0
+    || (is_dark_square(bishop_square) != is_dark_square(promotion_square)
+      && distance(weak_king, promotion_square) <= 1)
+}
+
+/**
+ * The bishops (the only pieces left with the pawns) on squares of different colors can't
+ * fight each other, so the weak side can usually block the pawns.
+ */
+fn opposite_bishops_scale(board: &Board) -> Option<i32> {
+  let white_bishop: i32 = square_64(board, board.pieces_list()[Pieces::Wb as usize][0]);
+  let black_bishop: i32 = square_64(board, board.pieces_list()[Pieces::Bb as usize][0]);
+  let counts: &[i32; 13] = board.actual_pieces_number();
+
+  if is_dark_square(white_bishop) == is_dark_square(black_bishop) {
+    None
+  } else if (counts[Pieces::Wp as usize] - counts[Pieces::Bp as usize]).abs() <= 1 {
+    Some(OPPOSITE_BISHOPS_SCALE)
+  } else {
+    Some(OPPOSITE_BISHOPS_PAWNS_SCALE)
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Endgame>
+ *
+ * The known endgames are found by the material signature of the board, with each color
+ * as the strong side: the won ones get their exact score, the drawish ones a scale factor.
+ */
+pub fn probe_endgame(board: &Board) -> Option<Endgame> {
+  let mut signature: u64;
+  let mut sign: i32;
+
+  for strong in [Colors::White, Colors::Black] {
+    signature = material_signature(board, strong);
+    sign = if strong == Colors::White { 1 } else { -1 };
+
+    match signature {
+      KQK | KRK => return Some(Endgame::Score(sign * evaluate_lone_king(board, strong))),
+      KBNK => return Some(Endgame::Score(sign * evaluate_bishop_knight(board, strong))),
+      KRKR => return Some(Endgame::Scale(ROOKS_SCALE)),
+      KRKB | KRKN => return Some(Endgame::Scale(ROOK_MINOR_SCALE)),
+      _ => {}
+    }
+    if signature & !PAWNS_MASK == KBK
+      && signature & WEAK_PAWNS_MASK == 0
+      && is_wrong_rook_pawn(board, strong)
+    {
+      return Some(Endgame::Scale(0));
+    }
+    if signature & !PAWNS_MASK == KBKB {
+      return opposite_bishops_scale(board).map(Endgame::Scale);
+    }
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::evaluate::evaluate_position;
+  use crate::movegen::generate_legal_moves;
+  use crate::parameters::EvaluationParameters;
+  use crate::pawns::PawnHashTable;
+  use crate::pvtable::HashTable;
+  use crate::search::{is_in_check, search_best_move, SearchInfo};
+
+  /**
+   * The static evaluation, from the point of view of the side to move.
+   */
+  fn evaluation(fen: &str) -> i32 {
+    let board: Board = Board::from_fen(fen).unwrap();
+
+    evaluate_position(
+      &board,
+      &EvaluationParameters::new(),
+      &mut PawnHashTable::default(),
+    )
+  }
+
+  /**
+   * Both sides search at the given depth until the game ends or the moves are over,
+   * it returns true if the side to move at the start gave the mate.
+   */
+  fn gives_mate(fen: &str, depth: i32, moves: usize) -> bool {
+    let mut board: Board = Board::from_fen(fen).unwrap();
+    let mut info: SearchInfo = SearchInfo::new();
+    let mut table: HashTable = HashTable::new(16);
+    let side: Colors = board.side();
+
+    for _ in 0..2 * moves {
+      if generate_legal_moves(&mut board).is_empty() {
+        return is_in_check(&board) && board.side() != side;
+      }
+      info.clear_limits();
+      info.set_depth(depth);
+      let (best_move, _): (Move, i32) = search_best_move(&mut board, &mut info, &mut table);
+      assert!(board.make_move(best_move));
+    }
+    false
+  }
+
+  #[test]
+  fn lone_king_is_pushed_to_the_edge() {
+    for (edge, centre) in [
+      (
+        "7k/8/8/8/8/8/8/1Q2K3 w - - 0 1",
+        "8/8/8/4k3/8/8/8/1Q2K3 w - - 0 1",
+      ),
+      (
+        "7k/8/8/8/8/8/8/1R2K3 w - - 0 1",
+        "8/8/8/4k3/8/8/8/1R2K3 w - - 0 1",
+      ),
+    ] {
+      assert!(evaluation(centre) > KNOWN_WIN, "{}", centre);
+      assert!(evaluation(edge) > evaluation(centre), "{}", edge);
+    }
+    // The strong king comes near to the weak one
+    assert!(
+      evaluation("7k/8/6K1/8/8/8/8/1R6 w - - 0 1") > evaluation("7k/8/8/8/8/8/8/1R4K1 w - - 0 1")
+    );
+    // The same seen from the weak side
+    assert!(evaluation("8/8/8/4k3/8/8/8/1Q2K3 b - - 0 1") < -KNOWN_WIN);
+  }
+
+  #[test]
+  fn bishop_and_knight_push_to_the_bishop_corner() {
+    // The bishop on a dark square mates on a1 or h8, on a light square on a8 or h1
+    let dark_corner: i32 = evaluation("7k/8/8/8/8/8/8/2B1KN2 w - - 0 1");
+    let light_corner: i32 = evaluation("k7/8/8/8/8/8/8/2B1KN2 w - - 0 1");
+    assert!(dark_corner > light_corner);
+    assert!(light_corner > KNOWN_WIN);
+
+    let dark_corner: i32 = evaluation("7k/8/8/8/8/8/8/3BKN2 w - - 0 1");
+    let light_corner: i32 = evaluation("k7/8/8/8/8/8/8/3BKN2 w - - 0 1");
+    assert!(light_corner > dark_corner);
+  }
+
+  #[test]
+  fn queen_and_rook_give_the_mate() {
+    assert!(gives_mate("8/8/8/3k4/8/8/8/3QK3 w - - 0 1", 4, 30));
+    assert!(gives_mate("8/8/8/3k4/8/8/8/3RK3 w - - 0 1", 4, 40));
+    assert!(gives_mate("3rk3/8/8/3K4/8/8/8/8 b - - 0 1", 4, 40));
+  }
+
+  #[test]
+  fn drawish_endgames_are_scaled() {
+    // A rook against a minor piece or a rook
+    for fen in [
+      "4k3/8/8/8/2b5/8/8/R3K3 w - - 0 1",
+      "4k3/8/8/8/2n5/8/8/R3K3 w - - 0 1",
+      "4k3/8/8/8/2R5/8/r7/4K3 b - - 0 1",
+    ] {
+      assert!(matches!(
+        probe_endgame(&Board::from_fen(fen).unwrap()),
+        Some(Endgame::Scale(_))
+      ));
+      assert!(evaluation(fen).abs() < 50, "{}", fen);
+    }
+
+    // The a pawn with the bishop that does not control a8, the black king in front of it
+    assert_eq!(evaluation("k7/8/8/8/8/8/P7/2B1K3 w - - 0 1"), 0);
+    assert_eq!(evaluation("k7/8/8/8/8/P7/P7/2B1K3 w - - 0 1"), 0);
+    assert!(evaluation("k7/8/8/8/8/8/P7/3BK3 w - - 0 1") > 100);
+    assert!(evaluation("8/8/8/8/4k3/8/P7/2B1K3 w - - 0 1") > 100);
+
+    // A pawn up with the bishops on squares of different colors, and of the same color
+    let opposite: i32 = evaluation("4k3/5p2/4b3/8/8/8/3BP1P1/4K3 w - - 0 1");
+    let same: i32 = evaluation("4k3/5p2/3b4/8/8/8/3BP1P1/4K3 w - - 0 1");
+    assert!(opposite.abs() < 50, "{}", opposite);
+    assert!(same > 2 * opposite.max(1), "{} {}", same, opposite);
+  }
+}
@@ -0,0 +1,563 @@
+use crate::attack::*;
+use crate::board::*;
+use crate::definitions::*;
+use crate::endgame::*;
+use crate::parameters::*;
+use crate::pawns::*;
+
+/**
+ * When the material of the opponent (without the king) is below this value,
+ * we consider the position an endgame (a rook, a knight and two pawns).
+ */
+const ENDGAME_MATERIAL: i32 = PIECE_VALUE[Pieces::Wr as usize]
+  + PIECE_VALUE[Pieces::Wn as usize]
+  + 2 * PIECE_VALUE[Pieces::Wp as usize]
+  + PIECE_VALUE[Pieces::Wk as usize];
+
+/**
+ * The average number of reachable squares of knights, bishops, rooks and queens:
+ * a piece with more squares than these gets a mobility bonus, with less a malus.
+ */
+const AVERAGE_MOBILITY: [i32; 4] = [4, 7, 7, 14];
+
+/**
+ * How much of the king attack weight becomes a malus (in percentage) by number of attackers:
+ * a single attacker is not dangerous, many attackers together are.
+ */
+const KING_ATTACKERS_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+/**
+ * The pieces whose activity is evaluated (the king is evaluated by king_shelter).
+ */
+const WHITE_PIECES: [Pieces; 4] = [Pieces::Wn, Pieces::Wb, Pieces::Wr, Pieces::Wq];
+const BLACK_PIECES: [Pieces; 4] = [Pieces::Bn, Pieces::Bb, Pieces::Br, Pieces::Bq];
+
+/**
+ * The seventh rank seen from each color (in 64 squares notation).
+ */
+const SEVENTH_RANK_MASK: [u64; 2] = [0xFF << 48, 0xFF << 8];
+
+/**
+ * The number of pieces attacking the squares around a king and the sum of their weights.
+ */
+struct KingAttack {
+  attackers: i32,
+  weight: i32,
+}
+
+/**
+ * It flips the square vertically: A1 (0) becomes A8 (56), E2 (12) becomes E7 (52) and so on.
+ */
+fn mirror_64(square_64: i32) -> i32 {
+  square_64 ^ 56
+}
+
+fn opponent_side(color: Colors) -> Colors {
+  if color == Colors::White {
+    Colors::Black
+  } else {
+    Colors::White
+  }
+}
+
+/**
+ * The score of the pieces of the given type, with the piece square table
+ * seen from the point of view of the pieces color.
+ */
+fn pieces_table_score(board: &Board, piece: Pieces, table: &[i32; 64]) -> i32 {
+  let mut score: i32 = 0;
+  let mut square_64: i32;
+
+  for piece_number in 0..board.actual_pieces_number()[piece as usize] {
+    square_64 = board
+      .definitions()
+      .board_120_squares_in_64_squares_notation()
+      [board.pieces_list()[piece as usize][piece_number as usize] as usize];
+    if PIECE_COLOR[piece as usize] == Colors::Black {
+      square_64 = mirror_64(square_64);
+    }
+    score += table[square_64 as usize];
+  }
+
+  score
+}
+
+/**
+ * The material of white minus the material of black, with the values of the parameters.
+ */
+fn material_score(board: &Board, parameters: &EvaluationParameters) -> i32 {
+  let mut score: i32 = 0;
+
+  for piece in Pieces::Wp as usize..=Pieces::Wq as usize {
+    score += parameters.piece_value[piece - Pieces::Wp as usize]
+      * (board.actual_pieces_number()[piece] - board.actual_pieces_number()[piece + 6]);
+  }
+
+  score
+}
+
+fn attacked_by_pawn(
+  pieces: &[Pieces; BOARD_SQUARE_NUMBER],
+  square_120: i32,
+  color: Colors,
+) -> bool {
+  if color == Colors::White {
+    pieces[(square_120 - 9) as usize] == Pieces::Wp
+      || pieces[(square_120 - 11) as usize] == Pieces::Wp
+  } else {
+    pieces[(square_120 + 9) as usize] == Pieces::Bp
+      || pieces[(square_120 + 11) as usize] == Pieces::Bp
+  }
+}
+
+/**
+ * The king zone is the king square with the squares around it.
+ */
+fn in_king_zone(definitions: &Definitions, square_120: i32, king_square_120: i32) -> bool {
+  (definitions.files_board()[square_120 as usize]
+    - definitions.files_board()[king_square_120 as usize])
+    .abs()
+    <= 1
+    && (definitions.ranks_board()[square_120 as usize]
+      - definitions.ranks_board()[king_square_120 as usize])
+      .abs()
+      <= 1
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Mobility>
+ * <https://www.chessprogramming.org/King_Safety>
+ *
+ * The squares attacked by the piece are the ones of the attack queries (see visit_piece_attacks):
+ * the empty and the opponent squares not attacked by an opponent pawn are the mobility of the piece,
+ * and if any of them is around the opponent king, the piece attacks the king.
+ */
+fn piece_mobility(
+  board: &Board,
+  square: Squares,
+  color: Colors,
+  opponent_king_square: i32,
+  king_attack: &mut KingAttack,
+  parameters: &EvaluationParameters,
+) -> i32 {
+  let pieces: &[Pieces; BOARD_SQUARE_NUMBER] = board.pieces();
+  let piece: Pieces = pieces[square as usize];
+  let piece_type: usize = piece_type_index(piece);
+  let opponent: Colors = opponent_side(color);
+  let mut mobility: i32 = 0;
+  let mut attacks_king: bool = false;
+
+  visit_piece_attacks(square, pieces, |temp_square| {
+    let target_piece: Pieces = pieces[temp_square as usize];
+
+    if in_king_zone(
+      board.definitions(),
+      temp_square as i32,
+      opponent_king_square,
+    ) {
+      attacks_king = true;
+    }
+    if (target_piece == Pieces::Empty || PIECE_COLOR[target_piece as usize] == opponent)
+      && !attacked_by_pawn(pieces, temp_square as i32, opponent)
+    {
+      mobility += 1;
+    }
+  });
+
+  if attacks_king {
+    king_attack.attackers += 1;
+    king_attack.weight += parameters.king_attack_weight[piece_type];
+  }
+
+  parameters.mobility[piece_type] * (mobility - AVERAGE_MOBILITY[piece_type])
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Outposts>
+ *
+ * A knight or a bishop on the opponent half of the board, defended by an own pawn,
+ * that no opponent pawn can ever attack.
+ */
+fn is_outpost(board: &Board, square_120: i32, color: Colors) -> bool {
+  let definitions: &Definitions = board.definitions();
+  let square_64: i32 = definitions.board_120_squares_in_64_squares_notation()[square_120 as usize];
+  let rank: usize = relative_rank(square_64, color);
+  let file: usize = (square_64 % 8) as usize;
+
+  (3..=5).contains(&rank)
+    && attacked_by_pawn(board.pieces(), square_120, color)
+    && board.pawns()[opponent_side(color) as usize]
+      & definitions.passed_pawn_masks()[color as usize][square_64 as usize]
+      & definitions.adjacent_files_masks()[file]
+      == 0
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Trapped_Pieces>
+ *
+ * A bishop that captured the pawn on a7 (h7) and is locked in by the pawn on b6 (g6).
+ */
+fn is_trapped_bishop(board: &Board, square_120: i32, color: Colors) -> bool {
+  let pieces: &[Pieces; BOARD_SQUARE_NUMBER] = board.pieces();
+
+  if color == Colors::White {
+    (square_120 == Squares::A7 as i32 && pieces[Squares::B6 as usize] == Pieces::Bp)
+      || (square_120 == Squares::H7 as i32 && pieces[Squares::G6 as usize] == Pieces::Bp)
+  } else {
+    (square_120 == Squares::A2 as i32 && pieces[Squares::B3 as usize] == Pieces::Wp)
+      || (square_120 == Squares::H2 as i32 && pieces[Squares::G3 as usize] == Pieces::Wp)
+  }
+}
+
+/**
+ * A rook locked in the corner by its own king, that can't castle anymore on that side.
+ */
+fn is_trapped_rook(board: &Board, square_120: i32, color: Colors) -> bool {
+  let definitions: &Definitions = board.definitions();
+  let king_square: i32 = board.king_square()[color as usize] as i32;
+  let (first_rank, king_side_castle, queen_side_castle): (i32, CastlingRights, CastlingRights) =
+    if color == Colors::White {
+      (
+        ChessboardRanks::R1 as i32,
+        CastlingRights::WHITE_KING_SIDE,
+        CastlingRights::WHITE_QUEEN_SIDE,
+      )
+    } else {
+      (
+        ChessboardRanks::R8 as i32,
+        CastlingRights::BLACK_KING_SIDE,
+        CastlingRights::BLACK_QUEEN_SIDE,
+      )
+    };
+  let rook_file: i32 = definitions.files_board()[square_120 as usize];
+  let king_file: i32 = definitions.files_board()[king_square as usize];
+
+  if definitions.ranks_board()[square_120 as usize] != first_rank
+    || definitions.ranks_board()[king_square as usize] != first_rank
+  {
+    return false;
+  }
+
+  (king_file >= ChessboardFiles::F as i32
+    && king_file <= ChessboardFiles::G as i32
+    && rook_file > king_file
+    && !board.castling_rights().contains(king_side_castle))
+    || (king_file >= ChessboardFiles::B as i32
+      && king_file <= ChessboardFiles::D as i32
+      && rook_file < king_file
+      && !board.castling_rights().contains(queen_side_castle))
+}
+
+/**
+ * The activity of the knights, bishops, rooks and queens of the color: mobility, attacks
+ * to the opponent king (returned in king_attack), rooks on open files and on the seventh rank,
+ * bishop pair, outposts and trapped pieces.
+ */
+fn evaluate_pieces(
+  board: &Board,
+  parameters: &EvaluationParameters,
+  color: Colors,
+  king_attack: &mut KingAttack,
+) -> i32 {
+  let definitions: &Definitions = board.definitions();
+  let opponent: Colors = opponent_side(color);
+  let (side_pieces, bishop): ([Pieces; 4], Pieces) = if color == Colors::White {
+    (WHITE_PIECES, Pieces::Wb)
+  } else {
+    (BLACK_PIECES, Pieces::Bb)
+  };
+  let opponent_king_square: i32 = board.king_square()[opponent as usize] as i32;
+  let opponent_king_64: i32 =
+    definitions.board_120_squares_in_64_squares_notation()[opponent_king_square as usize];
+  let mut score: i32 = 0;
+  let mut square_120: i32;
+  let mut square_64: i32;
+  let mut file_mask: u64;
+
+  for piece in side_pieces {
+    for &square in
+      &board.pieces_list()[piece as usize][0..board.actual_pieces_number()[piece as usize] as usize]
+    {
+      square_120 = square as i32;
+      square_64 = definitions.board_120_squares_in_64_squares_notation()[square_120 as usize];
+      score += piece_mobility(
+        board,
+        square,
+        color,
+        opponent_king_square,
+        king_attack,
+        parameters,
+      );
+
+      if PIECE_KNIGHT[piece as usize] {
+        if is_outpost(board, square_120, color) {
+          score += parameters.knight_outpost;
+        }
+      } else if PIECE_ROOK_QUEEN[piece as usize] && !PIECE_BISHOP_QUEEN[piece as usize] {
+        file_mask = definitions.file_masks()[(square_64 % 8) as usize];
+        if board.pawns()[Colors::Both as usize] & file_mask == 0 {
+          score += parameters.rook_open_file;
+        } else if board.pawns()[color as usize] & file_mask == 0 {
+          score += parameters.rook_semi_open_file;
+        }
+        // Useful only if it attacks pawns or keeps the king on the last rank
+        if relative_rank(square_64, color) == 6
+          && (relative_rank(opponent_king_64, color) == 7
+            || board.pawns()[opponent as usize] & SEVENTH_RANK_MASK[color as usize] != 0)
+        {
+          score += parameters.rook_seventh_rank;
+        }
+        if is_trapped_rook(board, square_120, color) {
+          score += parameters.trapped_rook;
+        }
+      } else if !PIECE_ROOK_QUEEN[piece as usize] {
+        if is_outpost(board, square_120, color) {
+          score += parameters.bishop_outpost;
+        }
+        if is_trapped_bishop(board, square_120, color) {
+          score += parameters.trapped_bishop;
+        }
+      }
+    }
+  }
+
+  if board.actual_pieces_number()[bishop as usize] >= 2 {
+    score += parameters.bishop_pair;
+  }
+
+  score
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/King_Safety#Pawn_Shield>
+ * <https://www.chessprogramming.org/King_Safety#Pawn_Storm>
+ *
+ * The own pawns in front of the king protect it, the opponent pawns coming
+ * toward it open the files for the attack. Only the king file and the adjacent ones are considered.
+ */
+fn king_shelter(board: &Board, parameters: &EvaluationParameters, color: Colors) -> i32 {
+  let pieces: &[Pieces; BOARD_SQUARE_NUMBER] = board.pieces();
+  let king_square: i32 = board.king_square()[color as usize] as i32;
+  let (pawn, opponent_pawn, forward): (Pieces, Pieces, i32) = if color == Colors::White {
+    (Pieces::Wp, Pieces::Bp, 10)
+  } else {
+    (Pieces::Bp, Pieces::Wp, -10)
+  };
+  let mut score: i32 = 0;
+  let mut file_square: i32;
+  let mut temp_square: i32;
+
+  for file_direction in [-1, 0, 1] {
+    file_square = king_square + file_direction;
+    if pieces[file_square as usize] == Pieces::OffBoard {
+      continue;
+    }
+
+    if pieces[(file_square + forward) as usize] == pawn {
+      score += parameters.pawn_shield[0];
+    } else if pieces[(file_square + 2 * forward) as usize] == pawn {
+      score += parameters.pawn_shield[1];
+    } else {
+      score += parameters.pawn_shield[2];
+    }
+
+    for distance in 1..=3 {
+      temp_square = file_square + distance * forward;
+      if temp_square < 0 || temp_square as usize >= BOARD_SQUARE_NUMBER {
+        break;
+      }
+      if pieces[temp_square as usize] == opponent_pawn {
+        score += parameters.pawn_storm[distance as usize - 1];
+        break;
+      }
+    }
+  }
+
+  score
+}
+
+/**
+ * The malus for the pieces attacking the squares around the king.
+ */
+fn king_danger(king_attack: &KingAttack) -> i32 {
+  king_attack.weight * KING_ATTACKERS_SCALE[(king_attack.attackers as usize).min(7)] / 100
+}
+
+/**
+ * It returns the static evaluation of the position from the point of view of the side to move,
+ * a positive score means that the side to move is better.
+ * The pawn structure is read from the pawn hash table (and evaluated if it's not there).
+ * If the board has a NNUE network, the network evaluates the position instead.
+ * The known endgames have their own score, or scale the evaluation toward a draw.
+ */
+pub fn evaluate_position(
+  board: &Board,
+  parameters: &EvaluationParameters,
+  pawn_table: &mut PawnHashTable,
+) -> i32 {
+  let scale: i32 = match probe_endgame(board) {
+    Some(Endgame::Score(score)) => {
+      return if board.side() == Colors::White {
+        score
+      } else {
+        -score
+      };
+    }
+    Some(Endgame::Scale(scale)) => scale,
+    None => SCALE_NORMAL,
+  };
+
+  if let Some(network) = board.network() {
+    return network.evaluate(board.accumulator(), board.side()) * scale / SCALE_NORMAL;
+  }
+
+  let pawn_entry: PawnEntry = pawn_table.probe(board, parameters);
+  let mut white_king_attack: KingAttack = KingAttack {
+    attackers: 0,
+    weight: 0,
+  };
+  let mut black_king_attack: KingAttack = KingAttack {
+    attackers: 0,
+    weight: 0,
+  };
+  let mut score: i32 = material_score(board, parameters);
+
+  score += pawn_entry.score();
+
+  score += pieces_table_score(board, Pieces::Wp, &parameters.pawn_table);
+  score -= pieces_table_score(board, Pieces::Bp, &parameters.pawn_table);
+  score += pieces_table_score(board, Pieces::Wn, &parameters.knight_table);
+  score -= pieces_table_score(board, Pieces::Bn, &parameters.knight_table);
+  score += pieces_table_score(board, Pieces::Wb, &parameters.bishop_table);
+  score -= pieces_table_score(board, Pieces::Bb, &parameters.bishop_table);
+  score += pieces_table_score(board, Pieces::Wr, &parameters.rook_table);
+  score -= pieces_table_score(board, Pieces::Br, &parameters.rook_table);
+  score += pieces_table_score(board, Pieces::Wq, &parameters.rook_table);
+  score -= pieces_table_score(board, Pieces::Bq, &parameters.rook_table);
+
+  // The white pieces attack the black king and vice versa
+  score += evaluate_pieces(board, parameters, Colors::White, &mut black_king_attack);
+  score -= evaluate_pieces(board, parameters, Colors::Black, &mut white_king_attack);
+
+  if board.material()[Colors::Black as usize] <= ENDGAME_MATERIAL {
+    score += pieces_table_score(board, Pieces::Wk, &parameters.king_endgame_table);
+    score += passed_pawns_king_proximity(board, parameters, &pawn_entry, Colors::White);
+  } else {
+    score += pieces_table_score(board, Pieces::Wk, &parameters.king_opening_table);
+    score += king_shelter(board, parameters, Colors::White);
+    score -= king_danger(&white_king_attack);
+  }
+  if board.material()[Colors::White as usize] <= ENDGAME_MATERIAL {
+    score -= pieces_table_score(board, Pieces::Bk, &parameters.king_endgame_table);
+    score -= passed_pawns_king_proximity(board, parameters, &pawn_entry, Colors::Black);
+  } else {
+    score -= pieces_table_score(board, Pieces::Bk, &parameters.king_opening_table);
+    score -= king_shelter(board, parameters, Colors::Black);
+    score += king_danger(&black_king_attack);
+  }
+  score = score * scale / SCALE_NORMAL;
+
+  if board.side() == Colors::White {
+    score
+  } else {
+    -score
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /**
+   * The same position with the colors swapped: the ranks are mirrored and the side to move,
+   * the castling rights and the en passant square change color.
+   */
+  fn color_flipped(fen: &str) -> String {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let swap_case = |text: &str| -> String {
+      text
+        .chars()
+        .map(|character: char| {
+          if character.is_ascii_uppercase() {
+            character.to_ascii_lowercase()
+          } else {
+            character.to_ascii_uppercase()
+          }
+        })
+        .collect()
+    };
+    let ranks: Vec<&str> = fields[0].split('/').rev().collect();
+    let side: &str = if fields[1] == "w" { "b" } else { "w" };
+    let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+    let en_passant: String = fields[3]
+      .replace('3', "x")
+      .replace('6', "3")
+      .replace('x', "6");
+
+    // The white rights come first
+    castling
+      .sort_by_key(|&right: &char| (right.is_ascii_lowercase(), right != 'K' && right != 'k'));
+
+    format!(
+      "{} {} {} {} {}",
+      swap_case(&ranks.join("/")),
+      side,
+      castling.into_iter().collect::<String>(),
+      en_passant,
+      fields[4..].join(" ")
+    )
+  }
+
+  /**
+   * The static evaluation from the white point of view.
+   */
+  fn white_evaluation(fen: &str) -> i32 {
+    let board: Board = Board::from_fen(fen).unwrap();
+    let score: i32 = evaluate_position(
+      &board,
+      &EvaluationParameters::new(),
+      &mut PawnHashTable::default(),
+    );
+
+    if board.side() == Colors::White {
+      score
+    } else {
+      -score
+    }
+  }
+
+  #[test]
+  fn color_flipped_positions_have_the_negated_score() {
+    for fen in [
+      "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+      "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 8",
+      "6k1/5ppp/8/3N4/8/1P6/P4PPP/3R2K1 b - - 0 30",
+      "8/2k5/3p4/p2P1p2/P2P1P2/8/3K4/8 w - - 0 50",
+      "2kr3r/ppp2q2/2n1b3/3pP1p1/3P4/2PB1N2/P4QPP/R4RK1 w - - 0 20",
+      "8/8/4k3/8/2R5/8/3KB3/8 b - - 0 60",
+    ] {
+      let flipped: String = color_flipped(fen);
+
+      assert_eq!(
+        white_evaluation(fen),
+        -white_evaluation(&flipped),
+        "{} and {}",
+        fen,
+        flipped
+      );
+    }
+  }
+
+  #[test]
+  fn color_flip_mirrors_the_fen() {
+    assert_eq!(
+      color_flipped("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 0 2"),
+      "r3k2r/8/8/8/3Pp3/8/8/R3K2R b Qk d3 0 2"
+    );
+  }
+}
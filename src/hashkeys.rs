@@ -5,12 +5,10 @@ pub fn generate_position_key(definitions: &Definitions, board: &Board) -> u64 {
   let mut final_key: u64 = 0;
   let mut piece: Pieces;
 
-  for square_120 in 0..BOARD_SQUARE_NUMBER {
-    if board.pieces()[square_120 as usize] != Squares::OffBoard as i32 {
-      piece = Pieces::from_u32(board.pieces()[square_120 as usize] as u32);
-      if piece as u32 != Squares::NoSquare as u32 && piece != Pieces::Empty {
-        final_key ^= definitions.piece_keys()[piece as usize][square_120 as usize];
-      }
+  for square in Squares::iter() {
+    piece = board.piece_at(square);
+    if piece != Pieces::Empty {
+      final_key ^= definitions.piece_keys()[piece as usize][square as usize];
     }
   }
 
@@ -23,7 +21,24 @@ pub fn generate_position_key(definitions: &Definitions, board: &Board) -> u64 {
       definitions.piece_keys()[Pieces::Empty as usize][board.en_passant_square() as usize];
   }
 
-  final_key ^= definitions.castle_keys()[board.castel_permission() as usize];
+  final_key ^= definitions.castle_keys()[board.castling_rights().bits() as usize];
+
+  final_key
+}
+
+/**
+ * The same as the position key, but only with the pawns.
+ */
+pub fn generate_pawn_key(definitions: &Definitions, board: &Board) -> u64 {
+  let mut final_key: u64 = 0;
+  let mut piece: Pieces;
+
+  for square in Squares::iter() {
+    piece = board.piece_at(square);
+    if piece == Pieces::Wp || piece == Pieces::Bp {
+      final_key ^= definitions.piece_keys()[piece as usize][square as usize];
+    }
+  }
 
   final_key
 }
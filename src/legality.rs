@@ -0,0 +1,254 @@
+use std::fmt;
+
+use crate::attack::*;
+use crate::bitboards::*;
+use crate::board::*;
+use crate::definitions::*;
+
+/**
+ * Why a position can not be reached from the start position with legal moves
+ * (see Board::is_legal_position).
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum IllegalPosition {
+  KingCount {
+    color: Colors,
+    count: i32,
+  },
+  PawnOnBackRank {
+    square: Squares,
+  },
+  TooManyPawns {
+    color: Colors,
+    count: i32,
+  },
+  /**
+   * More pieces than the start ones plus the pawns that are missing (they could have promoted).
+   */
+  TooManyPromotedPieces {
+    color: Colors,
+    promoted: i32,
+  },
+  /**
+   * The king of the side that has just moved is attacked.
+   */
+  OpponentInCheck,
+  /**
+   * More than two checkers, or two that no move could have given together.
+   */
+  ImpossibleCheck {
+    checkers: Vec<Squares>,
+  },
+  /**
+   * No pawn could have made the double push that allows the en passant capture.
+   */
+  EnPassantSquare {
+    square: Squares,
+  },
+  CastlingRights {
+    castle: CastlingRights,
+  },
+}
+
+impl fmt::Display for IllegalPosition {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      IllegalPosition::KingCount { color, count } => {
+        write!(formatter, "{:?} has {} kings", color, count)
+      }
+      IllegalPosition::PawnOnBackRank { square } => write!(formatter, "pawn on {}", square),
+      IllegalPosition::TooManyPawns { color, count } => {
+        write!(formatter, "{:?} has {} pawns", color, count)
+      }
+      IllegalPosition::TooManyPromotedPieces { color, promoted } => write!(
+        formatter,
+        "{:?} has {} promoted pieces, more than its missing pawns",
+        color, promoted
+      ),
+      IllegalPosition::OpponentInCheck => write!(formatter, "the side not to move is in check"),
+      IllegalPosition::ImpossibleCheck { checkers } => write!(
+        formatter,
+        "impossible check from {}",
+        checkers
+          .iter()
+          .map(|square| square.to_string())
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      IllegalPosition::EnPassantSquare { square } => {
+        write!(formatter, "impossible en passant square {}", square)
+      }
+      IllegalPosition::CastlingRights { castle } => {
+        write!(
+          formatter,
+          "castling rights {} without its king and rook",
+          castle
+        )
+      }
+    }
+  }
+}
+
+/**
+ * Two checkers are possible only if one of them is a slider uncovered by the move of the
+ * other (or by an en passant capture), so they can not be two pawns or knights, nor be on
+ * the same line on the two sides of the king (the uncovered slider was blocked on a square
+ * that the other checker can not come from).
+ */
+fn possible_checkers(board: &Board, king_square: Squares, checkers: &[Squares]) -> bool {
+  let slider = |square: &Squares| PIECE_SLIDES[board.piece_at(*square) as usize];
+  let offset = |square: Squares| {
+    (
+      square.file() as i32 - king_square.file() as i32,
+      square.rank() as i32 - king_square.rank() as i32,
+    )
+  };
+
+  match checkers {
+    [_, _, _, ..] => false,
+    [first, second] => {
+      let ((first_file, first_rank), (second_file, second_rank)): ((i32, i32), (i32, i32)) =
+        (offset(*first), offset(*second));
+
+      checkers.iter().any(slider) && first_file * second_rank != second_file * first_rank
+    }
+    _ => true,
+  }
+}
+
+impl Board {
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Chess_Position#Legal_Positions>
+   *
+   * parse_fen only checks the syntax and the kings, this checks the positions set up by the
+   * user (or a board not set up yet): the kings, the pawns and the promoted pieces, the checks, the en passant square and the castling
+   * rights (on their standard squares when it's not Chess960). It returns all the reasons
+   * why the position is not legal.
+   */
+  pub fn is_legal_position(&self) -> Result<(), Vec<IllegalPosition>> {
+    let mut reasons: Vec<IllegalPosition> = Vec::new();
+    let pieces_number: &[i32; 13] = self.actual_pieces_number();
+    let side: Colors = self.side();
+    let opponent: Colors = if side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    let mut kings_found: bool = true;
+
+    for color in [Colors::White, Colors::Black] {
+      let [pawn, knight, bishop, rook, queen, king]: [Pieces; 6] = if color == Colors::White {
+        [
+          Pieces::Wp,
+          Pieces::Wn,
+          Pieces::Wb,
+          Pieces::Wr,
+          Pieces::Wq,
+          Pieces::Wk,
+        ]
+      } else {
+        [
+          Pieces::Bp,
+          Pieces::Bn,
+          Pieces::Bb,
+          Pieces::Br,
+          Pieces::Bq,
+          Pieces::Bk,
+        ]
+      };
+      // The bishops of each square color beyond the first one are promoted
+      let light_bishops: i32 = Squares::iter()
+        .filter(|&square| {
+          self.piece_at(square) == bishop && (square.file() as i32 + square.rank() as i32) % 2 == 1
+        })
+        .count() as i32;
+      let promoted: i32 = (pieces_number[knight as usize] - 2).max(0)
+        + (light_bishops - 1).max(0)
+        + (pieces_number[bishop as usize] - light_bishops - 1).max(0)
+        + (pieces_number[rook as usize] - 2).max(0)
+        + (pieces_number[queen as usize] - 1).max(0);
+
+      if pieces_number[king as usize] != 1 {
+        kings_found = false;
+        reasons.push(IllegalPosition::KingCount {
+          color,
+          count: pieces_number[king as usize],
+        });
+      }
+      if pieces_number[pawn as usize] > 8 {
+        reasons.push(IllegalPosition::TooManyPawns {
+          color,
+          count: pieces_number[pawn as usize],
+        });
+      }
+      if promoted > (8 - pieces_number[pawn as usize]).max(0) {
+        reasons.push(IllegalPosition::TooManyPromotedPieces { color, promoted });
+      }
+    }
+
+    // Matched and not looked up in PIECE_PAWN, as the squares of an unset board are off board
+    for square in Squares::iter() {
+      if matches!(self.piece_at(square), Pieces::Wp | Pieces::Bp)
+        && matches!(square.rank(), ChessboardRanks::R1 | ChessboardRanks::R8)
+      {
+        reasons.push(IllegalPosition::PawnOnBackRank { square });
+      }
+    }
+
+    // Without one king for each side the checks have no meaning
+    if kings_found {
+      if square_attacked(self.king_square()[opponent as usize], side, self) {
+        reasons.push(IllegalPosition::OpponentInCheck);
+      }
+      let king_square: Squares = self.king_square()[side as usize];
+      let checkers: Vec<Squares> = bitboard_squares(self.attackers_to(king_square, opponent));
+      if !possible_checkers(self, king_square, &checkers) {
+        reasons.push(IllegalPosition::ImpossibleCheck { checkers });
+      }
+    }
+
+    // The pawn that has just made the double push is in front of the en passant square,
+    // which is empty as the start square of the pawn
+    if self.en_passant_square() != Squares::NoSquare {
+      let square: i32 = self.en_passant_square() as i32;
+      let (rank, forward, opponent_pawn): (ChessboardRanks, i32, Pieces) = if side == Colors::White
+      {
+        (ChessboardRanks::R6, 10, Pieces::Bp)
+      } else {
+        (ChessboardRanks::R3, -10, Pieces::Wp)
+      };
+      if self.en_passant_square().rank() as i32 != rank as i32
+        || self.pieces()[(square - forward) as usize] != opponent_pawn
+        || self.pieces()[square as usize] != Pieces::Empty
+        || self.pieces()[(square + forward) as usize] != Pieces::Empty
+      {
+        reasons.push(IllegalPosition::EnPassantSquare {
+          square: self.en_passant_square(),
+        });
+      }
+    }
+
+    for castle in self.castling_rights().iter() {
+      let index: usize = castle_index(castle);
+      let (king_square, king): (Squares, Pieces) = if index < 2 {
+        (Squares::E1, Pieces::Wk)
+      } else {
+        (Squares::E8, Pieces::Bk)
+      };
+      if !self.castle_has_king_and_rook(castle)
+        || (!self.chess960()
+          && (self.piece_at(king_square) != king
+            || self.castle_rooks()[index] != STANDARD_CASTLE_ROOKS[index]))
+      {
+        reasons.push(IllegalPosition::CastlingRights { castle });
+      }
+    }
+
+    if reasons.is_empty() {
+      Ok(())
+    } else {
+      Err(reasons)
+    }
+  }
+}
@@ -0,0 +1,54 @@
+/*!
+ * A UCI chess engine as a library: the board (with the FEN and Chess960 support),
+ * the move generation, the search and the tools built on them (the UCI loop, the tuner,
+ * the data generator and the engine match). The modules are private, the public API
+ * is what is exported here.
+ */
+
+mod attack;
+mod bitboards;
+mod board;
+mod datagen;
+mod definitions;
+mod endgame;
+mod evaluate;
+mod hashkeys;
+mod legality;
+mod match_runner;
+mod movegen;
+mod movepicker;
+mod nnue;
+mod parameters;
+mod pawns;
+mod perft;
+mod pvtable;
+mod render;
+mod search;
+mod see;
+mod syzygy;
+mod tuner;
+mod uci;
+
+pub use attack::attacked_squares_to_string;
+pub use bitboards::{bitboard_squares, bitboard_to_string};
+pub use board::{chess960_fen, Board, InvariantViolation};
+pub use datagen::datagen;
+pub use definitions::{
+  CastlingRights, ChessboardFiles as File, ChessboardRanks as Rank, Colors as Color, Move,
+  MoveKind, MoveList, Pieces as Piece, Squares as Square, INFINITE, IS_MATE, MAX_DEPTH, START_FEN,
+};
+pub use legality::IllegalPosition;
+pub use match_runner::{parse_epd_openings, parse_pgn_openings, run_match};
+pub use movegen::{generate_all_moves, generate_legal_moves, move_to_san, parse_move, parse_san};
+pub use nnue::Network;
+pub use parameters::EvaluationParameters;
+pub use perft::{perft, perft_test};
+pub use pvtable::HashTable;
+pub use render::{BoardStyle, RenderOptions};
+pub use search::{
+  insufficient_material, is_in_check, is_repetition, search_best_lines, search_best_move,
+  search_position, uci_score, SearchInfo, SearchLine, SearchOptions, MAX_MULTI_PV, MAX_SKILL_LEVEL,
+};
+pub use syzygy::Tablebases;
+pub use tuner::tune;
+pub use uci::uci_loop;
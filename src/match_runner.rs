@@ -0,0 +1,1124 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::*;
+use crate::definitions::*;
+use crate::movegen::*;
+use crate::search::*;
+
+/**
+ * How long an engine can take to answer "uci" and "isready", and to answer "go" when
+ * the moves are searched at fixed nodes.
+ */
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(10);
+const NODES_TIMEOUT: Duration = Duration::from_secs(60);
+/**
+ * Time (in milliseconds) that an engine can exceed on its clock before losing on time,
+ * for the delay of the communication.
+ */
+const TIME_MARGIN: u64 = 100;
+const DEFAULT_TIME: u64 = 10000;
+const DEFAULT_INCREMENT: u64 = 100;
+/**
+ * The game is won when the engines agree for WIN_ADJUDICATION_PLIES plies that the score is at
+ * least WIN_ADJUDICATION_SCORE, and it's drawn when after DRAW_ADJUDICATION_START plies the score
+ * stays within DRAW_ADJUDICATION_SCORE for DRAW_ADJUDICATION_PLIES plies.
+ */
+const WIN_ADJUDICATION_SCORE: i32 = 1000;
+const WIN_ADJUDICATION_PLIES: i32 = 6;
+const DRAW_ADJUDICATION_SCORE: i32 = 10;
+const DRAW_ADJUDICATION_PLIES: i32 = 8;
+const DRAW_ADJUDICATION_START: i32 = 80;
+const MAX_GAME_PLIES: i32 = 600;
+/**
+ * The bound of the 95% confidence interval of the Elo difference, in standard deviations.
+ */
+const CONFIDENCE_BOUND: f64 = 1.96;
+const PGN_LINE_LENGTH: usize = 80;
+
+/**
+ * The match settings, given on the command line:
+ * match [--engine1 <command>] [--engine2 <command>] [--option1 <name>=<value>]
+ *       [--option2 <name>=<value>] [--openings <file.epd|file.pgn>] [--games <n>]
+ *       [--concurrency <n>] [--tc <seconds>[+<increment>]] [--nodes <n>] [--pgn <file>]
+ *       [--sprt <elo0> <elo1>] [--alpha <a>] [--beta <b>]
+ * An engine without a command is this engine, so that two configurations of it can be
+ * played against each other with the options.
+ */
+struct MatchSettings {
+  engines: [String; 2],
+  options: [Vec<(String, String)>; 2],
+  openings_path: Option<String>,
+  games: usize,
+  concurrency: usize,
+  /**
+   * The time of each side and the increment, in milliseconds.
+   */
+  time: u64,
+  increment: u64,
+  /**
+   * The moves are searched at fixed nodes instead of with the clock, if it's set.
+   */
+  nodes: Option<u64>,
+  pgn_path: Option<String>,
+  /**
+   * The Elo differences of the null and of the alternative hypothesis of the SPRT,
+   * the match stops when one of them is accepted.
+   */
+  sprt: Option<(f64, f64)>,
+  alpha: f64,
+  beta: f64,
+}
+
+impl MatchSettings {
+  fn from_arguments(arguments: &[String]) -> Result<MatchSettings, String> {
+    let this_engine: String = std::env::current_exe()
+      .map_err(|error| format!("this engine: {}", error))?
+      .to_string_lossy()
+      .into_owned();
+    let mut settings: MatchSettings = MatchSettings {
+      engines: [this_engine.clone(), this_engine],
+      options: [Vec::new(), Vec::new()],
+      openings_path: None,
+      games: 100,
+      concurrency: 1,
+      time: DEFAULT_TIME,
+      increment: DEFAULT_INCREMENT,
+      nodes: None,
+      pgn_path: None,
+      sprt: None,
+      alpha: 0.05,
+      beta: 0.05,
+    };
+    let mut tokens = arguments.iter();
+
+    while let Some(token) = tokens.next() {
+      match token.as_str() {
+        "--engine1" => settings.engines[0] = argument_value(&mut tokens, token)?,
+        "--engine2" => settings.engines[1] = argument_value(&mut tokens, token)?,
+        "--option1" | "--option2" => {
+          let option: String = argument_value(&mut tokens, token)?;
+          let (name, value): (&str, &str) = option
+            .split_once('=')
+            .ok_or_else(|| format!("{}: {} is not <name>=<value>", token, option))?;
+          settings.options[if token == "--option1" { 0 } else { 1 }]
+            .push((String::from(name), String::from(value)));
+        }
+        "--openings" => settings.openings_path = Some(argument_value(&mut tokens, token)?),
+        "--pgn" => settings.pgn_path = Some(argument_value(&mut tokens, token)?),
+        "--games" | "--concurrency" | "--nodes" => {
+          let value: u64 = parse_number(token, &argument_value(&mut tokens, token)?)?;
+          match token.as_str() {
+            "--games" => settings.games = value as usize,
+            "--concurrency" => settings.concurrency = (value as usize).max(1),
+            _ => settings.nodes = Some(value.max(1)),
+          }
+        }
+        "--tc" => {
+          let time_control: String = argument_value(&mut tokens, token)?;
+          let (time, increment): (&str, &str) =
+            time_control.split_once('+').unwrap_or((&time_control, "0"));
+          settings.time = (parse_number::<f64>(token, time)? * 1000.0) as u64;
+          settings.increment = (parse_number::<f64>(token, increment)? * 1000.0) as u64;
+        }
+        "--sprt" => {
+          settings.sprt = Some((
+            parse_number(token, &argument_value(&mut tokens, token)?)?,
+            parse_number(token, &argument_value(&mut tokens, token)?)?,
+          ))
+        }
+        "--alpha" => settings.alpha = parse_number(token, &argument_value(&mut tokens, token)?)?,
+        "--beta" => settings.beta = parse_number(token, &argument_value(&mut tokens, token)?)?,
+        _ => {
+          return Err(String::from(
+            "usage: match [--engine1 <command>] [--engine2 <command>] \
+             [--option1 <name>=<value>] [--option2 <name>=<value>] \
+             [--openings <file.epd|file.pgn>] [--games <n>] [--concurrency <n>] \
+             [--tc <seconds>[+<increment>]] [--nodes <n>] [--pgn <file>] \
+             [--sprt <elo0> <elo1>] [--alpha <a>] [--beta <b>]",
+          ))
+        }
+      }
+    }
+
+    Ok(settings)
+  }
+}
+
+fn argument_value<'a>(
+  tokens: &mut impl Iterator<Item = &'a String>,
+  token: &str,
+) -> Result<String, String> {
+  tokens
+    .next()
+    .cloned()
+    .ok_or_else(|| format!("{} needs a value", token))
+}
+
+fn parse_number<T: std::str::FromStr>(token: &str, value: &str) -> Result<T, String> {
+  value
+    .parse::<T>()
+    .map_err(|_| format!("{}: {} is not a valid number", token, value))
+}
+
+/**
+ * An engine played as a child process through the UCI protocol. Its output is read by another
+ * thread, so that the answers can be waited with a timeout (the clock of the engine).
+ */
+struct Engine {
+  name: String,
+  child: Child,
+  stdin: ChildStdin,
+  receiver: Receiver<String>,
+}
+
+impl Engine {
+  fn start(command: &str, options: &[(String, String)]) -> Result<Engine, String> {
+    let mut words = command.split_whitespace();
+    let program: &str = words.next().ok_or("empty engine command")?;
+    let mut child: Child = Command::new(program)
+      .args(words)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()
+      .map_err(|error| format!("{}: {}", command, error))?;
+    let stdin: ChildStdin = child.stdin.take().ok_or("engine stdin not available")?;
+    let stdout: ChildStdout = child.stdout.take().ok_or("engine stdout not available")?;
+    let (sender, receiver): (Sender<String>, Receiver<String>) = mpsc::channel();
+
+    thread::spawn(move || {
+      for line in BufReader::new(stdout).lines() {
+        let line: String = match line {
+          Ok(line) => line,
+          Err(_) => break,
+        };
+        if sender.send(line).is_err() {
+          break;
+        }
+      }
+    });
+
+    let mut engine: Engine = Engine {
+      name: String::from(command),
+      child,
+      stdin,
+      receiver,
+    };
+    engine.send("uci")?;
+    for line in engine.wait_for("uciok", ENGINE_TIMEOUT)? {
+      if let Some(name) = line.strip_prefix("id name ") {
+        engine.name = String::from(name.trim());
+      }
+    }
+    for (name, value) in options {
+      engine.send(&format!("setoption name {} value {}", name, value))?;
+    }
+    engine.send("isready")?;
+    engine.wait_for("readyok", ENGINE_TIMEOUT)?;
+
+    Ok(engine)
+  }
+
+  fn send(&mut self, command: &str) -> Result<(), String> {
+    writeln!(self.stdin, "{}", command)
+      .and_then(|_| self.stdin.flush())
+      .map_err(|error| format!("{}: {}", self.name, error))
+  }
+
+  /**
+   * It reads the lines of the engine until one starts with the given answer,
+   * and returns all of them.
+   */
+  fn wait_for(&mut self, answer: &str, timeout: Duration) -> Result<Vec<String>, String> {
+    let stop_time: Instant = Instant::now() + timeout;
+    let mut lines: Vec<String> = Vec::new();
+
+    loop {
+      match self
+        .receiver
+        .recv_timeout(stop_time.saturating_duration_since(Instant::now()))
+      {
+        Ok(line) => {
+          let done: bool = line.starts_with(answer);
+          lines.push(line);
+          if done {
+            return Ok(lines);
+          }
+        }
+        Err(RecvTimeoutError::Timeout) => {
+          return Err(format!("{}: no {} in time", self.name, answer))
+        }
+        Err(RecvTimeoutError::Disconnected) => return Err(format!("{}: disconnected", self.name)),
+      }
+    }
+  }
+
+  fn new_game(&mut self) -> Result<(), String> {
+    self.send("ucinewgame")?;
+    self.send("isready")?;
+    self.wait_for("readyok", ENGINE_TIMEOUT).map(|_| ())
+  }
+
+  /**
+   * It returns the best move of the engine and its last score (from the point of view of the
+   * side to move), None if the engine didn't answer in time.
+   */
+  fn go(
+    &mut self,
+    position: &str,
+    go: &str,
+    timeout: Duration,
+  ) -> Result<Option<(String, Option<i32>)>, String> {
+    let mut score: Option<i32> = None;
+
+    self.send(position)?;
+    self.send(go)?;
+    let lines: Vec<String> = match self.wait_for("bestmove", timeout) {
+      Ok(lines) => lines,
+      Err(_) if self.child.try_wait().ok().flatten().is_none() => {
+        // Out of time: the late best move is read, so that it doesn't reach the next search
+        self.send("stop")?;
+        self.wait_for("bestmove", ENGINE_TIMEOUT)?;
+        return Ok(None);
+      }
+      Err(error) => return Err(error),
+    };
+
+    for line in &lines {
+      if line.starts_with("info") {
+        score = parse_score(line).or(score);
+      }
+    }
+    Ok(lines.last().and_then(|line| {
+      line
+        .split_whitespace()
+        .nth(1)
+        .map(|best_move| (String::from(best_move), score))
+    }))
+  }
+}
+
+impl Drop for Engine {
+  fn drop(&mut self) {
+    let _ = self.send("quit");
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+/**
+ * The score of an "info" line, the mate scores are the highest ones.
+ */
+fn parse_score(line: &str) -> Option<i32> {
+  let mut tokens = line
+    .split_whitespace()
+    .skip_while(|&token| token != "score");
+
+  match (tokens.nth(1), tokens.next()?.parse::<i32>().ok()?) {
+    (Some("cp"), score) => Some(score),
+    (Some("mate"), moves) if moves > 0 => Some(INFINITE - moves),
+    (Some("mate"), moves) => Some(-INFINITE - moves),
+    _ => None,
+  }
+}
+
+/**
+ * The openings of the EPD file or of the PGN file (from the extension), as FEN strings.
+ */
+fn load_openings(board: &mut Board, path: &str) -> Result<Vec<String>, String> {
+  let text: String = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+
+  if path.to_lowercase().ends_with(".pgn") {
+    parse_pgn_openings(board, &text)
+  } else {
+    parse_epd_openings(board, &text)
+  }
+  .map_err(|error| format!("{}: {}", path, error))
+}
+
+/**
+ * It sets the opening on the board, the engines can play only from a legal position.
+ */
+fn set_opening(board: &mut Board, fen: &str) -> Result<(), String> {
+  board.parse_fen(fen)?;
+  board.is_legal_position().map_err(|reasons| {
+    format!(
+      "illegal position \"{}\": {}",
+      fen,
+      reasons
+        .iter()
+        .map(|reason| reason.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+    )
+  })
+}
+
+/**
+ * The openings of an EPD text, the first four fields of each line are the position.
+ */
+pub fn parse_epd_openings(board: &mut Board, text: &str) -> Result<Vec<String>, String> {
+  let mut openings: Vec<String> = Vec::new();
+
+  for line in text.lines() {
+    let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+    if fields.len() == 4 {
+      let fen: String = format!("{} 0 1", fields.join(" "));
+      set_opening(board, &fen)?;
+      openings.push(fen);
+    }
+  }
+
+  Ok(openings)
+}
+
+/**
+ * The openings of a PGN text, the position at the end of each game (from the FEN tag
+ * if there is one).
+ */
+pub fn parse_pgn_openings(board: &mut Board, text: &str) -> Result<Vec<String>, String> {
+  let mut openings: Vec<String> = Vec::new();
+  let mut movetext: String = String::new();
+  let mut fen: String = String::from(START_FEN);
+
+  for line in text.lines().chain(std::iter::once("[Event \"\"]")) {
+    if let Some(tag) = line.trim().strip_prefix('[') {
+      if !movetext.trim().is_empty() {
+        openings.push(play_pgn_moves(board, &fen, &movetext)?);
+        movetext.clear();
+        fen = String::from(START_FEN);
+      }
+      if let Some(value) = tag.strip_prefix("FEN ") {
+        fen = String::from(value.trim_end_matches(']').trim().trim_matches('"'));
+      }
+    } else {
+      movetext.push_str(line);
+      movetext.push('\n');
+    }
+  }
+
+  Ok(openings)
+}
+
+/**
+ * It plays the moves of a PGN movetext from the given position, and returns the final one.
+ * The comments, the variations, the move numbers and the annotations are skipped.
+ */
+fn play_pgn_moves(board: &mut Board, fen: &str, movetext: &str) -> Result<String, String> {
+  let mut text: String = String::new();
+  let mut comment: bool = false;
+  let mut variation_depth: i32 = 0;
+  let mut mov_e: Move;
+
+  for character in movetext.chars() {
+    match character {
+      '{' => comment = true,
+      '}' => comment = false,
+      '(' if !comment => variation_depth += 1,
+      ')' if !comment => variation_depth -= 1,
+      _ if !comment && variation_depth == 0 => text.push(character),
+      _ => {}
+    }
+  }
+
+  set_opening(board, fen)?;
+  for token in text.split_whitespace() {
+    let san: &str = token
+      .trim_start_matches(|character: char| character.is_ascii_digit())
+      .trim_start_matches('.');
+    if san.is_empty() || san.starts_with('$') || ["1-0", "0-1", "1/2-1/2", "*"].contains(&token) {
+      continue;
+    }
+    mov_e = parse_san(board, san);
+    if mov_e == Move::NO_MOVE {
+      break;
+    }
+    board.make_move(mov_e);
+  }
+
+  Ok(board.to_fen())
+}
+
+/**
+ * A played game: the result (from the white point of view) and why the game ended.
+ */
+struct Game {
+  opening: String,
+  san_moves: Vec<String>,
+  result: f32,
+  termination: String,
+}
+
+/**
+ * It plays a game from the opening between the engines (white first), checking every move
+ * and the end of the game with the board, and adjudicating it with the scores of the engines.
+ */
+fn play_game(
+  board: &mut Board,
+  mut engines: [&mut Engine; 2],
+  opening: &str,
+  settings: &MatchSettings,
+) -> Result<Game, String> {
+  let mut game: Game = Game {
+    opening: String::from(opening),
+    san_moves: Vec::new(),
+    result: 0.5,
+    termination: String::new(),
+  };
+  let mut uci_moves: Vec<String> = Vec::new();
+  let mut clocks: [u64; 2] = [settings.time; 2];
+  let mut win_plies: i32 = 0;
+  let mut draw_plies: i32 = 0;
+  let mut legal_moves: Vec<Move>;
+  let mut side: usize;
+  let mut white_score: i32;
+  let mut position: String;
+  let mut go: String;
+  let mut start_time: Instant;
+  let mut elapsed: u64;
+  let mut mov_e: Move;
+
+  for engine in engines.iter_mut() {
+    engine.new_game()?;
+  }
+  board.parse_fen(opening)?;
+
+  for ply in 0.. {
+    side = board.side() as usize;
+    legal_moves = generate_legal_moves(board);
+    let loss: f32 = if side == Colors::White as usize {
+      0.0
+    } else {
+      1.0
+    };
+    let (result, termination): (f32, &str) = if legal_moves.is_empty() && is_in_check(board) {
+      (loss, "checkmate")
+    } else if legal_moves.is_empty() {
+      (0.5, "stalemate")
+    } else if board.fifty_full_moves() >= 100 {
+      (0.5, "fifty moves rule")
+    } else if repetitions(board) >= 2 {
+      (0.5, "threefold repetition")
+    } else if insufficient_material(board) {
+      (0.5, "insufficient material")
+    } else if ply >= MAX_GAME_PLIES {
+      (0.5, "adjudication: maximum game length")
+    } else {
+      (0.5, "")
+    };
+    if !termination.is_empty() {
+      game.result = result;
+      game.termination = String::from(termination);
+      return Ok(game);
+    }
+
+    position = if uci_moves.is_empty() {
+      format!("position fen {}", opening)
+    } else {
+      format!("position fen {} moves {}", opening, uci_moves.join(" "))
+    };
+    go = match settings.nodes {
+      Some(nodes) => format!("go nodes {}", nodes),
+      None => format!(
+        "go wtime {} btime {} winc {} binc {}",
+        clocks[0], clocks[1], settings.increment, settings.increment
+      ),
+    };
+    start_time = Instant::now();
+    let answer: Option<(String, Option<i32>)> = engines[side].go(
+      &position,
+      &go,
+      match settings.nodes {
+        Some(_) => NODES_TIMEOUT,
+        None => Duration::from_millis(clocks[side] + TIME_MARGIN),
+      },
+    )?;
+    elapsed = start_time.elapsed().as_millis() as u64;
+
+    let (best_move, score): (String, Option<i32>) = match answer {
+      Some(answer) if settings.nodes.is_some() || elapsed <= clocks[side] + TIME_MARGIN => answer,
+      _ => {
+        game.result = loss;
+        game.termination = format!("{} loses on time", engines[side].name);
+        return Ok(game);
+      }
+    };
+    if settings.nodes.is_none() {
+      clocks[side] = clocks[side].saturating_sub(elapsed) + settings.increment;
+    }
+
+    mov_e = parse_move(board, &best_move);
+    if !legal_moves.contains(&mov_e) {
+      game.result = loss;
+      game.termination = format!(
+        "{} plays the illegal move {}",
+        engines[side].name, best_move
+      );
+      return Ok(game);
+    }
+    game.san_moves.push(move_to_san(board, mov_e));
+    uci_moves.push(best_move);
+    board.make_move(mov_e);
+
+    let Some(score) = score else {
+      (win_plies, draw_plies) = (0, 0);
+      continue;
+    };
+    white_score = if side == Colors::White as usize {
+      score
+    } else {
+      -score
+    };
+    win_plies = if white_score.abs() >= WIN_ADJUDICATION_SCORE {
+      win_plies + 1
+    } else {
+      0
+    };
+    if win_plies >= WIN_ADJUDICATION_PLIES {
+      game.result = if white_score > 0 { 1.0 } else { 0.0 };
+      game.termination = String::from("adjudication: won position");
+      return Ok(game);
+    }
+    draw_plies = if ply >= DRAW_ADJUDICATION_START && white_score.abs() <= DRAW_ADJUDICATION_SCORE {
+      draw_plies + 1
+    } else {
+      0
+    };
+    if draw_plies >= DRAW_ADJUDICATION_PLIES {
+      game.termination = String::from("adjudication: drawn position");
+      return Ok(game);
+    }
+  }
+
+  unreachable!()
+}
+
+/**
+ * How many times the position has already been found since the last capture or pawn move.
+ */
+fn repetitions(board: &Board) -> usize {
+  let history: &[Undo] = board.history();
+  let first_index: usize = history
+    .len()
+    .saturating_sub(board.fifty_full_moves() as usize);
+
+  history[first_index..]
+    .iter()
+    .filter(|undo| undo.position_key() == board.position_key())
+    .count()
+}
+
+fn pgn_result(result: f32) -> &'static str {
+  if result == 1.0 {
+    "1-0"
+  } else if result == 0.0 {
+    "0-1"
+  } else {
+    "1/2-1/2"
+  }
+}
+
+fn write_pgn(
+  writer: &mut BufWriter<File>,
+  game: &Game,
+  round: usize,
+  names: [&str; 2],
+) -> std::io::Result<()> {
+  let fields: Vec<&str> = game.opening.split_whitespace().collect();
+  let mut move_number: usize = fields
+    .get(5)
+    .and_then(|number| number.parse().ok())
+    .unwrap_or(1);
+  let mut white_to_move: bool = fields.get(1) != Some(&"b");
+  let mut line: String = String::new();
+  let mut token: String;
+
+  writeln!(writer, "[Event \"Engine match\"]")?;
+  writeln!(writer, "[Site \"?\"]")?;
+  writeln!(writer, "[Round \"{}\"]", round)?;
+  writeln!(writer, "[White \"{}\"]", names[0])?;
+  writeln!(writer, "[Black \"{}\"]", names[1])?;
+  writeln!(writer, "[Result \"{}\"]", pgn_result(game.result))?;
+  if game.opening != START_FEN {
+    writeln!(writer, "[FEN \"{}\"]", game.opening)?;
+    writeln!(writer, "[SetUp \"1\"]")?;
+  }
+  writeln!(writer, "[Termination \"{}\"]", game.termination)?;
+  writeln!(writer)?;
+
+  for (index, san) in game.san_moves.iter().enumerate() {
+    token = if white_to_move {
+      format!("{}. {}", move_number, san)
+    } else if index == 0 {
+      format!("{}... {}", move_number, san)
+    } else {
+      san.clone()
+    };
+    if !white_to_move {
+      move_number += 1;
+    }
+    white_to_move = !white_to_move;
+
+    if !line.is_empty() && line.len() + token.len() + 1 > PGN_LINE_LENGTH {
+      writeln!(writer, "{}", line)?;
+      line.clear();
+    }
+    if !line.is_empty() {
+      line.push(' ');
+    }
+    line.push_str(&token);
+  }
+  if !line.is_empty() && line.len() + pgn_result(game.result).len() + 1 > PGN_LINE_LENGTH {
+    writeln!(writer, "{}", line)?;
+    line.clear();
+  }
+  if !line.is_empty() {
+    line.push(' ');
+  }
+  writeln!(writer, "{}{}\n", line, pgn_result(game.result))
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Match_Statistics>
+ *
+ * The results of the first engine, with the Elo difference and its 95% confidence interval.
+ */
+#[derive(Default)]
+struct MatchScore {
+  wins: u32,
+  losses: u32,
+  draws: u32,
+}
+
+fn elo_difference(score: f64) -> f64 {
+  -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn expected_score(elo: f64) -> f64 {
+  1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+impl MatchScore {
+  fn games(&self) -> u32 {
+    self.wins + self.losses + self.draws
+  }
+
+  /**
+   * The mean score of a game and its variance, with the given number of games
+   * added to each result.
+   */
+  fn score_and_variance(&self, prior: f64) -> (f64, f64) {
+    let (wins, losses, draws): (f64, f64, f64) = (
+      self.wins as f64 + prior,
+      self.losses as f64 + prior,
+      self.draws as f64 + prior,
+    );
+    let games: f64 = wins + losses + draws;
+    let score: f64 = (wins + draws / 2.0) / games;
+    let variance: f64 =
+      (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2))
+        / games;
+
+    (score, variance)
+  }
+
+  /**
+   * The Elo difference and the half width of its confidence interval,
+   * None while all the games have been won or lost.
+   */
+  fn elo(&self) -> Option<(f64, f64)> {
+    let (score, variance): (f64, f64) = self.score_and_variance(0.0);
+    let deviation: f64 = (variance / self.games() as f64).sqrt();
+    let (lower, upper): (f64, f64) = (
+      score - CONFIDENCE_BOUND * deviation,
+      score + CONFIDENCE_BOUND * deviation,
+    );
+
+    if score <= 0.0 || score >= 1.0 {
+      return None;
+    }
+    Some((
+      elo_difference(score),
+      (elo_difference(upper.min(1.0 - f64::EPSILON)) - elo_difference(lower.max(f64::EPSILON)))
+        / 2.0,
+    ))
+  }
+
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Sequential_Probability_Ratio_Test>
+   *
+   * The log-likelihood ratio of the alternative hypothesis (elo1) against the null one (elo0),
+   * with the normal approximation of the distribution of the score. Half a game is added
+   * to each result, so that the variance is not zero when all the games end the same way.
+   */
+  fn log_likelihood_ratio(&self, elo0: f64, elo1: f64) -> f64 {
+    let (score, variance): (f64, f64) = self.score_and_variance(0.5);
+    let (score0, score1): (f64, f64) = (expected_score(elo0), expected_score(elo1));
+
+    (score1 - score0) * (2.0 * score - score0 - score1) * self.games() as f64 / (2.0 * variance)
+  }
+}
+
+/**
+ * The log-likelihood ratios under which the null hypothesis is accepted and over which the
+ * alternative one is, for the given type I (alpha) and type II (beta) error rates.
+ */
+fn sprt_bounds(alpha: f64, beta: f64) -> (f64, f64) {
+  ((beta / (1.0 - alpha)).ln(), ((1.0 - beta) / alpha).ln())
+}
+
+/**
+ * The state of the match shared by the threads playing the games.
+ */
+struct MatchState {
+  score: MatchScore,
+  pgn_writer: Option<BufWriter<File>>,
+  verdict: Option<&'static str>,
+}
+
+/**
+ * It prints the score of the match, and returns the verdict of the SPRT if there is one.
+ */
+fn report(state: &MatchState, settings: &MatchSettings) -> Option<&'static str> {
+  let score: &MatchScore = &state.score;
+  let mut verdict: Option<&'static str> = None;
+
+  print!(
+    "games {} wins {} losses {} draws {}",
+    score.games(),
+    score.wins,
+    score.losses,
+    score.draws
+  );
+  if let Some((elo, error)) = score.elo() {
+    print!(" elo {:.1} +/- {:.1}", elo, error);
+  }
+  if let Some((elo0, elo1)) = settings.sprt {
+    let llr: f64 = score.log_likelihood_ratio(elo0, elo1);
+    let (lower_bound, upper_bound): (f64, f64) = sprt_bounds(settings.alpha, settings.beta);
+    print!(" llr {:.2} ({:.2}, {:.2})", llr, lower_bound, upper_bound);
+    if llr >= upper_bound {
+      verdict = Some("H1 accepted");
+    } else if llr <= lower_bound {
+      verdict = Some("H0 accepted");
+    }
+  }
+  println!();
+
+  verdict
+}
+
+/**
+ * The games of a thread of the match: each opening is played twice with the colors swapped,
+ * the first engine is white in the even games.
+ */
+fn play_games(
+  settings: &MatchSettings,
+  openings: &[String],
+  names: [&str; 2],
+  next_game: &AtomicUsize,
+  stop: &AtomicBool,
+  state: &Mutex<MatchState>,
+) -> Result<(), String> {
+  let mut board: Board = Board::new();
+  let mut first: Engine = Engine::start(&settings.engines[0], &settings.options[0])?;
+  let mut second: Engine = Engine::start(&settings.engines[1], &settings.options[1])?;
+  let mut game_index: usize;
+  let mut game: Game;
+  let mut first_result: f32;
+
+  first.name = String::from(names[0]);
+  second.name = String::from(names[1]);
+  loop {
+    game_index = next_game.fetch_add(1, Ordering::Relaxed);
+    if game_index >= settings.games || stop.load(Ordering::Relaxed) {
+      return Ok(());
+    }
+    let opening: &str = &openings[(game_index / 2) % openings.len()];
+
+    if game_index.is_multiple_of(2) {
+      game = play_game(&mut board, [&mut first, &mut second], opening, settings)?;
+      first_result = game.result;
+    } else {
+      game = play_game(&mut board, [&mut second, &mut first], opening, settings)?;
+      first_result = 1.0 - game.result;
+    }
+
+    let mut state: MutexGuard<MatchState> = state.lock().expect("match lock poisoned");
+    if first_result == 1.0 {
+      state.score.wins += 1;
+    } else if first_result == 0.0 {
+      state.score.losses += 1;
+    } else {
+      state.score.draws += 1;
+    }
+    if let Some(writer) = state.pgn_writer.as_mut() {
+      let game_names: [&str; 2] = if game_index.is_multiple_of(2) {
+        names
+      } else {
+        [names[1], names[0]]
+      };
+      write_pgn(writer, &game, game_index + 1, game_names)
+        .map_err(|error| format!("pgn: {}", error))?;
+    }
+    if let Some(verdict) = report(&state, settings) {
+      state.verdict = Some(verdict);
+      stop.store(true, Ordering::Relaxed);
+    }
+  }
+}
+
+/**
+ * The match subcommand: two UCI engines (or two configurations of this engine) play paired
+ * games from the openings with the colors swapped. The games are checked and adjudicated
+ * with the board, written as PGN, and the match ends with the Elo difference and,
+ * if it's set, the verdict of the SPRT (that can also end the match early).
+ */
+pub fn run_match(arguments: &[String]) {
+  let settings: MatchSettings = match MatchSettings::from_arguments(arguments) {
+    Ok(settings) => settings,
+    Err(error) => {
+      println!("{}", error);
+      return;
+    }
+  };
+  let mut board: Board = Board::new();
+  let openings: Vec<String> = match &settings.openings_path {
+    Some(path) => match load_openings(&mut board, path) {
+      Ok(openings) if !openings.is_empty() => openings,
+      Ok(_) => {
+        println!("{}: no openings found", path);
+        return;
+      }
+      Err(error) => {
+        println!("{}", error);
+        return;
+      }
+    },
+    None => vec![String::from(START_FEN)],
+  };
+  let pgn_writer: Option<BufWriter<File>> = match &settings.pgn_path {
+    Some(path) => match File::create(path) {
+      Ok(file) => Some(BufWriter::new(file)),
+      Err(error) => {
+        println!("{}: {}", path, error);
+        return;
+      }
+    },
+    None => None,
+  };
+
+  // The names of the engines, told apart when they are the same engine
+  let mut names: [String; 2] = [String::new(), String::new()];
+  for (index, name) in names.iter_mut().enumerate() {
+    match Engine::start(&settings.engines[index], &settings.options[index]) {
+      Ok(engine) => *name = engine.name.clone(),
+      Err(error) => {
+        println!("{}", error);
+        return;
+      }
+    }
+  }
+  if names[0] == names[1] {
+    names[0].push_str(" (1)");
+    names[1].push_str(" (2)");
+  }
+
+  let state: Mutex<MatchState> = Mutex::new(MatchState {
+    score: MatchScore::default(),
+    pgn_writer,
+    verdict: None,
+  });
+  let next_game: AtomicUsize = AtomicUsize::new(0);
+  let stop: AtomicBool = AtomicBool::new(false);
+
+  println!(
+    "{} vs {}: games {} concurrency {} openings {}",
+    names[0],
+    names[1],
+    settings.games,
+    settings.concurrency,
+    openings.len()
+  );
+
+  thread::scope(|scope| {
+    for _ in 0..settings.concurrency {
+      let settings: &MatchSettings = &settings;
+      let openings: &[String] = &openings;
+      let names: [&str; 2] = [&names[0], &names[1]];
+      let next_game: &AtomicUsize = &next_game;
+      let stop: &AtomicBool = &stop;
+      let state: &Mutex<MatchState> = &state;
+
+      scope.spawn(move || {
+        if let Err(error) = play_games(settings, openings, names, next_game, stop, state) {
+          println!("{}", error);
+          stop.store(true, Ordering::Relaxed);
+        }
+      });
+    }
+  });
+
+  let mut state: MatchState = state.into_inner().expect("match lock poisoned");
+  if let Some(writer) = state.pgn_writer.as_mut() {
+    if let Err(error) = writer.flush() {
+      println!("pgn: {}", error);
+    }
+  }
+  if settings.sprt.is_some() {
+    println!("sprt: {}", state.verdict.unwrap_or("no verdict"));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn match_score(wins: u32, losses: u32, draws: u32) -> MatchScore {
+    MatchScore {
+      wins,
+      losses,
+      draws,
+    }
+  }
+
+  fn assert_close(value: f64, expected: f64) {
+    assert!(
+      (value - expected).abs() < 1e-6,
+      "{} instead of {}",
+      value,
+      expected
+    );
+  }
+
+  #[test]
+  fn sprt_bounds() {
+    let (lower_bound, upper_bound): (f64, f64) = super::sprt_bounds(0.05, 0.05);
+
+    // ln(1 / 19) and ln(19)
+    assert_close(lower_bound, -2.944438979);
+    assert_close(upper_bound, 2.944438979);
+
+    let (lower_bound, upper_bound): (f64, f64) = super::sprt_bounds(0.05, 0.1);
+
+    // ln(0.1 / 0.95) and ln(0.9 / 0.05)
+    assert_close(lower_bound, -2.251291799);
+    assert_close(upper_bound, 2.890371758);
+  }
+
+  #[test]
+  fn sprt_log_likelihood_ratio() {
+    for (wins, losses, draws, elo0, elo1, expected) in [
+      (60, 40, 100, 0.0, 5.0, 0.539192206),
+      (400, 380, 1220, 0.0, 5.0, 0.206380999),
+      (380, 400, 1220, 0.0, 5.0, -1.268020707),
+      (1000, 900, 3000, -1.0, 4.0, 2.927559056),
+    ] {
+      assert_close(
+        match_score(wins, losses, draws).log_likelihood_ratio(elo0, elo1),
+        expected,
+      );
+    }
+
+    // The same score with more games gives more evidence
+    assert!(
+      match_score(600, 400, 1000).log_likelihood_ratio(0.0, 5.0)
+        > match_score(60, 40, 100).log_likelihood_ratio(0.0, 5.0)
+    );
+    // And when all the games are won the ratio is still finite
+    assert!(match_score(10, 0, 0)
+      .log_likelihood_ratio(0.0, 5.0)
+      .is_finite());
+  }
+
+  #[test]
+  fn elo_with_error() {
+    let (elo, error): (f64, f64) = match_score(60, 40, 100).elo().unwrap();
+
+    assert_close(elo, 34.860070288);
+    assert_close(error, 34.159900716);
+    assert_close(elo_difference(0.75), 190.848501888);
+    assert_close(expected_score(elo_difference(0.3)), 0.3);
+    assert_eq!(match_score(50, 50, 0).elo().unwrap().0, 0.0);
+    assert!(match_score(10, 0, 0).elo().is_none());
+    assert!(match_score(0, 10, 0).elo().is_none());
+  }
+
+  #[test]
+  fn engine_scores() {
+    assert_eq!(
+      parse_score("info depth 8 seldepth 12 score cp -35 nodes 1000 pv e2e4"),
+      Some(-35)
+    );
+    assert_eq!(
+      parse_score("info depth 8 score mate 3 pv e2e4"),
+      Some(INFINITE - 3)
+    );
+    assert_eq!(
+      parse_score("info depth 8 score mate -2 pv e2e4"),
+      Some(-INFINITE + 2)
+    );
+    assert_eq!(parse_score("info depth 8 nodes 1000"), None);
+  }
+
+  #[test]
+  fn epd_openings() {
+    let mut board: Board = Board::new();
+    let text: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 bm e5; id \"e4\";\n\
+                      \n\
+                      r3k2r/8/8/8/8/8/8/R3K2R w KQkq -\n";
+
+    assert_eq!(
+      parse_epd_openings(&mut board, text).unwrap(),
+      [
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+      ]
+    );
+    // The side not to move is in check
+    assert!(parse_epd_openings(
+      &mut board,
+      "4k3/8/8/8/8/8/8/4R2K b - -\n4k3/8/8/8/8/8/8/4R2K w - -\n"
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn pgn_openings() {
+    let mut board: Board = Board::new();
+    let text: &str = "[Event \"first\"]\n\
+                      [White \"a\"]\n\
+                      \n\
+                      1. e4 {the best} e5 (1... c5 2. Nf3 {open Sicilian}) 2. Nf3 $1 Nc6 *\n\
+                      \n\
+                      [Event \"second\"]\n\
+                      [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\
+                      \n\
+                      1. e4 Kd7 2. Kd2 1/2-1/2\n";
+
+    assert_eq!(
+      parse_pgn_openings(&mut board, text).unwrap(),
+      [
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "8/3k4/8/8/4P3/8/3K4/8 b - - 2 2",
+      ]
+    );
+    // The moves after an illegal one are not played
+    assert_eq!(
+      parse_pgn_openings(&mut board, "1. e4 e5 2. Ke3 Nc6\n").unwrap(),
+      ["rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"]
+    );
+    assert!(
+      parse_pgn_openings(&mut board, "[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n1. e4\n").is_err()
+    );
+  }
+}
@@ -0,0 +1,488 @@
+use crate::attack::*;
+use crate::board::*;
+use crate::definitions::*;
+
+/**
+ * The pieces that can move of each side, used to loop over the pieces_list of the board.
+ */
+const WHITE_PIECES: [Pieces; 6] = [
+  Pieces::Wp,
+  Pieces::Wn,
+  Pieces::Wb,
+  Pieces::Wr,
+  Pieces::Wq,
+  Pieces::Wk,
+];
+const BLACK_PIECES: [Pieces; 6] = [
+  Pieces::Bp,
+  Pieces::Bn,
+  Pieces::Bb,
+  Pieces::Br,
+  Pieces::Bq,
+  Pieces::Bk,
+];
+
+/**
+ * Which moves a generation has to add to the move list:
+ * - All: every pseudo legal move.
+ * - Captures: the moves that capture a piece (en passant included).
+ * - Quiets: the moves that do not capture a piece (castles and not capturing promotions included).
+ *
+ * Captures and Quiets together are exactly All, so the search can generate the captures
+ * first and the quiets only if no capture has produced a cutoff.
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub enum GeneratedMoves {
+  All,
+  Captures,
+  Quiets,
+}
+
+/**
+ * The directions in which every piece type moves (pawns are handled separately).
+ */
+fn piece_directions(piece: Pieces) -> &'static [i32] {
+  if PIECE_KNIGHT[piece as usize] {
+    &KNIGHT_DIRECTIONS
+  } else if PIECE_KING[piece as usize]
+    || (PIECE_ROOK_QUEEN[piece as usize] && PIECE_BISHOP_QUEEN[piece as usize])
+  {
+    &KING_DIRECTION
+  } else if PIECE_ROOK_QUEEN[piece as usize] {
+    &ROOK_DIRECTIONS
+  } else {
+    &BISHOP_DIRECTIONS
+  }
+}
+
+fn opponent_side(side: Colors) -> Colors {
+  if side == Colors::White {
+    Colors::Black
+  } else {
+    Colors::White
+  }
+}
+
+/**
+ * A pawn move reaching the last rank is added once for every promotion piece,
+ * otherwise it is added as it is.
+ */
+fn add_pawn_move(
+  board: &Board,
+  from: i32,
+  to: i32,
+  captured: Option<Pieces>,
+  side: Colors,
+  list: &mut MoveList,
+) {
+  let promotion_rank: i32 = if side == Colors::White {
+    ChessboardRanks::R8 as i32
+  } else {
+    ChessboardRanks::R1 as i32
+  };
+  let promotion_pieces: [Pieces; 4] = if side == Colors::White {
+    [Pieces::Wq, Pieces::Wr, Pieces::Wb, Pieces::Wn]
+  } else {
+    [Pieces::Bq, Pieces::Br, Pieces::Bb, Pieces::Bn]
+  };
+
+  if board.definitions().ranks_board()[to as usize] == promotion_rank {
+    for promoted in promotion_pieces {
+      list.add_move(Move::promotion(
+        Squares::from_120(from),
+        Squares::from_120(to),
+        captured,
+        promoted,
+      ));
+    }
+  } else if let Some(captured) = captured {
+    list.add_move(Move::capture(
+      Squares::from_120(from),
+      Squares::from_120(to),
+      captured,
+    ));
+  } else {
+    list.add_move(Move::quiet(Squares::from_120(from), Squares::from_120(to)));
+  }
+}
+
+fn generate_pawn_moves(
+  board: &Board,
+  from: i32,
+  generated_moves: GeneratedMoves,
+  list: &mut MoveList,
+) {
+  let side: Colors = board.side();
+  let pieces: &[Pieces; BOARD_SQUARE_NUMBER] = board.pieces();
+  // White pawns go up the board (+10), black pawns go down (-10)
+  let forward: i32 = if side == Colors::White { 10 } else { -10 };
+  let start_rank: i32 = if side == Colors::White {
+    ChessboardRanks::R2 as i32
+  } else {
+    ChessboardRanks::R7 as i32
+  };
+  let mut to: i32;
+
+  if generated_moves != GeneratedMoves::Captures
+    && pieces[(from + forward) as usize] == Pieces::Empty
+  {
+    add_pawn_move(board, from, from + forward, None, side, list);
+    if board.definitions().ranks_board()[from as usize] == start_rank
+      && pieces[(from + 2 * forward) as usize] == Pieces::Empty
+    {
+      list.add_move(Move::double_push(
+        Squares::from_120(from),
+        Squares::from_120(from + 2 * forward),
+      ));
+    }
+  }
+
+  if generated_moves != GeneratedMoves::Quiets {
+    for capture_direction in [forward - 1, forward + 1] {
+      to = from + capture_direction;
+      if pieces[to as usize] != Pieces::OffBoard
+        && pieces[to as usize] != Pieces::Empty
+        && PIECE_COLOR[pieces[to as usize] as usize] == opponent_side(side)
+        && !PIECE_KING[pieces[to as usize] as usize]
+      {
+        add_pawn_move(board, from, to, Some(pieces[to as usize]), side, list);
+      }
+      if board.en_passant_square() != Squares::NoSquare && to == board.en_passant_square() as i32 {
+        list.add_move(Move::en_passant(
+          Squares::from_120(from),
+          Squares::from_120(to),
+        ));
+      }
+    }
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Chess960#Castling>
+ *
+ * The castle is possible if the permission is still there, the squares crossed by king and rook
+ * are empty (but for the king and the rook themselves) and the king does not start from or pass
+ * through an attacked square (the arrival square is checked by make_move as for every other king move).
+ * In Chess960 the king and the rook can start on any file, but they arrive on the same squares
+ * of the standard castles.
+ */
+fn generate_castle_moves(board: &Board, list: &mut MoveList) {
+  let pieces: &[Pieces; BOARD_SQUARE_NUMBER] = board.pieces();
+  let side: Colors = board.side();
+  let opponent: Colors = opponent_side(side);
+  let king_from: i32 = board.king_square()[side as usize] as i32;
+  let rook: Pieces = if side == Colors::White {
+    Pieces::Wr
+  } else {
+    Pieces::Br
+  };
+  let mut rook_from: i32;
+  let mut first_square: i32;
+  let mut last_square: i32;
+
+  for &(king_to, rook_to, castle) in &CASTLES[side as usize] {
+    rook_from = board.castle_rooks()[castle_index(castle)] as i32;
+    if !board.castling_rights().contains(castle) || pieces[rook_from as usize] != rook {
+      continue;
+    }
+
+    first_square = king_from
+      .min(king_to as i32)
+      .min(rook_from)
+      .min(rook_to as i32);
+    last_square = king_from
+      .max(king_to as i32)
+      .max(rook_from)
+      .max(rook_to as i32);
+    if (first_square..=last_square).any(|square_120| {
+      square_120 != king_from
+        && square_120 != rook_from
+        && pieces[square_120 as usize] != Pieces::Empty
+    }) {
+      continue;
+    }
+
+    first_square = king_from.min(king_to as i32);
+    last_square = king_from.max(king_to as i32);
+    if (first_square..=last_square).any(|square_120| {
+      square_120 != king_to as i32
+        && square_attacked(Squares::from_120(square_120), opponent, board)
+    }) {
+      continue;
+    }
+
+    list.add_move(Move::castle(Squares::from_120(king_from), king_to));
+  }
+}
+
+/**
+ * It adds to the list the pseudo legal moves of the piece on the square (it has to be
+ * a piece of the side to move).
+ */
+pub fn generate_square_moves(
+  board: &Board,
+  square_120: i32,
+  generated_moves: GeneratedMoves,
+  list: &mut MoveList,
+) {
+  let pieces: &[Pieces; BOARD_SQUARE_NUMBER] = board.pieces();
+  let piece: Pieces = pieces[square_120 as usize];
+  let opponent: Colors = opponent_side(board.side());
+  let mut temp_square: i32;
+  let mut target_piece: Pieces;
+
+  if PIECE_PAWN[piece as usize] {
+    generate_pawn_moves(board, square_120, generated_moves, list);
+    return;
+  }
+
+  for &direction in piece_directions(piece) {
+    temp_square = square_120 + direction;
+    target_piece = pieces[temp_square as usize];
+    while target_piece != Pieces::OffBoard {
+      if target_piece != Pieces::Empty {
+        // The king is never captured, even when it's left in check (an illegal position)
+        if PIECE_COLOR[target_piece as usize] == opponent
+          && !PIECE_KING[target_piece as usize]
+          && generated_moves != GeneratedMoves::Quiets
+        {
+          list.add_move(Move::capture(
+            Squares::from_120(square_120),
+            Squares::from_120(temp_square),
+            target_piece,
+          ));
+        }
+        break;
+      }
+      if generated_moves != GeneratedMoves::Captures {
+        list.add_move(Move::quiet(
+          Squares::from_120(square_120),
+          Squares::from_120(temp_square),
+        ));
+      }
+      if !PIECE_SLIDES[piece as usize] {
+        break;
+      }
+      temp_square += direction;
+      target_piece = pieces[temp_square as usize];
+    }
+  }
+
+  if PIECE_KING[piece as usize] && generated_moves != GeneratedMoves::Captures {
+    generate_castle_moves(board, list);
+  }
+}
+
+/**
+ * It adds to the list the pseudo legal moves of the side to move, the legality
+ * of a move is checked when it's made (see Board::make_move).
+ */
+pub fn generate_moves(board: &Board, generated_moves: GeneratedMoves, list: &mut MoveList) {
+  let side_pieces: [Pieces; 6] = if board.side() == Colors::White {
+    WHITE_PIECES
+  } else {
+    BLACK_PIECES
+  };
+
+  list.clear();
+  for piece in side_pieces {
+    for piece_number in 0..board.actual_pieces_number()[piece as usize] {
+      generate_square_moves(
+        board,
+        board.pieces_list()[piece as usize][piece_number as usize] as i32,
+        generated_moves,
+        list,
+      );
+    }
+  }
+}
+
+pub fn generate_all_moves(board: &Board, list: &mut MoveList) {
+  generate_moves(board, GeneratedMoves::All, list);
+}
+
+pub fn generate_all_captures(board: &Board, list: &mut MoveList) {
+  generate_moves(board, GeneratedMoves::Captures, list);
+}
+
+pub fn generate_quiet_moves(board: &Board, list: &mut MoveList) {
+  generate_moves(board, GeneratedMoves::Quiets, list);
+}
+
+/**
+ * A move coming from another position (hash table, killer moves...) could be
+ * not possible in the current one, so before trying it we check that the piece on the
+ * starting square would generate it.
+ */
+pub fn move_is_pseudo_legal(board: &Board, mov_e: Move) -> bool {
+  let from: i32 = mov_e.from_square() as i32;
+  let mut list: MoveList = MoveList::new();
+
+  if mov_e == Move::NO_MOVE || from as usize >= BOARD_SQUARE_NUMBER {
+    return false;
+  }
+  let piece: Pieces = board.pieces()[from as usize];
+  if piece == Pieces::OffBoard
+    || piece == Pieces::Empty
+    || PIECE_COLOR[piece as usize] != board.side()
+  {
+    return false;
+  }
+
+  generate_square_moves(board, from, GeneratedMoves::All, &mut list);
+  list.contains(mov_e)
+}
+
+/**
+ * It checks that the move is legal in the position, the board is left as it was.
+ */
+pub fn move_exists(board: &mut Board, mov_e: Move) -> bool {
+  let mut list: MoveList = MoveList::new();
+
+  generate_all_moves(board, &mut list);
+  for &temp_move in list.moves() {
+    if temp_move == mov_e {
+      if !board.make_move(temp_move) {
+        return false;
+      }
+      board.take_move();
+      return true;
+    }
+  }
+  false
+}
+
+/**
+ * The legal moves of the position, the board is left as it was.
+ */
+pub fn generate_legal_moves(board: &mut Board) -> Vec<Move> {
+  let mut list: MoveList = MoveList::new();
+  let mut legal_moves: Vec<Move> = Vec::new();
+
+  generate_all_moves(board, &mut list);
+  for &mov_e in list.moves() {
+    if board.make_move(mov_e) {
+      board.take_move();
+      legal_moves.push(mov_e);
+    }
+  }
+
+  legal_moves
+}
+
+/**
+ * It converts a move in long algebraic notation (ex. "e2e4" or "a7a8q") to the move of the
+ * position, NO_MOVE is returned if the string is not a possible move.
+ */
+pub fn parse_move(board: &Board, algebraic: &str) -> Move {
+  let mut list: MoveList = MoveList::new();
+
+  generate_all_moves(board, &mut list);
+  for &mov_e in list.moves() {
+    if board.move_to_algebraic(mov_e) == algebraic {
+      return mov_e;
+    }
+  }
+
+  Move::NO_MOVE
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Algebraic_Chess_Notation#SAN>
+ *
+ * Standard algebraic notation (the one of the PGN format), ex. "e4", "Nbd7", "exd5", "O-O" or
+ * "e8=Q+". The move has to be legal, the board is left as it was.
+ */
+pub fn move_to_san(board: &mut Board, mov_e: Move) -> String {
+  let definitions: &Definitions = board.definitions();
+  let files: &[i32; BOARD_SQUARE_NUMBER] = definitions.files_board();
+  let ranks: &[i32; BOARD_SQUARE_NUMBER] = definitions.ranks_board();
+  let from: usize = mov_e.from_square() as usize;
+  let to: usize = mov_e.to_square() as usize;
+  let piece: Pieces = board.pieces()[from];
+  let capture: bool = mov_e.is_capture();
+  let mut san: String = String::new();
+  let mut other_from: usize;
+  let (mut ambiguous, mut same_file, mut same_rank): (bool, bool, bool) = (false, false, false);
+
+  if mov_e.is_castle() {
+    san.push_str(if files[to] == ChessboardFiles::G as i32 {
+      "O-O"
+    } else {
+      "O-O-O"
+    });
+  } else if PIECE_PAWN[piece as usize] {
+    if capture {
+      san.push(FILE_CHARACTERS[files[from] as usize]);
+      san.push('x');
+    }
+    san.push(FILE_CHARACTERS[files[to] as usize]);
+    san.push(RANK_CHARACTERS[ranks[to] as usize]);
+    if let Some(promoted) = mov_e.promoted() {
+      san.push('=');
+      san.push(PIECE_CHARACTERS[promoted as usize].to_ascii_uppercase());
+    }
+  } else {
+    // Another piece of the same type that can go to the same square has to be told apart
+    for other_move in generate_legal_moves(board) {
+      other_from = other_move.from_square() as usize;
+      if other_move.to_square() as usize == to
+        && other_from != from
+        && board.pieces()[other_from] == piece
+        && !other_move.is_castle()
+      {
+        ambiguous = true;
+        same_file |= files[other_from] == files[from];
+        same_rank |= ranks[other_from] == ranks[from];
+      }
+    }
+    san.push(PIECE_CHARACTERS[piece as usize].to_ascii_uppercase());
+    if ambiguous && (!same_file || same_rank) {
+      san.push(FILE_CHARACTERS[files[from] as usize]);
+    }
+    if same_file {
+      san.push(RANK_CHARACTERS[ranks[from] as usize]);
+    }
+    if capture {
+      san.push('x');
+    }
+    san.push(FILE_CHARACTERS[files[to] as usize]);
+    san.push(RANK_CHARACTERS[ranks[to] as usize]);
+  }
+
+  if board.make_move(mov_e) {
+    let side: Colors = board.side();
+    let opponent: Colors = if side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    if square_attacked(board.king_square()[side as usize], opponent, board) {
+      san.push(if generate_legal_moves(board).is_empty() {
+        '#'
+      } else {
+        '+'
+      });
+    }
+    board.take_move();
+  }
+  san
+}
+
+/**
+ * It converts a move in standard algebraic notation to the legal move of the position,
+ * NO_MOVE is returned if there is not such a move. The check and annotation symbols are ignored.
+ */
+pub fn parse_san(board: &mut Board, san: &str) -> Move {
+  let san: String = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+
+  for mov_e in generate_legal_moves(board) {
+    if move_to_san(board, mov_e).trim_end_matches(['+', '#']) == san {
+      return mov_e;
+    }
+  }
+
+  Move::NO_MOVE
+}
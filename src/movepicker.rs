@@ -0,0 +1,489 @@
+use crate::board::*;
+use crate::definitions::*;
+use crate::movegen::*;
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/MVV-LVA>
+ *
+ * MVV-LVA (Most Valuable Victim - Least Valuable Attacker): the captures are ordered by the
+ * value of the captured piece and, for the same captured piece, by the value of the capturing one.
+ * So PxQ (505) is tried before NxQ (504), that is tried before QxQ (501) and QxR (401).
+ */
+const VICTIM_SCORE: [i32; 13] = [
+  0, 100, 200, 300, 400, 500, 600, 100, 200, 300, 400, 500, 600,
+];
+
+/**
+ * The captures are always tried before the quiet moves, this base keeps their score
+ * above every history score.
+ */
+const CAPTURE_SCORE: i32 = 1_000_000;
+/**
+ * The not capturing promotions to queen are tried before the other quiet moves.
+ */
+const QUEEN_PROMOTION_SCORE: i32 = 900_000;
+/**
+ * The history scores are kept between -HISTORY_MAX and HISTORY_MAX.
+ */
+const HISTORY_MAX: i32 = 16384;
+
+pub fn mvv_lva_score(victim: Pieces, attacker: Pieces) -> i32 {
+  VICTIM_SCORE[victim as usize] + 6 - VICTIM_SCORE[attacker as usize] / 100
+}
+
+/**
+ * A quiet move is a move that does not capture anything (en passant is a capture).
+ */
+pub fn is_quiet_move(mov_e: Move) -> bool {
+  mov_e.mov_e() & MOVE_FLAG_CAPTURE == 0
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Killer_Heuristic>
+ * <https://www.chessprogramming.org/History_Heuristic>
+ * <https://www.chessprogramming.org/Countermove_Heuristic>
+ *
+ * What the search learns about the quiet moves that caused a beta cutoff:
+ * - killers: the last two cutoff moves found at each ply, a move good in a position
+ *   is often good in the sibling positions.
+ * - history: a butterfly table (indexed by side, from and to square) with a score that grows
+ *   when the move causes a cutoff and decreases when it has been tried without causing it.
+ * - counter_moves: for each previous move (indexed by moved piece and its arrival square)
+ *   the move that refuted it.
+ */
+#[derive(Copy, Clone)]
+pub struct SearchHeuristics {
+  killers: [[Move; 2]; MAX_DEPTH],
+  history: [[[i32; 64]; 64]; 2],
+  counter_moves: [[Move; BOARD_SQUARE_NUMBER]; 13],
+}
+
+impl SearchHeuristics {
+  pub fn new() -> SearchHeuristics {
+    let killers: [[Move; 2]; MAX_DEPTH] = [[Move::NO_MOVE; 2]; MAX_DEPTH];
+    let history: [[[i32; 64]; 64]; 2] = [[[0; 64]; 64]; 2];
+    let counter_moves: [[Move; BOARD_SQUARE_NUMBER]; 13] =
+      [[Move::NO_MOVE; BOARD_SQUARE_NUMBER]; 13];
+
+    SearchHeuristics {
+      killers,
+      history,
+      counter_moves,
+    }
+  }
+
+  pub fn clear(&mut self) {
+    *self = SearchHeuristics::new();
+  }
+
+  pub fn killers(&self, board: &Board) -> [Move; 2] {
+    self.killers[board.actual_half_moves() as usize]
+  }
+
+  pub fn store_killer(&mut self, board: &Board, mov_e: Move) {
+    let ply: usize = board.actual_half_moves() as usize;
+
+    if self.killers[ply][0] != mov_e {
+      self.killers[ply][1] = self.killers[ply][0];
+      self.killers[ply][0] = mov_e;
+    }
+  }
+
+  fn history_entry(&mut self, board: &Board, mov_e: Move) -> &mut i32 {
+    let from_64: i32 = board
+      .definitions()
+      .board_120_squares_in_64_squares_notation()[mov_e.from_square() as usize];
+    let to_64: i32 = board
+      .definitions()
+      .board_120_squares_in_64_squares_notation()[mov_e.to_square() as usize];
+
+    &mut self.history[board.side() as usize][from_64 as usize][to_64 as usize]
+  }
+
+  pub fn history_score(&self, board: &Board, mov_e: Move) -> i32 {
+    let from_64: i32 = board
+      .definitions()
+      .board_120_squares_in_64_squares_notation()[mov_e.from_square() as usize];
+    let to_64: i32 = board
+      .definitions()
+      .board_120_squares_in_64_squares_notation()[mov_e.to_square() as usize];
+
+    self.history[board.side() as usize][from_64 as usize][to_64 as usize]
+  }
+
+  /**
+   * The cutoff move gets a bonus and the quiet moves tried before it get a malus, both
+   * depending on the depth. The "gravity" formula keeps the score inside HISTORY_MAX,
+   * the more a score is near the limit the less it moves toward it.
+   */
+  pub fn update_history(&mut self, board: &Board, mov_e: Move, depth: i32, tried_quiets: &[Move]) {
+    let bonus: i32 = (depth * depth).min(HISTORY_MAX);
+    let mut entry: &mut i32;
+
+    entry = self.history_entry(board, mov_e);
+    *entry += bonus - *entry * bonus / HISTORY_MAX;
+
+    for &tried_move in tried_quiets {
+      if tried_move != mov_e {
+        entry = self.history_entry(board, tried_move);
+        *entry += -bonus - *entry * bonus / HISTORY_MAX;
+      }
+    }
+  }
+
+  /**
+   * The last move made on the board (it's the move to which we are searching an answer).
+   */
+  fn previous_move(board: &Board) -> Move {
+    match board.history().last() {
+      Some(undo) => Move::new(undo.half_move(), 0),
+      None => Move::NO_MOVE,
+    }
+  }
+
+  pub fn counter_move(&self, board: &Board) -> Move {
+    let previous_move: Move = SearchHeuristics::previous_move(board);
+    let to: Squares = previous_move.to_square();
+
+    if previous_move == Move::NO_MOVE {
+      return Move::NO_MOVE;
+    }
+    self.counter_moves[board.pieces()[to as usize] as usize][to as usize]
+  }
+
+  pub fn store_counter_move(&mut self, board: &Board, mov_e: Move) {
+    let previous_move: Move = SearchHeuristics::previous_move(board);
+    let to: Squares = previous_move.to_square();
+
+    if previous_move != Move::NO_MOVE {
+      self.counter_moves[board.pieces()[to as usize] as usize][to as usize] = mov_e;
+    }
+  }
+}
+
+/**
+ * The stages of the move picker, in the order in which they are done.
+ */
+#[derive(Copy, Clone, PartialEq)]
+enum PickerStages {
+  HashMove,
+  GenerateCaptures,
+  GoodCaptures,
+  FirstKiller,
+  SecondKiller,
+  CounterMove,
+  GenerateQuiets,
+  Quiets,
+  BadCaptures,
+  Done,
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Move_Ordering>
+ *
+ * The move picker gives to the search the moves of a position one by one, in the order
+ * in which they most likely cause a beta cutoff:
+ * 1. the hash move (the best move found by a previous search of the same position);
+ * 2. the captures that don't lose material (by SEE), by MVV-LVA;
+ * 3. the two killer moves;
+ * 4. the counter move;
+ * 5. the other quiet moves, by history score;
+ * 6. the captures that lose material.
+ *
+ * The moves are generated only when needed, so if the hash move or a capture causes a cutoff
+ * the quiet moves are never generated. The hash, killer and counter moves come from other
+ * positions too, so they are checked to be pseudo legal before being returned.
+ */
+pub struct MovePicker {
+  stage: PickerStages,
+  hash_move: Move,
+  killers: [Move; 2],
+  counter_move: Move,
+  /**
+   * The quiescence search only needs the captures, and it doesn't search the losing ones.
+   */
+  captures_only: bool,
+  list: MoveList,
+  index: usize,
+  bad_captures: MoveList,
+  bad_captures_index: usize,
+}
+
+impl MovePicker {
+  pub fn new(board: &Board, hash_move: Move, heuristics: &SearchHeuristics) -> MovePicker {
+    MovePicker {
+      stage: PickerStages::HashMove,
+      hash_move,
+      killers: heuristics.killers(board),
+      counter_move: heuristics.counter_move(board),
+      captures_only: false,
+      list: MoveList::new(),
+      index: 0,
+      bad_captures: MoveList::new(),
+      bad_captures_index: 0,
+    }
+  }
+
+  pub fn new_captures(hash_move: Move) -> MovePicker {
+    MovePicker {
+      stage: PickerStages::HashMove,
+      hash_move: if is_quiet_move(hash_move) {
+        Move::NO_MOVE
+      } else {
+        hash_move
+      },
+      killers: [Move::NO_MOVE; 2],
+      counter_move: Move::NO_MOVE,
+      captures_only: true,
+      list: MoveList::new(),
+      index: 0,
+      bad_captures: MoveList::new(),
+      bad_captures_index: 0,
+    }
+  }
+
+  /**
+   * It moves the best scored move not yet returned to the current index and returns it.
+   */
+  fn pick_best_move(&mut self) -> Option<Move> {
+    let moves: &mut [Move] = self.list.moves_mut();
+    let mut best_index: usize = self.index;
+
+    if self.index >= moves.len() {
+      return None;
+    }
+    for index in self.index + 1..moves.len() {
+      if moves[index].score() > moves[best_index].score() {
+        best_index = index;
+      }
+    }
+    moves.swap(self.index, best_index);
+    self.index += 1;
+
+    Some(moves[self.index - 1])
+  }
+
+  /**
+   * A killer or counter move is tried only if it's a quiet move possible in the position
+   * that has not already been returned.
+   */
+  fn is_usable_quiet_move(&self, board: &Board, mov_e: Move, already_returned: &[Move]) -> bool {
+    mov_e != Move::NO_MOVE
+      && mov_e != self.hash_move
+      && !already_returned.contains(&mov_e)
+      && is_quiet_move(mov_e)
+      && move_is_pseudo_legal(board, mov_e)
+  }
+
+  fn score_captures(&mut self, board: &Board) {
+    let mut victim: Pieces;
+
+    for mov_e in self.list.moves_mut() {
+      // An en passant move does not store the captured piece, but it's always a pawn
+      victim = if mov_e.is_en_passant() {
+        Pieces::Wp
+      } else {
+        mov_e.captured_piece().unwrap_or(Pieces::Empty)
+      };
+      mov_e.set_score(
+        CAPTURE_SCORE + mvv_lva_score(victim, board.pieces()[mov_e.from_square() as usize]),
+      );
+    }
+  }
+
+  fn score_quiets(&mut self, board: &Board, heuristics: &SearchHeuristics) {
+    for mov_e in self.list.moves_mut() {
+      if matches!(mov_e.promoted(), Some(Pieces::Wq | Pieces::Bq)) {
+        mov_e.set_score(QUEEN_PROMOTION_SCORE);
+      } else {
+        mov_e.set_score(heuristics.history_score(board, *mov_e));
+      }
+    }
+  }
+
+  /**
+   * It returns the next move to search, or None when every move has been returned.
+   * The moves are pseudo legal, the legality is checked by Board::make_move.
+   */
+  pub fn next_move(&mut self, board: &Board, heuristics: &SearchHeuristics) -> Option<Move> {
+    loop {
+      match self.stage {
+        PickerStages::HashMove => {
+          self.stage = PickerStages::GenerateCaptures;
+          if move_is_pseudo_legal(board, self.hash_move) {
+            return Some(self.hash_move);
+          }
+        }
+        PickerStages::GenerateCaptures => {
+          generate_all_captures(board, &mut self.list);
+          self.score_captures(board);
+          self.index = 0;
+          self.stage = PickerStages::GoodCaptures;
+        }
+        PickerStages::GoodCaptures => match self.pick_best_move() {
+          Some(mov_e) if mov_e == self.hash_move => {}
+          Some(mov_e) if !board.see_ge(mov_e, 0) => {
+            if !self.captures_only {
+              self.bad_captures.add_move(mov_e);
+            }
+          }
+          Some(mov_e) => return Some(mov_e),
+          None => {
+            self.stage = if self.captures_only {
+              PickerStages::Done
+            } else {
+              PickerStages::FirstKiller
+            };
+          }
+        },
+        PickerStages::FirstKiller => {
+          self.stage = PickerStages::SecondKiller;
+          if self.is_usable_quiet_move(board, self.killers[0], &[]) {
+            return Some(self.killers[0]);
+          }
+        }
+        PickerStages::SecondKiller => {
+          self.stage = PickerStages::CounterMove;
+          if self.is_usable_quiet_move(board, self.killers[1], &self.killers[0..1]) {
+            return Some(self.killers[1]);
+          }
+        }
+        PickerStages::CounterMove => {
+          self.stage = PickerStages::GenerateQuiets;
+          if self.is_usable_quiet_move(board, self.counter_move, &self.killers) {
+            return Some(self.counter_move);
+          }
+          // Not returned, so it does not have to be skipped in the quiet moves stage
+          self.counter_move = Move::NO_MOVE;
+        }
+        PickerStages::GenerateQuiets => {
+          generate_quiet_moves(board, &mut self.list);
+          self.score_quiets(board, heuristics);
+          self.index = 0;
+          self.stage = PickerStages::Quiets;
+        }
+        PickerStages::Quiets => match self.pick_best_move() {
+          Some(mov_e)
+            if mov_e == self.hash_move
+              || mov_e == self.counter_move
+              || self.killers.contains(&mov_e) => {}
+          Some(mov_e) => return Some(mov_e),
+          None => self.stage = PickerStages::BadCaptures,
+        },
+        PickerStages::BadCaptures => {
+          if self.bad_captures_index < self.bad_captures.count() {
+            self.bad_captures_index += 1;
+            return Some(self.bad_captures.moves()[self.bad_captures_index - 1]);
+          }
+          self.stage = PickerStages::Done;
+        }
+        PickerStages::Done => return None,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /**
+   * Every move given by the picker, until it's done.
+   */
+  fn picked_moves(
+    board: &Board,
+    mut picker: MovePicker,
+    heuristics: &SearchHeuristics,
+  ) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+
+    while let Some(mov_e) = picker.next_move(board, heuristics) {
+      moves.push(mov_e);
+    }
+    moves
+  }
+
+  fn algebraic_moves(moves: &[Move]) -> Vec<String> {
+    moves.iter().map(|mov_e| mov_e.to_string()).collect()
+  }
+
+  #[test]
+  fn moves_are_picked_by_stage() {
+    let mut board: Board = Board::from_fen("5k2/8/6p1/3q3p/4P3/2N5/P7/3QK3 b - - 0 1").unwrap();
+    let mut heuristics: SearchHeuristics = SearchHeuristics::new();
+    let mut all_moves: MoveList = MoveList::new();
+
+    // The counter move answers the last move made, the black king to e8
+    assert!(board.make_move(parse_move(&board, "f8e8")));
+    heuristics.store_counter_move(&board, parse_move(&board, "c3e2"));
+    heuristics.store_killer(&board, parse_move(&board, "a2a4"));
+    heuristics.store_killer(&board, parse_move(&board, "c3b5"));
+    heuristics.update_history(&board, parse_move(&board, "d1d2"), 4, &[]);
+
+    let moves: Vec<Move> = picked_moves(
+      &board,
+      MovePicker::new(&board, parse_move(&board, "a2a3"), &heuristics),
+      &heuristics,
+    );
+    let picked: Vec<String> = algebraic_moves(&moves);
+
+    // Hash move, winning captures by MVV-LVA (PxQ, NxQ, QxQ), killers, counter move,
+    // the quiet move with the best history, the other quiets and the losing QxP last
+    assert_eq!(
+      picked[0..8],
+      ["a2a3", "e4d5", "c3d5", "d1d5", "c3b5", "a2a4", "c3e2", "d1d2"]
+    );
+    assert_eq!(picked.last().map(String::as_str), Some("d1h5"));
+
+    // Each pseudo legal move is given once
+    generate_all_moves(&board, &mut all_moves);
+    assert_eq!(moves.len(), all_moves.count());
+    for mov_e in all_moves.moves() {
+      assert_eq!(
+        moves.iter().filter(|picked| *picked == mov_e).count(),
+        1,
+        "{}",
+        mov_e
+      );
+    }
+  }
+
+  #[test]
+  fn captures_are_ordered_by_mvv_lva() {
+    assert!(mvv_lva_score(Pieces::Bq, Pieces::Wp) > mvv_lva_score(Pieces::Bq, Pieces::Wn));
+    assert!(mvv_lva_score(Pieces::Bq, Pieces::Wq) > mvv_lva_score(Pieces::Br, Pieces::Wp));
+    assert!(mvv_lva_score(Pieces::Br, Pieces::Wk) > mvv_lva_score(Pieces::Bb, Pieces::Wp));
+
+    // The quiescence search only gets the captures that do not lose material
+    let board: Board = Board::from_fen("4k3/8/6p1/3q3p/4P3/2N5/P7/3QK3 w - - 0 1").unwrap();
+    let heuristics: SearchHeuristics = SearchHeuristics::new();
+    let moves: Vec<Move> = picked_moves(
+      &board,
+      MovePicker::new_captures(parse_move(&board, "a2a3")),
+      &heuristics,
+    );
+
+    assert_eq!(algebraic_moves(&moves), ["e4d5", "c3d5", "d1d5"]);
+  }
+
+  #[test]
+  fn hash_move_is_not_given_twice() {
+    let board: Board = Board::from_fen("4k3/8/6p1/3q3p/4P3/2N5/P7/3QK3 w - - 0 1").unwrap();
+    let heuristics: SearchHeuristics = SearchHeuristics::new();
+
+    for hash_move in ["c3d5", "d1h5", "a2a4"] {
+      let moves: Vec<Move> = picked_moves(
+        &board,
+        MovePicker::new(&board, parse_move(&board, hash_move), &heuristics),
+        &heuristics,
+      );
+      let picked: Vec<String> = algebraic_moves(&moves);
+
+      assert_eq!(picked[0], hash_move);
+      assert_eq!(picked.iter().filter(|mov_e| *mov_e == hash_move).count(), 1);
+    }
+  }
+}
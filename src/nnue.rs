@@ -0,0 +1,627 @@
+use std::fs;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::board::*;
+use crate::definitions::*;
+
+/**
+ * HalfKP: for each side (perspective) a feature is the king square of that side together with
+ * one of the other 10 pieces (kings excluded) on one of the 64 squares.
+ */
+pub const NNUE_INPUTS: usize = 64 * 10 * 64;
+pub const NNUE_HIDDEN: usize = 256;
+pub const NNUE_LAYER_1: usize = 32;
+
+const NNUE_MAGIC: &[u8; 4] = b"NNUE";
+const NNUE_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 4 + 4 * 4;
+/**
+ * The activations are clipped in 0..=127, so that they fit in 8 bits.
+ * The first layer weights have 6 fractional bits, and the network output is divided
+ * by OUTPUT_SCALE to get a score in centipawns.
+ */
+const ACTIVATION_MAX: i32 = 127;
+const WEIGHT_SCALE_BITS: i32 = 6;
+const OUTPUT_SCALE: i32 = 16;
+
+/**
+ * The feature transformer output of both sides, indexed by color (the perspective).
+ * It's the sum of the weights of the active features plus the biases: when a piece moves
+ * only the weights of the changed features are added and subtracted.
+ */
+#[derive(Copy, Clone, PartialEq)]
+#[repr(C, align(32))]
+pub struct Accumulator {
+  values: [[i16; NNUE_HIDDEN]; 2],
+}
+
+impl Accumulator {
+  pub fn new() -> Accumulator {
+    Accumulator {
+      values: [[0; NNUE_HIDDEN]; 2],
+    }
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/NNUE>
+ * <https://www.chessprogramming.org/Stockfish_NNUE>
+ *
+ * A quantised network: HalfKP (40960) -> 2 x 256 -> 32 -> 1.
+ * The feature transformer has int16 weights, the other layers int8 weights and int32 biases.
+ *
+ * The file is little endian:
+ * "NNUE", version (u32), inputs (u32), hidden (u32), layer 1 (u32),
+ * feature biases (hidden x i16), feature weights (inputs x hidden x i16),
+ * layer 1 biases (layer 1 x i32), layer 1 weights (layer 1 x 2 hidden x i8),
+ * output bias (i32), output weights (layer 1 x i8).
+ */
+pub struct Network {
+  feature_biases: Vec<i16>,
+  feature_weights: Vec<i16>,
+  layer_1_biases: Vec<i32>,
+  layer_1_weights: Vec<i8>,
+  output_bias: i32,
+  output_weights: Vec<i8>,
+  /**
+   * The AVX2 instructions are used if the processor has them, the scalar code otherwise.
+   */
+  avx2: bool,
+}
+
+/**
+ * It reads the little endian values one after the other.
+ */
+struct NetworkReader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl<'a> NetworkReader<'a> {
+  fn take<const N: usize>(&mut self) -> [u8; N] {
+    let mut value: [u8; N] = [0; N];
+
+    value.copy_from_slice(&self.bytes[self.position..self.position + N]);
+    self.position += N;
+    value
+  }
+
+  fn u32(&mut self) -> u32 {
+    u32::from_le_bytes(self.take::<4>())
+  }
+
+  fn i32s(&mut self, number: usize) -> Vec<i32> {
+    (0..number)
+      .map(|_| i32::from_le_bytes(self.take::<4>()))
+      .collect()
+  }
+
+  fn i16s(&mut self, number: usize) -> Vec<i16> {
+    (0..number)
+      .map(|_| i16::from_le_bytes(self.take::<2>()))
+      .collect()
+  }
+
+  fn i8s(&mut self, number: usize) -> Vec<i8> {
+    (0..number)
+      .map(|_| i8::from_le_bytes(self.take::<1>()))
+      .collect()
+  }
+}
+
+impl Network {
+  pub fn from_file(path: &str) -> Result<Network, String> {
+    let bytes: Vec<u8> = fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+
+    Network::from_bytes(&bytes).map_err(|error| format!("{}: {}", path, error))
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+    let expected_size: usize = HEADER_SIZE
+      + 2 * NNUE_HIDDEN
+      + 2 * NNUE_INPUTS * NNUE_HIDDEN
+      + 4 * NNUE_LAYER_1
+      + 2 * NNUE_HIDDEN * NNUE_LAYER_1
+      + 4
+      + NNUE_LAYER_1;
+    let mut reader: NetworkReader = NetworkReader { bytes, position: 0 };
+
+    if bytes.len() < HEADER_SIZE || &reader.take::<4>() != NNUE_MAGIC {
+      return Err(String::from("not a network file"));
+    }
+    let header: [u32; 4] = [reader.u32(), reader.u32(), reader.u32(), reader.u32()];
+    if header
+      != [
+        NNUE_VERSION,
+        NNUE_INPUTS as u32,
+        NNUE_HIDDEN as u32,
+        NNUE_LAYER_1 as u32,
+      ]
+    {
+      return Err(format!(
+        "unsupported network (version {}, {} -> 2x{} -> {} -> 1)",
+        header[0], header[1], header[2], header[3]
+      ));
+    }
+    if bytes.len() != expected_size {
+      return Err(format!(
+        "the size is {} bytes, {} expected",
+        bytes.len(),
+        expected_size
+      ));
+    }
+
+    Ok(Network {
+      feature_biases: reader.i16s(NNUE_HIDDEN),
+      feature_weights: reader.i16s(NNUE_INPUTS * NNUE_HIDDEN),
+      layer_1_biases: reader.i32s(NNUE_LAYER_1),
+      layer_1_weights: reader.i8s(2 * NNUE_HIDDEN * NNUE_LAYER_1),
+      output_bias: reader.i32s(1)[0],
+      output_weights: reader.i8s(NNUE_LAYER_1),
+      avx2: avx2_available(),
+    })
+  }
+
+  pub fn avx2(&self) -> bool {
+    self.avx2
+  }
+
+  /**
+   * It forces the scalar code even if the processor has AVX2 (the results are the same).
+   */
+  pub fn set_avx2(&mut self, avx2: bool) {
+    self.avx2 = avx2 && avx2_available();
+  }
+
+  fn feature_weights(&self, feature: usize) -> &[i16] {
+    &self.feature_weights[feature * NNUE_HIDDEN..(feature + 1) * NNUE_HIDDEN]
+  }
+
+  fn add_feature(&self, values: &mut [i16; NNUE_HIDDEN], feature: usize) {
+    #[cfg(target_arch = "x86_64")]
+    if self.avx2 {
+      // SAFETY: the processor has AVX2, and both slices have NNUE_HIDDEN values
+      unsafe { add_weights_avx2(values, self.feature_weights(feature)) };
+      return;
+    }
+    for (value, weight) in values.iter_mut().zip(self.feature_weights(feature)) {
+      *value = value.wrapping_add(*weight);
+    }
+  }
+
+  fn subtract_feature(&self, values: &mut [i16; NNUE_HIDDEN], feature: usize) {
+    #[cfg(target_arch = "x86_64")]
+    if self.avx2 {
+      // SAFETY: the processor has AVX2, and both slices have NNUE_HIDDEN values
+      unsafe { subtract_weights_avx2(values, self.feature_weights(feature)) };
+      return;
+    }
+    for (value, weight) in values.iter_mut().zip(self.feature_weights(feature)) {
+      *value = value.wrapping_sub(*weight);
+    }
+  }
+
+  /**
+   * The accumulator of one perspective computed from scratch, from all the pieces on the board.
+   */
+  fn refresh_perspective(
+    &self,
+    board: &Board,
+    perspective: Colors,
+    values: &mut [i16; NNUE_HIDDEN],
+  ) {
+    let definitions: &Definitions = board.definitions();
+    let king_square_64: i32 = definitions.board_120_squares_in_64_squares_notation()
+      [board.king_square()[perspective as usize] as usize];
+
+    values.copy_from_slice(&self.feature_biases);
+    for piece in Pieces::iter() {
+      for &square in &board.pieces_list()[piece as usize]
+        [0..board.actual_pieces_number()[piece as usize] as usize]
+      {
+        if let Some(feature) = feature_index(
+          perspective,
+          king_square_64,
+          piece,
+          definitions.board_120_squares_in_64_squares_notation()[square as usize],
+        ) {
+          self.add_feature(values, feature);
+        }
+      }
+    }
+  }
+
+  pub fn refresh_accumulator(&self, board: &Board) -> Accumulator {
+    let mut accumulator: Accumulator = Accumulator::new();
+
+    for perspective in [Colors::White, Colors::Black] {
+      self.refresh_perspective(
+        board,
+        perspective,
+        &mut accumulator.values[perspective as usize],
+      );
+    }
+
+    accumulator
+  }
+
+  /**
+   * References:
+   * <https://www.chessprogramming.org/NNUE#Incremental_Update>
+   *
+   * The accumulator after the move, computed from the one before it: the board has already
+   * made the move of the given side. The features depend on the king square, so when the king
+   * moves the accumulator of its side is computed again from all the pieces.
+   */
+  pub fn update_accumulator(
+    &self,
+    board: &Board,
+    previous: &Accumulator,
+    mov_e: Move,
+    side: Colors,
+  ) -> Accumulator {
+    let definitions: &Definitions = board.definitions();
+    let from: i32 = mov_e.from_square() as i32;
+    let to: i32 = mov_e.to_square() as i32;
+    let moved_piece: Pieces = board.pieces()[to as usize];
+    let (pawn, opponent_pawn, backward): (Pieces, Pieces, i32) = if side == Colors::White {
+      (Pieces::Wp, Pieces::Bp, -10)
+    } else {
+      (Pieces::Bp, Pieces::Wp, 10)
+    };
+    let original_piece: Pieces = if mov_e.is_promotion() {
+      pawn
+    } else {
+      moved_piece
+    };
+    // The (piece, square 120) that leave and enter the board
+    let mut removed: [(Pieces, i32); 2] = [(original_piece, from), (Pieces::Empty, 0)];
+    let mut added: [(Pieces, i32); 2] = [(moved_piece, to), (Pieces::Empty, 0)];
+    let mut accumulator: Accumulator = *previous;
+    let mut king_square_64: i32;
+
+    if let Some(captured) = mov_e.captured_piece() {
+      removed[1] = (captured, to);
+    } else if mov_e.is_en_passant() {
+      removed[1] = (opponent_pawn, to + backward);
+    } else if mov_e.is_castle() {
+      let (rook_from, rook_to): (Squares, Squares) = board.castle_rook_squares(mov_e.to_square());
+      removed[1] = (board.pieces()[rook_to as usize], rook_from as i32);
+      added[1] = (board.pieces()[rook_to as usize], rook_to as i32);
+    }
+
+    for perspective in [Colors::White, Colors::Black] {
+      let values: &mut [i16; NNUE_HIDDEN] = &mut accumulator.values[perspective as usize];
+
+      if PIECE_KING[original_piece as usize] && perspective == side {
+        self.refresh_perspective(board, perspective, values);
+        continue;
+      }
+      king_square_64 = definitions.board_120_squares_in_64_squares_notation()
+        [board.king_square()[perspective as usize] as usize];
+      for (piece, square_120) in removed {
+        if piece != Pieces::Empty {
+          if let Some(feature) = feature_index(
+            perspective,
+            king_square_64,
+            piece,
+            definitions.board_120_squares_in_64_squares_notation()[square_120 as usize],
+          ) {
+            self.subtract_feature(values, feature);
+          }
+        }
+      }
+      for (piece, square_120) in added {
+        if piece != Pieces::Empty {
+          if let Some(feature) = feature_index(
+            perspective,
+            king_square_64,
+            piece,
+            definitions.board_120_squares_in_64_squares_notation()[square_120 as usize],
+          ) {
+            self.add_feature(values, feature);
+          }
+        }
+      }
+    }
+
+    accumulator
+  }
+
+  /**
+   * The forward pass: the accumulator of the side to move comes first, then the other one.
+   * It returns the score from the point of view of the side to move.
+   */
+  pub fn evaluate(&self, accumulator: &Accumulator, side: Colors) -> i32 {
+    let opponent: Colors = if side == Colors::White {
+      Colors::Black
+    } else {
+      Colors::White
+    };
+    let mut input: [i16; 2 * NNUE_HIDDEN] = [0; 2 * NNUE_HIDDEN];
+    let mut output: i32 = self.output_bias;
+    let mut neuron: i32;
+
+    for (half, perspective) in [side, opponent].into_iter().enumerate() {
+      for (index, &value) in accumulator.values[perspective as usize].iter().enumerate() {
+        input[half * NNUE_HIDDEN + index] = (value as i32).clamp(0, ACTIVATION_MAX) as i16;
+      }
+    }
+
+    for (index, &bias) in self.layer_1_biases.iter().enumerate() {
+      let weights: &[i8] =
+        &self.layer_1_weights[index * 2 * NNUE_HIDDEN..(index + 1) * 2 * NNUE_HIDDEN];
+      neuron = bias + self.dot_product(&input, weights);
+      output += ((neuron >> WEIGHT_SCALE_BITS).clamp(0, ACTIVATION_MAX))
+        * self.output_weights[index] as i32;
+    }
+
+    (output / OUTPUT_SCALE).clamp(-IS_MATE + 1, IS_MATE - 1)
+  }
+
+  fn dot_product(&self, input: &[i16; 2 * NNUE_HIDDEN], weights: &[i8]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if self.avx2 {
+      // SAFETY: the processor has AVX2, and both slices have 2 * NNUE_HIDDEN values
+      return unsafe { dot_product_avx2(input, weights) };
+    }
+    input
+      .iter()
+      .zip(weights)
+      .map(|(&value, &weight)| value as i32 * weight as i32)
+      .sum()
+  }
+}
+
+/**
+ * The index of the feature (piece, square) seen by the given side: the board is flipped
+ * for black, so that both sides see their pieces as white pieces from the first rank.
+ * Own pieces come first (pawn to queen), then the opponent ones. Kings are not features.
+ */
+pub fn feature_index(
+  perspective: Colors,
+  king_square_64: i32,
+  piece: Pieces,
+  square_64: i32,
+) -> Option<usize> {
+  let (king, square): (usize, usize) = if perspective == Colors::White {
+    (king_square_64 as usize, square_64 as usize)
+  } else {
+    ((king_square_64 ^ 56) as usize, (square_64 ^ 56) as usize)
+  };
+
+  if PIECE_KING[piece as usize] {
+    return None;
+  }
+  let piece_type: usize = (piece as usize - Pieces::Wp as usize) % 6;
+  let piece_index: usize = if PIECE_COLOR[piece as usize] == perspective {
+    piece_type
+  } else {
+    piece_type + 5
+  };
+
+  Some((king * 10 + piece_index) * 64 + square)
+}
+
+fn avx2_available() -> bool {
+  #[cfg(target_arch = "x86_64")]
+  {
+    is_x86_feature_detected!("avx2")
+  }
+  #[cfg(not(target_arch = "x86_64"))]
+  {
+    false
+  }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn add_weights_avx2(values: &mut [i16; NNUE_HIDDEN], weights: &[i16]) {
+  let mut value: __m256i;
+  let mut weight: __m256i;
+
+  for index in (0..NNUE_HIDDEN).step_by(16) {
+    value = _mm256_loadu_si256(values.as_ptr().add(index) as *const __m256i);
+    weight = _mm256_loadu_si256(weights.as_ptr().add(index) as *const __m256i);
+    _mm256_storeu_si256(
+      values.as_mut_ptr().add(index) as *mut __m256i,
+      _mm256_add_epi16(value, weight),
+    );
+  }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn subtract_weights_avx2(values: &mut [i16; NNUE_HIDDEN], weights: &[i16]) {
+  let mut value: __m256i;
+  let mut weight: __m256i;
+
+  for index in (0..NNUE_HIDDEN).step_by(16) {
+    value = _mm256_loadu_si256(values.as_ptr().add(index) as *const __m256i);
+    weight = _mm256_loadu_si256(weights.as_ptr().add(index) as *const __m256i);
+    _mm256_storeu_si256(
+      values.as_mut_ptr().add(index) as *mut __m256i,
+      _mm256_sub_epi16(value, weight),
+    );
+  }
+}
+
+/**
+ * 16 inputs at a time: the int8 weights are widened to int16, and madd multiplies
+ * and sums them in pairs to 8 int32 values.
+ */
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn dot_product_avx2(input: &[i16; 2 * NNUE_HIDDEN], weights: &[i8]) -> i32 {
+  let mut sum: __m256i = _mm256_setzero_si256();
+  let mut value: __m256i;
+  let mut weight: __m256i;
+  let mut lanes: [i32; 8] = [0; 8];
+
+  for index in (0..2 * NNUE_HIDDEN).step_by(16) {
+    value = _mm256_loadu_si256(input.as_ptr().add(index) as *const __m256i);
+    weight = _mm256_cvtepi8_epi16(_mm_loadu_si128(
+      weights.as_ptr().add(index) as *const __m128i
+    ));
+    sum = _mm256_add_epi32(sum, _mm256_madd_epi16(value, weight));
+  }
+  _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+
+  lanes.iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use rand::rngs::StdRng;
+  use rand::{Rng, SeedableRng};
+
+  use super::*;
+  use crate::movegen::*;
+
+  /**
+   * A network file with small random weights, so that the accumulators do not overflow.
+   */
+  fn random_network(seed: u64) -> Network {
+    let mut rng: StdRng = StdRng::seed_from_u64(seed);
+    let mut random_bytes: Vec<u8> = vec![0; NNUE_HIDDEN + NNUE_INPUTS * NNUE_HIDDEN];
+    let mut bytes: Vec<u8> = NNUE_MAGIC.to_vec();
+
+    for value in [
+      NNUE_VERSION,
+      NNUE_INPUTS as u32,
+      NNUE_HIDDEN as u32,
+      NNUE_LAYER_1 as u32,
+    ] {
+      bytes.extend(value.to_le_bytes());
+    }
+    // The feature biases and weights in -16..16
+    rng.fill(&mut random_bytes[..]);
+    for &byte in &random_bytes {
+      bytes.extend(((byte as i8 >> 3) as i16).to_le_bytes());
+    }
+    for _ in 0..NNUE_LAYER_1 {
+      bytes.extend(rng.gen_range(-256i32..=256).to_le_bytes());
+    }
+    for _ in 0..2 * NNUE_HIDDEN * NNUE_LAYER_1 {
+      bytes.push(rng.gen_range(-32i8..=32) as u8);
+    }
+    bytes.extend(rng.gen_range(-256i32..=256).to_le_bytes());
+    for _ in 0..NNUE_LAYER_1 {
+      bytes.push(rng.gen_range(-32i8..=32) as u8);
+    }
+
+    Network::from_bytes(&bytes).unwrap()
+  }
+
+  /**
+   * The accumulator of the board is the one computed from scratch, and so is the evaluation.
+   */
+  fn assert_refreshed(board: &Board, network: &Network, step: &str) {
+    let refreshed: Accumulator = network.refresh_accumulator(board);
+
+    assert!(*board.accumulator() == refreshed, "{}", step);
+    assert_eq!(
+      network.evaluate(board.accumulator(), board.side()),
+      network.evaluate(&refreshed, board.side()),
+      "{}",
+      step
+    );
+  }
+
+  #[test]
+  fn incremental_updates_equal_a_refresh() {
+    let network: Arc<Network> = Arc::new(random_network(1));
+    let mut board: Board = Board::new();
+
+    for (fen, moves) in [
+      // Castles on both sides
+      (
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        "e1g1 e8c8 g1g2 c8b8",
+      ),
+      (
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        "e1c1 e8g8 h1h8 g8h8",
+      ),
+      // En passant of both colors
+      ("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1", "e2e4 d4e3 e1e2 e8d7"),
+      ("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1", "d7d5 e5d6 e8d7 e1d2"),
+      // Promotions, with and without a capture, and the capture of a promoted piece
+      (
+        "1n6/P5k1/8/8/8/8/6p1/4K2R w K - 0 1",
+        "a7b8q g2h1n b8h2 h1g3 h2g3 g7f7",
+      ),
+      ("4k3/P7/8/8/8/8/7p/4K3 w - - 0 1", "a7a8n h2h1b a8b6 h1e4"),
+      (
+        START_FEN,
+        "e2e4 d7d5 e4d5 d8d5 g1f3 c8g4 f1e2 b8c6 e1g1 e8c8",
+      ),
+    ] {
+      board.parse_fen(fen).unwrap();
+      board.set_network(Some(Arc::clone(&network)));
+      assert_refreshed(&board, &network, fen);
+
+      for algebraic in moves.split_whitespace() {
+        let mov_e: Move = parse_move(&board, algebraic);
+        assert_ne!(mov_e, Move::NO_MOVE, "{} in {}", algebraic, fen);
+        assert!(board.make_move(mov_e), "{} in {}", algebraic, fen);
+        assert_refreshed(&board, &network, algebraic);
+      }
+      for algebraic in moves.split_whitespace().rev() {
+        board.take_move();
+        assert_refreshed(&board, &network, algebraic);
+      }
+      assert_eq!(board.to_fen(), Board::from_fen(fen).unwrap().to_fen());
+    }
+  }
+
+  #[test]
+  fn random_games_keep_the_accumulator() {
+    let network: Arc<Network> = Arc::new(random_network(2));
+    let mut rng: StdRng = StdRng::seed_from_u64(3);
+    let mut board: Board = Board::new();
+
+    for _ in 0..5 {
+      board.parse_fen(START_FEN).unwrap();
+      board.set_network(Some(Arc::clone(&network)));
+      for _ in 0..100 {
+        let moves: Vec<Move> = generate_legal_moves(&mut board);
+        if moves.is_empty() {
+          break;
+        }
+        let mov_e: Move = moves[rng.gen_range(0..moves.len())];
+        assert!(board.make_move(mov_e));
+        assert_refreshed(&board, &network, &mov_e.to_string());
+      }
+    }
+  }
+
+  #[test]
+  fn avx2_and_scalar_code_agree() {
+    let mut network: Network = random_network(4);
+    let mut board: Board = Board::from_fen(START_FEN).unwrap();
+    let mov_e: Move = parse_move(&board, "e2e4");
+
+    assert!(board.make_move(mov_e));
+    let avx2: (Accumulator, Accumulator, i32) = (
+      network.refresh_accumulator(&board),
+      network.update_accumulator(&board, &Accumulator::new(), mov_e, Colors::White),
+      network.evaluate(&network.refresh_accumulator(&board), Colors::Black),
+    );
+    network.set_avx2(false);
+    let scalar: (Accumulator, Accumulator, i32) = (
+      network.refresh_accumulator(&board),
+      network.update_accumulator(&board, &Accumulator::new(), mov_e, Colors::White),
+      network.evaluate(&network.refresh_accumulator(&board), Colors::Black),
+    );
+
+    assert!(avx2.0 == scalar.0);
+    assert!(avx2.1 == scalar.1);
+    assert_eq!(avx2.2, scalar.2);
+  }
+}
@@ -0,0 +1,390 @@
+use crate::definitions::*;
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Evaluation>
+ *
+ * Every value used by the evaluation, so that they can be changed (and tuned) without touching
+ * the evaluation code. The values are in centipawns and from the point of view of the piece color.
+ * The arrays indexed by piece type use the order knight, bishop, rook, queen (see piece_type_index).
+ */
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EvaluationParameters {
+  /**
+   * Pawn, knight, bishop, rook and queen values (the king is never captured).
+   */
+  pub piece_value: [i32; 5],
+  pub pawn_table: [i32; 64],
+  pub knight_table: [i32; 64],
+  pub bishop_table: [i32; 64],
+  /**
+   * Used for the queens too, so they like the seventh rank and the central files.
+   */
+  pub rook_table: [i32; 64],
+  pub king_opening_table: [i32; 64],
+  pub king_endgame_table: [i32; 64],
+  pub isolated_pawn: i32,
+  pub doubled_pawn: i32,
+  pub backward_pawn: i32,
+  /**
+   * Indexed by the rank of the pawn seen from its side (0 is the first rank, 7 the last one).
+   */
+  pub connected_pawn: [i32; 8],
+  pub passed_pawn: [i32; 8],
+  pub passed_pawn_king_proximity: [i32; 8],
+  /**
+   * The bonus for every reachable square more (or less) than the average of the piece type.
+   */
+  pub mobility: [i32; 4],
+  /**
+   * How much every piece type attacking the squares around the opponent king adds to the danger.
+   */
+  pub king_attack_weight: [i32; 4],
+  /**
+   * Own pawns one and two ranks in front of the king, and no own pawn on a king file.
+   */
+  pub pawn_shield: [i32; 3],
+  /**
+   * Opponent pawns one, two and three ranks in front of the king, on the king files.
+   */
+  pub pawn_storm: [i32; 3],
+  pub rook_open_file: i32,
+  pub rook_semi_open_file: i32,
+  pub rook_seventh_rank: i32,
+  pub bishop_pair: i32,
+  pub knight_outpost: i32,
+  pub bishop_outpost: i32,
+  pub trapped_bishop: i32,
+  pub trapped_rook: i32,
+}
+
+impl EvaluationParameters {
+  pub fn new() -> EvaluationParameters {
+    EvaluationParameters {
+      piece_value: [100, 325, 325, 550, 1000],
+      pawn_table: PAWN_TABLE,
+      knight_table: KNIGHT_TABLE,
+      bishop_table: BISHOP_TABLE,
+      rook_table: ROOK_TABLE,
+      king_opening_table: KING_OPENING_TABLE,
+      king_endgame_table: KING_ENDGAME_TABLE,
+      isolated_pawn: -10,
+      doubled_pawn: -10,
+      backward_pawn: -8,
+      connected_pawn: [0, 2, 4, 6, 10, 16, 24, 0],
+      passed_pawn: [0, 5, 10, 20, 35, 60, 100, 0],
+      passed_pawn_king_proximity: [0, 0, 0, 1, 2, 3, 5, 0],
+      mobility: [4, 5, 2, 1],
+      king_attack_weight: [20, 20, 40, 80],
+      pawn_shield: [10, 5, -10],
+      pawn_storm: [-5, -15, -8],
+      rook_open_file: 20,
+      rook_semi_open_file: 10,
+      rook_seventh_rank: 20,
+      bishop_pair: 30,
+      knight_outpost: 20,
+      bishop_outpost: 10,
+      trapped_bishop: -100,
+      trapped_rook: -40,
+    }
+  }
+
+  /**
+   * Every parameter with its name, the single values are seen as arrays with one element.
+   */
+  pub fn named_values(&self) -> Vec<(&'static str, &[i32])> {
+    vec![
+      ("piece_value", self.piece_value.values()),
+      ("pawn_table", self.pawn_table.values()),
+      ("knight_table", self.knight_table.values()),
+      ("bishop_table", self.bishop_table.values()),
+      ("rook_table", self.rook_table.values()),
+      ("king_opening_table", self.king_opening_table.values()),
+      ("king_endgame_table", self.king_endgame_table.values()),
+      ("isolated_pawn", self.isolated_pawn.values()),
+      ("doubled_pawn", self.doubled_pawn.values()),
+      ("backward_pawn", self.backward_pawn.values()),
+      ("connected_pawn", self.connected_pawn.values()),
+      ("passed_pawn", self.passed_pawn.values()),
+      (
+        "passed_pawn_king_proximity",
+        self.passed_pawn_king_proximity.values(),
+      ),
+      ("mobility", self.mobility.values()),
+      ("king_attack_weight", self.king_attack_weight.values()),
+      ("pawn_shield", self.pawn_shield.values()),
+      ("pawn_storm", self.pawn_storm.values()),
+      ("rook_open_file", self.rook_open_file.values()),
+      ("rook_semi_open_file", self.rook_semi_open_file.values()),
+      ("rook_seventh_rank", self.rook_seventh_rank.values()),
+      ("bishop_pair", self.bishop_pair.values()),
+      ("knight_outpost", self.knight_outpost.values()),
+      ("bishop_outpost", self.bishop_outpost.values()),
+      ("trapped_bishop", self.trapped_bishop.values()),
+      ("trapped_rook", self.trapped_rook.values()),
+    ]
+  }
+
+  pub fn named_values_mut(&mut self) -> Vec<(&'static str, &mut [i32])> {
+    vec![
+      ("piece_value", self.piece_value.values_mut()),
+      ("pawn_table", self.pawn_table.values_mut()),
+      ("knight_table", self.knight_table.values_mut()),
+      ("bishop_table", self.bishop_table.values_mut()),
+      ("rook_table", self.rook_table.values_mut()),
+      ("king_opening_table", self.king_opening_table.values_mut()),
+      ("king_endgame_table", self.king_endgame_table.values_mut()),
+      ("isolated_pawn", self.isolated_pawn.values_mut()),
+      ("doubled_pawn", self.doubled_pawn.values_mut()),
+      ("backward_pawn", self.backward_pawn.values_mut()),
+      ("connected_pawn", self.connected_pawn.values_mut()),
+      ("passed_pawn", self.passed_pawn.values_mut()),
+      (
+        "passed_pawn_king_proximity",
+        self.passed_pawn_king_proximity.values_mut(),
+      ),
+      ("mobility", self.mobility.values_mut()),
+      ("king_attack_weight", self.king_attack_weight.values_mut()),
+      ("pawn_shield", self.pawn_shield.values_mut()),
+      ("pawn_storm", self.pawn_storm.values_mut()),
+      ("rook_open_file", self.rook_open_file.values_mut()),
+      ("rook_semi_open_file", self.rook_semi_open_file.values_mut()),
+      ("rook_seventh_rank", self.rook_seventh_rank.values_mut()),
+      ("bishop_pair", self.bishop_pair.values_mut()),
+      ("knight_outpost", self.knight_outpost.values_mut()),
+      ("bishop_outpost", self.bishop_outpost.values_mut()),
+      ("trapped_bishop", self.trapped_bishop.values_mut()),
+      ("trapped_rook", self.trapped_rook.values_mut()),
+    ]
+  }
+
+  /**
+   * All the values of the parameters one after the other, as the tuner sees them.
+   */
+  pub fn vector(&self) -> Vec<i32> {
+    self
+      .named_values()
+      .iter()
+      .flat_map(|(_, values)| values.iter().copied())
+      .collect()
+  }
+
+  pub fn set_vector(&mut self, vector: &[i32]) {
+    let mut index: usize = 0;
+
+    for (_, values) in self.named_values_mut() {
+      for value in values.iter_mut() {
+        *value = vector[index];
+        index += 1;
+      }
+    }
+  }
+
+  /**
+   * The parameters as a Rust expression, to paste in EvaluationParameters::new().
+   */
+  pub fn rust_source(&self) -> String {
+    let mut source: String = String::from("EvaluationParameters {\n");
+
+    for (name, values) in self.named_values() {
+      if values.len() == 1 {
+        source.push_str(&format!("  {}: {},\n", name, values[0]));
+      } else {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        source.push_str(&format!("  {}: [{}],\n", name, values.join(", ")));
+      }
+    }
+    source.push('}');
+
+    source
+  }
+
+  /**
+   * The parameters in the format read by from_file: one parameter for each line,
+   * with its name followed by its values.
+   */
+  pub fn file_text(&self) -> String {
+    let mut text: String = String::new();
+
+    for (name, values) in self.named_values() {
+      let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+      text.push_str(&format!("{} {}\n", name, values.join(" ")));
+    }
+
+    text
+  }
+
+  /**
+   * It reads the parameters written by file_text, the parameters missing
+   * in the file keep their default value.
+   */
+  pub fn from_file(path: &str) -> Result<EvaluationParameters, String> {
+    let text: String =
+      std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+    let mut parameters: EvaluationParameters = EvaluationParameters::new();
+
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+      let mut tokens = line.split_whitespace();
+      let name: &str = tokens.next().unwrap_or("");
+      let line_values: Vec<i32> = tokens
+        .map(|token| token.parse::<i32>())
+        .collect::<Result<Vec<i32>, _>>()
+        .map_err(|error| format!("{}: {}", name, error))?;
+
+      match parameters
+        .named_values_mut()
+        .into_iter()
+        .find(|(parameter_name, _)| *parameter_name == name)
+      {
+        Some((_, values)) if values.len() == line_values.len() => {
+          values.copy_from_slice(&line_values)
+        }
+        Some((_, values)) => {
+          return Err(format!(
+            "{}: {} values expected, {} found",
+            name,
+            values.len(),
+            line_values.len()
+          ))
+        }
+        None => return Err(format!("{}: unknown parameter", name)),
+      }
+    }
+
+    Ok(parameters)
+  }
+}
+
+impl Default for EvaluationParameters {
+  fn default() -> EvaluationParameters {
+    EvaluationParameters::new()
+  }
+}
+
+/**
+ * A single value or an array of values of the parameters, seen as a slice.
+ */
+trait ParameterValues {
+  fn values(&self) -> &[i32];
+  fn values_mut(&mut self) -> &mut [i32];
+}
+
+impl ParameterValues for i32 {
+  fn values(&self) -> &[i32] {
+    std::slice::from_ref(self)
+  }
+
+  fn values_mut(&mut self) -> &mut [i32] {
+    std::slice::from_mut(self)
+  }
+}
+
+impl<const N: usize> ParameterValues for [i32; N] {
+  fn values(&self) -> &[i32] {
+    self
+  }
+
+  fn values_mut(&mut self) -> &mut [i32] {
+    self
+  }
+}
+
+/**
+ * The index of the knights, bishops, rooks and queens of both colors in the arrays of the parameters.
+ */
+pub fn piece_type_index(piece: Pieces) -> usize {
+  (piece as usize - Pieces::Wn as usize) % 6
+}
+
+/**
+ * The piece square tables give a bonus (or a malus) to a piece based on the square where it is.
+ * They are written from the white point of view in 64 squares notation, so A1 is the first
+ * element and H8 the last one (the tables are drawn upside down compared to the board):
+ *
+ * ```text
+ *     A  B  C  D  E  F  G  H
+ * 1  00 01 02 03 04 05 06 07
+ * 2  08 09 10 11 12 13 14 15
+ * ...
+ * 8  56 57 58 59 60 61 62 63
+ * ```
+ *
+ * For a black piece the square is mirrored (see mirror_64) before looking at the table.
+ */
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+   0,   0,   0,   0,   0,   0,   0,   0,
+  10,  10,   0, -10, -10,   0,  10,  10,
+   5,   0,   0,   5,   5,   0,   0,   5,
+   0,   0,  10,  20,  20,  10,   0,   0,
+   5,   5,   5,  10,  10,   5,   5,   5,
+  10,  10,  10,  20,  20,  10,  10,  10,
+  20,  20,  20,  30,  30,  20,  20,  20,
+   0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+   0, -10,   0,   0,   0,   0, -10,   0,
+   0,   0,   0,   5,   5,   0,   0,   0,
+   0,   0,  10,  10,  10,  10,   0,   0,
+   0,   0,  10,  20,  20,  10,   5,   0,
+   5,  10,  15,  20,  20,  15,  10,   5,
+   5,  10,  10,  20,  20,  10,  10,   5,
+   0,   0,   5,  10,  10,   5,   0,   0,
+   0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+   0,   0, -10,   0,   0, -10,   0,   0,
+   0,   0,   0,  10,  10,   0,   0,   0,
+   0,   0,  10,  15,  15,  10,   0,   0,
+   0,  10,  15,  20,  20,  15,  10,   0,
+   0,  10,  15,  20,  20,  15,  10,   0,
+   0,   0,  10,  15,  15,  10,   0,   0,
+   0,   0,   0,  10,  10,   0,   0,   0,
+   0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+   0,   0,   5,  10,  10,   5,   0,   0,
+   0,   0,   5,  10,  10,   5,   0,   0,
+   0,   0,   5,  10,  10,   5,   0,   0,
+   0,   0,   5,  10,  10,   5,   0,   0,
+   0,   0,   5,  10,  10,   5,   0,   0,
+   0,   0,   5,  10,  10,   5,   0,   0,
+  25,  25,  25,  25,  25,  25,  25,  25,
+   0,   0,   5,  10,  10,   5,   0,   0,
+];
+
+/**
+ * In the endgame the king has to go to the center of the board.
+ */
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+  -50, -10,   0,   0,   0,   0, -10, -50,
+  -10,   0,  10,  10,  10,  10,   0, -10,
+    0,  10,  20,  20,  20,  20,  10,   0,
+    0,  10,  20,  40,  40,  20,  10,   0,
+    0,  10,  20,  40,  40,  20,  10,   0,
+    0,  10,  20,  20,  20,  20,  10,   0,
+  -10,   0,  10,  10,  10,  10,   0, -10,
+  -50, -10,   0,   0,   0,   0, -10, -50,
+];
+
+/**
+ * In the opening and in the middlegame the king has to stay safe behind its pawns.
+ */
+#[rustfmt::skip]
+const KING_OPENING_TABLE: [i32; 64] = [
+    0,   5,   5, -10, -10,   0,  10,   5,
+  -30, -30, -30, -30, -30, -30, -30, -30,
+  -50, -50, -50, -50, -50, -50, -50, -50,
+  -70, -70, -70, -70, -70, -70, -70, -70,
+  -70, -70, -70, -70, -70, -70, -70, -70,
+  -70, -70, -70, -70, -70, -70, -70, -70,
+  -70, -70, -70, -70, -70, -70, -70, -70,
+  -70, -70, -70, -70, -70, -70, -70, -70,
+];
@@ -0,0 +1,369 @@
+use crate::bitboards::*;
+use crate::board::*;
+use crate::definitions::*;
+use crate::parameters::*;
+
+/**
+ * In the endgame a passed pawn is stronger when the own king is near to its path and the opponent
+ * king is far from it. The distances are weighted with these values (and then with
+ * passed_pawn_king_proximity of the evaluation parameters).
+ */
+const OWN_KING_DISTANCE: i32 = 2;
+const OPPONENT_KING_DISTANCE: i32 = 5;
+
+const DEFAULT_PAWN_TABLE_MEGABYTES: usize = 2;
+
+/**
+ * The evaluation of a pawn structure: its score (from the white point of view)
+ * and the passed pawns of each side, used for the terms that depend on the other pieces.
+ */
+#[derive(Copy, Clone)]
+pub struct PawnEntry {
+  pawn_key: u64,
+  score: i32,
+  passed_pawns: [u64; 2],
+}
+
+impl PawnEntry {
+  pub fn new() -> PawnEntry {
+    let pawn_key: u64 = 0;
+    let score: i32 = 0;
+    let passed_pawns: [u64; 2] = [0; 2];
+
+    PawnEntry {
+      pawn_key,
+      score,
+      passed_pawns,
+    }
+  }
+
+  pub fn score(&self) -> i32 {
+    self.score
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Pawn_Hash_Table>
+ *
+ * The pawns move rarely, so the same pawn structure is found in most of the searched positions:
+ * its evaluation is stored indexed by the pawn key of the board, and computed only once.
+ */
+pub struct PawnHashTable {
+  entries: Vec<PawnEntry>,
+}
+
+impl PawnHashTable {
+  pub fn new(megabytes: usize) -> PawnHashTable {
+    let entries_number: usize = (megabytes * 1024 * 1024 / std::mem::size_of::<PawnEntry>()).max(1);
+    let entries: Vec<PawnEntry> = vec![PawnEntry::new(); entries_number];
+
+    PawnHashTable { entries }
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.fill(PawnEntry::new());
+  }
+
+  /**
+   * It returns the evaluation of the pawn structure of the board, from the table if it's there.
+   * A board without pawns has key zero, as the empty entries, and it's evaluated as an empty entry.
+   */
+  pub fn probe(&mut self, board: &Board, parameters: &EvaluationParameters) -> PawnEntry {
+    let index: usize = (board.pawn_key() % self.entries.len() as u64) as usize;
+
+    if self.entries[index].pawn_key != board.pawn_key() {
+      self.entries[index] = evaluate_pawn_structure(board, parameters);
+    }
+
+    self.entries[index]
+  }
+}
+
+impl Default for PawnHashTable {
+  fn default() -> PawnHashTable {
+    PawnHashTable::new(DEFAULT_PAWN_TABLE_MEGABYTES)
+  }
+}
+
+/**
+ * The rank of the square seen from the side of the given color.
+ */
+pub fn relative_rank(square_64: i32, color: Colors) -> usize {
+  if color == Colors::White {
+    (square_64 / 8) as usize
+  } else {
+    (7 - square_64 / 8) as usize
+  }
+}
+
+pub fn distance(square_a_64: i32, square_b_64: i32) -> i32 {
+  ((square_a_64 % 8) - (square_b_64 % 8))
+    .abs()
+    .max(((square_a_64 / 8) - (square_b_64 / 8)).abs())
+}
+
+/**
+ * The pawn structure score of one side, without looking at the other pieces.
+ */
+fn evaluate_side_pawns(
+  board: &Board,
+  parameters: &EvaluationParameters,
+  color: Colors,
+  passed_pawns: &mut u64,
+) -> i32 {
+  let definitions: &Definitions = board.definitions();
+  let (pawn, opponent, opponent_pawn, forward): (Pieces, Colors, Pieces, i32) =
+    if color == Colors::White {
+      (Pieces::Wp, Colors::Black, Pieces::Bp, 10)
+    } else {
+      (Pieces::Bp, Colors::White, Pieces::Wp, -10)
+    };
+  let pieces: &[Pieces; BOARD_SQUARE_NUMBER] = board.pieces();
+  let own_pawns: u64 = board.pawns()[color as usize];
+  let opponent_pawns: u64 = board.pawns()[opponent as usize];
+  let mut score: i32 = 0;
+  let mut square_120: i32;
+  let mut square_64: i32;
+  let mut file: usize;
+  let mut rank: usize;
+  let mut stop_square: i32;
+  let mut file_pawns: i32;
+
+  for file_mask in definitions.file_masks() {
+    file_pawns = count_bits(own_pawns & file_mask) as i32;
+    if file_pawns > 1 {
+      score += parameters.doubled_pawn * (file_pawns - 1);
+    }
+  }
+
+  for &square in
+    &board.pieces_list()[pawn as usize][0..board.actual_pieces_number()[pawn as usize] as usize]
+  {
+    square_120 = square as i32;
+    square_64 = definitions.board_120_squares_in_64_squares_notation()[square_120 as usize];
+    file = (square_64 % 8) as usize;
+    rank = relative_rank(square_64, color);
+
+    if own_pawns & definitions.adjacent_files_masks()[file] == 0 {
+      score += parameters.isolated_pawn;
+    } else if own_pawns
+      & definitions.adjacent_files_masks()[file]
+      & !definitions.passed_pawn_masks()[color as usize][square_64 as usize]
+      == 0
+    {
+      // No own pawn can defend it when it advances, and the opponent pawns stop it
+      stop_square = square_120 + forward;
+      if pieces[(stop_square + forward - 1) as usize] == opponent_pawn
+        || pieces[(stop_square + forward + 1) as usize] == opponent_pawn
+      {
+        score += parameters.backward_pawn;
+      }
+    }
+
+    // Defended by an own pawn or side by side with it
+    if pieces[(square_120 - forward - 1) as usize] == pawn
+      || pieces[(square_120 - forward + 1) as usize] == pawn
+      || pieces[(square_120 - 1) as usize] == pawn
+      || pieces[(square_120 + 1) as usize] == pawn
+    {
+      score += parameters.connected_pawn[rank];
+    }
+
+    if opponent_pawns & definitions.passed_pawn_masks()[color as usize][square_64 as usize] == 0 {
+      score += parameters.passed_pawn[rank];
+      *passed_pawns |= 1u64 << square_64;
+    }
+  }
+
+  score
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Pawn_Structure>
+ *
+ * It evaluates isolated, doubled, backward, connected and passed pawns of both sides.
+ */
+pub fn evaluate_pawn_structure(board: &Board, parameters: &EvaluationParameters) -> PawnEntry {
+  let mut entry: PawnEntry = PawnEntry::new();
+  let mut passed_pawns: [u64; 2] = [0; 2];
+
+  entry.pawn_key = board.pawn_key();
+  entry.score = evaluate_side_pawns(
+    board,
+    parameters,
+    Colors::White,
+    &mut passed_pawns[Colors::White as usize],
+  ) - evaluate_side_pawns(
+    board,
+    parameters,
+    Colors::Black,
+    &mut passed_pawns[Colors::Black as usize],
+  );
+  entry.passed_pawns = passed_pawns;
+
+  entry
+}
+
+/**
+ * The king proximity bonus of the passed pawns of the given color: the distances of the two kings
+ * from the square in front of each passed pawn. It's used only in the endgame.
+ */
+pub fn passed_pawns_king_proximity(
+  board: &Board,
+  parameters: &EvaluationParameters,
+  entry: &PawnEntry,
+  color: Colors,
+) -> i32 {
+  let definitions: &Definitions = board.definitions();
+  let (opponent, forward): (Colors, i32) = if color == Colors::White {
+    (Colors::Black, 8)
+  } else {
+    (Colors::White, -8)
+  };
+  let own_king: i32 = definitions.board_120_squares_in_64_squares_notation()
+    [board.king_square()[color as usize] as usize];
+  let opponent_king: i32 = definitions.board_120_squares_in_64_squares_notation()
+    [board.king_square()[opponent as usize] as usize];
+  let mut passed_pawns: u64 = entry.passed_pawns[color as usize];
+  let mut score: i32 = 0;
+  let mut square_64: i32;
+  let mut stop_square: i32;
+
+  while passed_pawns != 0 {
+    square_64 = pop_first_bit(&mut passed_pawns);
+    stop_square = square_64 + forward;
+    score += parameters.passed_pawn_king_proximity[relative_rank(square_64, color)]
+      * (OPPONENT_KING_DISTANCE * distance(opponent_king, stop_square)
+        - OWN_KING_DISTANCE * distance(own_king, stop_square));
+  }
+
+  score
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /**
+   * Parameters where only the pawn structure term set by the closure counts.
+   */
+  fn only(set: impl Fn(&mut EvaluationParameters)) -> EvaluationParameters {
+    let mut parameters: EvaluationParameters = EvaluationParameters::new();
+
+    parameters.isolated_pawn = 0;
+    parameters.doubled_pawn = 0;
+    parameters.backward_pawn = 0;
+    parameters.connected_pawn = [0; 8];
+    parameters.passed_pawn = [0; 8];
+    set(&mut parameters);
+    parameters
+  }
+
+  fn structure(fen: &str, parameters: &EvaluationParameters) -> PawnEntry {
+    evaluate_pawn_structure(&Board::from_fen(fen).unwrap(), parameters)
+  }
+
+  #[test]
+  fn doubled_pawns() {
+    let parameters: EvaluationParameters = only(|parameters| parameters.doubled_pawn = 1);
+
+    assert_eq!(
+      structure("4k3/8/8/8/8/3P4/3P4/4K3 w - - 0 1", &parameters).score,
+      1
+    );
+    assert_eq!(
+      structure("4k3/8/8/8/3P4/3P4/3P4/4K3 w - - 0 1", &parameters).score,
+      2
+    );
+    assert_eq!(
+      structure("4k3/3p4/3p4/8/8/3P4/4P3/4K3 w - - 0 1", &parameters).score,
+      -1
+    );
+  }
+
+  #[test]
+  fn isolated_pawns() {
+    let parameters: EvaluationParameters = only(|parameters| parameters.isolated_pawn = 1);
+
+    // The white a and c pawns have no neighbour, the black ones defend each other
+    assert_eq!(
+      structure("4k3/pp6/8/8/8/8/P1P5/4K3 w - - 0 1", &parameters).score,
+      2
+    );
+    assert_eq!(
+      structure("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1", &parameters).score,
+      0
+    );
+    assert_eq!(
+      structure("4k3/p6p/8/8/8/8/PP6/4K3 w - - 0 1", &parameters).score,
+      -2
+    );
+  }
+
+  #[test]
+  fn passed_pawns() {
+    let parameters: EvaluationParameters =
+      only(|parameters| parameters.passed_pawn = [0, 1, 2, 3, 4, 5, 6, 7]);
+    // The black d pawn stops the e pawn and the other way around, the h pawn is free
+    let entry: PawnEntry = structure("4k3/3p4/8/4P3/7P/8/8/4K3 w - - 0 1", &parameters);
+
+    assert_eq!(entry.score, 3);
+    assert_eq!(entry.passed_pawns, [1u64 << 31, 0]);
+
+    let entry: PawnEntry = structure("4k3/8/8/8/1p6/8/P7/4K3 b - - 0 1", &parameters);
+
+    assert_eq!(entry.score, 0);
+    assert_eq!(entry.passed_pawns, [0, 0]);
+
+    let entry: PawnEntry = structure("4k3/8/8/8/2p5/8/P7/4K3 b - - 0 1", &parameters);
+
+    assert_eq!(entry.score, 1 - 4);
+    assert_eq!(entry.passed_pawns, [1u64 << 8, 1u64 << 26]);
+  }
+
+  #[test]
+  fn table_hits_and_misses_give_the_evaluation() {
+    let parameters: EvaluationParameters = EvaluationParameters::new();
+    // A single entry, so every new pawn structure replaces the previous one
+    let mut table: PawnHashTable = PawnHashTable::new(0);
+    let fens: [&str; 3] = [
+      "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+      "4k3/3p4/8/4P3/7P/8/8/4K3 w - - 0 1",
+      "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+    ];
+
+    for fen in fens.iter().chain(fens.iter()).chain(fens.iter().rev()) {
+      let board: Board = Board::from_fen(fen).unwrap();
+      let expected: PawnEntry = evaluate_pawn_structure(&board, &parameters);
+
+      for _ in 0..2 {
+        let entry: PawnEntry = table.probe(&board, &parameters);
+
+        assert_eq!(entry.pawn_key, board.pawn_key(), "{}", fen);
+        assert_eq!(entry.score, expected.score, "{}", fen);
+        assert_eq!(entry.passed_pawns, expected.passed_pawns, "{}", fen);
+      }
+    }
+  }
+
+  #[test]
+  fn table_keeps_the_entries_of_other_structures() {
+    let parameters: EvaluationParameters = EvaluationParameters::new();
+    let mut table: PawnHashTable = PawnHashTable::default();
+    let mut board: Board = Board::from_fen("4k3/3p4/8/4P3/7P/8/8/4K3 w - - 0 1").unwrap();
+    let first: PawnEntry = table.probe(&board, &parameters);
+
+    // The pawn structure of the entry is evaluated again only on a miss
+    board = Board::from_fen("4k3/3p4/8/4P3/8/7P/8/4K3 w - - 0 1").unwrap();
+    table.probe(&board, &parameters);
+    board = Board::from_fen("4k3/3p4/8/4P3/7P/8/8/4K3 w - - 0 1").unwrap();
+    let index: usize = (board.pawn_key() % table.entries.len() as u64) as usize;
+
+    table.entries[index].score += 1;
+    assert_eq!(table.probe(&board, &parameters).score, first.score + 1);
+    table.clear();
+    assert_eq!(table.probe(&board, &parameters).score, first.score);
+  }
+}
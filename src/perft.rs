@@ -0,0 +1,62 @@
+use crate::board::*;
+use crate::definitions::*;
+use crate::movegen::*;
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Perft>
+ * <https://www.chessprogramming.org/Perft_Results>
+ *
+ * It counts the leaf nodes of the legal moves tree at the given depth, the results can be
+ * compared with the known ones to find bugs in the move generator and in make/take move.
+ */
+pub fn perft(depth: i32, board: &mut Board) -> u64 {
+  let mut list: MoveList = MoveList::new();
+  let mut leaf_nodes: u64 = 0;
+
+  if depth == 0 {
+    return 1;
+  }
+
+  generate_all_moves(board, &mut list);
+  for &mov_e in list.moves() {
+    if !board.make_move(mov_e) {
+      continue;
+    }
+    leaf_nodes += perft(depth - 1, board);
+    board.take_move();
+  }
+
+  leaf_nodes
+}
+
+/**
+ * As perft, but it prints the leaf nodes found after each move of the root position (divide).
+ */
+pub fn perft_test(depth: i32, board: &mut Board) -> u64 {
+  let mut list: MoveList = MoveList::new();
+  let mut leaf_nodes: u64 = 0;
+  let mut move_nodes: u64;
+  let mut move_number: i32 = 0;
+
+  println!("\nStarting Test To Depth:{}", depth);
+  generate_all_moves(board, &mut list);
+  for &mov_e in list.moves() {
+    if !board.make_move(mov_e) {
+      continue;
+    }
+    move_number += 1;
+    move_nodes = perft(depth - 1, board);
+    leaf_nodes += move_nodes;
+    board.take_move();
+    println!(
+      "move {} : {} : {}",
+      move_number,
+      board.move_to_algebraic(mov_e),
+      move_nodes
+    );
+  }
+
+  println!("\nTest Complete : {} nodes visited\n", leaf_nodes);
+  leaf_nodes
+}
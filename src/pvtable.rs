@@ -0,0 +1,241 @@
+use crate::board::*;
+use crate::definitions::*;
+use crate::movegen::*;
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Transposition_Table>
+ *
+ * What the score stored in an entry means:
+ * - Alpha: the search did not find a move better than alpha, the score is an upper bound.
+ * - Beta: the search found a move that caused a beta cutoff, the score is a lower bound.
+ * - Exact: the score is the real score of the position.
+ */
+#[derive(Copy, Clone, PartialEq)]
+pub enum HashFlags {
+  None,
+  Alpha,
+  Beta,
+  Exact,
+}
+
+/**
+ * The move is stored in its 16 bits form (see Move::to_compact), so that an entry
+ * takes 24 bytes instead of 32 and more of them fit in the same megabytes. A move that
+ * can not be decoded on the probed board (after a key collision) is read as NO_MOVE.
+ */
+#[derive(Copy, Clone)]
+pub struct HashEntry {
+  position_key: u64,
+  mov_e: u16,
+  score: i32,
+  depth: i32,
+  flags: HashFlags,
+}
+
+impl HashEntry {
+  pub fn new() -> HashEntry {
+    let position_key: u64 = 0;
+    let mov_e: u16 = Move::NO_MOVE.to_compact();
+    let score: i32 = 0;
+    let depth: i32 = 0;
+    let flags: HashFlags = HashFlags::None;
+
+    HashEntry {
+      position_key,
+      mov_e,
+      score,
+      depth,
+      flags,
+    }
+  }
+}
+
+/**
+ * The hash table stores for each searched position (indexed by its position key) the best move
+ * found, so that the search can try it first and extract the principal variation, and the score
+ * with the depth of the search, so that the same position is not searched twice.
+ */
+pub struct HashTable {
+  entries: Vec<HashEntry>,
+  /**
+   * Statistics: how many entries have been written on an empty slot or over an old position,
+   * how many probes found the position and how many of them produced a cutoff.
+   */
+  new_write: u64,
+  over_write: u64,
+  hit: u64,
+  cut: u64,
+}
+
+impl HashTable {
+  /**
+   * It allocates as many entries as they fit in the given megabytes.
+   */
+  pub fn new(megabytes: usize) -> HashTable {
+    let entries_number: usize = (megabytes * 1024 * 1024 / std::mem::size_of::<HashEntry>()).max(1);
+    let entries: Vec<HashEntry> = vec![HashEntry::new(); entries_number];
+
+    HashTable {
+      entries,
+      new_write: 0,
+      over_write: 0,
+      hit: 0,
+      cut: 0,
+    }
+  }
+
+  pub fn entries_number(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn new_write(&self) -> u64 {
+    self.new_write
+  }
+
+  pub fn over_write(&self) -> u64 {
+    self.over_write
+  }
+
+  pub fn hit(&self) -> u64 {
+    self.hit
+  }
+
+  pub fn cut(&self) -> u64 {
+    self.cut
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.fill(HashEntry::new());
+    self.clear_statistics();
+  }
+
+  pub fn clear_statistics(&mut self) {
+    self.new_write = 0;
+    self.over_write = 0;
+    self.hit = 0;
+    self.cut = 0;
+  }
+
+  fn index(&self, board: &Board) -> usize {
+    (board.position_key() % self.entries.len() as u64) as usize
+  }
+
+  /**
+   * The mate scores are stored as distance from the position (and not from the root), so that
+   * they are still correct when the position is found at another depth of the search.
+   */
+  pub fn store_hash_entry(
+    &mut self,
+    board: &Board,
+    mov_e: Move,
+    mut score: i32,
+    flags: HashFlags,
+    depth: i32,
+  ) {
+    let index: usize = self.index(board);
+
+    if self.entries[index].position_key == 0 {
+      self.new_write += 1;
+    } else {
+      self.over_write += 1;
+    }
+
+    if score > IS_MATE {
+      score += board.actual_half_moves();
+    } else if score < -IS_MATE {
+      score -= board.actual_half_moves();
+    }
+
+    self.entries[index] = HashEntry {
+      position_key: board.position_key(),
+      mov_e: mov_e.to_compact(),
+      score,
+      depth,
+      flags,
+    };
+  }
+
+  /**
+   * It returns the best move stored for the position (NO_MOVE if there is none) and, if the stored
+   * search was deep enough and its score is useful with the current alpha and beta, the score.
+   */
+  pub fn probe_hash_entry(
+    &mut self,
+    board: &Board,
+    alpha: i32,
+    beta: i32,
+    depth: i32,
+  ) -> (Move, Option<i32>) {
+    let entry: HashEntry = self.entries[self.index(board)];
+    let mut score: i32;
+
+    if entry.position_key != board.position_key() {
+      return (Move::NO_MOVE, None);
+    }
+
+    self.hit += 1;
+    let mov_e: Move = Move::from_compact(entry.mov_e, board.pieces()).unwrap_or(Move::NO_MOVE);
+    if entry.depth < depth {
+      return (mov_e, None);
+    }
+
+    score = entry.score;
+    if score > IS_MATE {
+      score -= board.actual_half_moves();
+    } else if score < -IS_MATE {
+      score += board.actual_half_moves();
+    }
+
+    match entry.flags {
+      HashFlags::Alpha if score <= alpha => {
+        self.cut += 1;
+        (mov_e, Some(alpha))
+      }
+      HashFlags::Beta if score >= beta => {
+        self.cut += 1;
+        (mov_e, Some(beta))
+      }
+      HashFlags::Exact => {
+        self.cut += 1;
+        (mov_e, Some(score))
+      }
+      _ => (mov_e, None),
+    }
+  }
+
+  pub fn probe_pv_move(&self, board: &Board) -> Move {
+    let entry: HashEntry = self.entries[self.index(board)];
+
+    if entry.position_key == board.position_key() {
+      Move::from_compact(entry.mov_e, board.pieces()).unwrap_or(Move::NO_MOVE)
+    } else {
+      Move::NO_MOVE
+    }
+  }
+
+  /**
+   * The principal variation is the sequence of best moves found by the search, it's rebuilt
+   * making the stored best move of each position (as long as it's legal) until the depth is reached.
+   */
+  pub fn get_pv_line(&self, depth: i32, board: &mut Board) -> Vec<Move> {
+    let mut pv_line: Vec<Move> = Vec::new();
+    let mut mov_e: Move = self.probe_pv_move(board);
+
+    while mov_e != Move::NO_MOVE && (pv_line.len() as i32) < depth {
+      if move_exists(board, mov_e) {
+        board.make_move(mov_e);
+        pv_line.push(mov_e);
+      } else {
+        break;
+      }
+      mov_e = self.probe_pv_move(board);
+    }
+
+    for _ in 0..pv_line.len() {
+      board.take_move();
+    }
+
+    pv_line
+  }
+}
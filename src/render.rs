@@ -0,0 +1,167 @@
+use std::fmt;
+
+use crate::board::*;
+use crate::definitions::*;
+
+// (index)pieces as in PIECE_CHARACTERS, the empty square is a middle dot
+const UNICODE_PIECES: [char; 13] = [
+  '·', '♙', '♘', '♗', '♖', '♕', '♔', '♟', '♞', '♝', '♜', '♛', '♚',
+];
+const ANSI_LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const ANSI_DARK_SQUARE: &str = "\x1b[48;5;137m";
+const ANSI_WHITE_PIECE: &str = "\x1b[1;97m";
+const ANSI_BLACK_PIECE: &str = "\x1b[1;30m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/**
+ * How the pieces are drawn: the FEN letters, the Unicode chess glyphs, or the glyphs on
+ * squares coloured with ANSI escape codes (for the terminals that support them).
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BoardStyle {
+  Ascii,
+  Unicode,
+  Ansi,
+}
+
+/**
+ * The board is seen from the white side unless flipped, the coordinates are the rank
+ * numbers on the left and the file letters below.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct RenderOptions {
+  pub style: BoardStyle,
+  pub flipped: bool,
+  pub coordinates: bool,
+}
+
+impl RenderOptions {
+  pub fn new() -> RenderOptions {
+    RenderOptions {
+      style: BoardStyle::Ascii,
+      flipped: false,
+      coordinates: true,
+    }
+  }
+}
+
+impl Default for RenderOptions {
+  fn default() -> RenderOptions {
+    RenderOptions::new()
+  }
+}
+
+/**
+ * The rows of the board from the top one, each cell given by cell(square): the padded
+ * cells already hold their spaces (so that a background colour covers them), the others
+ * are separated by one space.
+ */
+pub(crate) fn render_grid(
+  flipped: bool,
+  coordinates: bool,
+  padded: bool,
+  cell: impl Fn(Squares) -> String,
+) -> String {
+  let mut ranks: Vec<ChessboardRanks> = ChessboardRanks::iter().rev().collect();
+  let mut files: Vec<ChessboardFiles> = ChessboardFiles::iter().collect();
+  let separator: &str = if padded { "" } else { " " };
+  let mut output: String = String::new();
+
+  if flipped {
+    ranks.reverse();
+    files.reverse();
+  }
+
+  for rank in &ranks {
+    if coordinates {
+      output.push_str(&format!("{}  ", RANK_CHARACTERS[*rank as usize]));
+    }
+    output.push_str(
+      &files
+        .iter()
+        .map(|file| cell(Squares::from_file_rank(*file, *rank)))
+        .collect::<Vec<String>>()
+        .join(separator),
+    );
+    output.push('\n');
+  }
+
+  if coordinates {
+    output.push_str("\n   ");
+    output.push_str(
+      files
+        .iter()
+        .map(|file| {
+          if padded {
+            format!(" {} ", FILE_CHARACTERS[*file as usize])
+          } else {
+            FILE_CHARACTERS[*file as usize].to_string()
+          }
+        })
+        .collect::<Vec<String>>()
+        .join(separator)
+        .trim_end(),
+    );
+    output.push('\n');
+  }
+
+  output
+}
+
+fn piece_cell(style: BoardStyle, square: Squares, piece: Pieces) -> String {
+  match style {
+    BoardStyle::Ascii => piece.to_string(),
+    BoardStyle::Unicode => UNICODE_PIECES[piece as usize].to_string(),
+    BoardStyle::Ansi => {
+      let background: &str = if (square.file() as i32 + square.rank() as i32) % 2 == 1 {
+        ANSI_LIGHT_SQUARE
+      } else {
+        ANSI_DARK_SQUARE
+      };
+      // The solid glyphs in both colours read better on the coloured squares
+      let (foreground, glyph): (&str, char) = match piece {
+        Pieces::Empty => ("", ' '),
+        piece if PIECE_COLOR[piece as usize] == Colors::White => {
+          (ANSI_WHITE_PIECE, UNICODE_PIECES[piece as usize + 6])
+        }
+        piece => (ANSI_BLACK_PIECE, UNICODE_PIECES[piece as usize]),
+      };
+      format!("{}{} {} {}", background, foreground, glyph, ANSI_RESET)
+    }
+  }
+}
+
+impl Board {
+  /**
+   * The board drawn with the given options, followed by the side to move, the en passant
+   * square, the castling rights, the FEN and the position key.
+   */
+  pub fn render(&self, options: RenderOptions) -> String {
+    let mut output: String = render_grid(
+      options.flipped,
+      options.coordinates,
+      options.style == BoardStyle::Ansi,
+      |square| piece_cell(options.style, square, self.piece_at(square)),
+    );
+
+    output.push_str(&format!(
+      "\nside: {}\nen passant: {}\ncastle: {}\nfen: {}\nkey: {:016X}\n",
+      SIDE_CHARACTERS[self.side() as usize],
+      match self.en_passant_square() {
+        Squares::NoSquare => "-".to_string(),
+        square => square.to_string(),
+      },
+      self.castling_rights(),
+      self.to_fen(),
+      self.position_key()
+    ));
+
+    output
+  }
+}
+
+impl fmt::Display for Board {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    write!(formatter, "{}", self.render(RenderOptions::new()))
+  }
+}
@@ -0,0 +1,1226 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::rngs::ThreadRng;
+use rand::Rng;
+
+use crate::attack::*;
+use crate::board::*;
+use crate::definitions::*;
+use crate::evaluate::*;
+use crate::movegen::*;
+use crate::movepicker::*;
+use crate::parameters::*;
+use crate::pawns::*;
+use crate::pvtable::*;
+use crate::syzygy::*;
+
+/**
+ * Every how many nodes the search checks if the time is over or a stop has been requested.
+ */
+const CHECK_UP_NODES: u64 = 2047;
+/**
+ * How often (in milliseconds) a ponder search that has already ended checks for the ponderhit.
+ */
+const PONDER_WAIT: u64 = 1;
+
+/**
+ * Margins (by depth) under alpha for which the quiet moves are not searched anymore (futility pruning),
+ * and over beta for which the node is not searched at all (reverse futility pruning).
+ */
+const FUTILITY_MARGIN: [i32; 4] = [0, 200, 300, 500];
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+const REVERSE_FUTILITY_DEPTH: i32 = 6;
+/**
+ * Margin (by depth) under alpha for which the node is verified with the quiescence search only.
+ */
+const RAZORING_MARGIN: [i32; 4] = [0, 300, 400, 600];
+/**
+ * The first aspiration window around the score of the previous iteration, it's doubled at every fail.
+ */
+const ASPIRATION_WINDOW: i32 = 25;
+const ASPIRATION_DEPTH: i32 = 4;
+/**
+ * The maximum number of best lines searched in the MultiPV mode.
+ */
+pub const MAX_MULTI_PV: usize = 256;
+
+/**
+ * A line found by the search: its first move, its score (from the point of view
+ * of the side to move) and the principal variation.
+ */
+pub type SearchLine = (Move, i32, Vec<Move>);
+
+/**
+ * The skill level of the full strength, the lower levels search less deep
+ * and choose among SKILL_LINES best lines with a random error.
+ */
+pub const MAX_SKILL_LEVEL: i32 = 20;
+const SKILL_LINES: usize = 4;
+
+/**
+ * Every pruning, reduction and extension of the search can be disabled (as UCI option),
+ * so that their contribution to the engine strength can be measured one by one.
+ */
+#[derive(Copy, Clone)]
+pub struct SearchOptions {
+  pub null_move_pruning: bool,
+  pub late_move_reductions: bool,
+  pub principal_variation_search: bool,
+  pub aspiration_windows: bool,
+  pub futility_pruning: bool,
+  pub reverse_futility_pruning: bool,
+  pub razoring: bool,
+  pub check_extensions: bool,
+  pub internal_iterative_reductions: bool,
+}
+
+impl SearchOptions {
+  pub fn new() -> SearchOptions {
+    SearchOptions {
+      null_move_pruning: true,
+      late_move_reductions: true,
+      principal_variation_search: true,
+      aspiration_windows: true,
+      futility_pruning: true,
+      reverse_futility_pruning: true,
+      razoring: true,
+      check_extensions: true,
+      internal_iterative_reductions: true,
+    }
+  }
+}
+
+impl Default for SearchOptions {
+  fn default() -> SearchOptions {
+    SearchOptions::new()
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Late_Move_Reductions>
+ *
+ * The reduction of a quiet move grows with the depth and with the number of moves
+ * already searched, the later a move comes in the ordering the less likely it's good.
+ */
+fn init_late_move_reductions() -> [[i32; 64]; MAX_DEPTH] {
+  let mut reductions: [[i32; 64]; MAX_DEPTH] = [[0; 64]; MAX_DEPTH];
+
+  for (depth, depth_reductions) in reductions.iter_mut().enumerate().skip(1) {
+    for (moves_number, reduction) in depth_reductions.iter_mut().enumerate().skip(1) {
+      *reduction = (0.75 + (depth as f64).ln() * (moves_number as f64).ln() / 2.25) as i32;
+    }
+  }
+
+  reductions
+}
+
+/**
+ * The search limits (depth and time), the search options and the statistics of the current search.
+ */
+pub struct SearchInfo {
+  start_time: Instant,
+  /**
+   * When the search has to stop, if the time is set.
+   */
+  stop_time: Instant,
+  /**
+   * The time given to the search, kept to move the stop time at the ponderhit.
+   */
+  time_limit: Duration,
+  depth: i32,
+  time_set: bool,
+  /**
+   * The search stops after this number of nodes, if it's set.
+   */
+  node_limit: Option<u64>,
+  /**
+   * The search stops when it finds a mate in this number of moves (or less), if it's set.
+   */
+  mate_limit: Option<i32>,
+  /**
+   * The moves of "go searchmoves", the only ones searched at the root if it's not empty.
+   */
+  search_moves: Vec<Move>,
+  nodes: u64,
+  /**
+   * The last depth searched to the end, zero if the search stopped before the first one.
+   */
+  completed_depth: i32,
+  /**
+   * It becomes true when the time is over or a stop is requested, the search result
+   * of the current depth can't be used anymore.
+   */
+  stopped: bool,
+  /**
+   * Set from outside the search (ex. by the thread reading the GUI commands) to stop it.
+   */
+  stop_signal: Arc<AtomicBool>,
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Pondering>
+   *
+   * The search ponders (it searches the expected reply of the opponent on its time) until
+   * the ponder_signal is cleared by a ponderhit: only then the time limit starts.
+   */
+  pondering: bool,
+  ponder_signal: Arc<AtomicBool>,
+  /**
+   * The expected reply to the best move (the second move of the PV), NO_MOVE if it's unknown.
+   */
+  ponder_move: Move,
+  /**
+   * How many times a beta cutoff happened and how many times it happened with the first move tried,
+   * their ratio tells how good the move ordering is.
+   */
+  fail_high: f32,
+  fail_high_first: f32,
+  heuristics: SearchHeuristics,
+  parameters: EvaluationParameters,
+  pawn_table: PawnHashTable,
+  options: SearchOptions,
+  late_move_reductions: [[i32; 64]; MAX_DEPTH],
+  tablebases: Option<Arc<Tablebases>>,
+  /**
+   * The positions with up to this number of pieces are probed in the WDL tables during the search,
+   * zero if they are not probed.
+   */
+  tablebase_pieces: usize,
+  tablebase_hits: u64,
+  /**
+   * The moves searched at the root, all of them if it's empty.
+   */
+  root_moves: Vec<Move>,
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Principal_Variation#MultiPV>
+   *
+   * The number of best lines searched at each depth (MultiPV): every line is searched
+   * excluding at the root the first moves of the lines already found.
+   */
+  multi_pv: usize,
+  excluded_moves: Vec<Move>,
+  /**
+   * References:
+   * <https://www.chessprogramming.org/Playing_Strength>
+   *
+   * The strength of the engine, from 0 to MAX_SKILL_LEVEL (no limit).
+   */
+  skill_level: i32,
+}
+
+impl SearchInfo {
+  pub fn new() -> SearchInfo {
+    let start_time: Instant = Instant::now();
+    let stop_time: Instant = start_time;
+    let time_limit: Duration = Duration::ZERO;
+    let depth: i32 = MAX_DEPTH as i32;
+    let time_set: bool = false;
+    let node_limit: Option<u64> = None;
+    let mate_limit: Option<i32> = None;
+    let search_moves: Vec<Move> = Vec::new();
+    let nodes: u64 = 0;
+    let completed_depth: i32 = 0;
+    let stopped: bool = false;
+    let stop_signal: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let pondering: bool = false;
+    let ponder_signal: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let ponder_move: Move = Move::NO_MOVE;
+    let fail_high: f32 = 0.0;
+    let fail_high_first: f32 = 0.0;
+    let heuristics: SearchHeuristics = SearchHeuristics::new();
+    let parameters: EvaluationParameters = EvaluationParameters::new();
+    let pawn_table: PawnHashTable = PawnHashTable::default();
+    let options: SearchOptions = SearchOptions::new();
+    let late_move_reductions: [[i32; 64]; MAX_DEPTH] = init_late_move_reductions();
+    let tablebases: Option<Arc<Tablebases>> = None;
+    let tablebase_pieces: usize = 0;
+    let tablebase_hits: u64 = 0;
+    let root_moves: Vec<Move> = Vec::new();
+    let multi_pv: usize = 1;
+    let excluded_moves: Vec<Move> = Vec::new();
+    let skill_level: i32 = MAX_SKILL_LEVEL;
+
+    SearchInfo {
+      start_time,
+      stop_time,
+      time_limit,
+      depth,
+      time_set,
+      node_limit,
+      mate_limit,
+      search_moves,
+      nodes,
+      completed_depth,
+      stopped,
+      stop_signal,
+      pondering,
+      ponder_signal,
+      ponder_move,
+      fail_high,
+      fail_high_first,
+      heuristics,
+      parameters,
+      pawn_table,
+      options,
+      late_move_reductions,
+      tablebases,
+      tablebase_pieces,
+      tablebase_hits,
+      root_moves,
+      multi_pv,
+      excluded_moves,
+      skill_level,
+    }
+  }
+
+  pub fn depth(&self) -> i32 {
+    self.depth
+  }
+
+  pub fn set_depth(&mut self, depth: i32) {
+    self.depth = depth.clamp(1, MAX_DEPTH as i32);
+  }
+
+  /**
+   * The search will stop after the given time from its start.
+   */
+  pub fn set_time_limit(&mut self, time: Duration) {
+    self.start_time = Instant::now();
+    self.stop_time = self.start_time + time;
+    self.time_limit = time;
+    self.time_set = true;
+  }
+
+  /**
+   * The search ponders: the time limit starts at the ponderhit, before it only a stop ends the search.
+   * The ponder_signal is set by the input reader, in the order of the "go ponder" and "ponderhit" commands.
+   */
+  pub fn set_pondering(&mut self) {
+    self.pondering = true;
+  }
+
+  /**
+   * The search will stop after the given number of nodes.
+   */
+  pub fn set_node_limit(&mut self, nodes: u64) {
+    self.node_limit = Some(nodes);
+  }
+
+  /**
+   * The search will stop as soon as it finds a mate in the given number of moves.
+   */
+  pub fn set_mate_limit(&mut self, moves: i32) {
+    self.mate_limit = Some(moves);
+  }
+
+  /**
+   * Only the given moves will be searched at the root.
+   */
+  pub fn set_search_moves(&mut self, moves: Vec<Move>) {
+    self.search_moves = moves;
+  }
+
+  /**
+   * No depth, time, nodes or mate limit and all the root moves: the search goes on until it's stopped.
+   */
+  pub fn clear_limits(&mut self) {
+    self.start_time = Instant::now();
+    self.depth = MAX_DEPTH as i32;
+    self.time_set = false;
+    self.node_limit = None;
+    self.mate_limit = None;
+    self.search_moves.clear();
+    self.pondering = false;
+  }
+
+  pub fn nodes(&self) -> u64 {
+    self.nodes
+  }
+
+  pub fn completed_depth(&self) -> i32 {
+    self.completed_depth
+  }
+
+  pub fn stopped(&self) -> bool {
+    self.stopped
+  }
+
+  pub fn stop_signal(&self) -> Arc<AtomicBool> {
+    Arc::clone(&self.stop_signal)
+  }
+
+  pub fn ponder_signal(&self) -> Arc<AtomicBool> {
+    Arc::clone(&self.ponder_signal)
+  }
+
+  pub fn ponder_move(&self) -> Move {
+    self.ponder_move
+  }
+
+  /**
+   * The pawn structures evaluated in a game are still useful in the next one,
+   * but not after a change of the evaluation.
+   */
+  pub fn clear_pawn_table(&mut self) {
+    self.pawn_table.clear();
+  }
+
+  pub fn parameters(&self) -> &EvaluationParameters {
+    &self.parameters
+  }
+
+  /**
+   * The pawn table is cleared, because its entries were evaluated with the old parameters.
+   */
+  pub fn set_parameters(&mut self, parameters: EvaluationParameters) {
+    self.parameters = parameters;
+    self.pawn_table.clear();
+  }
+
+  pub fn options(&self) -> &SearchOptions {
+    &self.options
+  }
+
+  pub fn options_mut(&mut self) -> &mut SearchOptions {
+    &mut self.options
+  }
+
+  pub fn tablebases(&self) -> Option<&Tablebases> {
+    self.tablebases.as_deref()
+  }
+
+  pub fn set_tablebases(&mut self, tablebases: Option<Arc<Tablebases>>) {
+    self.tablebases = tablebases;
+  }
+
+  pub fn tablebase_hits(&self) -> u64 {
+    self.tablebase_hits
+  }
+
+  pub fn root_moves(&self) -> &[Move] {
+    &self.root_moves
+  }
+
+  pub fn multi_pv(&self) -> usize {
+    self.multi_pv
+  }
+
+  pub fn set_multi_pv(&mut self, lines: usize) {
+    self.multi_pv = lines.clamp(1, MAX_MULTI_PV);
+  }
+
+  pub fn skill_level(&self) -> i32 {
+    self.skill_level
+  }
+
+  pub fn set_skill_level(&mut self, skill_level: i32) {
+    self.skill_level = skill_level.clamp(0, MAX_SKILL_LEVEL);
+  }
+
+  fn late_move_reduction(&self, depth: i32, moves_number: i32) -> i32 {
+    self.late_move_reductions[(depth as usize).min(MAX_DEPTH - 1)][(moves_number as usize).min(63)]
+  }
+}
+
+impl Default for SearchInfo {
+  fn default() -> SearchInfo {
+    SearchInfo::new()
+  }
+}
+
+fn check_up(info: &mut SearchInfo) {
+  // At the ponderhit the search goes on as a normal one, with the time counted from now
+  if info.pondering && !info.ponder_signal.load(Ordering::Relaxed) {
+    info.pondering = false;
+    info.stop_time = Instant::now() + info.time_limit;
+  }
+  if (info.time_set && !info.pondering && Instant::now() > info.stop_time)
+    || info.stop_signal.load(Ordering::Relaxed)
+  {
+    info.stopped = true;
+  }
+}
+
+/**
+ * It counts a new node, unless the search has to stop: the node limit is checked at every
+ * node, so that the search never goes past it, the time and the signals every
+ * CHECK_UP_NODES nodes.
+ */
+fn count_node(info: &mut SearchInfo) -> bool {
+  if info
+    .node_limit
+    .is_some_and(|node_limit| info.nodes >= node_limit)
+  {
+    info.stopped = true;
+  }
+  if !info.stopped && (info.nodes & CHECK_UP_NODES) == 0 {
+    check_up(info);
+  }
+  if info.stopped {
+    return false;
+  }
+  info.nodes += 1;
+  true
+}
+
+/**
+ * A position is repeated if it was already on the board after the last capture or pawn move
+ * (before them it's impossible to find the same position).
+ */
+pub fn is_repetition(board: &Board) -> bool {
+  let history: &[Undo] = board.history();
+  let first_index: usize = history
+    .len()
+    .saturating_sub(board.fifty_full_moves() as usize);
+
+  history[first_index..]
+    .iter()
+    .any(|undo| undo.position_key() == board.position_key())
+}
+
+/**
+ * Neither side can mate: no pawns, no rooks and queens (the kings are the only major
+ * pieces left), at most one minor piece each.
+ */
+pub fn insufficient_material(board: &Board) -> bool {
+  board.actual_pieces_number()[Pieces::Wp as usize] == 0
+    && board.actual_pieces_number()[Pieces::Bp as usize] == 0
+    && board.major_pieces_number() == [1, 1]
+    && board.minor_pieces_number()[Colors::White as usize] <= 1
+    && board.minor_pieces_number()[Colors::Black as usize] <= 1
+}
+
+pub fn is_in_check(board: &Board) -> bool {
+  let side: Colors = board.side();
+  let opponent: Colors = if side == Colors::White {
+    Colors::Black
+  } else {
+    Colors::White
+  };
+
+  square_attacked(board.king_square()[side as usize], opponent, board)
+}
+
+fn clear_for_search(board: &mut Board, info: &mut SearchInfo, table: &mut HashTable) {
+  if !info.time_set {
+    info.start_time = Instant::now();
+  }
+  info.heuristics.clear();
+  board.set_actual_half_moves(0);
+  table.clear_statistics();
+  info.stopped = false;
+  info.nodes = 0;
+  info.completed_depth = 0;
+  info.tablebase_hits = 0;
+  info.fail_high = 0.0;
+  info.fail_high_first = 0.0;
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Quiescence_Search>
+ *
+ * At the end of the search the position could be in the middle of a captures sequence
+ * (the horizon effect), so only the captures are searched until the position is quiet.
+ */
+fn quiescence(
+  mut alpha: i32,
+  beta: i32,
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+) -> i32 {
+  let mut picker: MovePicker;
+  let mut legal: i32 = 0;
+  let mut score: i32;
+
+  if !count_node(info) {
+    return 0;
+  }
+
+  if is_repetition(board) || board.fifty_full_moves() >= 100 {
+    return 0;
+  }
+  if board.actual_half_moves() >= MAX_DEPTH as i32 - 1 {
+    return evaluate_position(board, &info.parameters, &mut info.pawn_table);
+  }
+
+  // Stand pat: the side to move is not forced to capture
+  score = evaluate_position(board, &info.parameters, &mut info.pawn_table);
+  if score >= beta {
+    return beta;
+  }
+  if score > alpha {
+    alpha = score;
+  }
+
+  picker = MovePicker::new_captures(table.probe_pv_move(board));
+  while let Some(mov_e) = picker.next_move(board, &info.heuristics) {
+    if !board.make_move(mov_e) {
+      continue;
+    }
+    legal += 1;
+    score = -quiescence(-beta, -alpha, board, info, table);
+    board.take_move();
+
+    if info.stopped {
+      return 0;
+    }
+    if score > alpha {
+      if score >= beta {
+        if legal == 1 {
+          info.fail_high_first += 1.0;
+        }
+        info.fail_high += 1.0;
+        return beta;
+      }
+      alpha = score;
+    }
+  }
+
+  alpha
+}
+
+/**
+ * The score of the position after the captures sequence is resolved, used
+ * (instead of the static evaluation) by the tuner for the positions that are not quiet.
+ */
+pub fn quiescence_evaluation(
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+) -> i32 {
+  clear_for_search(board, info, table);
+  quiescence(-INFINITE, INFINITE, board, info, table)
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Alpha-Beta>
+ * <https://www.chessprogramming.org/Principal_Variation_Search>
+ * <https://www.chessprogramming.org/Null_Move_Pruning>
+ * <https://www.chessprogramming.org/Futility_Pruning>
+ * <https://www.chessprogramming.org/Reverse_Futility_Pruning>
+ * <https://www.chessprogramming.org/Razoring>
+ * <https://www.chessprogramming.org/Check_Extensions>
+ * <https://www.chessprogramming.org/Internal_Iterative_Reductions>
+ *
+ * It returns the score of the position searched at the given depth, from the point of view
+ * of the side to move. A node with a window wider than one (beta - alpha > 1) is a PV node,
+ * the nodes searched with a null window only have to tell if the score is above or below alpha,
+ * so they can be pruned and reduced more aggressively.
+ */
+fn alpha_beta(
+  mut alpha: i32,
+  beta: i32,
+  mut depth: i32,
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+  do_null: bool,
+) -> i32 {
+  let old_alpha: i32 = alpha;
+  let pv_node: bool = beta - alpha > 1;
+  let in_check: bool = is_in_check(board);
+  let mut picker: MovePicker;
+  let mut tried_quiets: Vec<Move> = Vec::new();
+  let mut best_move: Move = Move::NO_MOVE;
+  let mut best_score: i32 = -INFINITE;
+  let mut legal: i32 = 0;
+  let mut score: i32;
+  let mut reduction: i32;
+  let mut gives_check: bool;
+
+  if in_check && info.options.check_extensions {
+    depth += 1;
+  }
+
+  if depth <= 0 {
+    return quiescence(alpha, beta, board, info, table);
+  }
+
+  if !count_node(info) {
+    return 0;
+  }
+
+  if (is_repetition(board) || board.fifty_full_moves() >= 100) && board.actual_half_moves() != 0 {
+    return 0;
+  }
+  if board.actual_half_moves() >= MAX_DEPTH as i32 - 1 {
+    return evaluate_position(board, &info.parameters, &mut info.pawn_table);
+  }
+
+  let (hash_move, hash_score): (Move, Option<i32>) =
+    table.probe_hash_entry(board, alpha, beta, depth);
+  if let Some(hash_score) = hash_score {
+    if !pv_node {
+      return hash_score;
+    }
+  }
+
+  // The tables ignore the fifty moves counter and the castling rights, so they are probed
+  // only after a capture or a pawn move (also because then the number of pieces changes)
+  if board.actual_half_moves() != 0
+    && board.fifty_full_moves() == 0
+    && board.castling_rights().is_empty()
+    && pieces_count(board) <= info.tablebase_pieces
+  {
+    if let Some(wdl) = info
+      .tablebases
+      .as_ref()
+      .and_then(|tablebases| tablebases.probe_wdl(board))
+    {
+      info.tablebase_hits += 1;
+      // The cursed wins and the blessed losses are draws, just a bit better or worse
+      return if wdl < WDL_BLESSED_LOSS {
+        -TABLEBASE_WIN + board.actual_half_moves()
+      } else if wdl > WDL_CURSED_WIN {
+        TABLEBASE_WIN - board.actual_half_moves()
+      } else {
+        2 * wdl
+      };
+    }
+  }
+
+  let static_evaluation: i32 = if in_check {
+    -INFINITE
+  } else {
+    evaluate_position(board, &info.parameters, &mut info.pawn_table)
+  };
+
+  if !pv_node && !in_check {
+    // The position is so good that the opponent will avoid it
+    if info.options.reverse_futility_pruning
+      && depth <= REVERSE_FUTILITY_DEPTH
+      && beta.abs() < IS_MATE
+      && static_evaluation - REVERSE_FUTILITY_MARGIN * depth >= beta
+    {
+      return beta;
+    }
+
+    // The position is so bad that only a capture could save it
+    if info.options.razoring
+      && depth < RAZORING_MARGIN.len() as i32
+      && static_evaluation + RAZORING_MARGIN[depth as usize] < alpha
+    {
+      score = quiescence(alpha, beta, board, info, table);
+      if score < alpha {
+        return score;
+      }
+    }
+
+    // If passing the move is still above beta, a real move will be above it too.
+    // Not in the positions with only king and pawns, where zugzwang is common
+    // (the king is a big piece, so more than one is needed).
+    if info.options.null_move_pruning
+      && do_null
+      && depth >= 3
+      && board.actual_half_moves() != 0
+      && board.big_pieces_number()[board.side() as usize] > 1
+      && static_evaluation >= beta
+    {
+      board.make_null_move();
+      score = -alpha_beta(
+        -beta,
+        -beta + 1,
+        depth - 4 - depth / 6,
+        board,
+        info,
+        table,
+        false,
+      );
+      board.take_null_move();
+
+      if info.stopped {
+        return 0;
+      }
+      if score >= beta && score.abs() < IS_MATE {
+        return beta;
+      }
+    }
+  }
+
+  // Without a hash move the ordering is worse, so the node is searched less deep
+  if info.options.internal_iterative_reductions && depth >= 4 && hash_move == Move::NO_MOVE {
+    depth -= 1;
+  }
+
+  let futility_pruning: bool = info.options.futility_pruning
+    && !pv_node
+    && !in_check
+    && depth < FUTILITY_MARGIN.len() as i32
+    && alpha.abs() < IS_MATE
+    && static_evaluation + FUTILITY_MARGIN[depth as usize] <= alpha;
+
+  picker = MovePicker::new(board, hash_move, &info.heuristics);
+  while let Some(mov_e) = picker.next_move(board, &info.heuristics) {
+    if board.actual_half_moves() == 0
+      && ((!info.root_moves.is_empty() && !info.root_moves.contains(&mov_e))
+        || info.excluded_moves.contains(&mov_e))
+    {
+      continue;
+    }
+    if !board.make_move(mov_e) {
+      continue;
+    }
+    legal += 1;
+    gives_check = is_in_check(board);
+
+    // A quiet move can't bring the score back above alpha
+    if futility_pruning
+      && legal > 1
+      && is_quiet_move(mov_e)
+      && mov_e.promoted().is_none()
+      && !gives_check
+    {
+      board.take_move();
+      continue;
+    }
+
+    if legal == 1 {
+      score = -alpha_beta(-beta, -alpha, depth - 1, board, info, table, true);
+    } else {
+      reduction = 0;
+      if info.options.late_move_reductions
+        && depth >= 3
+        && legal > 3
+        && is_quiet_move(mov_e)
+        && mov_e.promoted().is_none()
+        && !in_check
+        && !gives_check
+      {
+        reduction = info.late_move_reduction(depth, legal);
+        if pv_node {
+          reduction -= 1;
+        }
+        reduction = reduction.clamp(0, depth - 2);
+      }
+
+      if info.options.principal_variation_search {
+        score = -alpha_beta(
+          -alpha - 1,
+          -alpha,
+          depth - 1 - reduction,
+          board,
+          info,
+          table,
+          true,
+        );
+        if score > alpha && reduction > 0 {
+          score = -alpha_beta(-alpha - 1, -alpha, depth - 1, board, info, table, true);
+        }
+        if score > alpha && score < beta {
+          score = -alpha_beta(-beta, -alpha, depth - 1, board, info, table, true);
+        }
+      } else {
+        score = -alpha_beta(
+          -beta,
+          -alpha,
+          depth - 1 - reduction,
+          board,
+          info,
+          table,
+          true,
+        );
+        if score > alpha && reduction > 0 {
+          score = -alpha_beta(-beta, -alpha, depth - 1, board, info, table, true);
+        }
+      }
+    }
+    board.take_move();
+
+    if info.stopped {
+      return 0;
+    }
+    if score > best_score {
+      best_score = score;
+      best_move = mov_e;
+      if score > alpha {
+        if score >= beta {
+          if legal == 1 {
+            info.fail_high_first += 1.0;
+          }
+          info.fail_high += 1.0;
+
+          if is_quiet_move(mov_e) {
+            info.heuristics.store_killer(board, mov_e);
+            info
+              .heuristics
+              .update_history(board, mov_e, depth, &tried_quiets);
+            info.heuristics.store_counter_move(board, mov_e);
+          }
+
+          table.store_hash_entry(board, best_move, beta, HashFlags::Beta, depth);
+          return beta;
+        }
+        alpha = score;
+      }
+    }
+    if is_quiet_move(mov_e) {
+      tried_quiets.push(mov_e);
+    }
+  }
+
+  if legal == 0 {
+    if in_check {
+      return -INFINITE + board.actual_half_moves();
+    } else {
+      return 0;
+    }
+  }
+
+  // Every quiet move has been pruned, the score is only an upper bound
+  if best_move == Move::NO_MOVE {
+    return alpha;
+  }
+
+  if alpha != old_alpha {
+    table.store_hash_entry(board, best_move, best_score, HashFlags::Exact, depth);
+  } else {
+    table.store_hash_entry(board, best_move, alpha, HashFlags::Alpha, depth);
+  }
+
+  alpha
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Aspiration_Windows>
+ *
+ * The score of an iteration is usually near to the one of the previous iteration, so the search
+ * starts with a small window around it, that is widened every time the score falls outside.
+ */
+fn aspiration_search(
+  previous_score: i32,
+  depth: i32,
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+) -> i32 {
+  let mut window: i32 = ASPIRATION_WINDOW;
+  let mut alpha: i32 = -INFINITE;
+  let mut beta: i32 = INFINITE;
+  let mut score: i32;
+
+  if info.options.aspiration_windows && depth >= ASPIRATION_DEPTH && previous_score.abs() < IS_MATE
+  {
+    alpha = (previous_score - window).max(-INFINITE);
+    beta = (previous_score + window).min(INFINITE);
+  }
+
+  loop {
+    score = alpha_beta(alpha, beta, depth, board, info, table, true);
+    if info.stopped {
+      return score;
+    }
+    if score <= alpha && alpha > -INFINITE {
+      alpha = (alpha - window).max(-INFINITE);
+    } else if score >= beta && beta < INFINITE {
+      beta = (beta + window).min(INFINITE);
+    } else {
+      return score;
+    }
+    window *= 2;
+  }
+}
+
+/**
+ * If the root position is in the DTZ tables, only the moves that keep its result are searched,
+ * and the WDL tables are not probed anymore (they can't tell how to make progress in a won position).
+ * Otherwise they are probed by the search, with up to the pieces of the largest table.
+ */
+fn probe_root(board: &mut Board, info: &mut SearchInfo) {
+  info.root_moves.clear();
+  let tablebases: Arc<Tablebases> = match &info.tablebases {
+    Some(tablebases) => Arc::clone(tablebases),
+    None => {
+      info.tablebase_pieces = 0;
+      return;
+    }
+  };
+
+  info.tablebase_pieces = tablebases.max_pieces();
+  if !board.castling_rights().is_empty() || pieces_count(board) > info.tablebase_pieces {
+    return;
+  }
+  if let Some((root_moves, _)) = tablebases.root_moves(board) {
+    info.tablebase_hits += 1;
+    info.root_moves = root_moves;
+    info.tablebase_pieces = 0;
+  }
+}
+
+/**
+ * The root moves are only the ones of "go searchmoves", also among the ones left by the tables
+ * (unless the tables leave none of them).
+ */
+fn restrict_root_moves(info: &mut SearchInfo) {
+  if info.search_moves.is_empty() {
+    return;
+  }
+
+  let search_moves: &[Move] = &info.search_moves;
+  info
+    .root_moves
+    .retain(|root_move| search_moves.contains(root_move));
+  if info.root_moves.is_empty() {
+    info.root_moves = info.search_moves.clone();
+  }
+}
+
+/**
+ * The number of lines that can be searched at the root: the legal moves (only the ones
+ * left by the tables, if they are probed), but at least one to get the score of the position.
+ */
+fn root_lines_number(board: &mut Board, info: &SearchInfo, lines_number: usize) -> usize {
+  let legal_moves: usize = if info.root_moves.is_empty() {
+    generate_legal_moves(board).len()
+  } else {
+    info.root_moves.len()
+  };
+
+  lines_number.min(legal_moves).max(1)
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Iterative_Deepening>
+ *
+ * The position is searched at depth 1, then 2 and so on until the depth limit or the time limit
+ * are reached. Each iteration fills the hash table and the move ordering tables used by the next one.
+ * At every depth the given number of best lines is searched, each one excluding the first moves
+ * of the previous ones. It returns the lines of the last completed iteration sorted by score,
+ * printing the information about the first printed_lines of them.
+ * Below the maximum skill level the depth is limited to 1 + the skill level.
+ */
+fn iterative_deepening(
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+  lines_number: usize,
+  printed_lines: usize,
+) -> Vec<SearchLine> {
+  let max_depth: i32 = if info.skill_level < MAX_SKILL_LEVEL {
+    info.depth.min(1 + info.skill_level)
+  } else {
+    info.depth
+  };
+  let mut best_lines: Vec<SearchLine> = Vec::new();
+  let mut lines: Vec<SearchLine>;
+  let mut previous_score: i32;
+  let mut score: i32;
+  let mut pv_line: Vec<Move>;
+
+  clear_for_search(board, info, table);
+  info.ponder_move = Move::NO_MOVE;
+  probe_root(board, info);
+  restrict_root_moves(info);
+  let lines_number: usize = root_lines_number(board, info, lines_number);
+
+  for current_depth in 1..=max_depth {
+    lines = Vec::with_capacity(lines_number);
+    info.excluded_moves.clear();
+
+    for line in 0..lines_number {
+      previous_score = best_lines.get(line).map_or(0, |&(_, score, _)| score);
+      score = aspiration_search(previous_score, current_depth, board, info, table);
+      if info.stopped {
+        break;
+      }
+
+      pv_line = table.get_pv_line(current_depth, board);
+      let pv_move: Move = pv_line.first().copied().unwrap_or(Move::NO_MOVE);
+      info.excluded_moves.push(pv_move);
+      lines.push((pv_move, score, pv_line));
+    }
+    info.excluded_moves.clear();
+
+    // The lines of an interrupted iteration are not all comparable, so they are discarded
+    if info.stopped {
+      break;
+    }
+
+    lines.sort_by_key(|&(_, score, _)| -score);
+    best_lines = lines;
+    info.completed_depth = current_depth;
+    if let Some((_, _, pv_line)) = best_lines.first() {
+      info.ponder_move = pv_line.get(1).copied().unwrap_or(Move::NO_MOVE);
+    }
+
+    if printed_lines > 0 {
+      print_lines(
+        board,
+        info,
+        &best_lines[..printed_lines.min(best_lines.len())],
+        current_depth,
+      );
+    }
+    if info.mate_limit.is_some_and(|moves| {
+      best_lines
+        .first()
+        .is_some_and(|&(_, score, _)| score >= INFINITE - (2 * moves - 1))
+    }) {
+      break;
+    }
+  }
+
+  best_lines
+}
+
+/**
+ * The information about the lines of a completed iteration.
+ */
+fn print_lines(board: &Board, info: &SearchInfo, lines: &[SearchLine], depth: i32) {
+  for (line, (_, score, pv_line)) in lines.iter().enumerate() {
+    print!(
+      "info multipv {} score {} depth {} nodes {} tbhits {} time {} pv",
+      line + 1,
+      uci_score(*score),
+      depth,
+      info.nodes,
+      info.tablebase_hits,
+      info.start_time.elapsed().as_millis()
+    );
+    for &pv_move in pv_line {
+      print!(" {}", board.move_to_algebraic(pv_move));
+    }
+    println!();
+  }
+  if info.fail_high > 0.0 {
+    println!(
+      "info string ordering {:.2}",
+      info.fail_high_first / info.fail_high
+    );
+  }
+}
+
+/**
+ * The line played at a skill level below the maximum: a random error, that grows with
+ * the weakness, is added to the score of every line (more to the worse ones, and more
+ * when the lines are far apart) and the line with the highest score is chosen.
+ */
+fn pick_skill_line(lines: &[SearchLine], skill_level: i32) -> Option<&SearchLine> {
+  let weakness: i32 = 120 - 2 * skill_level;
+  let mut rng: ThreadRng = rand::thread_rng();
+  let top_score: i32 = lines.first()?.1;
+  let delta: i32 = (top_score - lines.last()?.1).min(PIECE_VALUE[Pieces::Wp as usize]);
+  let mut best_line: Option<&SearchLine> = None;
+  let mut best_score: i32 = -INFINITE;
+  let mut push: i32;
+
+  for line in lines {
+    push = (weakness * (top_score - line.1) + delta * rng.gen_range(0..weakness)) / 128;
+    if line.1 + push >= best_score {
+      best_score = line.1 + push;
+      best_line = Some(line);
+    }
+  }
+
+  best_line
+}
+
+/**
+ * The score in the UCI format: "mate" with the number of moves to the mate (negative
+ * if the side to move is mated), "cp" with the centipawns otherwise.
+ */
+pub fn uci_score(score: i32) -> String {
+  if score >= IS_MATE {
+    format!("mate {}", (INFINITE - score + 1) / 2)
+  } else if score <= -IS_MATE {
+    format!("mate {}", -(INFINITE + score) / 2)
+  } else {
+    format!("cp {}", score)
+  }
+}
+
+/**
+ * The search of the UCI "go" command, it ends printing the best move and the expected reply.
+ * A ponder search can end before the ponderhit (ex. at the maximum depth), but the best move
+ * is sent only after it (or after a stop). A stop that comes before the first iteration is
+ * completed still gets a legal move as answer.
+ */
+pub fn search_position(board: &mut Board, info: &mut SearchInfo, table: &mut HashTable) -> Move {
+  let mut lines_number: usize = info.multi_pv;
+  let skill: bool = info.skill_level < MAX_SKILL_LEVEL;
+
+  if skill {
+    lines_number = lines_number.max(SKILL_LINES);
+  }
+  let lines: Vec<SearchLine> = iterative_deepening(board, info, table, lines_number, info.multi_pv);
+  let best_line: Option<&SearchLine> = if skill {
+    pick_skill_line(&lines, info.skill_level)
+  } else {
+    lines.first()
+  };
+  let best_move: Move =
+    best_line.map_or_else(|| first_legal_move(board), |&(best_move, _, _)| best_move);
+  if let Some((_, _, pv_line)) = best_line {
+    info.ponder_move = pv_line.get(1).copied().unwrap_or(Move::NO_MOVE);
+  }
+
+  while info.pondering
+    && info.ponder_signal.load(Ordering::Relaxed)
+    && !info.stop_signal.load(Ordering::Relaxed)
+  {
+    thread::sleep(Duration::from_millis(PONDER_WAIT));
+  }
+  info.pondering = false;
+
+  if info.ponder_move == Move::NO_MOVE {
+    println!("bestmove {}", board.move_to_algebraic(best_move));
+  } else {
+    println!(
+      "bestmove {} ponder {}",
+      board.move_to_algebraic(best_move),
+      board.move_to_algebraic(info.ponder_move)
+    );
+  }
+  best_move
+}
+
+/**
+ * The move played when the search stops before completing the first depth
+ * (ex. with a very low node limit), NO_MOVE if there is no legal move.
+ */
+fn first_legal_move(board: &mut Board) -> Move {
+  generate_legal_moves(board)
+    .first()
+    .copied()
+    .unwrap_or(Move::NO_MOVE)
+}
+
+/**
+ * As search_position, but without any output: it returns the best move and its score
+ * (from the point of view of the side to move) of the last completed depth.
+ * If no depth has been completed (see completed_depth), it's the first legal move with score zero.
+ */
+pub fn search_best_move(
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+) -> (Move, i32) {
+  iterative_deepening(board, info, table, 1, 0)
+    .first()
+    .map_or_else(
+      || (first_legal_move(board), 0),
+      |&(best_move, score, _)| (best_move, score),
+    )
+}
+
+/**
+ * As search_best_move, but it returns the given number of best lines (less if there are
+ * not enough legal moves) sorted by score, with the limits of the search set in the info
+ * (the skill level too, that limits the depth).
+ */
+pub fn search_best_lines(
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+  lines_number: usize,
+) -> Vec<SearchLine> {
+  iterative_deepening(board, info, table, lines_number.clamp(1, MAX_MULTI_PV), 0)
+}
@@ -0,0 +1,184 @@
+use crate::attack::*;
+use crate::board::*;
+use crate::definitions::*;
+
+/**
+ * It returns the square of the least valuable piece of the side that attacks the square,
+ * looking at the given pieces array (where the pieces already used in the exchange are removed).
+ * Because the array is read again for every capture, a slider behind a piece that has already
+ * captured (an x-ray attacker) is found as soon as the square between them becomes empty.
+ */
+fn least_valuable_attacker(
+  square: Squares,
+  side: Colors,
+  pieces: &[Pieces; BOARD_SQUARE_NUMBER],
+) -> Option<Squares> {
+  let mut best_square: Option<Squares> = None;
+  let mut best_value: i32 = i32::MAX;
+
+  // A pawn is found first and no attacker is less valuable
+  find_attackers(square, side, pieces, |temp_square| {
+    let piece: Pieces = pieces[temp_square as usize];
+    if PIECE_VALUE[piece as usize] < best_value {
+      best_value = PIECE_VALUE[piece as usize];
+      best_square = Some(temp_square);
+    }
+    PIECE_PAWN[piece as usize]
+  });
+
+  best_square
+}
+
+fn opponent_side(side: Colors) -> Colors {
+  if side == Colors::White {
+    Colors::Black
+  } else {
+    Colors::White
+  }
+}
+
+/**
+ * What the moving side wins with the move itself: the captured piece (a pawn for en passant)
+ * and, for a promotion, the difference between the promoted piece and the pawn.
+ * It also returns the pieces array after the move, and the piece left on the arrival square.
+ */
+fn first_capture(board: &Board, mov_e: Move) -> (i32, [Pieces; BOARD_SQUARE_NUMBER], Pieces) {
+  let from: i32 = mov_e.from_square() as i32;
+  let to: i32 = mov_e.to_square() as i32;
+  let mut pieces: [Pieces; BOARD_SQUARE_NUMBER] = *board.pieces();
+  let mut moving_piece: Pieces = pieces[from as usize];
+  let mut gain: i32 = PIECE_VALUE[mov_e.captured_piece().unwrap_or(Pieces::Empty) as usize];
+
+  if mov_e.is_en_passant() {
+    gain = PIECE_VALUE[Pieces::Wp as usize];
+    if board.side() == Colors::White {
+      pieces[(to - 10) as usize] = Pieces::Empty;
+    } else {
+      pieces[(to + 10) as usize] = Pieces::Empty;
+    }
+  }
+  if let Some(promoted) = mov_e.promoted() {
+    gain += PIECE_VALUE[promoted as usize] - PIECE_VALUE[moving_piece as usize];
+    moving_piece = promoted;
+  }
+  pieces[from as usize] = Pieces::Empty;
+  pieces[to as usize] = moving_piece;
+
+  (gain, pieces, moving_piece)
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Static_Exchange_Evaluation>
+ * <https://www.chessprogramming.org/SEE_-_The_Swap_Algorithm>
+ *
+ * The Static Exchange Evaluation tells how much material the side to move wins (or loses) with
+ * the move, when both sides keep capturing on the arrival square always with their least valuable
+ * piece, and each side can stop capturing when it's not convenient anymore.
+ * The pinned pieces are not considered, a king captures only if the square is not defended anymore.
+ * NO_MOVE wins nothing.
+ */
+impl Board {
+  pub fn see(&self, mov_e: Move) -> i32 {
+    if mov_e == Move::NO_MOVE {
+      return 0;
+    }
+    let to: Squares = mov_e.to_square();
+    let (first_gain, mut pieces, mut piece_on_square): (
+      i32,
+      [Pieces; BOARD_SQUARE_NUMBER],
+      Pieces,
+    ) = first_capture(self, mov_e);
+    // The gain of each capture of the sequence, from the point of view of the side that makes it
+    let mut gain: [i32; 32] = [0; 32];
+    let mut depth: usize = 0;
+    let mut side: Colors = opponent_side(self.side());
+    let mut attacker_square: Squares;
+    let mut attacker: Pieces;
+
+    gain[0] = first_gain;
+    while depth < gain.len() - 1 {
+      attacker_square = match least_valuable_attacker(to, side, &pieces) {
+        Some(square) => square,
+        None => break,
+      };
+      attacker = pieces[attacker_square as usize];
+      pieces[attacker_square as usize] = Pieces::Empty;
+
+      // A king can't capture on a square still defended
+      if PIECE_KING[attacker as usize]
+        && least_valuable_attacker(to, opponent_side(side), &pieces).is_some()
+      {
+        break;
+      }
+
+      depth += 1;
+      gain[depth] = PIECE_VALUE[piece_on_square as usize] - gain[depth - 1];
+      piece_on_square = attacker;
+      pieces[to as usize] = attacker;
+      side = opponent_side(side);
+    }
+
+    // Each side chooses between capturing and stopping, going back from the last capture
+    while depth > 0 {
+      gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+      depth -= 1;
+    }
+
+    gain[0]
+  }
+
+  /**
+   * It tells if the SEE of the move is at least the threshold, without computing the exact value:
+   * the exchange is followed only until one of the two sides is sure to be above or below it.
+   */
+  pub fn see_ge(&self, mov_e: Move, threshold: i32) -> bool {
+    if mov_e == Move::NO_MOVE {
+      return threshold <= 0;
+    }
+    let to: Squares = mov_e.to_square();
+    let (first_gain, mut pieces, piece_on_square): (i32, [Pieces; BOARD_SQUARE_NUMBER], Pieces) =
+      first_capture(self, mov_e);
+    let mut side: Colors = self.side();
+    let mut attacker_square: Squares;
+    let mut attacker: Pieces;
+    // "swap" is how much the side that is winning can lose and still be above the threshold
+    let mut swap: i32 = first_gain - threshold;
+    // true if the side to move is above the threshold with the current capture
+    let mut result: bool = true;
+
+    if swap < 0 {
+      return false;
+    }
+    swap = PIECE_VALUE[piece_on_square as usize] - swap;
+    if swap <= 0 {
+      return true;
+    }
+
+    loop {
+      side = opponent_side(side);
+      attacker_square = match least_valuable_attacker(to, side, &pieces) {
+        Some(square) => square,
+        None => break,
+      };
+      attacker = pieces[attacker_square as usize];
+      pieces[attacker_square as usize] = Pieces::Empty;
+
+      // A king can capture only if the opponent has no more attackers
+      if PIECE_KING[attacker as usize] {
+        if least_valuable_attacker(to, opponent_side(side), &pieces).is_some() {
+          break;
+        }
+        return !result;
+      }
+
+      result = !result;
+      swap = PIECE_VALUE[attacker as usize] - swap;
+      if swap < result as i32 {
+        break;
+      }
+    }
+
+    result
+  }
+}
@@ -0,0 +1,1343 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::board::*;
+use crate::definitions::*;
+use crate::movegen::*;
+use crate::search::*;
+
+/**
+ * The score of a position won according to the tablebases, less the distance from the root
+ * (so that the nearest win is preferred). It's below the mate scores.
+ */
+pub const TABLEBASE_WIN: i32 = IS_MATE - MAX_DEPTH as i32;
+
+/**
+ * The results stored in the WDL tables, from the point of view of the side to move.
+ * A cursed win is a win that takes more than fifty moves without pawn moves or captures
+ * (so it's a draw), and a blessed loss is the same for the losing side.
+ */
+pub const WDL_LOSS: i32 = -2;
+pub const WDL_BLESSED_LOSS: i32 = -1;
+pub const WDL_DRAW: i32 = 0;
+pub const WDL_CURSED_WIN: i32 = 1;
+pub const WDL_WIN: i32 = 2;
+
+const TABLEBASE_PIECES: usize = 7;
+const MAX_DTZ: i32 = 1 << 18;
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+const WDL_EXTENSION: &str = "rtbw";
+const DTZ_EXTENSION: &str = "rtbz";
+
+// The flags of a table (first byte after the magic)
+const SPLIT_FLAG: u8 = 1;
+const HAS_PAWNS_FLAG: u8 = 2;
+
+// The flags of the pairs data of a table
+const STM_FLAG: u8 = 1;
+const MAPPED_FLAG: u8 = 2;
+const WIN_PLIES_FLAG: u8 = 4;
+const LOSS_PLIES_FLAG: u8 = 8;
+const WIDE_FLAG: u8 = 16;
+const SINGLE_VALUE_FLAG: u8 = 128;
+
+/**
+ * The pieces as they are written in the tables: white pawn to king 1-6, black pawn to king 9-14,
+ * so that the color of a piece is changed with piece ^ 8.
+ */
+const TABLE_PIECES: [u8; 13] = [0, 1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14];
+const TABLE_COLOR_FLIP: u8 = 8;
+
+#[derive(Copy, Clone, PartialEq)]
+enum ProbeState {
+  Fail,
+  Ok,
+  /**
+   * The DTZ table stores the other side to move.
+   */
+  ChangeSide,
+  /**
+   * The best move is a capture or a pawn move, the stored value can't be used.
+   */
+  ZeroingBestMove,
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Syzygy_Bases>
+ *
+ * The tables used to compute the index of a position: the positions are mirrored so that
+ * the leading piece is in the a1-d1-d4 triangle (or the leading pawn on the files a-d),
+ * and the groups of equal pieces are encoded as combinations of the free squares.
+ */
+struct EncodingTables {
+  /**
+   * The squares below the a1-h8 diagonal to 0..27.
+   */
+  map_b1h1h7: [u64; 64],
+  /**
+   * The squares of the a1-d1-d4 triangle to 0..9, the diagonal squares last.
+   */
+  map_a1d1d4: [u64; 64],
+  /**
+   * The 462 legal positions of two kings, with the first king in the a1-d1-d4 triangle.
+   */
+  map_kk: [[u64; 64]; 10],
+  binomial: [[u64; 64]; TABLEBASE_PIECES],
+  /**
+   * The squares a2-h7 to 0..47: the pawn with the highest value is the leading pawn.
+   */
+  map_pawns: [u64; 64],
+  lead_pawn_index: [[u64; 64]; 6],
+  lead_pawns_size: [[u64; 4]; 6],
+}
+
+static ENCODING_TABLES: OnceLock<EncodingTables> = OnceLock::new();
+
+/**
+ * The rank less the file: negative below the a1-h8 diagonal, zero on it.
+ */
+fn off_a1h8(square: usize) -> i32 {
+  (square / 8) as i32 - (square % 8) as i32
+}
+
+impl EncodingTables {
+  fn new() -> EncodingTables {
+    let mut tables: EncodingTables = EncodingTables {
+      map_b1h1h7: [0; 64],
+      map_a1d1d4: [0; 64],
+      map_kk: [[0; 64]; 10],
+      binomial: [[0; 64]; TABLEBASE_PIECES],
+      map_pawns: [0; 64],
+      lead_pawn_index: [[0; 64]; 6],
+      lead_pawns_size: [[0; 4]; 6],
+    };
+    let mut diagonal: Vec<usize> = Vec::new();
+    let mut both_on_diagonal: Vec<(usize, usize)> = Vec::new();
+    let mut code: u64 = 0;
+    let mut available_squares: u64 = 47;
+    let mut index: u64;
+    let mut square: usize;
+
+    for square in 0..64 {
+      if off_a1h8(square) < 0 {
+        tables.map_b1h1h7[square] = code;
+        code += 1;
+      }
+    }
+
+    code = 0;
+    for square in 0..=27 {
+      if off_a1h8(square) < 0 && square % 8 <= 3 {
+        tables.map_a1d1d4[square] = code;
+        code += 1;
+      } else if off_a1h8(square) == 0 && square % 8 <= 3 {
+        diagonal.push(square);
+      }
+    }
+    for square in diagonal {
+      tables.map_a1d1d4[square] = code;
+      code += 1;
+    }
+
+    code = 0;
+    for king_index in 0..10 {
+      for square_1 in 0..=27 {
+        // Only b1 is mapped to 0, the other squares out of the triangle are 0 too
+        if tables.map_a1d1d4[square_1] != king_index as u64 || (king_index == 0 && square_1 != 1) {
+          continue;
+        }
+        for square_2 in 0..64 {
+          if ((square_1 % 8) as i32 - (square_2 % 8) as i32).abs() <= 1
+            && ((square_1 / 8) as i32 - (square_2 / 8) as i32).abs() <= 1
+          {
+            // The kings are adjacent
+            continue;
+          } else if off_a1h8(square_1) == 0 && off_a1h8(square_2) > 0 {
+            continue;
+          } else if off_a1h8(square_1) == 0 && off_a1h8(square_2) == 0 {
+            both_on_diagonal.push((king_index, square_2));
+          } else {
+            tables.map_kk[king_index][square_2] = code;
+            code += 1;
+          }
+        }
+      }
+    }
+    for (king_index, square_2) in both_on_diagonal {
+      tables.map_kk[king_index][square_2] = code;
+      code += 1;
+    }
+
+    tables.binomial[0][0] = 1;
+    for n in 1..64 {
+      for k in 0..TABLEBASE_PIECES.min(n + 1) {
+        tables.binomial[k][n] = if k > 0 {
+          tables.binomial[k - 1][n - 1]
+        } else {
+          0
+        } + if k < n { tables.binomial[k][n - 1] } else { 0 };
+      }
+    }
+
+    for lead_pawns in 1..=5 {
+      for file in 0..4 {
+        index = 0;
+        for rank in 1..=6 {
+          square = rank * 8 + file;
+          if lead_pawns == 1 {
+            tables.map_pawns[square] = available_squares;
+            available_squares -= 1;
+            tables.map_pawns[square ^ 7] = available_squares;
+            available_squares = available_squares.saturating_sub(1);
+          }
+          tables.lead_pawn_index[lead_pawns][square] = index;
+          index += tables.binomial[lead_pawns - 1][tables.map_pawns[square] as usize];
+        }
+        tables.lead_pawns_size[lead_pawns][file] = index;
+      }
+    }
+
+    tables
+  }
+}
+
+fn encoding_tables() -> &'static EncodingTables {
+  ENCODING_TABLES.get_or_init(EncodingTables::new)
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Option<u8> {
+  bytes.get(offset).copied()
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+  Some(u16::from_le_bytes(
+    bytes.get(offset..offset + 2)?.try_into().ok()?,
+  ))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_le_bytes(
+    bytes.get(offset..offset + 4)?.try_into().ok()?,
+  ))
+}
+
+fn read_u32_big_endian(bytes: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_be_bytes(
+    bytes.get(offset..offset + 4)?.try_into().ok()?,
+  ))
+}
+
+fn read_u64_big_endian(bytes: &[u8], offset: usize) -> Option<u64> {
+  Some(u64::from_be_bytes(
+    bytes.get(offset..offset + 8)?.try_into().ok()?,
+  ))
+}
+
+fn shift_left(value: u64, shift: u64) -> u64 {
+  value.checked_shl(shift as u32).unwrap_or(0)
+}
+
+fn shift_right(value: u64, shift: u64) -> u64 {
+  value.checked_shr(shift as u32).unwrap_or(0)
+}
+
+/**
+ * The compressed values of one side to move (and one file of the leading pawn),
+ * with the offsets of its parts inside the table file.
+ */
+#[derive(Clone, Default)]
+struct PairsData {
+  flags: u8,
+  pieces: [u8; TABLEBASE_PIECES],
+  group_length: [usize; TABLEBASE_PIECES + 1],
+  group_index: [u64; TABLEBASE_PIECES + 1],
+  block_size: u64,
+  span: u64,
+  blocks_number: u64,
+  sparse_index: usize,
+  sparse_index_size: u64,
+  block_length: usize,
+  block_length_size: u64,
+  data: usize,
+  /**
+   * The value of all the positions, if the SINGLE_VALUE flag is set.
+   */
+  min_symbol_length: u8,
+  lowest_symbol: usize,
+  base64: Vec<u64>,
+  symbol_length: Vec<u8>,
+  btree: usize,
+  /**
+   * Where the DTZ values of each WDL result are mapped, in DTZ tables with the MAPPED flag.
+   */
+  map_index: [usize; 4],
+}
+
+impl PairsData {
+  /**
+   * Every symbol of the binary tree has 12 bits for the left child and 12 for the right one.
+   */
+  fn left_symbol(&self, bytes: &[u8], symbol: usize) -> Option<usize> {
+    let offset: usize = self.btree + 3 * symbol;
+
+    Some((((read_u8(bytes, offset + 1)? & 0xF) as usize) << 8) | read_u8(bytes, offset)? as usize)
+  }
+
+  fn right_symbol(&self, bytes: &[u8], symbol: usize) -> Option<usize> {
+    let offset: usize = self.btree + 3 * symbol;
+
+    Some(
+      ((read_u8(bytes, offset + 2)? as usize) << 4) | (read_u8(bytes, offset + 1)? >> 4) as usize,
+    )
+  }
+
+  /**
+   * The number of values (less one) represented by the symbol, which is expanded recursively
+   * in its left and right symbols (recursive pairing).
+   */
+  fn set_symbol_length(&mut self, bytes: &[u8], symbol: usize, visited: &mut [bool]) -> Option<u8> {
+    visited[symbol] = true;
+    let right: usize = self.right_symbol(bytes, symbol)?;
+    if right == 0xFFF {
+      return Some(0);
+    }
+    let left: usize = self.left_symbol(bytes, symbol)?;
+    if left >= visited.len() || right >= visited.len() {
+      return None;
+    }
+
+    if !visited[left] {
+      self.symbol_length[left] = self.set_symbol_length(bytes, left, visited)?;
+    }
+    if !visited[right] {
+      self.symbol_length[right] = self.set_symbol_length(bytes, right, visited)?;
+    }
+
+    Some(
+      self.symbol_length[left]
+        .wrapping_add(self.symbol_length[right])
+        .wrapping_add(1),
+    )
+  }
+
+  /**
+   * It reads the sizes of the blocks and the canonical Huffman code, returning the offset after them.
+   */
+  fn set_sizes(&mut self, bytes: &[u8], mut offset: usize) -> Option<usize> {
+    self.flags = read_u8(bytes, offset)?;
+    offset += 1;
+
+    if self.flags & SINGLE_VALUE_FLAG != 0 {
+      self.min_symbol_length = read_u8(bytes, offset)?;
+      return Some(offset + 1);
+    }
+
+    let table_size: u64 =
+      self.group_index[self.group_length.iter().position(|&length| length == 0)?];
+    self.block_size = shift_left(1, read_u8(bytes, offset)? as u64);
+    self.span = shift_left(1, read_u8(bytes, offset + 1)? as u64);
+    if self.span == 0 {
+      return None;
+    }
+    self.sparse_index_size = table_size.div_ceil(self.span);
+    let padding: u64 = read_u8(bytes, offset + 2)? as u64;
+    self.blocks_number = read_u32(bytes, offset + 3)? as u64;
+    self.block_length_size = self.blocks_number + padding;
+    let max_symbol_length: u8 = read_u8(bytes, offset + 7)?;
+    self.min_symbol_length = read_u8(bytes, offset + 8)?;
+    offset += 9;
+    self.lowest_symbol = offset;
+    if max_symbol_length < self.min_symbol_length {
+      return None;
+    }
+
+    // The longer symbols have lower values, so base64 decreases with the length of the symbol
+    let lengths_number: usize = (max_symbol_length - self.min_symbol_length) as usize + 1;
+    self.base64 = vec![0; lengths_number];
+    for index in (0..lengths_number - 1).rev() {
+      self.base64[index] = (self.base64[index + 1]
+        .wrapping_add(read_u16(bytes, self.lowest_symbol + 2 * index)? as u64)
+        .wrapping_sub(read_u16(bytes, self.lowest_symbol + 2 * (index + 1))? as u64))
+        / 2;
+    }
+    // Right padded to 64 bits
+    for (index, base) in self.base64.iter_mut().enumerate() {
+      *base = shift_left(
+        *base,
+        64u64.saturating_sub(index as u64 + self.min_symbol_length as u64),
+      );
+    }
+
+    offset += lengths_number * 2;
+    let symbols_number: usize = read_u16(bytes, offset)? as usize;
+    offset += 2;
+    self.btree = offset;
+    self.symbol_length = vec![0; symbols_number];
+    let mut visited: Vec<bool> = vec![false; symbols_number];
+    for symbol in 0..symbols_number {
+      if !visited[symbol] {
+        self.symbol_length[symbol] = self.set_symbol_length(bytes, symbol, &mut visited)?;
+      }
+    }
+
+    Some(offset + symbols_number * 3 + (symbols_number & 1))
+  }
+
+  /**
+   * The value stored at the given index: the block is found with the sparse index and
+   * the block lengths, then the Huffman symbols of the block are read until the one that
+   * contains the index, and the symbol is expanded down to the value.
+   */
+  fn decompress(&self, bytes: &[u8], index: u64) -> Option<i32> {
+    if self.flags & SINGLE_VALUE_FLAG != 0 {
+      return Some(self.min_symbol_length as i32);
+    }
+
+    let sparse_entry: usize = self.sparse_index + 6 * (index / self.span) as usize;
+    let mut block: u64 = read_u32(bytes, sparse_entry)? as u64;
+    let mut offset: i64 = read_u16(bytes, sparse_entry + 4)? as i64;
+    offset += (index % self.span) as i64 - (self.span / 2) as i64;
+
+    let block_length = |block: u64| -> Option<i64> {
+      Some(read_u16(bytes, self.block_length + 2 * block as usize)? as i64)
+    };
+    while offset < 0 {
+      block = block.checked_sub(1)?;
+      offset += block_length(block)? + 1;
+    }
+    while offset > block_length(block)? {
+      offset -= block_length(block)? + 1;
+      block += 1;
+    }
+
+    let mut pointer: usize = self.data + (block * self.block_size) as usize;
+    let mut buffer: u64 = read_u64_big_endian(bytes, pointer)?;
+    let mut buffer_size: u64 = 64;
+    let mut length: usize;
+    let mut symbol: usize;
+    pointer += 8;
+
+    loop {
+      length = 0;
+      while buffer < *self.base64.get(length)? {
+        length += 1;
+      }
+      symbol = shift_right(
+        buffer - self.base64[length],
+        64 - length as u64 - self.min_symbol_length as u64,
+      ) as u16 as usize;
+      symbol =
+        (symbol as u16).wrapping_add(read_u16(bytes, self.lowest_symbol + 2 * length)?) as usize;
+      if symbol >= self.symbol_length.len() {
+        return None;
+      }
+
+      if offset < self.symbol_length[symbol] as i64 + 1 {
+        break;
+      }
+      offset -= self.symbol_length[symbol] as i64 + 1;
+      length += self.min_symbol_length as usize;
+      buffer = shift_left(buffer, length as u64);
+      buffer_size = buffer_size.checked_sub(length as u64)?;
+      if buffer_size <= 32 {
+        buffer_size += 32;
+        buffer |= (read_u32_big_endian(bytes, pointer)? as u64) << (64 - buffer_size);
+        pointer += 4;
+      }
+    }
+
+    while self.symbol_length[symbol] != 0 {
+      let left: usize = self.left_symbol(bytes, symbol)?;
+      if left >= self.symbol_length.len() {
+        return None;
+      }
+      if offset < self.symbol_length[left] as i64 + 1 {
+        symbol = left;
+      } else {
+        offset -= self.symbol_length[left] as i64 + 1;
+        symbol = self.right_symbol(bytes, symbol)?;
+        if symbol >= self.symbol_length.len() {
+          return None;
+        }
+      }
+    }
+
+    Some(self.left_symbol(bytes, symbol)? as i32)
+  }
+}
+
+/**
+ * A table file read in memory: the pairs data are indexed by side to move and file.
+ */
+struct TableData {
+  bytes: Vec<u8>,
+  pairs: Vec<PairsData>,
+  sides: usize,
+  files: usize,
+}
+
+impl TableData {
+  fn pairs(&self, side: usize, file: usize) -> &PairsData {
+    &self.pairs[(side % self.sides) * self.files + file.min(self.files - 1)]
+  }
+}
+
+/**
+ * A material combination, like KRPvKR, with its WDL and (if found) DTZ files.
+ * The files are read the first time that they are probed.
+ */
+struct TableEntry {
+  wdl_path: PathBuf,
+  dtz_path: Option<PathBuf>,
+  /**
+   * The number of each piece (Wp to Bk) with the first side of the name as white, and as black.
+   */
+  key: [u8; 12],
+  key_2: [u8; 12],
+  pieces_number: usize,
+  has_pawns: bool,
+  has_unique_pieces: bool,
+  /**
+   * The pawns of the leading color (the one with fewer pawns, but some) and of the other one.
+   */
+  pawn_count: [usize; 2],
+  wdl: OnceLock<Option<TableData>>,
+  dtz: OnceLock<Option<TableData>>,
+}
+
+impl TableEntry {
+  /**
+   * The entry of a table name like "KRPvKR", None if the name is not a table.
+   */
+  fn new(name: &str, wdl_path: PathBuf, dtz_path: Option<PathBuf>) -> Option<TableEntry> {
+    let (white, black): (&str, &str) = name.split_once('v')?;
+    let mut key: [u8; 12] = [0; 12];
+    let mut key_2: [u8; 12] = [0; 12];
+    let piece_types: &str = "PNBRQK";
+
+    for (pieces, color_offset) in [(white, 0), (black, 6)] {
+      if pieces.matches('K').count() != 1 || !pieces.starts_with('K') {
+        return None;
+      }
+      for character in pieces.chars() {
+        key[color_offset + piece_types.find(character)?] += 1;
+      }
+    }
+    key_2[0..6].copy_from_slice(&key[6..12]);
+    key_2[6..12].copy_from_slice(&key[0..6]);
+
+    let pieces_number: usize = key.iter().map(|&count| count as usize).sum();
+    if pieces_number > TABLEBASE_PIECES {
+      return None;
+    }
+    let (white_pawns, black_pawns): (usize, usize) = (key[0] as usize, key[6] as usize);
+    // The leading color has fewer pawns, because this gives a better compression
+    let white_leads: bool = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+    Some(TableEntry {
+      wdl_path,
+      dtz_path,
+      key,
+      key_2,
+      pieces_number,
+      has_pawns: white_pawns + black_pawns > 0,
+      has_unique_pieces: (0..5).any(|piece_type| key[piece_type] == 1 || key[6 + piece_type] == 1),
+      pawn_count: if white_leads {
+        [white_pawns, black_pawns]
+      } else {
+        [black_pawns, white_pawns]
+      },
+      wdl: OnceLock::new(),
+      dtz: OnceLock::new(),
+    })
+  }
+
+  fn wdl_table(&self) -> Option<&TableData> {
+    self
+      .wdl
+      .get_or_init(|| self.read_table(&self.wdl_path, false))
+      .as_ref()
+  }
+
+  fn dtz_table(&self) -> Option<&TableData> {
+    self
+      .dtz
+      .get_or_init(|| self.read_table(self.dtz_path.as_ref()?, true))
+      .as_ref()
+  }
+
+  fn read_table(&self, path: &Path, dtz: bool) -> Option<TableData> {
+    let table: Option<TableData> = fs::read(path)
+      .ok()
+      .and_then(|bytes| self.parse_table(bytes, dtz));
+
+    if table.is_none() {
+      println!("info string {} is not a valid table", path.display());
+    }
+    table
+  }
+
+  /**
+   * The pieces of a table are divided in groups: the leading group (the two kings, three unique
+   * pieces or the leading pawns), the other pawns and then the groups of equal pieces. The index
+   * of a position is a sum of the group codes, each multiplied by the group_index of the group.
+   */
+  fn set_groups(&self, pairs: &mut PairsData, order: [usize; 2], file: usize) {
+    let tables: &EncodingTables = encoding_tables();
+    let both_sides_pawns: bool = self.has_pawns && self.pawn_count[1] > 0;
+    let mut first_length: i32 = if self.has_pawns {
+      0
+    } else if self.has_unique_pieces {
+      3
+    } else {
+      2
+    };
+    let mut groups: usize = 0;
+    let mut next: usize;
+    let mut free_squares: usize;
+    let mut index: u64 = 1;
+    let mut group: usize = 0;
+
+    pairs.group_length[0] = 1;
+    for piece in 1..self.pieces_number {
+      first_length -= 1;
+      if first_length > 0 || pairs.pieces[piece] == pairs.pieces[piece - 1] {
+        pairs.group_length[groups] += 1;
+      } else {
+        groups += 1;
+        pairs.group_length[groups] = 1;
+      }
+    }
+    groups += 1;
+    pairs.group_length[groups] = 0;
+
+    next = if both_sides_pawns { 2 } else { 1 };
+    free_squares = 64
+      - pairs.group_length[0]
+      - if both_sides_pawns {
+        pairs.group_length[1]
+      } else {
+        0
+      };
+    while next < groups || group == order[0] || group == order[1] {
+      if group == order[0] {
+        pairs.group_index[0] = index;
+        index *= if self.has_pawns {
+          tables.lead_pawns_size[pairs.group_length[0]][file]
+        } else if self.has_unique_pieces {
+          31332
+        } else {
+          462
+        };
+      } else if group == order[1] {
+        pairs.group_index[1] = index;
+        index *= tables.binomial[pairs.group_length[1]][48 - pairs.group_length[0]];
+      } else {
+        pairs.group_index[next] = index;
+        index *= tables.binomial[pairs.group_length[next]][free_squares];
+        free_squares -= pairs.group_length[next];
+        next += 1;
+      }
+      group += 1;
+    }
+    pairs.group_index[groups] = index;
+  }
+
+  /**
+   * The DTZ values of each WDL result can be mapped, the mapping follows the sizes.
+   */
+  fn set_dtz_map(bytes: &[u8], pairs: &mut [PairsData], mut offset: usize) -> Option<usize> {
+    for file_pairs in pairs.iter_mut() {
+      if file_pairs.flags & MAPPED_FLAG == 0 {
+        continue;
+      }
+      if file_pairs.flags & WIDE_FLAG != 0 {
+        offset += offset & 1;
+        for map_index in file_pairs.map_index.iter_mut() {
+          *map_index = offset + 2;
+          offset += 2 * read_u16(bytes, offset)? as usize + 2;
+        }
+      } else {
+        for map_index in file_pairs.map_index.iter_mut() {
+          *map_index = offset + 1;
+          offset += read_u8(bytes, offset)? as usize + 1;
+        }
+      }
+    }
+
+    Some(offset + (offset & 1))
+  }
+
+  fn parse_table(&self, bytes: Vec<u8>, dtz: bool) -> Option<TableData> {
+    let magic: [u8; 4] = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+    let flags: u8 = read_u8(&bytes, 4)?;
+    let sides: usize = if !dtz && self.key != self.key_2 { 2 } else { 1 };
+    let files: usize = if self.has_pawns { 4 } else { 1 };
+    let both_sides_pawns: bool = self.has_pawns && self.pawn_count[1] > 0;
+    let mut pairs: Vec<PairsData> = vec![PairsData::default(); sides * files];
+    let mut offset: usize = 5;
+    let mut order: [[usize; 2]; 2];
+    let mut byte: u8;
+
+    if bytes.get(0..4)? != magic
+      || (flags & HAS_PAWNS_FLAG != 0) != self.has_pawns
+      || (!dtz && (flags & SPLIT_FLAG != 0) != (self.key != self.key_2))
+    {
+      return None;
+    }
+
+    for file in 0..files {
+      byte = read_u8(&bytes, offset)?;
+      order = [[(byte & 0xF) as usize, 0xF], [(byte >> 4) as usize, 0xF]];
+      if both_sides_pawns {
+        byte = read_u8(&bytes, offset + 1)?;
+        order[0][1] = (byte & 0xF) as usize;
+        order[1][1] = (byte >> 4) as usize;
+      }
+      offset += 1 + both_sides_pawns as usize;
+
+      for piece in 0..self.pieces_number {
+        byte = read_u8(&bytes, offset)?;
+        for side in 0..sides {
+          pairs[side * files + file].pieces[piece] = if side == 1 { byte >> 4 } else { byte & 0xF };
+        }
+        offset += 1;
+      }
+      for side in 0..sides {
+        self.set_groups(&mut pairs[side * files + file], order[side], file);
+      }
+    }
+    offset += offset & 1;
+
+    for file in 0..files {
+      for side in 0..sides {
+        offset = pairs[side * files + file].set_sizes(&bytes, offset)?;
+      }
+    }
+    if dtz {
+      offset = TableEntry::set_dtz_map(&bytes, &mut pairs, offset)?;
+    }
+    for file in 0..files {
+      for side in 0..sides {
+        pairs[side * files + file].sparse_index = offset;
+        offset += 6 * pairs[side * files + file].sparse_index_size as usize;
+      }
+    }
+    for file in 0..files {
+      for side in 0..sides {
+        pairs[side * files + file].block_length = offset;
+        offset += 2 * pairs[side * files + file].block_length_size as usize;
+      }
+    }
+    for file in 0..files {
+      for side in 0..sides {
+        offset = (offset + 0x3F) & !0x3F;
+        pairs[side * files + file].data = offset;
+        offset += (pairs[side * files + file].blocks_number * pairs[side * files + file].block_size)
+          as usize;
+      }
+    }
+    if offset > bytes.len() {
+      return None;
+    }
+
+    Some(TableData {
+      bytes,
+      pairs,
+      sides,
+      files,
+    })
+  }
+}
+
+/**
+ * The number of each piece (Wp to Bk) on the board.
+ */
+fn material_key(board: &Board) -> [u8; 12] {
+  let mut key: [u8; 12] = [0; 12];
+
+  for (piece, count) in key.iter_mut().enumerate() {
+    *count = board.actual_pieces_number()[piece + 1] as u8;
+  }
+  key
+}
+
+/**
+ * The number of pieces on the board, kings included.
+ */
+pub fn pieces_count(board: &Board) -> usize {
+  board.actual_pieces_number()[Pieces::Wp as usize..=Pieces::Bk as usize]
+    .iter()
+    .sum::<i32>() as usize
+}
+
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+  match wdl {
+    WDL_WIN => 1,
+    WDL_CURSED_WIN => 101,
+    WDL_BLESSED_LOSS => -101,
+    WDL_LOSS => -1,
+    _ => 0,
+  }
+}
+
+fn is_zeroing_move(board: &Board, mov_e: Move) -> bool {
+  mov_e.is_capture() || PIECE_PAWN[board.pieces()[mov_e.from_square() as usize] as usize]
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Syzygy_Bases>
+ * <https://github.com/syzygy1/tb>
+ *
+ * The Syzygy tablebases found in the directories of the SyzygyPath option:
+ * the WDL tables (win/draw/loss) are probed by the search, the DTZ tables (distance to zeroing,
+ * the number of moves to the next capture or pawn move) choose the moves at the root.
+ */
+pub struct Tablebases {
+  entries: Vec<TableEntry>,
+  /**
+   * The entry of each material key, with both colors.
+   */
+  keys: HashMap<[u8; 12], usize>,
+  max_pieces: usize,
+}
+
+impl Tablebases {
+  /**
+   * It looks for the tables in the given directories, separated by ':' (';' on Windows).
+   */
+  pub fn new(paths: &str) -> Tablebases {
+    let separator: char = if cfg!(windows) { ';' } else { ':' };
+    let mut tablebases: Tablebases = Tablebases {
+      entries: Vec::new(),
+      keys: HashMap::new(),
+      max_pieces: 0,
+    };
+    let mut path: PathBuf;
+    let mut dtz_path: PathBuf;
+
+    for directory in paths
+      .split(separator)
+      .filter(|directory| !directory.is_empty())
+    {
+      let directory_entries: fs::ReadDir = match fs::read_dir(directory) {
+        Ok(directory_entries) => directory_entries,
+        Err(_) => continue,
+      };
+      for directory_entry in directory_entries.flatten() {
+        path = directory_entry.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some(WDL_EXTENSION) {
+          continue;
+        }
+        let name: String = match path.file_stem().and_then(|stem| stem.to_str()) {
+          Some(name) => name.to_string(),
+          None => continue,
+        };
+        dtz_path = path.with_extension(DTZ_EXTENSION);
+        let dtz_path: Option<PathBuf> = if dtz_path.exists() {
+          Some(dtz_path)
+        } else {
+          None
+        };
+        if let Some(entry) = TableEntry::new(&name, path, dtz_path) {
+          if tablebases.keys.contains_key(&entry.key) {
+            continue;
+          }
+          tablebases.max_pieces = tablebases.max_pieces.max(entry.pieces_number);
+          tablebases.keys.insert(entry.key, tablebases.entries.len());
+          tablebases
+            .keys
+            .insert(entry.key_2, tablebases.entries.len());
+          tablebases.entries.push(entry);
+        }
+      }
+    }
+
+    tablebases
+  }
+
+  pub fn tables_number(&self) -> usize {
+    self.entries.len()
+  }
+
+  /**
+   * The largest number of pieces of the tables found, zero if there are none.
+   */
+  pub fn max_pieces(&self) -> usize {
+    self.max_pieces
+  }
+
+  /**
+   * It computes the index of the position in the table and reads its value: the position is
+   * seen from the side of the table (white is the stronger side), mirrored to the canonical
+   * squares and its pieces are encoded group by group.
+   */
+  fn probe_table(&self, board: &Board, dtz: bool, wdl: i32, state: &mut ProbeState) -> i32 {
+    let tables: &EncodingTables = encoding_tables();
+    let key: [u8; 12] = material_key(board);
+
+    if pieces_count(board) == 2 {
+      return 0;
+    }
+    let entry: &TableEntry = match self.keys.get(&key) {
+      Some(&index) => &self.entries[index],
+      None => {
+        *state = ProbeState::Fail;
+        return 0;
+      }
+    };
+    let table: &TableData = match if dtz {
+      entry.dtz_table()
+    } else {
+      entry.wdl_table()
+    } {
+      Some(table) => table,
+      None => {
+        *state = ProbeState::Fail;
+        return 0;
+      }
+    };
+    let definitions: &Definitions = board.definitions();
+    // The tables store the positions with white as the stronger side, and only with white to move
+    // if both sides have the same pieces: otherwise the colors are swapped and the board flipped
+    let flip: bool =
+      key != entry.key || (entry.key == entry.key_2 && board.side() == Colors::Black);
+    let flip_color: u8 = if flip { TABLE_COLOR_FLIP } else { 0 };
+    let flip_squares: usize = if flip { 56 } else { 0 };
+    let side: usize = flip as usize ^ (board.side() == Colors::Black) as usize;
+    let mut squares: [usize; TABLEBASE_PIECES] = [0; TABLEBASE_PIECES];
+    let mut pieces: [u8; TABLEBASE_PIECES] = [0; TABLEBASE_PIECES];
+    let mut lead_pawns_number: usize = 0;
+    let mut lead_pawn: u8 = 0;
+    let mut size: usize;
+    let mut file: usize = 0;
+    let mut index: u64;
+    let mut piece: Pieces;
+
+    if entry.has_pawns {
+      // The pawns of the leading color come first in every table
+      lead_pawn = table.pairs(0, 0).pieces[0] ^ flip_color;
+      for square_64 in 0..64 {
+        piece = board.pieces()
+          [definitions.board_64_squares_in_120_squares_notation()[square_64] as usize];
+        if TABLE_PIECES[piece as usize] == lead_pawn {
+          squares[lead_pawns_number] = square_64 ^ flip_squares;
+          lead_pawns_number += 1;
+        }
+      }
+      let lead_index: usize = (0..lead_pawns_number)
+        .rev()
+        .max_by_key(|&index| tables.map_pawns[squares[index]])
+        .unwrap_or(0);
+      // The first of the pawns with the highest value
+      let first_lead_index: usize = (0..lead_pawns_number)
+        .find(|&index| tables.map_pawns[squares[index]] == tables.map_pawns[squares[lead_index]])
+        .unwrap_or(0);
+      squares.swap(0, first_lead_index);
+      file = (squares[0] % 8).min(7 - squares[0] % 8);
+    }
+
+    if dtz {
+      let flags: u8 = table.pairs(0, file).flags;
+      if (entry.has_pawns || entry.key != entry.key_2) && (flags & STM_FLAG) as usize != side {
+        *state = ProbeState::ChangeSide;
+        return 0;
+      }
+    }
+
+    size = lead_pawns_number;
+    for square_64 in 0..64 {
+      piece =
+        board.pieces()[definitions.board_64_squares_in_120_squares_notation()[square_64] as usize];
+      if piece == Pieces::Empty || (entry.has_pawns && TABLE_PIECES[piece as usize] == lead_pawn) {
+        continue;
+      }
+      if size == TABLEBASE_PIECES {
+        *state = ProbeState::Fail;
+        return 0;
+      }
+      squares[size] = square_64 ^ flip_squares;
+      pieces[size] = TABLE_PIECES[piece as usize] ^ flip_color;
+      size += 1;
+    }
+
+    let pairs: &PairsData = table.pairs(side, file);
+
+    // The pieces in the same order of the table
+    for first in lead_pawns_number..size.saturating_sub(1) {
+      for second in first + 1..size {
+        if pairs.pieces[first] == pieces[second] {
+          pieces.swap(first, second);
+          squares.swap(first, second);
+          break;
+        }
+      }
+    }
+
+    // The leading piece on the files a-d
+    if squares[0] % 8 > 3 {
+      for square in squares[0..size].iter_mut() {
+        *square ^= 7;
+      }
+    }
+
+    if entry.has_pawns {
+      index = tables.lead_pawn_index[lead_pawns_number][squares[0]];
+      squares[1..lead_pawns_number].sort_by_key(|&square| tables.map_pawns[square]);
+      for (pawn, &square) in squares.iter().enumerate().take(lead_pawns_number).skip(1) {
+        index += tables.binomial[pawn][tables.map_pawns[square] as usize];
+      }
+    } else {
+      // The leading piece on the ranks 1-4, and below the a1-h8 diagonal
+      if squares[0] / 8 > 3 {
+        for square in squares[0..size].iter_mut() {
+          *square ^= 56;
+        }
+      }
+      for piece_index in 0..pairs.group_length[0] {
+        if off_a1h8(squares[piece_index]) == 0 {
+          continue;
+        }
+        if off_a1h8(squares[piece_index]) > 0 {
+          for square in squares[piece_index..size].iter_mut() {
+            *square = ((*square >> 3) | (*square << 3)) & 63;
+          }
+        }
+        break;
+      }
+
+      if entry.has_unique_pieces {
+        let adjust_1: u64 = (squares[1] > squares[0]) as u64;
+        let adjust_2: u64 = (squares[2] > squares[0]) as u64 + (squares[2] > squares[1]) as u64;
+        let (square_0, square_1, square_2): (u64, u64, u64) =
+          (squares[0] as u64, squares[1] as u64, squares[2] as u64);
+
+        index = if off_a1h8(squares[0]) != 0 {
+          (tables.map_a1d1d4[squares[0]] * 63 + (square_1 - adjust_1)) * 62 + square_2 - adjust_2
+        } else if off_a1h8(squares[1]) != 0 {
+          (6 * 63 + (square_0 / 8) * 28 + tables.map_b1h1h7[squares[1]]) * 62 + square_2 - adjust_2
+        } else if off_a1h8(squares[2]) != 0 {
+          6 * 63 * 62
+            + 4 * 28 * 62
+            + (square_0 / 8) * 7 * 28
+            + ((square_1 / 8) - adjust_1) * 28
+            + tables.map_b1h1h7[squares[2]]
+        } else {
+          6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + (square_0 / 8) * 7 * 6
+            + ((square_1 / 8) - adjust_1) * 6
+            + (square_2 / 8)
+            - adjust_2
+        };
+      } else {
+        index = tables.map_kk[tables.map_a1d1d4[squares[0]] as usize][squares[1]];
+      }
+    }
+
+    // The other groups, each as a combination of the squares not used by the previous groups
+    index *= pairs.group_index[0];
+    let mut group_start: usize = pairs.group_length[0];
+    let mut remaining_pawns: bool = entry.has_pawns && entry.pawn_count[1] > 0;
+    let mut next: usize = 1;
+    let mut combination: u64;
+    while pairs.group_length[next] != 0 {
+      let group_end: usize = (group_start + pairs.group_length[next]).min(size);
+      squares[group_start..group_end].sort();
+      combination = 0;
+      for piece_index in group_start..group_end {
+        let adjust: usize = squares[0..group_start]
+          .iter()
+          .filter(|&&square| squares[piece_index] > square)
+          .count();
+        combination += tables.binomial[piece_index - group_start + 1]
+          [squares[piece_index] - adjust - 8 * remaining_pawns as usize];
+      }
+      remaining_pawns = false;
+      index += combination * pairs.group_index[next];
+      group_start = group_end;
+      next += 1;
+    }
+
+    let value: i32 = match pairs.decompress(&table.bytes, index) {
+      Some(value) => value,
+      None => {
+        *state = ProbeState::Fail;
+        return 0;
+      }
+    };
+    if !dtz {
+      return value - 2;
+    }
+    self.map_dtz_score(table, file, value, wdl)
+  }
+
+  /**
+   * The DTZ value, in plies: the tables store moves when the flags say so.
+   */
+  fn map_dtz_score(&self, table: &TableData, file: usize, mut value: i32, wdl: i32) -> i32 {
+    const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+    let pairs: &PairsData = table.pairs(0, file);
+    let map_index: usize = pairs.map_index[WDL_MAP[(wdl + 2) as usize]];
+
+    if pairs.flags & MAPPED_FLAG != 0 {
+      value = if pairs.flags & WIDE_FLAG != 0 {
+        read_u16(&table.bytes, map_index + 2 * value as usize).unwrap_or(0) as i32
+      } else {
+        read_u8(&table.bytes, map_index + value as usize).unwrap_or(0) as i32
+      };
+    }
+
+    if (wdl == WDL_WIN && pairs.flags & WIN_PLIES_FLAG == 0)
+      || (wdl == WDL_LOSS && pairs.flags & LOSS_PLIES_FLAG == 0)
+      || wdl == WDL_CURSED_WIN
+      || wdl == WDL_BLESSED_LOSS
+    {
+      value *= 2;
+    }
+
+    value + 1
+  }
+
+  /**
+   * The tables don't know the en passant captures, and a position where the best move is
+   * a capture (or a pawn move, with check_zeroing_moves) stores a "don't care" DTZ value:
+   * so the captures are searched before probing the table.
+   */
+  fn search(&self, board: &mut Board, check_zeroing_moves: bool, state: &mut ProbeState) -> i32 {
+    let legal_moves: Vec<Move> = generate_legal_moves(board);
+    let mut best_value: i32 = WDL_LOSS;
+    let mut moves_number: usize = 0;
+    let mut value: i32;
+
+    for &mov_e in &legal_moves {
+      if !mov_e.is_capture()
+        && (!check_zeroing_moves
+          || !PIECE_PAWN[board.pieces()[mov_e.from_square() as usize] as usize])
+      {
+        continue;
+      }
+      moves_number += 1;
+
+      board.make_move(mov_e);
+      value = -self.search(board, false, state);
+      board.take_move();
+
+      if *state == ProbeState::Fail {
+        return WDL_DRAW;
+      }
+      if value > best_value {
+        best_value = value;
+        if value >= WDL_WIN {
+          *state = ProbeState::ZeroingBestMove;
+          return value;
+        }
+      }
+    }
+
+    // All the moves have been searched, the table could store a wrong value
+    let no_more_moves: bool = moves_number > 0 && moves_number == legal_moves.len();
+    if no_more_moves {
+      value = best_value;
+    } else {
+      value = self.probe_table(board, false, WDL_DRAW, state);
+      if *state == ProbeState::Fail {
+        return WDL_DRAW;
+      }
+    }
+
+    if best_value >= value {
+      *state = if best_value > WDL_DRAW || no_more_moves {
+        ProbeState::ZeroingBestMove
+      } else {
+        ProbeState::Ok
+      };
+      return best_value;
+    }
+
+    *state = ProbeState::Ok;
+    value
+  }
+
+  /**
+   * The WDL result of the position for the side to move, None if it's not in the tables.
+   */
+  pub fn probe_wdl(&self, board: &mut Board) -> Option<i32> {
+    let mut state: ProbeState = ProbeState::Ok;
+    let wdl: i32 = self.search(board, false, &mut state);
+
+    if state == ProbeState::Fail {
+      None
+    } else {
+      Some(wdl)
+    }
+  }
+
+  fn probe_dtz_state(&self, board: &mut Board, state: &mut ProbeState) -> i32 {
+    let mut min_dtz: i32 = 0xFFFF;
+    let mut dtz: i32;
+    let mut zeroing: bool;
+
+    *state = ProbeState::Ok;
+    let wdl: i32 = self.search(board, true, state);
+    // The DTZ tables don't store the draws
+    if *state == ProbeState::Fail || wdl == WDL_DRAW {
+      return 0;
+    }
+    if *state == ProbeState::ZeroingBestMove {
+      return dtz_before_zeroing(wdl);
+    }
+
+    dtz = self.probe_table(board, true, wdl, state);
+    if *state == ProbeState::Fail {
+      return 0;
+    }
+    if *state != ProbeState::ChangeSide {
+      return (dtz + 100 * (wdl == WDL_BLESSED_LOSS || wdl == WDL_CURSED_WIN) as i32)
+        * wdl.signum();
+    }
+
+    // The table stores the other side to move: one ply search for the move with the best DTZ
+    for mov_e in generate_legal_moves(board) {
+      zeroing = is_zeroing_move(board, mov_e);
+      board.make_move(mov_e);
+
+      dtz = if zeroing {
+        -dtz_before_zeroing(self.search(board, false, state))
+      } else {
+        -self.probe_dtz_state(board, state)
+      };
+      // A mate
+      if dtz == 1 && is_in_check(board) && generate_legal_moves(board).is_empty() {
+        min_dtz = 1;
+      }
+      if !zeroing {
+        dtz += dtz.signum();
+      }
+      if dtz < min_dtz && dtz.signum() == wdl.signum() {
+        min_dtz = dtz;
+      }
+
+      board.take_move();
+      if *state == ProbeState::Fail {
+        return 0;
+      }
+    }
+
+    if min_dtz == 0xFFFF {
+      -1
+    } else {
+      min_dtz
+    }
+  }
+
+  /**
+   * The distance in plies to the next capture or pawn move (positive if the side to move wins,
+   * negative if it loses, zero for a draw), None if it's not in the tables.
+   */
+  pub fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+    let mut state: ProbeState = ProbeState::Ok;
+    let dtz: i32 = self.probe_dtz_state(board, &mut state);
+
+    if state == ProbeState::Fail {
+      None
+    } else {
+      Some(dtz)
+    }
+  }
+
+  /**
+   * The root moves that keep the best result according to the DTZ tables: a win is kept
+   * within the fifty moves rule, and a loss is delayed as much as possible. It returns
+   * the moves with the score of the position, None if the tables can't rank every move.
+   */
+  pub fn root_moves(&self, board: &mut Board) -> Option<(Vec<Move>, i32)> {
+    let fifty_moves: i32 = board.fifty_full_moves();
+    let repetition: bool = board.history()
+      [board.history().len().saturating_sub(fifty_moves as usize)..]
+      .iter()
+      .enumerate()
+      .any(|(index, undo)| {
+        board.history()[board.history().len().saturating_sub(fifty_moves as usize) + index + 1..]
+          .iter()
+          .any(|other| other.position_key() == undo.position_key())
+      });
+    let mut ranked_moves: Vec<(Move, i32)> = Vec::new();
+    let mut state: ProbeState = ProbeState::Ok;
+    let mut dtz: i32;
+    let mut rank: i32;
+
+    for mov_e in generate_legal_moves(board) {
+      board.make_move(mov_e);
+
+      if board.fifty_full_moves() == 0 {
+        let mut wdl_state: ProbeState = ProbeState::Ok;
+        dtz = dtz_before_zeroing(-self.search(board, false, &mut wdl_state));
+        if wdl_state == ProbeState::Fail {
+          state = ProbeState::Fail;
+        }
+      } else if board.fifty_full_moves() >= 100 || is_repetition(board) {
+        dtz = 0;
+      } else {
+        dtz = -self.probe_dtz_state(board, &mut state);
+        dtz += dtz.signum();
+      }
+      // A mate
+      if dtz == 2 && is_in_check(board) && generate_legal_moves(board).is_empty() {
+        dtz = 1;
+      }
+
+      board.take_move();
+      if state == ProbeState::Fail {
+        return None;
+      }
+
+      // The wins within the fifty moves rule are ranked equally, the losses too unless
+      // a draw by the fifty moves rule is near
+      rank = if dtz > 0 {
+        if dtz + fifty_moves <= 99 && !repetition {
+          MAX_DTZ
+        } else {
+          MAX_DTZ - (dtz + fifty_moves)
+        }
+      } else if dtz < 0 {
+        if -dtz * 2 + fifty_moves < 100 {
+          -MAX_DTZ
+        } else {
+          -MAX_DTZ + (-dtz + fifty_moves)
+        }
+      } else {
+        0
+      };
+      ranked_moves.push((mov_e, rank));
+    }
+
+    let best_rank: i32 = ranked_moves.iter().map(|&(_, rank)| rank).max()?;
+    let score: i32 = if best_rank >= MAX_DTZ - 100 {
+      TABLEBASE_WIN
+    } else if best_rank > 0 {
+      (best_rank - (MAX_DTZ - 200)).max(3) * PIECE_VALUE[Pieces::Wp as usize] / 200
+    } else if best_rank == 0 {
+      0
+    } else if best_rank > -(MAX_DTZ - 100) {
+      (best_rank + (MAX_DTZ - 200)).min(-3) * PIECE_VALUE[Pieces::Wp as usize] / 200
+    } else {
+      -TABLEBASE_WIN
+    };
+
+    Some((
+      ranked_moves
+        .into_iter()
+        .filter(|&(_, rank)| rank == best_rank)
+        .map(|(mov_e, _)| mov_e)
+        .collect(),
+      score,
+    ))
+  }
+}
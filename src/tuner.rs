@@ -0,0 +1,413 @@
+use std::fs;
+use std::thread;
+
+use crate::board::*;
+use crate::definitions::*;
+use crate::evaluate::*;
+use crate::parameters::*;
+use crate::pawns::*;
+use crate::pvtable::*;
+use crate::search::*;
+
+/**
+ * The tuner settings, given on the command line after the positions file:
+ * tune <positions file> [--threads <n>] [--iterations <n>] [--output <file>] [--quiescence]
+ * An output file ending with ".rs" gets the parameters as Rust source, any other file gets
+ * them in the format read by EvaluationParameters::from_file.
+ */
+struct TunerSettings {
+  positions_path: String,
+  threads: usize,
+  iterations: usize,
+  output_path: String,
+  quiescence: bool,
+}
+
+impl TunerSettings {
+  fn from_arguments(arguments: &[String]) -> Result<TunerSettings, String> {
+    let mut settings: TunerSettings = TunerSettings {
+      positions_path: String::new(),
+      threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+      iterations: 100,
+      output_path: String::from("parameters.txt"),
+      quiescence: false,
+    };
+    let mut tokens = arguments.iter();
+
+    while let Some(token) = tokens.next() {
+      match token.as_str() {
+        "--threads" | "--iterations" | "--output" => {
+          let value: &String = tokens
+            .next()
+            .ok_or_else(|| format!("{} needs a value", token))?;
+          match token.as_str() {
+            "--threads" => {
+              settings.threads = value
+                .parse::<usize>()
+                .map_err(|error| format!("--threads: {}", error))?
+                .max(1)
+            }
+            "--iterations" => {
+              settings.iterations = value
+                .parse::<usize>()
+                .map_err(|error| format!("--iterations: {}", error))?
+            }
+            _ => settings.output_path = value.clone(),
+          }
+        }
+        "--quiescence" => settings.quiescence = true,
+        _ => settings.positions_path = token.clone(),
+      }
+    }
+
+    if settings.positions_path.is_empty() {
+      return Err(String::from(
+        "usage: tune <positions file> [--threads <n>] [--iterations <n>] [--output <file>] [--quiescence]",
+      ));
+    }
+    Ok(settings)
+  }
+}
+
+/**
+ * A labelled position: the FEN and the result of the game from the white point of view
+ * (1.0 white won, 0.5 draw, 0.0 black won).
+ */
+struct TuningPosition {
+  fen: String,
+  result: f64,
+}
+
+/**
+ * Every line has a FEN (at least the first four fields) followed by the result of the game,
+ * written as "1-0", "0-1", "1/2-1/2" or as "[1.0]", "[0.5]", "[0.0]".
+ * The "fen | score | result" lines written by datagen are read as well.
+ */
+fn parse_position_line(line: &str) -> Option<TuningPosition> {
+  let fields: Vec<&str> = line.split_whitespace().collect();
+
+  if let [fen, _, result] = line.split('|').collect::<Vec<&str>>()[..] {
+    let fen_fields: Vec<&str> = fen.split_whitespace().collect();
+    return match (fen_fields.len() >= 4, result.trim().parse::<f64>()) {
+      (true, Ok(result)) => Some(TuningPosition {
+        fen: format!("{} 0 1", fen_fields[0..4].join(" ")),
+        result,
+      }),
+      _ => None,
+    };
+  }
+
+  if fields.len() < 5 {
+    return None;
+  }
+  let label: String = fields[4..].join(" ");
+  let result: f64 = if label.contains("1/2-1/2") || label.contains("[0.5]") {
+    0.5
+  } else if label.contains("1-0") || label.contains("[1.0]") || label.contains("[1]") {
+    1.0
+  } else if label.contains("0-1") || label.contains("[0.0]") || label.contains("[0]") {
+    0.0
+  } else {
+    return None;
+  };
+
+  Some(TuningPosition {
+    fen: format!("{} 0 1", fields[0..4].join(" ")),
+    result,
+  })
+}
+
+fn load_positions(path: &str) -> Result<Vec<TuningPosition>, String> {
+  let text: String = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+  let mut board: Board = Board::new();
+  // The lines with a FEN that can not be read are skipped as the unlabelled ones
+  let positions: Vec<TuningPosition> = text
+    .lines()
+    .filter_map(parse_position_line)
+    .filter(|position| board.parse_fen(&position.fen).is_ok())
+    .collect();
+
+  if positions.is_empty() {
+    return Err(format!("{}: no labelled position found", path));
+  }
+  Ok(positions)
+}
+
+/**
+ * It converts a score to the expected result of the game for white.
+ */
+fn sigmoid(score: f64, k: f64) -> f64 {
+  1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+/**
+ * What each thread of the tuner needs to evaluate its positions, kept between the evaluations
+ * so that the tables are not allocated every time the parameters change.
+ */
+struct TunerThread {
+  info: SearchInfo,
+  table: HashTable,
+  pawn_table: PawnHashTable,
+}
+
+impl TunerThread {
+  fn new() -> TunerThread {
+    TunerThread {
+      info: SearchInfo::new(),
+      table: HashTable::new(1),
+      pawn_table: PawnHashTable::new(1),
+    }
+  }
+
+  /**
+   * The scores (from the white point of view) of a slice of positions with the given parameters.
+   */
+  fn position_scores(
+    &mut self,
+    positions: &[TuningPosition],
+    parameters: &EvaluationParameters,
+    quiescence: bool,
+  ) -> Vec<f64> {
+    let mut board: Board = Board::new();
+
+    // The stored pawn structures were evaluated with the previous parameters
+    if quiescence {
+      self.info.set_parameters(*parameters);
+    } else {
+      self.pawn_table.clear();
+    }
+
+    positions
+      .iter()
+      .map(|position| {
+        board
+          .parse_fen(&position.fen)
+          .expect("Error: the positions are checked when loaded");
+        let score: i32 = if quiescence {
+          quiescence_evaluation(&mut board, &mut self.info, &mut self.table)
+        } else {
+          evaluate_position(&board, parameters, &mut self.pawn_table)
+        };
+        if board.side() == Colors::White {
+          score as f64
+        } else {
+          -score as f64
+        }
+      })
+      .collect()
+  }
+}
+
+/**
+ * The scores of every position, each thread evaluates a part of them.
+ */
+fn all_scores(
+  positions: &[TuningPosition],
+  parameters: &EvaluationParameters,
+  settings: &TunerSettings,
+  threads: &mut [TunerThread],
+) -> Vec<f64> {
+  let chunk_size: usize = positions.len().div_ceil(threads.len());
+
+  thread::scope(|scope| {
+    let handles: Vec<_> = positions
+      .chunks(chunk_size)
+      .zip(threads.iter_mut())
+      .map(|(chunk, tuner_thread)| {
+        scope.spawn(move || tuner_thread.position_scores(chunk, parameters, settings.quiescence))
+      })
+      .collect();
+
+    handles
+      .into_iter()
+      .flat_map(|handle| handle.join().expect("tuner thread panicked"))
+      .collect()
+  })
+}
+
+/**
+ * The mean squared error between the game results and the results expected from the scores.
+ */
+fn mean_squared_error(positions: &[TuningPosition], scores: &[f64], k: f64) -> f64 {
+  positions
+    .iter()
+    .zip(scores)
+    .map(|(position, &score)| (position.result - sigmoid(score, k)).powi(2))
+    .sum::<f64>()
+    / positions.len() as f64
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Texel%27s_Tuning_Method>
+ *
+ * The scaling constant K that minimizes the error with the current parameters, found
+ * refining the best value one decimal digit at a time.
+ */
+fn find_best_k(positions: &[TuningPosition], scores: &[f64]) -> f64 {
+  let mut best_k: f64 = 1.0;
+  let mut best_error: f64 = mean_squared_error(positions, scores, best_k);
+  let mut step: f64 = 1.0;
+  let mut k: f64;
+  let mut error: f64;
+
+  for _ in 0..5 {
+    let start: f64 = (best_k - 10.0 * step).max(0.0);
+    for index in 0..=20 {
+      k = start + index as f64 * step;
+      error = mean_squared_error(positions, scores, k);
+      if error < best_error {
+        best_error = error;
+        best_k = k;
+      }
+    }
+    step /= 10.0;
+  }
+
+  best_k
+}
+
+fn write_parameters(parameters: &EvaluationParameters, path: &str) {
+  let text: String = if path.ends_with(".rs") {
+    parameters.rust_source() + "\n"
+  } else {
+    parameters.file_text()
+  };
+
+  if let Err(error) = fs::write(path, text) {
+    println!("{}: {}", path, error);
+  }
+}
+
+/**
+ * References:
+ * <https://www.chessprogramming.org/Texel%27s_Tuning_Method>
+ *
+ * The tune subcommand: every parameter is moved by one, up and then down, and the change is kept
+ * if the error over the labelled positions decreases (local search). The passes over the parameters
+ * go on until none of them improves the error, or the iterations are over.
+ * The parameters are written to the output file after every pass.
+ */
+pub fn tune(arguments: &[String]) {
+  let settings: TunerSettings = match TunerSettings::from_arguments(arguments) {
+    Ok(settings) => settings,
+    Err(error) => {
+      println!("{}", error);
+      return;
+    }
+  };
+  let positions: Vec<TuningPosition> = match load_positions(&settings.positions_path) {
+    Ok(positions) => positions,
+    Err(error) => {
+      println!("{}", error);
+      return;
+    }
+  };
+  let mut threads: Vec<TunerThread> = (0..settings.threads).map(|_| TunerThread::new()).collect();
+  let mut parameters: EvaluationParameters = EvaluationParameters::new();
+  let mut vector: Vec<i32> = parameters.vector();
+  let mut scores: Vec<f64> = all_scores(&positions, &parameters, &settings, &mut threads);
+  let k: f64 = find_best_k(&positions, &scores);
+  let mut best_error: f64 = mean_squared_error(&positions, &scores, k);
+  let mut improved: bool = true;
+  let mut error: f64;
+
+  println!(
+    "positions {} threads {} K {:.4} error {:.8}",
+    positions.len(),
+    settings.threads,
+    k,
+    best_error
+  );
+
+  for iteration in 1..=settings.iterations {
+    if !improved {
+      break;
+    }
+    improved = false;
+
+    for index in 0..vector.len() {
+      for change in [1, -2] {
+        vector[index] += change;
+        parameters.set_vector(&vector);
+        scores = all_scores(&positions, &parameters, &settings, &mut threads);
+        error = mean_squared_error(&positions, &scores, k);
+        if error < best_error {
+          best_error = error;
+          improved = true;
+          break;
+        }
+        if change == -2 {
+          // Neither up nor down is better: back to the starting value
+          vector[index] += 1;
+        }
+      }
+    }
+
+    parameters.set_vector(&vector);
+    write_parameters(&parameters, &settings.output_path);
+    println!("iteration {} error {:.8}", iteration, best_error);
+  }
+
+  println!("{}", parameters.rust_source());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sigmoid_values() {
+    assert_eq!(sigmoid(0.0, 1.3), 0.5);
+    assert!((sigmoid(400.0, 1.0) - 10.0 / 11.0).abs() < 1e-12);
+    assert!((sigmoid(-200.0, 2.0) - 1.0 / 11.0).abs() < 1e-12);
+    assert!((sigmoid(150.0, 1.2) + sigmoid(-150.0, 1.2) - 1.0).abs() < 1e-12);
+  }
+
+  #[test]
+  fn best_k_fits_the_results() {
+    let scores: Vec<f64> = (-20..=20).map(|index| index as f64 * 40.0).collect();
+
+    for expected_k in [0.6, 1.13, 1.9] {
+      // The results are the expected ones of the scores with the wanted K
+      let positions: Vec<TuningPosition> = scores
+        .iter()
+        .map(|&score| TuningPosition {
+          fen: String::from(START_FEN),
+          result: sigmoid(score, expected_k),
+        })
+        .collect();
+      let k: f64 = find_best_k(&positions, &scores);
+
+      assert!(
+        (k - expected_k).abs() < 1e-3,
+        "{} instead of {}",
+        k,
+        expected_k
+      );
+      assert!(mean_squared_error(&positions, &scores, k) < 1e-8);
+    }
+  }
+
+  #[test]
+  fn position_lines() {
+    let fen: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3";
+
+    for (line, result) in [
+      (format!("{} 0 1 1-0", fen), 1.0),
+      (format!("{} c9 \"0-1\";", fen), 0.0),
+      (format!("{} 1/2-1/2", fen), 0.5),
+      (format!("{} [0.5]", fen), 0.5),
+      (format!("{} [1.0]", fen), 1.0),
+      (format!("{} 0 1 | 35 | 0.0", fen), 0.0),
+    ] {
+      let position: TuningPosition = parse_position_line(&line).unwrap();
+
+      assert_eq!(position.fen, format!("{} 0 1", fen), "{}", line);
+      assert_eq!(position.result, result, "{}", line);
+    }
+    assert!(parse_position_line(fen).is_none());
+    assert!(parse_position_line(&format!("{} 0 1", fen)).is_none());
+    assert!(parse_position_line("8/8 w | 35 | 1.0").is_none());
+  }
+}
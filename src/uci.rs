@@ -0,0 +1,572 @@
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::board::*;
+use crate::definitions::*;
+use crate::movegen::*;
+use crate::nnue::*;
+use crate::parameters::*;
+use crate::pvtable::*;
+use crate::render::*;
+use crate::search::*;
+use crate::syzygy::*;
+
+/**
+ * When the GUI does not tell in how many moves the time has to be used,
+ * we assume there are still this number of moves to play.
+ */
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+/**
+ * Time (in milliseconds) kept aside to send the best move before the clock runs out.
+ */
+const TIME_MARGIN: u64 = 50;
+const DEFAULT_HASH_MEGABYTES: usize = 64;
+const MAX_HASH_MEGABYTES: usize = 1024;
+/**
+ * The range of UCI_Elo, from the weakest to the strongest anchor of ELO_ANCHORS.
+ */
+const MIN_ELO: i32 = 800;
+const MAX_ELO: i32 = 2500;
+/**
+ * The skill levels measured with the match runner, and their Elo. The
+ * reference is the full strength engine (Skill Level 20), pinned at MAX_ELO.
+ * Every pair played 100 games at 20000 nodes per move, from 300 openings of 8
+ * random legal moves:
+ * chess-engine match --option1 "Skill Level=<a>" --option2 "Skill Level=<b>"
+ * --openings <epd> --games 100 --nodes 20000
+ *
+ * 17, 14, 11 and 8 were played against the reference (-413, -424, -576,
+ * -727 +/- 2847), 6 against 8 (-56) and each level from 0 to 5 against the
+ * next one (-338, -235, -147, -56, -100, -35). The anchor of 8 rests on 1
+ * win in 100 games, so the levels below it only have a rough absolute Elo.
+ */
+const ELO_ANCHORS: [(i32, i32); 11] = [
+  (0, 806),
+  (1, 1144),
+  (2, 1379),
+  (3, 1526),
+  (4, 1582),
+  (5, 1682),
+  (6, 1717),
+  (8, 1773),
+  (11, 1924),
+  (14, 2076),
+  (17, 2087),
+];
+
+/**
+ * The name of the UCI option of every search technique that can be disabled.
+ */
+const SEARCH_OPTIONS: [&str; 9] = [
+  "NullMovePruning",
+  "LateMoveReductions",
+  "PrincipalVariationSearch",
+  "AspirationWindows",
+  "FutilityPruning",
+  "ReverseFutilityPruning",
+  "Razoring",
+  "CheckExtensions",
+  "InternalIterativeReductions",
+];
+
+fn search_option<'a>(options: &'a mut SearchOptions, name: &str) -> Option<&'a mut bool> {
+  match name {
+    "NullMovePruning" => Some(&mut options.null_move_pruning),
+    "LateMoveReductions" => Some(&mut options.late_move_reductions),
+    "PrincipalVariationSearch" => Some(&mut options.principal_variation_search),
+    "AspirationWindows" => Some(&mut options.aspiration_windows),
+    "FutilityPruning" => Some(&mut options.futility_pruning),
+    "ReverseFutilityPruning" => Some(&mut options.reverse_futility_pruning),
+    "Razoring" => Some(&mut options.razoring),
+    "CheckExtensions" => Some(&mut options.check_extensions),
+    "InternalIterativeReductions" => Some(&mut options.internal_iterative_reductions),
+    _ => None,
+  }
+}
+
+/**
+ * The input is read by another thread, so that a "stop" (or a "quit") sent by the GUI
+ * reaches the search while it's running. Every line is then passed to the UCI loop.
+ * The stop_signal is cleared here when a "go" is read, never by the search: a "stop" read
+ * after the "go" is never lost, even if it arrives before the search has started.
+ * The same for the "ponderhit": the ponder_signal is set by a "go ponder" and cleared
+ * by the ponderhit, in the order they are read.
+ */
+fn spawn_input_reader(
+  stop_signal: Arc<AtomicBool>,
+  ponder_signal: Arc<AtomicBool>,
+) -> Receiver<String> {
+  let (sender, receiver): (Sender<String>, Receiver<String>) = mpsc::channel();
+
+  thread::spawn(move || {
+    for line in io::stdin().lock().lines() {
+      let line: String = match line {
+        Ok(line) => line,
+        Err(_) => break,
+      };
+      let command: &str = line.split_whitespace().next().unwrap_or("");
+      if command == "stop" || command == "quit" {
+        stop_signal.store(true, Ordering::Relaxed);
+      } else if command == "go" {
+        stop_signal.store(false, Ordering::Relaxed);
+        ponder_signal.store(
+          line.split_whitespace().any(|token| token == "ponder"),
+          Ordering::Relaxed,
+        );
+      } else if command == "ponderhit" {
+        ponder_signal.store(false, Ordering::Relaxed);
+      }
+      if sender.send(line).is_err() {
+        break;
+      }
+    }
+    // The input is closed, the engine quits after the running search
+    let _ = sender.send(String::from("quit"));
+  });
+
+  receiver
+}
+
+/**
+ * The NNUE network loaded with the EvalFile option, used by the evaluation
+ * only when the UseNNUE option is set (the classical evaluation otherwise).
+ */
+struct NnueSettings {
+  network: Option<Arc<Network>>,
+  use_nnue: bool,
+}
+
+impl NnueSettings {
+  fn new() -> NnueSettings {
+    NnueSettings {
+      network: None,
+      use_nnue: false,
+    }
+  }
+
+  fn apply(&self, board: &mut Board) {
+    if self.use_nnue && self.network.is_none() {
+      println!("info string UseNNUE needs a network, set EvalFile");
+    }
+    board.set_network(if self.use_nnue {
+      self.network.clone()
+    } else {
+      None
+    });
+  }
+}
+
+/**
+ * The strength set with the Skill Level option, or with UCI_Elo when the
+ * UCI_LimitStrength option is set.
+ */
+struct StrengthSettings {
+  skill_level: i32,
+  limit_strength: bool,
+  elo: i32,
+}
+
+impl StrengthSettings {
+  fn new() -> StrengthSettings {
+    StrengthSettings {
+      skill_level: MAX_SKILL_LEVEL,
+      limit_strength: false,
+      elo: MAX_ELO,
+    }
+  }
+
+  fn apply(&self, info: &mut SearchInfo) {
+    info.set_skill_level(if self.limit_strength {
+      elo_skill_level(self.elo)
+    } else {
+      self.skill_level
+    });
+  }
+}
+
+/**
+ * The skill level that plays at the given Elo, interpolated between the
+ * anchors of ELO_ANCHORS and rounded down, so that the engine never plays
+ * stronger than asked. MAX_ELO is only reached by the full strength.
+ */
+fn elo_skill_level(elo: i32) -> i32 {
+  let mut lower: (i32, i32) = ELO_ANCHORS[0];
+
+  if elo >= MAX_ELO {
+    return MAX_SKILL_LEVEL;
+  }
+  for upper in ELO_ANCHORS[1..]
+    .iter()
+    .copied()
+    .chain([(MAX_SKILL_LEVEL, MAX_ELO)])
+  {
+    if elo < upper.1 {
+      return lower.0 + (elo - lower.1).max(0) * (upper.0 - lower.0) / (upper.1 - lower.1);
+    }
+    lower = upper;
+  }
+  MAX_SKILL_LEVEL
+}
+
+fn print_options(info: &SearchInfo) {
+  let mut options: SearchOptions = *info.options();
+
+  println!(
+    "option name Hash type spin default {} min 1 max {}",
+    DEFAULT_HASH_MEGABYTES, MAX_HASH_MEGABYTES
+  );
+  println!("option name ParametersFile type string default <empty>");
+  println!("option name EvalFile type string default <empty>");
+  println!("option name UseNNUE type check default false");
+  println!("option name SyzygyPath type string default <empty>");
+  println!("option name Ponder type check default true");
+  println!(
+    "option name MultiPV type spin default 1 min 1 max {}",
+    MAX_MULTI_PV
+  );
+  println!(
+    "option name Skill Level type spin default {} min 0 max {}",
+    MAX_SKILL_LEVEL, MAX_SKILL_LEVEL
+  );
+  println!("option name UCI_LimitStrength type check default false");
+  println!(
+    "option name UCI_Elo type spin default {} min {} max {}",
+    MAX_ELO, MIN_ELO, MAX_ELO
+  );
+  println!("option name UCI_Chess960 type check default false");
+  for name in SEARCH_OPTIONS {
+    if let Some(value) = search_option(&mut options, name) {
+      println!("option name {} type check default {}", name, value);
+    }
+  }
+}
+
+/**
+ * setoption name <name> [value <value>]
+ */
+fn parse_set_option(
+  line: &str,
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+  nnue: &mut NnueSettings,
+  strength: &mut StrengthSettings,
+  ponder: &mut bool,
+) {
+  let (name, value): (&str, &str) = match line.strip_prefix("setoption name ") {
+    Some(option) => match option.split_once(" value ") {
+      Some((name, value)) => (name.trim(), value.trim()),
+      None => (option.trim(), ""),
+    },
+    None => return,
+  };
+
+  if name == "Hash" {
+    if let Ok(megabytes) = value.parse::<usize>() {
+      *table = HashTable::new(megabytes.clamp(1, MAX_HASH_MEGABYTES));
+    }
+  } else if name == "ParametersFile" {
+    // The evaluation parameters written by the tuner
+    if !value.is_empty() && value != "<empty>" {
+      match EvaluationParameters::from_file(value) {
+        Ok(parameters) => info.set_parameters(parameters),
+        Err(error) => println!("info string {}", error),
+      }
+    }
+  } else if name == "EvalFile" {
+    if !value.is_empty() && value != "<empty>" {
+      match Network::from_file(value) {
+        Ok(network) => {
+          nnue.network = Some(Arc::new(network));
+          nnue.apply(board);
+        }
+        Err(error) => println!("info string {}", error),
+      }
+    }
+  } else if name == "UseNNUE" {
+    nnue.use_nnue = value == "true";
+    nnue.apply(board);
+  } else if name == "SyzygyPath" {
+    if value.is_empty() || value == "<empty>" {
+      info.set_tablebases(None);
+    } else {
+      let tablebases: Tablebases = Tablebases::new(value);
+      println!(
+        "info string found {} tablebases with up to {} pieces",
+        tablebases.tables_number(),
+        tablebases.max_pieces()
+      );
+      info.set_tablebases(Some(Arc::new(tablebases)));
+    }
+  } else if name == "MultiPV" {
+    if let Ok(lines) = value.parse::<usize>() {
+      info.set_multi_pv(lines);
+    }
+  } else if name == "Ponder" {
+    *ponder = value == "true";
+  } else if name == "Skill Level" {
+    if let Ok(skill_level) = value.parse::<i32>() {
+      strength.skill_level = skill_level.clamp(0, MAX_SKILL_LEVEL);
+      strength.apply(info);
+    }
+  } else if name == "UCI_LimitStrength" {
+    strength.limit_strength = value == "true";
+    strength.apply(info);
+  } else if name == "UCI_Elo" {
+    if let Ok(elo) = value.parse::<i32>() {
+      strength.elo = elo.clamp(MIN_ELO, MAX_ELO);
+      strength.apply(info);
+    }
+  } else if name == "UCI_Chess960" {
+    board.set_chess960(value == "true");
+  } else if let Some(option) = search_option(info.options_mut(), name) {
+    match value {
+      "true" => *option = true,
+      "false" => *option = false,
+      _ => {}
+    }
+  }
+}
+
+/**
+ * print [ascii | unicode | ansi] [flip] [nocoords]
+ */
+fn parse_print(line: &str, board: &Board) {
+  let mut options: RenderOptions = RenderOptions::new();
+
+  for token in line.split_whitespace().skip(1) {
+    match token {
+      "ascii" => options.style = BoardStyle::Ascii,
+      "unicode" => options.style = BoardStyle::Unicode,
+      "ansi" => options.style = BoardStyle::Ansi,
+      "flip" => options.flipped = true,
+      "nocoords" => options.coordinates = false,
+      _ => {}
+    }
+  }
+  println!("\n{}", board.render(options));
+}
+
+/**
+ * position [startpos | fen <fen>] [moves <move1> ... <moveN>]
+ */
+fn parse_position(line: &str, board: &mut Board) {
+  let (position, moves): (&str, &str) = match line.split_once(" moves ") {
+    Some((position, moves)) => (position, moves),
+    None => (line, ""),
+  };
+
+  // A FEN that can not be read leaves an empty board, that parse_go does not search
+  if let Err(error) = board.parse_fen(position.strip_prefix("position fen ").unwrap_or(START_FEN)) {
+    println!("info string {}", error);
+    return;
+  }
+  // The GUI is told what is wrong with the position, that parse_go will not search
+  if let Err(reasons) = board.is_legal_position() {
+    for reason in reasons {
+      println!("info string illegal position: {}", reason);
+    }
+  }
+
+  for algebraic in moves.split_whitespace() {
+    let mov_e: Move = parse_move(board, algebraic);
+    if mov_e == Move::NO_MOVE || !board.make_move(mov_e) {
+      break;
+    }
+  }
+  board.set_actual_half_moves(0);
+}
+
+/**
+ * go [depth <d>] [movetime <ms>] [wtime <ms>] [btime <ms>] [winc <ms>] [binc <ms>]
+ *    [movestogo <n>] [nodes <n>] [mate <n>] [searchmoves <move> ...] [ponder] [infinite]
+ *
+ * An illegal position (ex. without a king) is not searched, the answer is the null move.
+ */
+fn parse_go(
+  line: &str,
+  board: &mut Board,
+  info: &mut SearchInfo,
+  table: &mut HashTable,
+  ponder_enabled: bool,
+) {
+  if board.is_legal_position().is_err() {
+    println!("info string illegal position, not searched");
+    println!("bestmove 0000");
+    return;
+  }
+
+  let mut tokens = line.split_whitespace().skip(1).peekable();
+  let mut depth: Option<i32> = None;
+  let mut nodes: Option<u64> = None;
+  let mut mate: Option<i32> = None;
+  let mut search_moves: Vec<Move> = Vec::new();
+  let mut mov_e: Move;
+  let mut move_time: Option<u64> = None;
+  let mut time: Option<u64> = None;
+  let mut increment: u64 = 0;
+  let mut moves_to_go: u64 = DEFAULT_MOVES_TO_GO;
+  let mut ponder: bool = false;
+  let white_to_move: bool = board.side() == Colors::White;
+  let legal_moves: Vec<Move> = generate_legal_moves(board);
+
+  while let Some(token) = tokens.next() {
+    // The moves of searchmoves go on until a token that is not a legal move
+    if token == "searchmoves" {
+      while let Some(&algebraic) = tokens.peek() {
+        mov_e = parse_move(board, algebraic);
+        if !legal_moves.contains(&mov_e) {
+          break;
+        }
+        search_moves.push(mov_e);
+        tokens.next();
+      }
+      continue;
+    }
+    let value: Option<u64> = match token {
+      "infinite" | "ponder" => None,
+      _ => tokens.next().and_then(|value| value.parse::<u64>().ok()),
+    };
+    match (token, value) {
+      ("depth", Some(value)) => depth = Some(value as i32),
+      ("nodes", Some(value)) => nodes = Some(value),
+      ("mate", Some(value)) if value > 0 => mate = Some(value as i32),
+      ("movetime", Some(value)) => move_time = Some(value),
+      ("wtime", Some(value)) if white_to_move => time = Some(value),
+      ("btime", Some(value)) if !white_to_move => time = Some(value),
+      ("winc", Some(value)) if white_to_move => increment = value,
+      ("binc", Some(value)) if !white_to_move => increment = value,
+      ("movestogo", Some(value)) if value > 0 => moves_to_go = value,
+      ("ponder", None) => ponder = ponder_enabled,
+      _ => {}
+    }
+  }
+
+  info.clear_limits();
+  if let Some(depth) = depth {
+    info.set_depth(depth);
+  }
+  if let Some(move_time) = move_time {
+    info.set_time_limit(Duration::from_millis(
+      move_time.saturating_sub(TIME_MARGIN).max(1),
+    ));
+  } else if let Some(time) = time {
+    let time_per_move: u64 = (time / moves_to_go + increment).min(time.saturating_sub(TIME_MARGIN));
+    info.set_time_limit(Duration::from_millis(
+      time_per_move.saturating_sub(TIME_MARGIN).max(1),
+    ));
+  }
+  if let Some(nodes) = nodes {
+    info.set_node_limit(nodes);
+  }
+  if let Some(mate) = mate {
+    info.set_mate_limit(mate);
+  }
+  if !search_moves.is_empty() {
+    info.set_search_moves(search_moves);
+  }
+  if ponder {
+    info.set_pondering();
+  }
+
+  search_position(board, info, table);
+}
+
+/**
+ * References:
+ * <https://www.wbec-ridderkerk.nl/html/UCIProtocol.html>
+ *
+ * It reads the commands of the GUI until "quit" is received.
+ * The Ponder option tells if a "go ponder" ponders: without it the search is a normal one,
+ * that ends with its limits. "hard" and "easy" turn it on and off too: they are not UCI
+ * commands (a GUI never sends them), they are there for the one typing in the terminal.
+ */
+pub fn uci_loop(board: &mut Board, info: &mut SearchInfo, table: &mut HashTable) {
+  let stop_signal: Arc<AtomicBool> = info.stop_signal();
+  let receiver: Receiver<String> =
+    spawn_input_reader(Arc::clone(&stop_signal), info.ponder_signal());
+  let mut nnue: NnueSettings = NnueSettings::new();
+  let mut strength: StrengthSettings = StrengthSettings::new();
+  let mut ponder: bool = true;
+
+  board
+    .parse_fen(START_FEN)
+    .expect("Error: the start position is a valid FEN");
+  while let Ok(line) = receiver.recv() {
+    let line: &str = line.trim();
+    match line.split_whitespace().next().unwrap_or("") {
+      "uci" => {
+        println!("id name {}", PROGRAM_NAME);
+        println!("id author federicobaldini");
+        print_options(info);
+        println!("uciok");
+      }
+      "isready" => println!("readyok"),
+      "ucinewgame" => {
+        table.clear();
+        board
+          .parse_fen(START_FEN)
+          .expect("Error: the start position is a valid FEN");
+      }
+      "position" => parse_position(line, board),
+      "go" => parse_go(line, board, info, table, ponder),
+      "setoption" => parse_set_option(
+        line,
+        board,
+        info,
+        table,
+        &mut nnue,
+        &mut strength,
+        &mut ponder,
+      ),
+      "hard" => ponder = true,
+      "easy" => ponder = false,
+      "print" => parse_print(line, board),
+      "quit" => break,
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn elo_maps_to_the_measured_skill_levels() {
+    for (elo, skill_level) in [
+      (MIN_ELO, 0),
+      (806, 0),
+      (1143, 0),
+      (1144, 1),
+      (1400, 2),
+      (1740, 6),
+      (1773, 8),
+      (1900, 10),
+      (2087, 17),
+      (2300, 18),
+      (2499, 19),
+      (MAX_ELO, MAX_SKILL_LEVEL),
+    ] {
+      assert_eq!(elo_skill_level(elo), skill_level, "elo {}", elo);
+    }
+  }
+
+  #[test]
+  fn limit_strength_chooses_between_elo_and_skill_level() {
+    let mut info: SearchInfo = SearchInfo::new();
+    let mut strength: StrengthSettings = StrengthSettings::new();
+
+    strength.skill_level = 5;
+    strength.elo = 1400;
+    strength.apply(&mut info);
+    assert_eq!(info.skill_level(), 5);
+    strength.limit_strength = true;
+    strength.apply(&mut info);
+    assert_eq!(info.skill_level(), 2);
+  }
+}
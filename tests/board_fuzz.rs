@@ -0,0 +1,52 @@
+use chess_engine::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/**
+ * Random games from positions with every kind of move: the board has to be valid after each
+ * move made and taken back, and back to the start position at the end.
+ */
+#[test]
+fn random_games_keep_the_board_valid() {
+  let mut rng: StdRng = StdRng::seed_from_u64(0x5EED);
+  let mut board: Board = Board::new();
+  let mut moves: Vec<Move>;
+  let mut made_moves: usize;
+
+  for (fen, chess960) in [
+    (START_FEN.to_string(), false),
+    (
+      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+      false,
+    ),
+    ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1".to_string(), false),
+    (
+      "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1".to_string(),
+      false,
+    ),
+    (chess960_fen(rng.gen_range(0..960)), true),
+    (chess960_fen(rng.gen_range(0..960)), true),
+  ] {
+    board.set_chess960(chess960);
+    board.parse_fen(&fen).unwrap();
+    assert_eq!(board.validate(), Ok(()));
+
+    for _ in 0..20 {
+      made_moves = 0;
+      for _ in 0..120 {
+        moves = generate_legal_moves(&mut board);
+        if moves.is_empty() {
+          break;
+        }
+        assert!(board.make_move(moves[rng.gen_range(0..moves.len())]));
+        assert_eq!(board.validate(), Ok(()), "{}", board.to_fen());
+        made_moves += 1;
+      }
+      for _ in 0..made_moves {
+        board.take_move();
+        assert_eq!(board.validate(), Ok(()), "{}", board.to_fen());
+      }
+      assert_eq!(board.to_fen(), fen);
+    }
+  }
+}
@@ -0,0 +1,722 @@
+use chess_engine::*;
+
+const KIWIPETE_FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+#[test]
+fn fen_round_trip() {
+  let mut board: Board = Board::new();
+
+  for fen in [
+    START_FEN,
+    KIWIPETE_FEN,
+    "8/8/8/3k4/8/8/4P3/4K3 b - - 0 1",
+    "8/8/8/3k4/8/8/4P3/4K3 b - - 37 52",
+  ] {
+    board.parse_fen(fen).unwrap();
+    assert_eq!(board.to_fen(), fen);
+  }
+
+  // The counters go on from the ones of the FEN, they are 0 and 1 if it has none
+  assert_eq!(board.fifty_full_moves(), 37);
+  board.make_move(parse_move(&board, "d5d4"));
+  assert_eq!(board.to_fen(), "8/8/8/8/3k4/8/4P3/4K3 w - - 38 53");
+  board.make_move(parse_move(&board, "e2e3"));
+  assert_eq!(board.to_fen(), "8/8/8/8/3k4/4P3/8/4K3 b - - 0 53");
+  board.parse_fen("8/8/8/3k4/8/8/4P3/4K3 b - -").unwrap();
+  assert_eq!(board.to_fen(), "8/8/8/3k4/8/8/4P3/4K3 b - - 0 1");
+}
+
+#[test]
+fn perft_start_position() {
+  let mut board: Board = Board::new();
+
+  board.parse_fen(START_FEN).unwrap();
+  assert_eq!(perft(1, &mut board), 20);
+  assert_eq!(perft(2, &mut board), 400);
+  assert_eq!(perft(3, &mut board), 8902);
+  assert_eq!(board.to_fen(), START_FEN);
+}
+
+#[test]
+fn perft_kiwipete() {
+  let mut board: Board = Board::new();
+
+  board.parse_fen(KIWIPETE_FEN).unwrap();
+  assert_eq!(perft(1, &mut board), 48);
+  assert_eq!(perft(2, &mut board), 2039);
+}
+
+#[test]
+fn perft_chess960() {
+  let mut board: Board = Board::new();
+
+  board.set_chess960(true);
+  board
+    .parse_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9")
+    .unwrap();
+  assert_eq!(perft(1, &mut board), 21);
+  assert_eq!(perft(2, &mut board), 528);
+}
+
+#[test]
+fn legal_moves_and_notation() {
+  let mut board: Board = Board::new();
+  let mut mov_e: Move;
+
+  board.parse_fen(KIWIPETE_FEN).unwrap();
+  assert_eq!(generate_legal_moves(&mut board).len(), 48);
+
+  mov_e = parse_move(&board, "e1c1");
+  assert_ne!(mov_e, Move::NO_MOVE);
+  assert_eq!(move_to_san(&mut board, mov_e), "O-O-O");
+  assert_eq!(parse_san(&mut board, "O-O-O"), mov_e);
+
+  mov_e = parse_san(&mut board, "Nxf7");
+  assert_eq!(board.move_to_algebraic(mov_e), "e5f7");
+  assert_eq!(parse_san(&mut board, "Nxe9"), Move::NO_MOVE);
+}
+
+#[test]
+fn game_state() {
+  let mut board: Board = Board::new();
+
+  board
+    .parse_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")
+    .unwrap();
+  assert!(board.make_move(parse_move(&board, "a1a8")));
+  assert!(is_in_check(&board));
+  assert!(generate_legal_moves(&mut board).is_empty());
+
+  board.parse_fen("8/8/4k3/8/8/3NK3/8/8 w - - 0 1").unwrap();
+  assert!(insufficient_material(&board));
+  board.parse_fen("8/8/4k3/8/8/3RK3/8/8 w - - 0 1").unwrap();
+  assert!(!insufficient_material(&board));
+}
+
+#[test]
+fn search_finds_the_mate() {
+  let mut board: Board = Board::new();
+  let mut info: SearchInfo = SearchInfo::new();
+  let mut table: HashTable = HashTable::new(16);
+
+  board
+    .parse_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")
+    .unwrap();
+  info.clear_limits();
+  info.set_depth(4);
+  let (best_move, score): (Move, i32) = search_best_move(&mut board, &mut info, &mut table);
+
+  assert_eq!(board.move_to_algebraic(best_move), "a1a8");
+  assert_eq!(uci_score(score), "mate 1");
+}
+
+#[test]
+fn search_node_limit_and_searchmoves() {
+  let mut board: Board = Board::from_fen(KIWIPETE_FEN).unwrap();
+  let mut info: SearchInfo = SearchInfo::new();
+  let mut table: HashTable = HashTable::new(16);
+
+  for node_limit in [1, 500, 3000, 20000] {
+    table.clear();
+    info.clear_limits();
+    info.set_node_limit(node_limit);
+    let (best_move, _): (Move, i32) = search_best_move(&mut board, &mut info, &mut table);
+    assert!(info.stopped(), "{}", node_limit);
+    assert_eq!(info.nodes(), node_limit);
+    assert_eq!(board.to_fen(), KIWIPETE_FEN);
+    // A move to play even when the first depth is not completed
+    assert!(generate_legal_moves(&mut board).contains(&best_move));
+    assert_eq!(
+      info.completed_depth() == 0,
+      node_limit == 1,
+      "{}",
+      node_limit
+    );
+  }
+
+  // The queen takes the rook for free, but only the king and the pawn moves can be searched
+  board
+    .parse_fen("4k3/8/8/3r4/8/8/P7/3QK3 w - - 0 1")
+    .unwrap();
+  let search_moves: Vec<Move> = vec![parse_move(&board, "e1f2"), parse_move(&board, "a2a4")];
+  for depth in 1..=4 {
+    info.clear_limits();
+    info.set_depth(depth);
+    info.set_search_moves(search_moves.clone());
+    let (best_move, _): (Move, i32) = search_best_move(&mut board, &mut info, &mut table);
+    assert!(
+      search_moves.contains(&best_move),
+      "{} at depth {}",
+      best_move,
+      depth
+    );
+  }
+  info.clear_limits();
+  info.set_depth(2);
+  let (best_move, _): (Move, i32) = search_best_move(&mut board, &mut info, &mut table);
+  assert_eq!(board.move_to_algebraic(best_move), "d1d5");
+}
+
+#[test]
+fn search_best_lines_sorted() {
+  let mut board: Board = Board::new();
+  let mut info: SearchInfo = SearchInfo::new();
+  let mut table: HashTable = HashTable::new(16);
+
+  board.parse_fen(START_FEN).unwrap();
+  info.clear_limits();
+  info.set_depth(4);
+  let lines: Vec<SearchLine> = search_best_lines(&mut board, &mut info, &mut table, 3);
+
+  assert_eq!(lines.len(), 3);
+  assert!(lines.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+  for (index, (mov_e, _, pv_line)) in lines.iter().enumerate() {
+    assert_eq!(pv_line.first(), Some(mov_e));
+    assert!(lines[..index].iter().all(|(other, _, _)| other != mov_e));
+  }
+  assert_eq!(board.to_fen(), START_FEN);
+
+  // No more lines than legal moves: the king can only take the rook or go to f1
+  board.parse_fen("4k3/8/8/8/8/8/3r4/4K3 w - - 0 1").unwrap();
+  let lines: Vec<SearchLine> = search_best_lines(&mut board, &mut info, &mut table, 5);
+  assert_eq!(lines.len(), 2);
+  assert_eq!(lines[0].0, parse_move(&board, "e1d2"));
+
+  // Below the maximum skill level the depth is limited to 1 + the level
+  board.parse_fen(START_FEN).unwrap();
+  for (skill_level, pv_length) in [(0, 1), (2, 3)] {
+    info.clear_limits();
+    info.set_depth(6);
+    info.set_skill_level(skill_level);
+    let lines: Vec<SearchLine> = search_best_lines(&mut board, &mut info, &mut table, 3);
+    assert_eq!(lines.len(), 3);
+    for (_, _, pv_line) in &lines {
+      assert!(pv_line.len() <= pv_length, "{}", skill_level);
+    }
+    assert_eq!(
+      lines.iter().map(|(_, _, pv_line)| pv_line.len()).max(),
+      Some(pv_length)
+    );
+  }
+  info.set_skill_level(MAX_SKILL_LEVEL);
+}
+
+/**
+ * The full search, the search without null move and the search without any pruning,
+ * reduction or extension.
+ */
+fn pruning_options() -> [SearchOptions; 3] {
+  let full: SearchOptions = SearchOptions::new();
+  let without_null_move: SearchOptions = SearchOptions {
+    null_move_pruning: false,
+    ..full
+  };
+  let without_pruning: SearchOptions = SearchOptions {
+    null_move_pruning: false,
+    late_move_reductions: false,
+    principal_variation_search: false,
+    aspiration_windows: false,
+    futility_pruning: false,
+    reverse_futility_pruning: false,
+    razoring: false,
+    check_extensions: false,
+    internal_iterative_reductions: false,
+  };
+
+  [full, without_null_move, without_pruning]
+}
+
+#[test]
+fn pruning_keeps_the_tactics() {
+  let mut info: SearchInfo = SearchInfo::new();
+  let mut table: HashTable = HashTable::new(16);
+
+  for (fen, expected_move, expected_score) in [
+    // Mates in two, with a queen sacrifice and with a queen check
+    (
+      "r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - 0 1",
+      "h6h7",
+      "mate 2",
+    ),
+    (
+      "5k2/6pp/p1qN4/1p1p4/3P4/2PKP2Q/PP3r2/3R4 b - - 0 1",
+      "c6c4",
+      "mate 2",
+    ),
+    // The rook attacks the queen, pinned to the king
+    (
+      "5rk1/1ppb3p/p1pb4/6q1/3P1p1r/2P1R2P/PP1BQ1P1/5RKN w - - 0 1",
+      "e3g3",
+      "",
+    ),
+    // Zugzwang: after the king move the black rook has to leave the seventh rank or the king,
+    // a null move for black would hide it
+    ("1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1", "g5h6", ""),
+  ] {
+    let mut board: Board = Board::from_fen(fen).unwrap();
+
+    for (index, options) in pruning_options().into_iter().enumerate() {
+      *info.options_mut() = options;
+      table.clear();
+      info.clear_limits();
+      info.set_depth(5);
+      let (best_move, score): (Move, i32) = search_best_move(&mut board, &mut info, &mut table);
+
+      assert_eq!(
+        board.move_to_algebraic(best_move),
+        expected_move,
+        "{} with options {}",
+        fen,
+        index
+      );
+      if !expected_score.is_empty() {
+        assert_eq!(
+          uci_score(score),
+          expected_score,
+          "{} with options {}",
+          fen,
+          index
+        );
+      }
+    }
+  }
+
+  // Trebuchet: the side to move has to leave its pawn, with the null move too
+  for fen in [
+    "8/8/8/2Kp4/3Pk3/8/8/8 w - - 0 1",
+    "8/8/8/2Kp4/3Pk3/8/8/8 b - - 0 1",
+  ] {
+    let mut board: Board = Board::from_fen(fen).unwrap();
+
+    for (index, options) in pruning_options().into_iter().enumerate() {
+      *info.options_mut() = options;
+      table.clear();
+      info.clear_limits();
+      info.set_depth(8);
+      let (_, score): (Move, i32) = search_best_move(&mut board, &mut info, &mut table);
+
+      assert!(score < -100, "{} with options {}: {}", fen, index, score);
+    }
+  }
+}
+
+#[test]
+fn boards_are_owned_values() {
+  let board: Board = Board::from_fen(KIWIPETE_FEN).unwrap();
+  let mut copy: Board = board.clone();
+
+  for _ in 0..5 {
+    let mov_e: Move = generate_legal_moves(&mut copy)[0];
+    assert!(copy.make_move(mov_e));
+  }
+  assert_ne!(copy.to_fen(), KIWIPETE_FEN);
+  copy.parse_fen(START_FEN).unwrap();
+  assert_eq!(board.to_fen(), KIWIPETE_FEN);
+  assert_eq!(board.validate(), Ok(()));
+
+  // A board moved to another thread is changed there and given back
+  let handle: std::thread::JoinHandle<Board> = std::thread::spawn(move || {
+    let mut board: Board = board;
+    let castle: Move = parse_move(&board, "e1g1");
+    assert!(board.make_move(castle));
+    board
+  });
+  let board: Board = handle.join().unwrap();
+  assert_eq!(copy.to_fen(), START_FEN);
+  assert!(board
+    .to_fen()
+    .starts_with("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R4RK1 b kq"));
+
+  // The board not set up yet is the empty one
+  let empty: Board = Board::default();
+  assert_eq!(empty.validate(), Ok(()));
+  assert_eq!(empty.to_fen(), Board::new().to_fen());
+  assert!(empty.to_string().contains("fen: 8/8/8/8/8/8/8/8 w - -"));
+}
+
+#[test]
+fn typed_squares_pieces_and_castles() {
+  let board: Board = Board::from_fen(KIWIPETE_FEN).unwrap();
+
+  assert_eq!("e4".parse::<Square>(), Ok(Square::E4));
+  assert_eq!(Square::E4.to_string(), "e4");
+  assert_eq!(Square::try_from(Square::H8 as u32), Ok(Square::H8));
+  assert!(Square::try_from(30).is_err());
+  assert!("i9".parse::<Square>().is_err());
+  assert!("-".parse::<Square>().is_err());
+  assert!(Square::try_from(Square::NoSquare as u32).is_err());
+  assert!(Square::try_from(Square::OffBoard as u32).is_err());
+  assert_eq!(Square::iter().count(), 64);
+  assert_eq!(Square::from_file_rank(File::G, Rank::R6), Square::G6);
+
+  assert_eq!("N".parse::<Piece>(), Ok(Piece::Wn));
+  assert!(Piece::try_from(14).is_err());
+  assert_eq!(board.piece_at(Square::E5), Piece::Wn);
+  assert_eq!(board.piece_at(Square::E5).to_string(), "N");
+
+  assert_eq!(board.castling_rights(), CastlingRights::ALL);
+  assert!(board
+    .castling_rights()
+    .contains(CastlingRights::WHITE_QUEEN_SIDE));
+  assert_eq!(CastlingRights::from_bits(16), None);
+  assert_eq!(
+    (CastlingRights::WHITE_KING_SIDE | CastlingRights::BLACK_QUEEN_SIDE).to_string(),
+    "Kq"
+  );
+}
+
+#[test]
+fn typed_moves() {
+  let mut board: Board = Board::from_fen(KIWIPETE_FEN).unwrap();
+  let promotion: Move = Move::promotion(Square::B7, Square::A8, Some(Piece::Br), Piece::Wq);
+
+  assert_eq!(
+    parse_move(&board, "e2a6"),
+    Move::capture(Square::E2, Square::A6, Piece::Bb)
+  );
+  assert_eq!(
+    parse_move(&board, "e1g1"),
+    Move::castle(Square::E1, Square::G1)
+  );
+  assert_eq!(
+    parse_move(&board, "a2a4"),
+    Move::double_push(Square::A2, Square::A4)
+  );
+  assert_eq!(
+    parse_move(&board, "a2a3"),
+    Move::quiet(Square::A2, Square::A3)
+  );
+
+  assert_eq!(promotion.to_string(), "b7a8q");
+  assert_eq!(promotion.kind(), MoveKind::Promotion);
+  assert_eq!(promotion.captured_piece(), Some(Piece::Br));
+  assert!(promotion.is_capture() && promotion.is_promotion());
+  assert_eq!(
+    format!("{:?}", Move::en_passant(Square::E5, Square::D6)),
+    "Move { e5d6, EnPassant, captured: None, promoted: None, score: 0 }"
+  );
+  assert_eq!(Move::NO_MOVE.to_compact(), 0);
+
+  let mut kinds: Vec<MoveKind> = Vec::new();
+
+  for fen in [
+    KIWIPETE_FEN,
+    "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+    "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+  ] {
+    board.parse_fen(fen).unwrap();
+    for mov_e in generate_legal_moves(&mut board) {
+      assert_eq!(
+        Move::from_compact(mov_e.to_compact(), board.pieces()),
+        Some(mov_e)
+      );
+      kinds.push(mov_e.kind());
+    }
+  }
+  for kind in [
+    MoveKind::Quiet,
+    MoveKind::DoublePush,
+    MoveKind::Capture,
+    MoveKind::EnPassant,
+    MoveKind::Castle,
+    MoveKind::Promotion,
+  ] {
+    assert!(kinds.contains(&kind), "{:?}", kind);
+  }
+
+  // e2 is 12 and e4 is 28 in the 64 squares notation, the flag is in the last 4 bits
+  board.parse_fen(START_FEN).unwrap();
+  for compact in [
+    0,
+    12 | 28 << 6 | 0x8000,
+    12 | 28 << 6 | 0xF000,
+    12 | 28 << 6 | 0x4000,
+    28 | 36 << 6,
+    1 | 11 << 6,
+    1 | 18 << 6 | 0x1000,
+    4 | 5 << 6 | 0x3000,
+  ] {
+    assert_eq!(
+      Move::from_compact(compact, board.pieces()),
+      None,
+      "{:#x}",
+      compact
+    );
+  }
+}
+
+#[test]
+fn position_legality() {
+  let mut board: Board = Board::new();
+
+  assert!(board
+    .is_legal_position()
+    .unwrap_err()
+    .contains(&IllegalPosition::KingCount {
+      color: Color::White,
+      count: 0,
+    }));
+
+  for fen in [
+    START_FEN,
+    KIWIPETE_FEN,
+    "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    "4k3/8/8/8/8/8/4R3/R3K3 b Q - 0 1",
+    "4k3/8/5N2/8/8/8/4R3/4K3 b - - 0 1",
+  ] {
+    board.parse_fen(fen).unwrap();
+    assert_eq!(board.is_legal_position(), Ok(()), "{}", fen);
+  }
+
+  for (fen, reason) in [
+    (
+      "4k3/8/8/8/8/8/8/P3K3 w - - 0 1",
+      IllegalPosition::PawnOnBackRank { square: Square::A1 },
+    ),
+    (
+      "4k3/8/8/8/8/8/4R3/4K3 w - - 0 1",
+      IllegalPosition::OpponentInCheck,
+    ),
+    (
+      "4k3/8/8/8/8/8/8/R4K1R w K - 0 1",
+      IllegalPosition::CastlingRights {
+        castle: CastlingRights::WHITE_KING_SIDE,
+      },
+    ),
+    (
+      "4k3/8/8/8/8/8/PPPPPPPP/QQQQK3 w - - 0 1",
+      IllegalPosition::TooManyPromotedPieces {
+        color: Color::White,
+        promoted: 3,
+      },
+    ),
+    (
+      "4k3/8/8/8/4P3/8/4P3/4K3 b - e3 0 1",
+      IllegalPosition::EnPassantSquare { square: Square::E3 },
+    ),
+    (
+      "4k3/8/3N1N2/8/8/8/8/4K3 b - - 0 1",
+      IllegalPosition::ImpossibleCheck {
+        checkers: vec![Square::D6, Square::F6],
+      },
+    ),
+    (
+      "R3k2R/8/8/8/8/8/8/4K3 b - - 0 1",
+      IllegalPosition::ImpossibleCheck {
+        checkers: vec![Square::A8, Square::H8],
+      },
+    ),
+  ] {
+    board.parse_fen(fen).unwrap();
+    assert!(
+      board.is_legal_position().unwrap_err().contains(&reason),
+      "{}",
+      fen
+    );
+  }
+
+  // The moves of an illegal position never capture the king left in check
+  board
+    .parse_fen("n1n5/PpPk4/8/8/8/8/4Krpp/5N1N b - - 0 1")
+    .unwrap();
+  assert!(generate_legal_moves(&mut board)
+    .iter()
+    .all(|mov_e| mov_e.to_square() != Square::E2));
+}
+
+#[test]
+fn malformed_fens_are_errors() {
+  let mut board: Board = Board::new();
+
+  for fen in [
+    "",
+    "8/8/8/8 w - - 0 1",
+    "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "rnbqkbnrr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkz - 0 1",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9 0 1",
+    "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1",
+    "QQQQQQQQ/QQQ5/8/8/8/8/8/4K2k w - - 0 1",
+    "8/8/8/8/8/8/8/4K3 w - - 0 1",
+    "4k3/8/8/8/8/8/8/4KK2 w - - 0 1",
+    "rnbqkbnr/ppp1p1pp/8/3pP2p/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+    "rnbqkbnr/ppp1pppp/3N4/3pP3/8/8/PPPP1PPP/RNBQKB1R w KQkq d6 0 3",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 -1",
+  ] {
+    assert!(board.parse_fen(fen).is_err(), "{}", fen);
+    assert!(Board::from_fen(fen).is_err(), "{}", fen);
+  }
+  board.parse_fen(START_FEN).unwrap();
+  assert_eq!(board.to_fen(), START_FEN);
+}
+
+#[test]
+fn board_rendering() {
+  let board: Board =
+    Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+
+  let ascii: String = board.to_string();
+  assert!(ascii.starts_with("8  r n b q k b n r\n7  p p p p p p p p\n"));
+  assert!(ascii.contains("4  . . . . P . . .\n"));
+  assert!(ascii.contains("\n   a b c d e f g h\n"));
+  assert!(ascii.contains("side: b\nen passant: e3\ncastle: KQkq\n"));
+  assert!(ascii.contains(&format!("fen: {}\n", board.to_fen())));
+  assert!(ascii.contains(&format!("key: {:016X}\n", board.position_key())));
+
+  let flipped: String = board.render(RenderOptions {
+    style: BoardStyle::Unicode,
+    flipped: true,
+    coordinates: false,
+  });
+  assert!(flipped.starts_with("♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖\n♙ ♙ ♙ · ♙ ♙ ♙ ♙\n"));
+  assert!(flipped.contains("· · · ♙ · · · ·\n"));
+  assert!(!flipped.contains(" a "));
+
+  let ansi: String = board.render(RenderOptions {
+    style: BoardStyle::Ansi,
+    ..RenderOptions::new()
+  });
+  assert!(ansi.contains('\x1b'));
+  assert!(ansi.contains("\n    a  b  c  d  e  f  g  h\n"));
+
+  assert!(bitboard_to_string(1 << 28).contains("4  - - - - X - - -\n"));
+  assert!(attacked_squares_to_string(Color::Black, &board).starts_with("8  - X X X X X X -\n"));
+}
+
+#[test]
+fn attack_queries() {
+  let board: Board = Board::from_fen("4k3/8/8/q7/3b4/2NQ4/1P3R2/3RK1R1 w - - 0 1").unwrap();
+
+  assert_eq!(
+    bitboard_squares(board.attackers_to(Square::E2, Color::White)),
+    vec![Square::E1, Square::F2, Square::C3, Square::D3]
+  );
+  assert_eq!(
+    bitboard_squares(board.attackers_to(Square::C3, Color::Black)),
+    vec![Square::D4, Square::A5]
+  );
+  assert_eq!(
+    bitboard_squares(board.pinned_pieces(Color::White)),
+    vec![Square::C3]
+  );
+  assert_eq!(board.pinned_pieces(Color::Black), 0);
+  assert_eq!(
+    bitboard_squares(board.xray_attackers(Square::D5, Color::White)),
+    vec![Square::D3]
+  );
+
+  for color in [Color::White, Color::Black] {
+    let counts: [u32; 64] = board.attacker_counts(color);
+    let attacked: u64 = board.attack_map(color);
+    for square in Square::iter() {
+      let index: usize = square.rank() as usize * 8 + square.file() as usize;
+      assert_eq!(
+        counts[index],
+        board.attackers_to(square, color).count_ones()
+      );
+      assert_eq!(attacked >> index & 1 == 1, counts[index] > 0, "{}", square);
+    }
+  }
+  assert_eq!(
+    board.attacker_counts(Color::White)
+      [Square::E2.rank() as usize * 8 + Square::E2.file() as usize],
+    4
+  );
+
+  // The sentinels are not squares of the chessboard
+  for square in [Square::NoSquare, Square::OffBoard] {
+    for color in [Color::White, Color::Black] {
+      assert_eq!(board.attackers_to(square, color), 0);
+      assert_eq!(board.xray_attackers(square, color), 0);
+    }
+  }
+}
+
+#[test]
+fn king_attacks_every_neighbour() {
+  let mut board: Board = Board::from_fen("7k/8/8/8/3K4/8/8/8 w - - 0 1").unwrap();
+  let neighbours: [Square; 8] = [
+    Square::C3,
+    Square::D3,
+    Square::E3,
+    Square::C4,
+    Square::E4,
+    Square::C5,
+    Square::D5,
+    Square::E5,
+  ];
+
+  for square in neighbours {
+    assert_eq!(
+      bitboard_squares(board.attackers_to(square, Color::White)),
+      vec![Square::D4],
+      "{}",
+      square
+    );
+  }
+  assert_eq!(board.attack_map(Color::White).count_ones(), 8);
+  assert_eq!(generate_legal_moves(&mut board).len(), 8);
+
+  // The kings can not stand next to each other, on the file or on the diagonal
+  for fen in [
+    "8/8/8/8/3k4/8/3K4/8 w - - 0 1",
+    "8/8/8/8/3k4/8/5K2/8 w - - 0 1",
+  ] {
+    let mut board: Board = Board::from_fen(fen).unwrap();
+    for mov_e in generate_legal_moves(&mut board) {
+      assert!(board.make_move(mov_e), "{} in {}", mov_e, fen);
+      let king: Square = board.king_square()[Color::White as usize];
+      assert!(
+        (king.file() as i32 - Square::D4.file() as i32).abs() > 1
+          || (king.rank() as i32 - Square::D4.rank() as i32).abs() > 1,
+        "{} in {}",
+        mov_e,
+        fen
+      );
+      board.take_move();
+    }
+  }
+}
+
+#[test]
+fn static_exchange_evaluation() {
+  let mut board: Board = Board::new();
+
+  // Pawn 100, knight and bishop 325, rook 550, queen 1000
+  for (fen, algebraic, see) in [
+    ("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1", "d1d5", 100),
+    ("4k3/8/4p3/3p4/8/8/8/3RK3 w - - 0 1", "d1d5", -450),
+    ("4k3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5", 100),
+    ("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5", -450),
+    (
+      "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+      "e1e5",
+      100,
+    ),
+    (
+      "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+      "d3e5",
+      -225,
+    ),
+    ("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q", 900),
+    ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q", -100),
+    ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q", 1450),
+    ("3k4/3p4/8/8/8/8/3R4/3RK3 w - - 0 1", "d2d7", 100),
+    ("3k4/3p4/8/8/8/8/3R4/4K3 w - - 0 1", "d2d7", -450),
+    ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100),
+  ] {
+    board.parse_fen(fen).unwrap();
+    let mov_e: Move = parse_move(&board, algebraic);
+
+    assert_ne!(mov_e, Move::NO_MOVE, "{} {}", fen, algebraic);
+    assert_eq!(board.see(mov_e), see, "{} {}", fen, algebraic);
+    assert!(board.see_ge(mov_e, see), "{} {}", fen, algebraic);
+    assert!(!board.see_ge(mov_e, see + 1), "{} {}", fen, algebraic);
+  }
+
+  assert_eq!(board.see(Move::NO_MOVE), 0);
+  assert!(board.see_ge(Move::NO_MOVE, 0));
+  assert!(!board.see_ge(Move::NO_MOVE, 1));
+}
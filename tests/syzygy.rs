@@ -0,0 +1,235 @@
+use std::path::Path;
+
+use chess_engine::*;
+
+#[path = "syzygy/generator.rs"]
+mod generator;
+
+use generator::*;
+
+/**
+ * The tables of the tests, written by the generator: the ones reached by the captures and
+ * promotions of KPvK and KQvKR are there too, and the DTZ tables of the draws are not.
+ */
+const TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+const GENERATED_TABLES: [(&str, bool); 6] = [
+  ("KNvK", false),
+  ("KBvK", false),
+  ("KRvK", true),
+  ("KQvK", true),
+  ("KPvK", true),
+  ("KQvKR", true),
+];
+
+// The score of a win within the fifty moves rule
+const TABLEBASE_WIN: i32 = IS_MATE - MAX_DEPTH as i32;
+
+// The black king in the centre, white mates in more than 20 half moves
+const KRVK_FEN: &str = "8/8/8/4k3/8/8/8/R3K3 w - - 0 1";
+
+/**
+ * The WDL and DTZ values of the FEN position in the tables of the tests.
+ */
+fn probe(tablebases: &Tablebases, fen: &str) -> (Option<i32>, Option<i32>) {
+  let mut board: Board = Board::from_fen(fen).unwrap();
+  let wdl: Option<i32> = tablebases.probe_wdl(&mut board);
+  let dtz: Option<i32> = tablebases.probe_dtz(&mut board);
+
+  assert_eq!(board.to_fen(), fen);
+  (wdl, dtz)
+}
+
+/**
+ * The best root moves of the FEN position in UCI notation, sorted, and its score.
+ */
+fn root_moves(tablebases: &Tablebases, fen: &str) -> (Vec<String>, i32) {
+  let mut board: Board = Board::from_fen(fen).unwrap();
+  let (moves, score): (Vec<Move>, i32) = tablebases.root_moves(&mut board).unwrap();
+  let mut moves: Vec<String> = moves.iter().map(|mov_e| mov_e.to_string()).collect();
+
+  moves.sort();
+  (moves, score)
+}
+
+#[test]
+fn missing_tables_are_not_probed() {
+  let tablebases: Tablebases = Tablebases::new("/nonexistent:");
+  let mut board: Board = Board::from_fen(KRVK_FEN).unwrap();
+
+  assert_eq!(tablebases.tables_number(), 0);
+  assert_eq!(tablebases.max_pieces(), 0);
+  assert_eq!(tablebases.probe_wdl(&mut board), None);
+  assert_eq!(tablebases.probe_dtz(&mut board), None);
+  assert!(tablebases.root_moves(&mut board).is_none());
+  assert_eq!(board.to_fen(), KRVK_FEN);
+}
+
+#[test]
+fn generated_tables_are_found() {
+  let tablebases: Tablebases = Tablebases::new(TABLES);
+
+  assert_eq!(tablebases.tables_number(), GENERATED_TABLES.len());
+  assert_eq!(tablebases.max_pieces(), 4);
+}
+
+#[test]
+fn krvk_is_probed() {
+  let tablebases: Tablebases = Tablebases::new(TABLES);
+
+  // Rh8 mates, and the mated side has nothing to play
+  assert_eq!(
+    probe(&tablebases, "k7/8/1K6/8/8/8/8/7R w - - 0 1"),
+    (Some(2), Some(1))
+  );
+  assert_eq!(
+    probe(&tablebases, "k6R/8/1K6/8/8/8/8/8 b - - 0 1"),
+    (Some(-2), Some(-1))
+  );
+  assert_eq!(probe(&tablebases, KRVK_FEN), (Some(2), Some(27)));
+  // The king takes the rook
+  assert_eq!(
+    probe(&tablebases, "8/8/8/8/8/8/1k6/R3K3 b - - 0 1"),
+    (Some(0), Some(0))
+  );
+}
+
+#[test]
+fn kpvk_is_probed() {
+  let tablebases: Tablebases = Tablebases::new(TABLES);
+
+  // The king takes the opposition, the pawn moves on the third half move
+  assert_eq!(
+    probe(&tablebases, "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+    (Some(2), Some(3))
+  );
+  assert_eq!(
+    probe(&tablebases, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+    (Some(-2), Some(-4))
+  );
+  // The same position with the colors flipped, the table is probed from the other side
+  assert_eq!(
+    probe(&tablebases, "8/8/8/8/4p3/4k3/8/4K3 b - - 0 1"),
+    (Some(2), Some(3))
+  );
+  // A stalemate
+  assert_eq!(
+    probe(&tablebases, "4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"),
+    (Some(0), Some(0))
+  );
+}
+
+#[test]
+fn kqvkr_is_probed() {
+  let tablebases: Tablebases = Tablebases::new(TABLES);
+
+  // The queen takes the rook
+  assert_eq!(
+    probe(&tablebases, "4k3/8/8/8/8/8/8/rQ2K3 w - - 0 1"),
+    (Some(2), Some(1))
+  );
+  assert_eq!(
+    probe(&tablebases, "8/8/8/3k4/8/8/2r5/KQ6 b - - 0 1"),
+    (Some(-2), Some(-58))
+  );
+  // The queen wins the rook, or the rook skewers the queen with black to move
+  assert_eq!(
+    probe(&tablebases, "3k4/8/8/8/7r/8/8/Q3K3 w - - 0 1"),
+    (Some(2), Some(3))
+  );
+  assert_eq!(
+    probe(&tablebases, "3k4/8/8/8/7r/8/8/Q3K3 b - - 0 1"),
+    (Some(2), Some(3))
+  );
+}
+
+#[test]
+fn root_moves_keep_the_best_result() {
+  let tablebases: Tablebases = Tablebases::new(TABLES);
+
+  // The rook leaves the king, every move that keeps it wins
+  assert_eq!(
+    root_moves(&tablebases, "8/8/8/8/8/8/1k6/R3K3 w - - 0 1"),
+    (
+      vec!["a1a4", "a1a5", "a1a6", "a1a7", "a1a8", "a1d1"]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+      TABLEBASE_WIN
+    )
+  );
+  assert_eq!(
+    root_moves(&tablebases, "3k4/8/8/8/7r/8/8/Q3K3 b - - 0 1"),
+    (vec![String::from("h4h1")], TABLEBASE_WIN)
+  );
+  // The loss is delayed as much as possible
+  assert_eq!(
+    root_moves(&tablebases, "8/8/8/3k4/8/8/2r5/KQ6 b - - 0 1"),
+    (
+      vec![String::from("c2c4"), String::from("c2d2")],
+      -TABLEBASE_WIN
+    )
+  );
+  assert_eq!(
+    root_moves(&tablebases, "8/8/8/8/8/8/1k6/R3K3 b - - 0 1"),
+    (vec![String::from("b2a1")], 0)
+  );
+}
+
+#[test]
+fn halfmove_clock_curses_a_win() {
+  let tablebases: Tablebases = Tablebases::new(TABLES);
+  let mut board: Board = Board::from_fen(KRVK_FEN).unwrap();
+  let dtz: i32 = tablebases.probe_dtz(&mut board).unwrap();
+
+  let (moves, score): (Vec<Move>, i32) = tablebases.root_moves(&mut board).unwrap();
+  assert!(!moves.is_empty());
+  assert_eq!(score, TABLEBASE_WIN);
+
+  // The same position 90 half moves after the last capture or pawn move: the mate comes
+  // after the draw by the fifty moves rule, the win is cursed
+  board.parse_fen("8/8/8/4k3/8/8/8/R3K3 w - - 90 60").unwrap();
+  assert_eq!(tablebases.probe_dtz(&mut board), Some(dtz));
+  let (moves, score): (Vec<Move>, i32) = tablebases.root_moves(&mut board).unwrap();
+  assert!(!moves.is_empty());
+  assert!(score > 0 && score < 100, "{}", score);
+}
+
+/**
+ * It writes the tables of the tests again, and checks every position of them (one in 50 of
+ * KQvKR) against the solved endings. It's slow in debug mode:
+ * cargo test --release --test syzygy -- --ignored
+ */
+#[test]
+#[ignore]
+fn generated_tables_match_the_solved_endings() {
+  let mut generator: Generator = Generator::new();
+
+  for (name, dtz) in GENERATED_TABLES {
+    generator.solve(name);
+    generator.write(name, Path::new(TABLES), dtz);
+  }
+
+  let tablebases: Tablebases = Tablebases::new(TABLES);
+  let mut board: Board = Board::new();
+  assert_eq!(tablebases.tables_number(), GENERATED_TABLES.len());
+  for (name, step) in [("KRvK", 1), ("KQvK", 1), ("KPvK", 1), ("KQvKR", 50)] {
+    for (pieces, side) in generator.positions(name, step) {
+      let fen: String = position_fen(&pieces, side);
+      let (wdl, dtz): (i8, i8) = generator.probe(name, &pieces, side);
+
+      board.parse_fen(&fen).unwrap();
+      assert_eq!(
+        tablebases.probe_wdl(&mut board),
+        Some(wdl as i32),
+        "{}",
+        fen
+      );
+      assert_eq!(
+        tablebases.probe_dtz(&mut board),
+        Some(dtz as i32),
+        "{}",
+        fen
+      );
+    }
+  }
+}
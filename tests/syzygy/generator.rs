@@ -0,0 +1,1455 @@
+/*!
+ * References:
+ * <https://www.chessprogramming.org/Retrograde_Analysis>
+ * <https://github.com/syzygy1/tb>
+ *
+ * The generator of the small tables of the tests: the endings are solved by retrograde
+ * analysis and written in the Syzygy format. The tables are only as compressed as the tests
+ * need (the values are paired and Huffman coded, but the pieces are always in the same
+ * order), and they don't store cursed wins: the endings have none.
+ */
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs;
+use std::path::Path;
+
+const PAWN: usize = 0;
+const KNIGHT: usize = 1;
+const BISHOP: usize = 2;
+const ROOK: usize = 3;
+const QUEEN: usize = 4;
+const KING: usize = 5;
+const PIECE_CHARACTERS: &str = "PNBRQK";
+
+const WHITE: usize = 0;
+const BLACK: usize = 1;
+
+const LOSS: i8 = -2;
+const DRAW: i8 = 0;
+const WIN: i8 = 2;
+/**
+ * The positions not yet solved, and the illegal or mirrored ones.
+ */
+const UNKNOWN: i8 = i8::MIN;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+const SPLIT_FLAG: u8 = 1;
+const HAS_PAWNS_FLAG: u8 = 2;
+const WIN_PLIES_FLAG: u8 = 4;
+const LOSS_PLIES_FLAG: u8 = 8;
+const SINGLE_VALUE_FLAG: u8 = 128;
+
+const BLOCK_SIZE_BITS: u8 = 6;
+const SPAN_BITS: u8 = 12;
+const MAX_SYMBOLS: usize = 4095;
+/**
+ * A symbol is expanded in at most 256 values, and a block holds at most 65536 values.
+ */
+const MAX_SYMBOL_VALUES: u32 = 256;
+const MAX_BLOCK_VALUES: u32 = 65536;
+const MAX_CODE_LENGTH: u32 = 24;
+const MAX_PIECES: usize = 4;
+const LEAF: u16 = 0xFFF;
+
+/**
+ * A piece as (color, type).
+ */
+type Piece = (usize, usize);
+
+const KING_STEPS: [(i32, i32); 8] = [
+  (-1, -1),
+  (-1, 0),
+  (-1, 1),
+  (0, -1),
+  (0, 1),
+  (1, -1),
+  (1, 0),
+  (1, 1),
+];
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+  (-2, -1),
+  (-2, 1),
+  (-1, -2),
+  (-1, 2),
+  (1, -2),
+  (1, 2),
+  (2, -1),
+  (2, 1),
+];
+const ROOK_STEPS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_STEPS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+fn square(file: i32, rank: i32) -> Option<usize> {
+  if (0..8).contains(&file) && (0..8).contains(&rank) {
+    Some((rank * 8 + file) as usize)
+  } else {
+    None
+  }
+}
+
+/**
+ * The pieces of an ending in the order of the table names: white then black, and the king,
+ * queen, rook, bishop, knight and pawn of each color.
+ */
+fn material_order(piece: Piece) -> (usize, usize) {
+  (piece.0, KING - piece.1)
+}
+
+fn material_name(pieces: &[Piece]) -> String {
+  let mut name: String = String::new();
+
+  for color in [WHITE, BLACK] {
+    if color == BLACK {
+      name.push('v');
+    }
+    for &(piece_color, kind) in pieces {
+      if piece_color == color {
+        name.push(PIECE_CHARACTERS.as_bytes()[kind] as char);
+      }
+    }
+  }
+  name
+}
+
+fn parse_material(name: &str) -> Vec<Piece> {
+  let (white, black): (&str, &str) = name.split_once('v').expect("Error: no v in the name");
+  let mut pieces: Vec<Piece> = Vec::new();
+
+  for (part, color) in [(white, WHITE), (black, BLACK)] {
+    for character in part.chars() {
+      pieces.push((
+        color,
+        PIECE_CHARACTERS
+          .find(character)
+          .expect("Error: unknown piece"),
+      ));
+    }
+  }
+  pieces.sort_by_key(|&piece| material_order(piece));
+  pieces
+}
+
+/**
+ * The 8 symmetries of the board, or only the one of the files when there are pawns.
+ */
+fn transform(square: usize, symmetry: usize) -> usize {
+  let flipped: usize = square ^ [0, 7, 56, 63][symmetry % 4];
+
+  if symmetry >= 4 {
+    ((flipped >> 3) | (flipped << 3)) & 63
+  } else {
+    flipped
+  }
+}
+
+/**
+ * A solved ending: the result and the distance to zeroing of every position, indexed by
+ * the side to move and the squares of the pieces. Only the canonical positions (the first
+ * of their symmetries) are solved.
+ */
+struct Ending {
+  pieces: Vec<Piece>,
+  symmetries: usize,
+  wdl: Vec<i8>,
+  dtz: Vec<i8>,
+  mated: Vec<bool>,
+}
+
+impl Ending {
+  fn new(pieces: Vec<Piece>) -> Ending {
+    let size: usize = 2 << (6 * pieces.len());
+
+    Ending {
+      symmetries: if pieces.iter().any(|&(_, kind)| kind == PAWN) {
+        2
+      } else {
+        8
+      },
+      pieces,
+      wdl: vec![UNKNOWN; size],
+      dtz: vec![0; size],
+      mated: vec![false; size],
+    }
+  }
+
+  fn index(&self, side: usize, squares: &[usize]) -> usize {
+    squares
+      .iter()
+      .rev()
+      .fold(side, |index, &square| (index << 6) | square)
+  }
+
+  fn decode(&self, index: usize) -> (usize, [usize; MAX_PIECES]) {
+    let mut squares: [usize; MAX_PIECES] = [0; MAX_PIECES];
+
+    for (piece, square) in squares.iter_mut().enumerate().take(self.pieces.len()) {
+      *square = (index >> (6 * piece)) & 63;
+    }
+    (index >> (6 * self.pieces.len()), squares)
+  }
+
+  /**
+   * The index of the position among its symmetries, with the equal pieces sorted by square.
+   */
+  fn canonical_index(&self, side: usize, squares: &[usize]) -> usize {
+    let mut transformed: [usize; MAX_PIECES] = [0; MAX_PIECES];
+    let mut best: usize = usize::MAX;
+
+    for symmetry in 0..self.symmetries {
+      for (piece, &square) in squares.iter().enumerate() {
+        transformed[piece] = transform(square, symmetry);
+      }
+      for first in 0..squares.len() {
+        for second in first + 1..squares.len() {
+          if self.pieces[first] == self.pieces[second] && transformed[second] < transformed[first] {
+            transformed.swap(first, second);
+          }
+        }
+      }
+      best = best.min(self.index(side, &transformed[..squares.len()]));
+    }
+    best
+  }
+}
+
+/**
+ * A position of an ending: the pieces with their squares, and the side to move.
+ */
+#[derive(Clone, Copy)]
+struct Position {
+  pieces: [(Piece, usize); MAX_PIECES],
+  count: usize,
+  side: usize,
+}
+
+impl Position {
+  fn new(pieces: &[(Piece, usize)], side: usize) -> Position {
+    let mut position: Position = Position {
+      pieces: [((WHITE, KING), 0); MAX_PIECES],
+      count: pieces.len(),
+      side,
+    };
+
+    position.pieces[..pieces.len()].copy_from_slice(pieces);
+    position
+  }
+
+  fn pieces(&self) -> &[(Piece, usize)] {
+    &self.pieces[..self.count]
+  }
+
+  fn piece_at(&self, square: usize) -> Option<Piece> {
+    self
+      .pieces()
+      .iter()
+      .find(|&&(_, piece_square)| piece_square == square)
+      .map(|&(piece, _)| piece)
+  }
+
+  fn attacks(&self, (piece, from): (Piece, usize), to: usize) -> bool {
+    let (file, rank): (i32, i32) = ((from % 8) as i32, (from / 8) as i32);
+    let (file_distance, rank_distance): (i32, i32) =
+      ((to % 8) as i32 - file, (to / 8) as i32 - rank);
+    let (file_step, rank_step): (i32, i32) = (file_distance.signum(), rank_distance.signum());
+
+    match piece.1 {
+      PAWN => file_distance.abs() == 1 && rank_distance == if piece.0 == WHITE { 1 } else { -1 },
+      KNIGHT => file_distance.abs() * rank_distance.abs() == 2,
+      KING => from != to && file_distance.abs() <= 1 && rank_distance.abs() <= 1,
+      _ => {
+        let straight: bool = file_distance == 0 || rank_distance == 0;
+        let diagonal: bool = file_distance.abs() == rank_distance.abs();
+
+        if from == to
+          || !(straight && piece.1 != BISHOP || diagonal && piece.1 != ROOK)
+          || !(straight || diagonal)
+        {
+          return false;
+        }
+        (1..file_distance.abs().max(rank_distance.abs())).all(|step| {
+          self
+            .piece_at(square(file + step * file_step, rank + step * rank_step).unwrap())
+            .is_none()
+        })
+      }
+    }
+  }
+
+  fn in_check(&self, color: usize) -> bool {
+    let king: usize = self
+      .pieces()
+      .iter()
+      .find(|&&(piece, _)| piece == (color, KING))
+      .expect("Error: no king")
+      .1;
+
+    self
+      .pieces()
+      .iter()
+      .any(|&(piece, from)| piece.0 != color && self.attacks((piece, from), king))
+  }
+
+  /**
+   * The positions after the legal moves, with true if the move is a capture or a pawn move.
+   */
+  fn children(&self) -> Vec<(Position, bool)> {
+    let mut children: Vec<(Position, bool)> = Vec::new();
+    let mut targets: Vec<(usize, bool)> = Vec::with_capacity(32);
+
+    for (moved, &(piece, from)) in self.pieces().iter().enumerate() {
+      if piece.0 != self.side {
+        continue;
+      }
+      targets.clear();
+      let (file, rank): (i32, i32) = ((from % 8) as i32, (from / 8) as i32);
+      match piece.1 {
+        PAWN => {
+          let forward: i32 = if piece.0 == WHITE { 1 } else { -1 };
+          if let Some(to) = square(file, rank + forward).filter(|&to| self.piece_at(to).is_none()) {
+            targets.push((to, false));
+            let start_rank: i32 = if piece.0 == WHITE { 1 } else { 6 };
+            if let Some(to) = square(file, rank + 2 * forward)
+              .filter(|&to| rank == start_rank && self.piece_at(to).is_none())
+            {
+              targets.push((to, false));
+            }
+          }
+          for file_step in [-1, 1] {
+            if let Some(to) = square(file + file_step, rank + forward)
+              .filter(|&to| self.piece_at(to).is_some_and(|other| other.0 != piece.0))
+            {
+              targets.push((to, true));
+            }
+          }
+        }
+        KNIGHT | KING => {
+          for (file_step, rank_step) in if piece.1 == KNIGHT {
+            KNIGHT_STEPS
+          } else {
+            KING_STEPS
+          } {
+            if let Some(to) = square(file + file_step, rank + rank_step) {
+              match self.piece_at(to) {
+                None => targets.push((to, false)),
+                Some(other) if other.0 != piece.0 => targets.push((to, true)),
+                _ => {}
+              }
+            }
+          }
+        }
+        _ => {
+          let steps: &[(i32, i32)] = match piece.1 {
+            BISHOP => &BISHOP_STEPS,
+            ROOK => &ROOK_STEPS,
+            _ => &KING_STEPS,
+          };
+          for &(file_step, rank_step) in steps {
+            let mut distance: i32 = 1;
+            while let Some(to) = square(file + distance * file_step, rank + distance * rank_step) {
+              match self.piece_at(to) {
+                None => targets.push((to, false)),
+                Some(other) => {
+                  if other.0 != piece.0 {
+                    targets.push((to, true));
+                  }
+                  break;
+                }
+              }
+              distance += 1;
+            }
+          }
+        }
+      }
+
+      for &(to, capture) in &targets {
+        let promotions: &[usize] = if piece.1 == PAWN && (to / 8 == 0 || to / 8 == 7) {
+          &[QUEEN, ROOK, BISHOP, KNIGHT]
+        } else {
+          &[piece.1]
+        };
+        for &kind in promotions {
+          let mut child: Position = Position::new(&[], self.side ^ 1);
+          for (other, &(other_piece, other_square)) in self.pieces().iter().enumerate() {
+            if other == moved {
+              child.pieces[child.count] = ((piece.0, kind), to);
+              child.count += 1;
+            } else if other_square != to {
+              child.pieces[child.count] = (other_piece, other_square);
+              child.count += 1;
+            }
+          }
+          if !child.in_check(self.side) {
+            children.push((child, capture || piece.1 == PAWN));
+          }
+        }
+      }
+    }
+    children
+  }
+}
+
+/**
+ * The solved endings, by their pieces.
+ */
+pub struct Generator {
+  endings: HashMap<Vec<Piece>, Ending>,
+}
+
+impl Generator {
+  pub fn new() -> Generator {
+    Generator {
+      endings: HashMap::new(),
+    }
+  }
+
+  /**
+   * The ending of the position and the index of its canonical position.
+   */
+  fn find<'a>(&'a self, solving: &'a Ending, position: &Position) -> Option<(&'a Ending, usize)> {
+    let mut pieces: Position = *position;
+    let mut squares: [usize; MAX_PIECES] = [0; MAX_PIECES];
+
+    // The moves without captures and promotions keep the order of the pieces
+    let ending: &Ending = if position.count == solving.pieces.len()
+      && position
+        .pieces()
+        .iter()
+        .zip(&solving.pieces)
+        .all(|(&(piece, _), &other)| piece == other)
+    {
+      solving
+    } else {
+      pieces.pieces[..pieces.count].sort_by_key(|&(piece, _)| material_order(piece));
+      self.endings.get(
+        &pieces
+          .pieces()
+          .iter()
+          .map(|&(piece, _)| piece)
+          .collect::<Vec<Piece>>(),
+      )?
+    };
+    for (square, &(_, piece_square)) in squares.iter_mut().zip(pieces.pieces()) {
+      *square = piece_square;
+    }
+
+    Some((
+      ending,
+      ending.canonical_index(position.side, &squares[..pieces.count]),
+    ))
+  }
+
+  fn position(ending: &Ending, index: usize) -> Option<Position> {
+    let (side, squares): (usize, [usize; MAX_PIECES]) = ending.decode(index);
+    let squares: &[usize] = &squares[..ending.pieces.len()];
+    let mut position: Position = Position::new(&[], side);
+
+    for (&piece, &square) in ending.pieces.iter().zip(squares) {
+      position.pieces[position.count] = (piece, square);
+      position.count += 1;
+    }
+    if (0..squares.len()).any(|piece| squares[piece + 1..].contains(&squares[piece]))
+      || position
+        .pieces()
+        .iter()
+        .any(|&(piece, square)| piece.1 == PAWN && (square / 8 == 0 || square / 8 == 7))
+      || ending.canonical_index(side, squares) != index
+      || position.in_check(side ^ 1)
+    {
+      None
+    } else {
+      Some(position)
+    }
+  }
+
+  /**
+   * It solves the ending of the table name (and first the endings after its captures and
+   * promotions): the results by propagating the mates and the results of the captures and
+   * promotions back, then the distances to zeroing by increasing distance.
+   */
+  pub fn solve(&mut self, name: &str) {
+    let pieces: Vec<Piece> = parse_material(name);
+    let mut ending: Ending;
+    let mut unknown: Vec<usize> = Vec::new();
+    let mut changed: bool = true;
+    let mut distance: i8 = 0;
+
+    if self.endings.contains_key(&pieces) {
+      return;
+    }
+    for (index, &(color, kind)) in pieces.iter().enumerate() {
+      if kind != KING {
+        let mut captured: Vec<Piece> = pieces.clone();
+        captured.remove(index);
+        self.solve(&material_name(&captured));
+      }
+      if kind == PAWN {
+        for promotion in [KNIGHT, BISHOP, ROOK, QUEEN] {
+          let mut promoted: Vec<Piece> = pieces.clone();
+          promoted[index] = (color, promotion);
+          promoted.sort_by_key(|&piece| material_order(piece));
+          self.solve(&material_name(&promoted));
+        }
+      }
+    }
+
+    ending = Ending::new(pieces.clone());
+    for index in 0..ending.wdl.len() {
+      if let Some(position) = Generator::position(&ending, index) {
+        if position.children().is_empty() {
+          ending.mated[index] = position.in_check(position.side);
+          ending.wdl[index] = if ending.mated[index] { LOSS } else { DRAW };
+          ending.dtz[index] = -(ending.mated[index] as i8);
+        } else {
+          unknown.push(index);
+        }
+      }
+    }
+
+    // Every pass uses the results of the previous one
+    while changed {
+      let mut solved: Vec<(usize, i8)> = Vec::new();
+      unknown.retain(|&index| {
+        let position: Position = Generator::position(&ending, index).unwrap();
+        let mut all_won: bool = true;
+
+        for (child, _) in position.children() {
+          let (child_ending, child_index): (&Ending, usize) = self.find(&ending, &child).unwrap();
+          match child_ending.wdl[child_index] {
+            LOSS => {
+              solved.push((index, WIN));
+              return false;
+            }
+            WIN => {}
+            _ => all_won = false,
+          }
+        }
+        if all_won {
+          solved.push((index, LOSS));
+        }
+        !all_won
+      });
+      changed = !solved.is_empty();
+      for (index, wdl) in solved {
+        ending.wdl[index] = wdl;
+      }
+    }
+    for index in unknown {
+      ending.wdl[index] = DRAW;
+    }
+
+    unknown = (0..ending.wdl.len())
+      .filter(|&index| ending.wdl[index] != DRAW && ending.wdl[index] != UNKNOWN)
+      .filter(|&index| !ending.mated[index])
+      .collect();
+    while !unknown.is_empty() {
+      let mut solved: Vec<(usize, i8)> = Vec::new();
+      distance += 1;
+      assert!(distance < 100, "{} has cursed wins", name);
+      unknown.retain(|&index| {
+        let position: Position = Generator::position(&ending, index).unwrap();
+        let wdl: i8 = ending.wdl[index];
+        let mut best: Option<i8> = None;
+
+        for (child, zeroing) in position.children() {
+          let (child_ending, child_index): (&Ending, usize) = self.find(&ending, &child).unwrap();
+          if child_ending.wdl[child_index] != -wdl {
+            continue;
+          }
+          let child_distance: Option<i8> = if zeroing || child_ending.mated[child_index] {
+            Some(1)
+          } else if child_ending.dtz[child_index] != 0 {
+            Some(1 + child_ending.dtz[child_index].abs())
+          } else {
+            None
+          };
+          best = match (wdl, best, child_distance) {
+            // The winner takes the shortest way to the next zeroing move, the loser the longest
+            (WIN, _, None) => best,
+            (WIN, Some(best), Some(child)) => Some(best.min(child)),
+            (WIN, None, child) => child,
+            (_, _, None) => return true,
+            (_, best, Some(child)) => Some(best.unwrap_or(0).max(child)),
+          };
+        }
+        match best {
+          // A win is solved at the pass of its distance, a loss when all its moves are
+          Some(best) if wdl == LOSS || best <= distance => {
+            solved.push((index, if wdl == WIN { best } else { -best }));
+            false
+          }
+          _ => true,
+        }
+      });
+      for (index, dtz) in solved {
+        ending.dtz[index] = dtz;
+      }
+    }
+
+    self.endings.insert(pieces, ending);
+  }
+
+  fn ending(&self, name: &str) -> &Ending {
+    self
+      .endings
+      .get(&parse_material(name))
+      .expect("Error: the ending is not solved")
+  }
+
+  /**
+   * The solved result and distance to zeroing of a legal position, from the side to move.
+   */
+  pub fn probe(&self, name: &str, pieces: &[(Piece, usize)], side: usize) -> (i8, i8) {
+    let ending: &Ending = self.ending(name);
+    let (ending, index): (&Ending, usize) =
+      self.find(ending, &Position::new(pieces, side)).unwrap();
+
+    (ending.wdl[index], ending.dtz[index])
+  }
+
+  /**
+   * The legal positions of an ending, every step-th one of them.
+   */
+  pub fn positions(&self, name: &str, step: usize) -> Vec<(Vec<(Piece, usize)>, usize)> {
+    let ending: &Ending = self.ending(name);
+
+    (0..ending.wdl.len())
+      .filter(|&index| ending.wdl[index] != UNKNOWN)
+      .step_by(step)
+      .map(|index| {
+        let position: Position = Generator::position(ending, index).unwrap();
+        (position.pieces().to_vec(), position.side)
+      })
+      .collect()
+  }
+
+  /**
+   * It writes the WDL table of the ending, and the DTZ one if asked, in the directory.
+   */
+  pub fn write(&self, name: &str, directory: &Path, dtz: bool) {
+    let ending: &Ending = self.ending(name);
+    let table: TableWriter = TableWriter::new(ending);
+
+    fs::write(
+      directory.join(format!("{}.rtbw", name)),
+      table.write(self, ending, false),
+    )
+    .expect("Error: the WDL table is not written");
+    if dtz {
+      fs::write(
+        directory.join(format!("{}.rtbz", name)),
+        table.write(self, ending, true),
+      )
+      .expect("Error: the DTZ table is not written");
+    }
+  }
+}
+
+/**
+ * The tables of the index of a position in a table, as in the reader of the engine.
+ */
+struct Encoding {
+  map_b1h1h7: [u64; 64],
+  map_a1d1d4: [u64; 64],
+  map_kk: [[u64; 64]; 10],
+  binomial: [[u64; 64]; 7],
+  map_pawns: [u64; 64],
+  lead_pawn_index: [[u64; 64]; 6],
+  lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn off_a1h8(square: usize) -> i32 {
+  (square / 8) as i32 - (square % 8) as i32
+}
+
+impl Encoding {
+  fn new() -> Encoding {
+    let mut encoding: Encoding = Encoding {
+      map_b1h1h7: [0; 64],
+      map_a1d1d4: [0; 64],
+      map_kk: [[0; 64]; 10],
+      binomial: [[0; 64]; 7],
+      map_pawns: [0; 64],
+      lead_pawn_index: [[0; 64]; 6],
+      lead_pawns_size: [[0; 4]; 6],
+    };
+    let mut code: u64 = 0;
+    let mut available_squares: u64 = 47;
+
+    for square in (0..64).filter(|&square| off_a1h8(square) < 0) {
+      encoding.map_b1h1h7[square] = code;
+      code += 1;
+    }
+
+    // The a1-d1-d4 triangle, below the diagonal and then on it
+    code = 0;
+    for on_diagonal in [false, true] {
+      for square in (0..=27).filter(|&square| square % 8 <= 3) {
+        if (off_a1h8(square) == 0) == on_diagonal && off_a1h8(square) <= 0 {
+          encoding.map_a1d1d4[square] = code;
+          code += 1;
+        }
+      }
+    }
+
+    // The two kings, not adjacent, and both on the diagonal last
+    code = 0;
+    for both_on_diagonal in [false, true] {
+      for king in 0..10 {
+        let first: usize = (0..=27)
+          .find(|&square| {
+            square % 8 <= 3 && off_a1h8(square) <= 0 && encoding.map_a1d1d4[square] == king as u64
+          })
+          .unwrap();
+        for second in 0..64 {
+          if ((first % 8) as i32 - (second % 8) as i32).abs() <= 1
+            && ((first / 8) as i32 - (second / 8) as i32).abs() <= 1
+            || off_a1h8(first) == 0 && off_a1h8(second) > 0
+            || (off_a1h8(first) == 0 && off_a1h8(second) == 0) != both_on_diagonal
+          {
+            continue;
+          }
+          encoding.map_kk[king][second] = code;
+          code += 1;
+        }
+      }
+    }
+    assert_eq!(code, 462);
+
+    encoding.binomial[0][0] = 1;
+    for n in 1..64 {
+      for k in 0..7.min(n + 1) {
+        encoding.binomial[k][n] = if k > 0 {
+          encoding.binomial[k - 1][n - 1]
+        } else {
+          0
+        } + if k < n {
+          encoding.binomial[k][n - 1]
+        } else {
+          0
+        };
+      }
+    }
+
+    for lead_pawns in 1..=5 {
+      for file in 0..4 {
+        let mut index: u64 = 0;
+        for rank in 1..=6 {
+          let square: usize = rank * 8 + file;
+          if lead_pawns == 1 {
+            encoding.map_pawns[square] = available_squares;
+            available_squares -= 1;
+            encoding.map_pawns[square ^ 7] = available_squares;
+            available_squares = available_squares.saturating_sub(1);
+          }
+          encoding.lead_pawn_index[lead_pawns][square] = index;
+          index += encoding.binomial[lead_pawns - 1][encoding.map_pawns[square] as usize];
+        }
+        encoding.lead_pawns_size[lead_pawns][file] = index;
+      }
+    }
+
+    encoding
+  }
+}
+
+/**
+ * The table codes of the pieces: white pawn to king 1-6, black pawn to king 9-14.
+ */
+fn table_piece(piece: Piece) -> u8 {
+  (piece.0 * 8 + piece.1 + 1) as u8
+}
+
+/**
+ * The layout of a table: the pieces in table order (a single leading pawn, or three unique
+ * pieces first), their groups and the size of each file.
+ */
+struct TableWriter {
+  encoding: Encoding,
+  pieces: Vec<Piece>,
+  has_pawns: bool,
+  group_length: Vec<usize>,
+  files: usize,
+}
+
+impl TableWriter {
+  fn new(ending: &Ending) -> TableWriter {
+    let mut pieces: Vec<Piece> = ending.pieces.clone();
+    let has_pawns: bool = pieces.iter().any(|&(_, kind)| kind == PAWN);
+    let mut group_length: Vec<usize> = Vec::new();
+
+    if has_pawns {
+      // The white pawn leads, the writer knows only the endings with one pawn
+      assert_eq!(pieces.iter().filter(|&&(_, kind)| kind == PAWN).count(), 1);
+      pieces.sort_by_key(|&piece| (piece != (WHITE, PAWN), material_order(piece)));
+      group_length.push(1);
+    } else {
+      assert!(pieces.iter().any(|&piece| {
+        piece.1 != KING
+          && ending
+            .pieces
+            .iter()
+            .filter(|&&other| other == piece)
+            .count()
+            == 1
+      }));
+      // The kings and a unique piece lead
+      let unique: usize = pieces
+        .iter()
+        .position(|&piece| {
+          piece.1 != KING
+            && ending
+              .pieces
+              .iter()
+              .filter(|&&other| other == piece)
+              .count()
+              == 1
+        })
+        .unwrap();
+      let leading: Piece = pieces.remove(unique);
+      pieces.insert(1, leading);
+      group_length.push(3);
+    }
+    let mut piece: usize = group_length[0];
+    while piece < pieces.len() {
+      let length: usize = pieces[piece..]
+        .iter()
+        .take_while(|&&other| other == pieces[piece])
+        .count();
+      group_length.push(length);
+      piece += length;
+    }
+
+    TableWriter {
+      encoding: Encoding::new(),
+      pieces,
+      has_pawns,
+      group_length,
+      files: if has_pawns { 4 } else { 1 },
+    }
+  }
+
+  /**
+   * The factor of each group in the index, the leading group first, and the size of the file.
+   */
+  fn group_index(&self, file: usize) -> (Vec<u64>, u64) {
+    let mut group_index: Vec<u64> = Vec::new();
+    let mut index: u64 = 1;
+    let mut free_squares: usize = 64 - self.group_length[0];
+
+    for (group, &length) in self.group_length.iter().enumerate() {
+      group_index.push(index);
+      index *= if group > 0 {
+        let size: u64 = self.encoding.binomial[length][free_squares];
+        free_squares -= length;
+        size
+      } else if self.has_pawns {
+        self.encoding.lead_pawns_size[length][file]
+      } else {
+        31332
+      };
+    }
+    (group_index, index)
+  }
+
+  /**
+   * The file of the leading pawn and the index of the position, with white as in the table.
+   */
+  fn index(&self, pieces: &[(Piece, usize)]) -> (usize, u64) {
+    let encoding: &Encoding = &self.encoding;
+    let mut squares: Vec<usize> = self
+      .pieces
+      .iter()
+      .map(|&piece| pieces.iter().find(|&&(other, _)| other == piece).unwrap().1)
+      .collect();
+    let mut file: usize = 0;
+    let mut index: u64;
+
+    if self.has_pawns {
+      file = (squares[0] % 8).min(7 - squares[0] % 8);
+    }
+    if squares[0] % 8 > 3 {
+      for square in squares.iter_mut() {
+        *square ^= 7;
+      }
+    }
+
+    if self.has_pawns {
+      index = encoding.lead_pawn_index[1][squares[0]];
+    } else {
+      if squares[0] / 8 > 3 {
+        for square in squares.iter_mut() {
+          *square ^= 56;
+        }
+      }
+      if let Some(piece) = (0..3).find(|&piece| off_a1h8(squares[piece]) != 0) {
+        if off_a1h8(squares[piece]) > 0 {
+          for square in squares[piece..].iter_mut() {
+            *square = ((*square >> 3) | (*square << 3)) & 63;
+          }
+        }
+      }
+      let adjust_1: u64 = (squares[1] > squares[0]) as u64;
+      let adjust_2: u64 = (squares[2] > squares[0]) as u64 + (squares[2] > squares[1]) as u64;
+      let (square_0, square_1, square_2): (u64, u64, u64) =
+        (squares[0] as u64, squares[1] as u64, squares[2] as u64);
+
+      index = if off_a1h8(squares[0]) != 0 {
+        (encoding.map_a1d1d4[squares[0]] * 63 + (square_1 - adjust_1)) * 62 + square_2 - adjust_2
+      } else if off_a1h8(squares[1]) != 0 {
+        (6 * 63 + (square_0 / 8) * 28 + encoding.map_b1h1h7[squares[1]]) * 62 + square_2 - adjust_2
+      } else if off_a1h8(squares[2]) != 0 {
+        6 * 63 * 62
+          + 4 * 28 * 62
+          + (square_0 / 8) * 7 * 28
+          + ((square_1 / 8) - adjust_1) * 28
+          + encoding.map_b1h1h7[squares[2]]
+      } else {
+        6 * 63 * 62
+          + 4 * 28 * 62
+          + 4 * 7 * 28
+          + (square_0 / 8) * 7 * 6
+          + ((square_1 / 8) - adjust_1) * 6
+          + (square_2 / 8)
+          - adjust_2
+      };
+    }
+
+    let (group_index, _): (Vec<u64>, u64) = self.group_index(file);
+    let mut group_start: usize = self.group_length[0];
+    for (group, &length) in self.group_length.iter().enumerate().skip(1) {
+      squares[group_start..group_start + length].sort();
+      let mut combination: u64 = 0;
+      for piece in group_start..group_start + length {
+        let adjust: usize = squares[0..group_start]
+          .iter()
+          .filter(|&&square| squares[piece] > square)
+          .count();
+        combination += encoding.binomial[piece - group_start + 1][squares[piece] - adjust];
+      }
+      index += combination * group_index[group];
+      group_start += length;
+    }
+
+    (file, index)
+  }
+
+  /**
+   * The solved positions (the result and the distance to zeroing) of every index of each side
+   * and file. The indexes that are never probed are None: the illegal positions, and in the
+   * DTZ table the draws and the wins with a winning capture or pawn move, because the reader
+   * searches these moves before the table.
+   */
+  fn solved(
+    &self,
+    generator: &Generator,
+    ending: &Ending,
+    dtz: bool,
+  ) -> Vec<Vec<Option<(i8, i8)>>> {
+    let name: String = material_name(&ending.pieces);
+    let mut solved: Vec<Vec<Option<(i8, i8)>>> = (0..2 * self.files)
+      .map(|pairs| vec![None; self.group_index(pairs % self.files).1 as usize])
+      .collect();
+
+    for index in 0..ending.wdl.len() {
+      let (side, squares): (usize, [usize; MAX_PIECES]) = ending.decode(index);
+      let squares: &[usize] = &squares[..ending.pieces.len()];
+      if (0..squares.len()).any(|piece| squares[piece + 1..].contains(&squares[piece]))
+        || ending
+          .pieces
+          .iter()
+          .zip(squares)
+          .any(|(&(_, kind), &square)| kind == PAWN && (square / 8 == 0 || square / 8 == 7))
+      {
+        continue;
+      }
+      let pieces: Vec<(Piece, usize)> = ending
+        .pieces
+        .iter()
+        .copied()
+        .zip(squares.iter().copied())
+        .collect();
+      let position: Position = Position::new(&pieces, side);
+      if position.in_check(side ^ 1) {
+        continue;
+      }
+      let (wdl, distance): (i8, i8) = generator.probe(&name, &pieces, side);
+      if dtz
+        && (wdl == DRAW
+          || wdl == WIN
+            && position.children().iter().any(|(child, zeroing)| {
+              let (child_ending, child_index): (&Ending, usize) =
+                generator.find(ending, child).unwrap();
+              *zeroing && child_ending.wdl[child_index] == LOSS
+            }))
+      {
+        continue;
+      }
+      let (file, table_index): (usize, u64) = self.index(&pieces);
+      let stored: &mut Option<(i8, i8)> =
+        &mut solved[side * self.files + file][table_index as usize];
+      assert!(
+        stored.is_none() || *stored == Some((wdl, distance)),
+        "{} index {} has two values",
+        name,
+        table_index
+      );
+      *stored = Some((wdl, distance));
+    }
+    solved
+  }
+
+  /**
+   * The table of the given sides to move: the WDL results plus 2, or the distances to zeroing
+   * less 1, halved if they are all odd (in moves instead of plies). The indexes that are never
+   * probed repeat the previous value.
+   */
+  fn write_sides(&self, solved: &[Vec<Option<(i8, i8)>>], sides: &[usize], dtz: bool) -> Vec<u8> {
+    let mut pairs: Vec<PairsWriter> = Vec::new();
+    let mut bytes: Vec<u8> = if dtz { DTZ_MAGIC } else { WDL_MAGIC }.to_vec();
+
+    for &side in sides {
+      for file in 0..self.files {
+        let positions: &[Option<(i8, i8)>] = &solved[side * self.files + file];
+        let plies = |wdl: i8| {
+          positions
+            .iter()
+            .flatten()
+            .any(|&(value, distance)| value == wdl && distance % 2 == 0)
+        };
+        let (win_plies, loss_plies): (bool, bool) = (dtz && plies(WIN), dtz && plies(LOSS));
+        let mut previous: u16 = u16::MAX;
+        let mut values: Vec<u16> = positions
+          .iter()
+          .map(|position| {
+            if let Some((wdl, distance)) = *position {
+              let distance: u16 = distance.unsigned_abs() as u16 - 1;
+              previous = if !dtz {
+                (wdl + 2) as u16
+              } else if wdl == WIN && win_plies || wdl == LOSS && loss_plies {
+                distance
+              } else {
+                distance / 2
+              };
+            }
+            previous
+          })
+          .collect();
+        // The first indexes take the first value
+        let first: u16 = values
+          .iter()
+          .copied()
+          .find(|&value| value != u16::MAX)
+          .unwrap_or(0);
+        for value in values.iter_mut().take_while(|value| **value == u16::MAX) {
+          *value = first;
+        }
+        pairs.push(PairsWriter::new(
+          &values,
+          self.group_index(file).1,
+          if dtz { side as u8 } else { 0 }
+            | if win_plies { WIN_PLIES_FLAG } else { 0 }
+            | if loss_plies { LOSS_PLIES_FLAG } else { 0 },
+        ));
+      }
+    }
+
+    bytes.push(if self.has_pawns { HAS_PAWNS_FLAG } else { 0 } | if dtz { 0 } else { SPLIT_FLAG });
+    for _ in 0..self.files {
+      // The leading group is the first factor of the index, for both sides
+      bytes.extend(
+        std::iter::once(0).chain(
+          self
+            .pieces
+            .iter()
+            .map(|&piece| table_piece(piece) | table_piece(piece) << 4),
+        ),
+      );
+    }
+    bytes.resize(bytes.len() + (bytes.len() & 1), 0);
+
+    // The parts of every pairs data, in the order of the files and then of the sides
+    let order: Vec<usize> = (0..self.files)
+      .flat_map(|file| (0..sides.len()).map(move |side| side * self.files + file))
+      .collect();
+    for &index in &order {
+      pairs[index].write_sizes(&mut bytes);
+    }
+    if dtz {
+      bytes.resize(bytes.len() + (bytes.len() & 1), 0);
+    }
+    for &index in &order {
+      bytes.extend_from_slice(&pairs[index].sparse_index);
+    }
+    for &index in &order {
+      bytes.extend_from_slice(&pairs[index].block_length);
+    }
+    for &index in &order {
+      bytes.resize((bytes.len() + 0x3F) & !0x3F, 0);
+      bytes.extend_from_slice(&pairs[index].data);
+    }
+    // The decoder reads 64 bits at a time
+    bytes.resize(bytes.len() + 8, 0);
+    bytes
+  }
+
+  /**
+   * The WDL table stores both sides to move, the DTZ table the one that takes less space.
+   */
+  fn write(&self, generator: &Generator, ending: &Ending, dtz: bool) -> Vec<u8> {
+    let solved: Vec<Vec<Option<(i8, i8)>>> = self.solved(generator, ending, dtz);
+
+    if dtz {
+      [[WHITE], [BLACK]]
+        .iter()
+        .map(|side| self.write_sides(&solved, side, true))
+        .min_by_key(Vec::len)
+        .unwrap()
+    } else {
+      self.write_sides(&solved, &[WHITE, BLACK], false)
+    }
+  }
+}
+
+/**
+ * The compressed values of one side and file: the values are paired recursively in symbols
+ * of up to 256 values, the symbols are coded with a canonical Huffman code and the codes are
+ * written in blocks, found with a sparse index of the values.
+ */
+struct PairsWriter {
+  flags: u8,
+  sizes: Vec<u8>,
+  sparse_index: Vec<u8>,
+  block_length: Vec<u8>,
+  data: Vec<u8>,
+}
+
+impl PairsWriter {
+  fn new(values: &[u16], table_size: u64, flags: u8) -> PairsWriter {
+    let span: u64 = 1 << SPAN_BITS;
+    let sparse_index_size: u64 = table_size.div_ceil(span);
+    let mut writer: PairsWriter = PairsWriter {
+      flags,
+      sizes: Vec::new(),
+      sparse_index: Vec::new(),
+      block_length: Vec::new(),
+      data: Vec::new(),
+    };
+
+    if values.iter().all(|&value| value == values[0]) {
+      writer.flags |= SINGLE_VALUE_FLAG;
+      writer.sizes.push(values[0] as u8);
+      return writer;
+    }
+
+    // Every sparse index entry points to a value
+    let mut padded: Vec<u16> = values.to_vec();
+    padded.resize(
+      padded
+        .len()
+        .max(((sparse_index_size - 1) * span + span / 2 + 1) as usize),
+      *values.last().unwrap(),
+    );
+    let (symbols, sequence): (Vec<(u16, u16, u32)>, Vec<u16>) = pair_values(&padded);
+    let lengths: Vec<u32> = code_lengths(&symbols, &sequence);
+
+    // The coded symbols are numbered from the longest code, the others follow them
+    let mut order: Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by_key(|&symbol| (lengths[symbol] == 0, Reverse(lengths[symbol])));
+    let mut number: Vec<u16> = vec![0; symbols.len()];
+    for (new_number, &symbol) in order.iter().enumerate() {
+      number[symbol] = new_number as u16;
+    }
+    let coded: Vec<usize> = order
+      .iter()
+      .copied()
+      .filter(|&symbol| lengths[symbol] > 0)
+      .collect();
+    let max_length: u32 = lengths[coded[0]];
+    let min_length: u32 = lengths[*coded.last().unwrap()];
+
+    // The first number and the first code of each length, the longer codes are the lower ones
+    let mut first_number: Vec<u16> = vec![0; (max_length - min_length + 1) as usize];
+    let mut first_code: Vec<u64> = vec![0; first_number.len()];
+    let count = |length: u32| {
+      coded
+        .iter()
+        .filter(|&&symbol| lengths[symbol] == length)
+        .count()
+    };
+    for length in (min_length..max_length).rev() {
+      let longer: usize = (length + 1 - min_length) as usize;
+      first_number[longer - 1] = first_number[longer] + count(length + 1) as u16;
+      assert_eq!((first_code[longer] + count(length + 1) as u64) % 2, 0);
+      first_code[longer - 1] = (first_code[longer] + count(length + 1) as u64) / 2;
+    }
+    let code = |symbol: usize| -> (u64, u32) {
+      let length: usize = (lengths[symbol] - min_length) as usize;
+      (
+        first_code[length] + (number[symbol] - first_number[length]) as u64,
+        lengths[symbol],
+      )
+    };
+
+    // The blocks, each with a whole number of symbols
+    let block_bits: u64 = 8 << BLOCK_SIZE_BITS;
+    let mut blocks: Vec<(Vec<u8>, u32)> = Vec::new();
+    let mut bits: u64 = block_bits;
+    for &symbol in &sequence {
+      let (code, length): (u64, u32) = code(symbol as usize);
+      let symbol_values: u32 = symbols[symbol as usize].2;
+      if bits + length as u64 > block_bits
+        || blocks.last().unwrap().1 + symbol_values > MAX_BLOCK_VALUES
+      {
+        blocks.push((vec![0; 1 << BLOCK_SIZE_BITS], 0));
+        bits = 0;
+      }
+      let block: &mut (Vec<u8>, u32) = blocks.last_mut().unwrap();
+      for bit in (0..length).rev() {
+        if code >> bit & 1 != 0 {
+          block.0[(bits / 8) as usize] |= 0x80 >> (bits % 8);
+        }
+        bits += 1;
+      }
+      block.1 += symbol_values;
+    }
+
+    let mut block_start: u64 = 0;
+    let mut block: usize = 0;
+    for entry in 0..sparse_index_size {
+      let position: u64 = entry * span + span / 2;
+      while block_start + blocks[block].1 as u64 <= position {
+        block_start += blocks[block].1 as u64;
+        block += 1;
+      }
+      writer
+        .sparse_index
+        .extend_from_slice(&(block as u32).to_le_bytes());
+      writer
+        .sparse_index
+        .extend_from_slice(&((position - block_start) as u16).to_le_bytes());
+    }
+    for (block_data, block_values) in &blocks {
+      writer
+        .block_length
+        .extend_from_slice(&((block_values - 1) as u16).to_le_bytes());
+      writer.data.extend_from_slice(block_data);
+    }
+
+    writer
+      .sizes
+      .extend_from_slice(&[BLOCK_SIZE_BITS, SPAN_BITS, 0]);
+    writer
+      .sizes
+      .extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+    writer
+      .sizes
+      .extend_from_slice(&[max_length as u8, min_length as u8]);
+    for first in &first_number {
+      writer.sizes.extend_from_slice(&first.to_le_bytes());
+    }
+    writer
+      .sizes
+      .extend_from_slice(&(symbols.len() as u16).to_le_bytes());
+    for &symbol in &order {
+      let (left, right, _): (u16, u16, u32) = symbols[symbol];
+      let (left, right): (u16, u16) = if right == LEAF {
+        (left, right)
+      } else {
+        (number[left as usize], number[right as usize])
+      };
+      writer.sizes.extend_from_slice(&[
+        left as u8,
+        (left >> 8) as u8 | (right << 4) as u8,
+        (right >> 4) as u8,
+      ]);
+    }
+    if symbols.len() & 1 != 0 {
+      writer.sizes.push(0);
+    }
+
+    writer
+  }
+
+  fn write_sizes(&self, bytes: &mut Vec<u8>) {
+    bytes.push(self.flags);
+    bytes.extend_from_slice(&self.sizes);
+  }
+}
+
+/**
+ * It replaces the most frequent pairs of adjacent symbols with new symbols, starting from a
+ * symbol for each value, and returns the symbols (the left and right parts, or the value and
+ * LEAF, with the number of values) and the sequence of symbols.
+ */
+fn pair_values(values: &[u16]) -> (Vec<(u16, u16, u32)>, Vec<u16>) {
+  let mut distinct: Vec<u16> = values.to_vec();
+  distinct.sort();
+  distinct.dedup();
+  let mut symbols: Vec<(u16, u16, u32)> = distinct.iter().map(|&value| (value, LEAF, 1)).collect();
+  let mut sequence: Vec<u16> = values
+    .iter()
+    .map(|value| distinct.binary_search(value).unwrap() as u16)
+    .collect();
+
+  while symbols.len() < MAX_SYMBOLS {
+    let mut counts: HashMap<(u16, u16), u32> = HashMap::new();
+    let mut index: usize = 0;
+    while index + 1 < sequence.len() {
+      *counts
+        .entry((sequence[index], sequence[index + 1]))
+        .or_insert(0) += 1;
+      // A run of a symbol is paired without overlaps
+      index += if sequence[index] == sequence[index + 1] {
+        2
+      } else {
+        1
+      };
+    }
+    let mut candidates: Vec<((u16, u16), u32)> = counts
+      .into_iter()
+      .filter(|&((left, right), count)| {
+        count >= 8 && symbols[left as usize].2 + symbols[right as usize].2 <= MAX_SYMBOL_VALUES
+      })
+      .collect();
+    if candidates.is_empty() {
+      break;
+    }
+    candidates.sort_by_key(|&(pair, count)| (Reverse(count), pair));
+
+    // Pairs without common symbols, so that they are replaced in the same pass
+    let mut replaced: HashMap<(u16, u16), u16> = HashMap::new();
+    let mut used: Vec<u16> = Vec::new();
+    for ((left, right), _) in candidates {
+      if replaced.len() == 64 || symbols.len() == MAX_SYMBOLS {
+        break;
+      }
+      if used.contains(&left) || used.contains(&right) {
+        continue;
+      }
+      used.extend([left, right]);
+      replaced.insert((left, right), symbols.len() as u16);
+      symbols.push((
+        left,
+        right,
+        symbols[left as usize].2 + symbols[right as usize].2,
+      ));
+    }
+
+    let mut paired: Vec<u16> = Vec::with_capacity(sequence.len());
+    index = 0;
+    while index < sequence.len() {
+      match sequence
+        .get(index + 1)
+        .and_then(|&right| replaced.get(&(sequence[index], right)))
+      {
+        Some(&symbol) => {
+          paired.push(symbol);
+          index += 2;
+        }
+        None => {
+          paired.push(sequence[index]);
+          index += 1;
+        }
+      }
+    }
+    sequence = paired;
+  }
+
+  (symbols, sequence)
+}
+
+/**
+ * The length of the Huffman code of each symbol (zero if it's not in the sequence), the
+ * frequencies are halved until the codes are short enough.
+ */
+fn code_lengths(symbols: &[(u16, u16, u32)], sequence: &[u16]) -> Vec<u32> {
+  let mut frequencies: Vec<u64> = vec![0; symbols.len()];
+  let mut lengths: Vec<u32>;
+
+  for &symbol in sequence {
+    frequencies[symbol as usize] += 1;
+  }
+  loop {
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = BinaryHeap::new();
+    // The children of the nodes of the tree, the symbols are the first nodes
+    let mut parent: Vec<usize> = vec![usize::MAX; symbols.len()];
+    lengths = vec![0; symbols.len()];
+
+    for (symbol, &frequency) in frequencies.iter().enumerate() {
+      if frequency > 0 {
+        heap.push(Reverse((frequency, symbol)));
+      }
+    }
+    while heap.len() > 1 {
+      let Reverse((first, first_node)) = heap.pop().unwrap();
+      let Reverse((second, second_node)) = heap.pop().unwrap();
+      parent.push(usize::MAX);
+      parent[first_node] = parent.len() - 1;
+      parent[second_node] = parent.len() - 1;
+      heap.push(Reverse((first + second, parent.len() - 1)));
+    }
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+      if frequencies[symbol] > 0 {
+        let mut node: usize = symbol;
+        // A single symbol has a code of one bit
+        *length = 0;
+        while parent[node] != usize::MAX {
+          node = parent[node];
+          *length += 1;
+        }
+        *length = (*length).max(1);
+      }
+    }
+    if lengths.iter().all(|&length| length <= MAX_CODE_LENGTH) {
+      return lengths;
+    }
+    for frequency in frequencies.iter_mut().filter(|frequency| **frequency > 0) {
+      *frequency = (*frequency).div_ceil(2);
+    }
+  }
+}
+
+/**
+ * The FEN of a position of an ending.
+ */
+pub fn position_fen(pieces: &[(Piece, usize)], side: usize) -> String {
+  let mut fen: String = String::new();
+
+  for rank in (0..8).rev() {
+    let mut empty: u32 = 0;
+    for file in 0..8 {
+      match pieces
+        .iter()
+        .find(|&&(_, square)| square == rank * 8 + file)
+      {
+        Some(&((color, kind), _)) => {
+          if empty > 0 {
+            fen.push(char::from_digit(empty, 10).unwrap());
+            empty = 0;
+          }
+          let character: char = PIECE_CHARACTERS.as_bytes()[kind] as char;
+          fen.push(if color == WHITE {
+            character
+          } else {
+            character.to_ascii_lowercase()
+          });
+        }
+        None => empty += 1,
+      }
+    }
+    if empty > 0 {
+      fen.push(char::from_digit(empty, 10).unwrap());
+    }
+    if rank > 0 {
+      fen.push('/');
+    }
+  }
+  fen
+    + if side == WHITE {
+      " w - - 0 1"
+    } else {
+      " b - - 0 1"
+    }
+}
@@ -0,0 +1,282 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/**
+ * The engine binary with the given commands sent to it, the lines it prints are read
+ * by another thread so that a missing answer fails the test instead of hanging it.
+ */
+fn start_engine(commands: &str) -> (Child, ChildStdin, Receiver<String>) {
+  let mut engine: Child = Command::new(env!("CARGO_BIN_EXE_chess-engine"))
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .spawn()
+    .expect("Error: the engine can not be started");
+  let mut stdin: ChildStdin = engine.stdin.take().expect("Error: no engine input");
+  let stdout = engine.stdout.take().expect("Error: no engine output");
+  let (sender, receiver) = mpsc::channel();
+
+  thread::spawn(move || {
+    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+      if sender.send(line).is_err() {
+        break;
+      }
+    }
+  });
+  stdin
+    .write_all(commands.as_bytes())
+    .expect("Error: the commands can not be sent");
+  stdin.flush().expect("Error: the commands can not be sent");
+
+  (engine, stdin, receiver)
+}
+
+fn wait_for_best_move(receiver: &Receiver<String>) -> Option<String> {
+  loop {
+    let line: String = receiver.recv_timeout(Duration::from_secs(10)).ok()?;
+    if line.starts_with("bestmove") {
+      return Some(line);
+    }
+  }
+}
+
+/**
+ * The lines printed by a search, the bestmove one last.
+ */
+fn search_output(receiver: &Receiver<String>) -> Option<Vec<String>> {
+  let mut lines: Vec<String> = Vec::new();
+
+  loop {
+    let line: String = receiver.recv_timeout(Duration::from_secs(10)).ok()?;
+    lines.push(line);
+    if lines.last().unwrap().starts_with("bestmove") {
+      return Some(lines);
+    }
+  }
+}
+
+/**
+ * The token that follows the given one in a line, as "4" follows "depth" in "info depth 4".
+ */
+fn token_after<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+  let mut tokens = line.split_whitespace();
+
+  tokens.by_ref().find(|&token| token == name)?;
+  tokens.next()
+}
+
+/**
+ * The deepest iteration of a search output.
+ */
+fn searched_depth(lines: &[String]) -> Option<i32> {
+  lines
+    .iter()
+    .filter_map(|line| token_after(line, "depth")?.parse::<i32>().ok())
+    .max()
+}
+
+#[test]
+fn stop_right_after_go_is_answered() {
+  for _ in 0..5 {
+    let (mut engine, mut stdin, receiver) =
+      start_engine("setoption name Hash value 64\nposition startpos\ngo infinite\nstop\n");
+    let best_move: Option<String> = wait_for_best_move(&receiver);
+
+    if best_move.is_none() {
+      engine.kill().unwrap();
+    }
+    assert!(best_move.is_some(), "no bestmove after the stop");
+    assert_ne!(best_move.unwrap(), "bestmove 0000");
+    stdin.write_all(b"quit\n").unwrap();
+    engine.wait().unwrap();
+  }
+}
+
+#[test]
+fn illegal_positions_are_not_searched() {
+  for fen in [
+    "8/8/8/8/8/8/8/4K3 w - - 0 1",
+    "4k3/8/8/8/8/8/4R3/4K3 w - - 0 1",
+  ] {
+    let (mut engine, mut stdin, receiver) =
+      start_engine(&format!("position fen {}\ngo depth 3\n", fen));
+    let best_move: Option<String> = wait_for_best_move(&receiver);
+
+    if best_move.is_none() {
+      engine.kill().unwrap();
+    }
+    assert_eq!(best_move.as_deref(), Some("bestmove 0000"), "{}", fen);
+    stdin.write_all(b"quit\n").unwrap();
+    assert!(engine.wait().unwrap().success(), "{}", fen);
+  }
+}
+
+#[test]
+fn easy_and_hard_toggle_pondering() {
+  // Without pondering "go ponder" is a normal search, answered at its depth
+  for ponder_off in ["easy", "setoption name Ponder value false"] {
+    let (mut engine, mut stdin, receiver) = start_engine(&format!(
+      "{}\nposition startpos\ngo ponder depth 2\n",
+      ponder_off
+    ));
+    let best_move: Option<String> = wait_for_best_move(&receiver);
+
+    if best_move.is_none() {
+      engine.kill().unwrap();
+    }
+    assert!(best_move.is_some(), "no bestmove after {}", ponder_off);
+    stdin.write_all(b"quit\n").unwrap();
+    engine.wait().unwrap();
+  }
+
+  // With pondering the answer waits for the ponderhit
+  let (mut engine, mut stdin, receiver) =
+    start_engine("easy\nhard\nposition startpos\ngo ponder depth 2\n");
+  thread::sleep(Duration::from_secs(1));
+  let early: Option<String> = receiver
+    .try_iter()
+    .find(|line| line.starts_with("bestmove"));
+
+  stdin.write_all(b"ponderhit\n").unwrap();
+  let best_move: Option<String> = wait_for_best_move(&receiver);
+  if best_move.is_none() {
+    engine.kill().unwrap();
+  }
+  assert_eq!(early, None, "bestmove before the ponderhit");
+  assert!(best_move.is_some(), "no bestmove after the ponderhit");
+  stdin.write_all(b"quit\n").unwrap();
+  engine.wait().unwrap();
+}
+
+#[test]
+fn go_mate_stops_at_the_mate() {
+  // 1. Ra7 Kg8 2. Rb8# or 1. Rb7 Kg8 2. Ra8#
+  let fen: &str = "7k/8/8/8/8/8/R7/1R4K1 w - - 0 1";
+
+  for (mate, depth, stopped) in [(2, 12, true), (3, 12, true), (1, 5, false)] {
+    let (mut engine, mut stdin, receiver) = start_engine(&format!(
+      "position fen {}\ngo mate {} depth {}\n",
+      fen, mate, depth
+    ));
+    let lines: Option<Vec<String>> = search_output(&receiver);
+
+    if lines.is_none() {
+      engine.kill().unwrap();
+    }
+    let lines: Vec<String> = lines.expect("no bestmove");
+    let last_info: &String = lines
+      .iter()
+      .rfind(|line| line.starts_with("info multipv 1 "))
+      .expect("no search information");
+    assert_eq!(token_after(last_info, "mate"), Some("2"), "{}", last_info);
+    // The mate in 2 is found at depth 3: the search stops there if it's within the limit
+    if stopped {
+      assert!(searched_depth(&lines) < Some(depth), "go mate {}", mate);
+    } else {
+      assert_eq!(searched_depth(&lines), Some(depth), "go mate {}", mate);
+    }
+    let best_move: Option<&str> = token_after(&lines[lines.len() - 1], "bestmove");
+    assert!(
+      matches!(best_move, Some("a2a7" | "b1b7")),
+      "{:?}",
+      best_move
+    );
+    stdin.write_all(b"quit\n").unwrap();
+    engine.wait().unwrap();
+  }
+}
+
+/**
+ * The "info multipv" lines of a search output, as (depth, line number, first move).
+ */
+fn multi_pv_lines(lines: &[String]) -> Vec<(i32, usize, String)> {
+  lines
+    .iter()
+    .filter(|line| line.starts_with("info multipv "))
+    .map(|line| {
+      (
+        token_after(line, "depth").unwrap().parse().unwrap(),
+        token_after(line, "multipv").unwrap().parse().unwrap(),
+        token_after(line, "pv").unwrap().to_string(),
+      )
+    })
+    .collect()
+}
+
+#[test]
+fn multi_pv_prints_every_line() {
+  for (options, lines_number, depth) in [
+    ("setoption name MultiPV value 3\n", 3, 4),
+    // The skill level searches more lines than it prints, at depth 1 + the level
+    (
+      "setoption name MultiPV value 2\nsetoption name Skill Level value 0\n",
+      2,
+      1,
+    ),
+  ] {
+    let (mut engine, mut stdin, receiver) =
+      start_engine(&format!("{}position startpos\ngo depth 4\n", options));
+    let lines: Option<Vec<String>> = search_output(&receiver);
+
+    if lines.is_none() {
+      engine.kill().unwrap();
+    }
+    let lines: Vec<String> = lines.expect("no bestmove");
+    let info_lines: Vec<(i32, usize, String)> = multi_pv_lines(&lines);
+    assert_eq!(
+      info_lines.len(),
+      depth as usize * lines_number,
+      "{}",
+      options
+    );
+    for (index, (line_depth, line, first_move)) in info_lines.iter().enumerate() {
+      assert_eq!(
+        *line_depth,
+        1 + (index / lines_number) as i32,
+        "{}",
+        options
+      );
+      assert_eq!(*line, 1 + index % lines_number, "{}", options);
+      // The lines of a depth start with different moves
+      let depth_start: usize = index - index % lines_number;
+      assert!(
+        info_lines[depth_start..index]
+          .iter()
+          .all(|(_, _, other)| other != first_move),
+        "{}",
+        options
+      );
+    }
+    assert_ne!(lines.last().unwrap(), "bestmove 0000");
+    stdin.write_all(b"quit\n").unwrap();
+    engine.wait().unwrap();
+  }
+}
+
+#[test]
+fn uci_elo_limits_the_depth_with_limit_strength() {
+  // 1000 Elo is Skill Level 0 and 1400 is 2, searching 1 + the level deep
+  for (elo, limit_strength, depth) in [(1000, false, 4), (1000, true, 1), (1400, true, 3)] {
+    let (mut engine, mut stdin, receiver) = start_engine(&format!(
+      "setoption name UCI_Elo value {}\nsetoption name UCI_LimitStrength value {}\n\
+       position startpos\ngo depth 4\n",
+      elo, limit_strength
+    ));
+    let lines: Option<Vec<String>> = search_output(&receiver);
+
+    if lines.is_none() {
+      engine.kill().unwrap();
+    }
+    assert_eq!(
+      searched_depth(&lines.expect("no bestmove")),
+      Some(depth),
+      "UCI_Elo {} UCI_LimitStrength {}",
+      elo,
+      limit_strength
+    );
+    stdin.write_all(b"quit\n").unwrap();
+    engine.wait().unwrap();
+  }
+}